@@ -0,0 +1,220 @@
+//! Append-only record of login attempts in `audit.log` (one JSON object per line),
+//! read back by the `audit` command.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::get_config_dir_with_override;
+use crate::error::{OidcError, Result};
+use crate::metrics::MetricsRecord;
+use crate::utils::fs::FileLock;
+
+/// One login attempt. Only the outcome is kept, never tokens or error details.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Unix timestamp (seconds) when the login started
+    pub timestamp: u64,
+    pub event: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<String>,
+}
+
+impl From<&MetricsRecord> for AuditEntry {
+    fn from(record: &MetricsRecord) -> Self {
+        Self {
+            timestamp: record.started_at,
+            event: record.command.clone(),
+            profile: record.profile.clone(),
+            success: record.success,
+            error_kind: record.error_kind.clone(),
+        }
+    }
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new_with_override(override_dir: Option<PathBuf>) -> Result<Self> {
+        let mut path = get_config_dir_with_override(override_dir)?;
+        path.push("audit.log");
+        Ok(Self { path })
+    }
+
+    pub fn append(&self, entry: &AuditEntry) -> Result<()> {
+        let _lock = FileLock::exclusive(&self.path)?;
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| OidcError::Config(format!("Failed to write {:?}: {e}", self.path)))
+    }
+
+    /// Every entry in file order. A missing log is empty; lines that do not
+    /// parse (e.g. a write cut short) are skipped.
+    pub fn read(&self) -> Result<Vec<AuditEntry>> {
+        let _lock = FileLock::shared(&self.path)?;
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
+
+/// Parse a duration such as `90s`, `30m`, `12h`, `7d` or `2w`; a bare number is seconds
+pub fn parse_duration(input: &str) -> Result<Duration> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let invalid = || {
+        OidcError::Config(format!(
+            "Invalid duration '{input}' (expected e.g. 90s, 30m, 12h, 7d or 2w)"
+        ))
+    };
+
+    let value: u64 = number.parse().map_err(|_| invalid())?;
+    let unit_secs = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return Err(invalid()),
+    };
+    value
+        .checked_mul(unit_secs)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+/// Which entries `audit` prints
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    /// Keep entries at or after this Unix timestamp
+    pub since: Option<u64>,
+    pub profile: Option<String>,
+}
+
+impl AuditFilter {
+    /// A filter for entries no older than `since`, measured back from `now`
+    pub fn new(since: Option<Duration>, profile: Option<String>, now: SystemTime) -> Self {
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            since: since.map(|age| now.saturating_sub(age.as_secs())),
+            profile,
+        }
+    }
+
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        self.since.is_none_or(|since| entry.timestamp >= since)
+            && self
+                .profile
+                .as_ref()
+                .is_none_or(|profile| entry.profile.as_ref() == Some(profile))
+    }
+
+    pub fn apply(&self, entries: Vec<AuditEntry>) -> Vec<AuditEntry> {
+        entries
+            .into_iter()
+            .filter(|entry| self.matches(entry))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: u64, profile: &str, success: bool) -> AuditEntry {
+        AuditEntry {
+            timestamp,
+            event: "login".to_string(),
+            profile: Some(profile.to_string()),
+            success,
+            error_kind: (!success).then(|| "timeout".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("45").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::from_secs(1_800));
+        assert_eq!(parse_duration("12h").unwrap(), Duration::from_secs(43_200));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604_800));
+        assert_eq!(
+            parse_duration("2w").unwrap(),
+            Duration::from_secs(1_209_600)
+        );
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("7 days").is_err());
+        assert!(parse_duration("-1d").is_err());
+        assert!(parse_duration("99999999999999999999w").is_err());
+    }
+
+    #[test]
+    fn test_audit_filter() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let entries = vec![
+            entry(1_000_000 - 10 * 86_400, "dev", true),
+            entry(1_000_000 - 2 * 86_400, "dev", false),
+            entry(1_000_000 - 3_600, "prod", true),
+        ];
+
+        let recent = AuditFilter::new(Some(Duration::from_secs(7 * 86_400)), None, now);
+        assert_eq!(recent.apply(entries.clone()), entries[1..].to_vec());
+
+        let dev = AuditFilter::new(None, Some("dev".to_string()), now);
+        assert_eq!(dev.apply(entries.clone()), entries[..2].to_vec());
+
+        let both = AuditFilter::new(
+            Some(Duration::from_secs(86_400)),
+            Some("dev".to_string()),
+            now,
+        );
+        assert!(both.apply(entries).is_empty());
+    }
+
+    #[test]
+    fn test_audit_log_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::new_with_override(Some(dir.path().to_path_buf())).unwrap();
+        assert!(log.read().unwrap().is_empty());
+
+        log.append(&entry(100, "dev", true)).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(dir.path().join("audit.log"))
+            .unwrap()
+            .write_all(b"{\"timestamp\":\n")
+            .unwrap();
+        log.append(&entry(200, "prod", false)).unwrap();
+
+        let entries = log.read().unwrap();
+        assert_eq!(
+            entries,
+            vec![entry(100, "dev", true), entry(200, "prod", false)]
+        );
+    }
+}
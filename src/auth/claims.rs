@@ -0,0 +1,232 @@
+use serde_json::Value;
+
+use crate::auth::TokenResponse;
+use crate::crypto::decode_jwt_segments;
+use crate::error::{OidcError, Result};
+
+/// Claim requirements asserted against the id_token after login
+#[derive(Debug, Clone, Default)]
+pub struct ClaimRequirements {
+    pub acr: Option<String>,
+    pub amr: Option<String>,
+}
+
+impl ClaimRequirements {
+    pub fn is_empty(&self) -> bool {
+        self.acr.is_none() && self.amr.is_none()
+    }
+
+    /// Decode the id_token from a token response and assert every requirement
+    pub fn check(&self, token_response: &TokenResponse) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let id_token = token_response.id_token.as_deref().ok_or_else(|| {
+            OidcError::Auth(
+                "Claim requirements were given but no id_token was returned".to_string(),
+            )
+        })?;
+
+        let claims = decode_id_token_claims(id_token)?;
+
+        if let Some(ref acr) = self.acr {
+            assert_acr(&claims, acr)?;
+        }
+
+        if let Some(ref amr) = self.amr {
+            assert_amr(&claims, amr)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Decode the payload of an id_token without verifying its signature
+pub fn decode_id_token_claims(id_token: &str) -> Result<Value> {
+    let (_, claims) = decode_jwt_segments(id_token)?;
+    Ok(claims)
+}
+
+/// Check the id_token's audience, authorized party and expiry for this client.
+///
+/// Only the claims are checked; the signature is not verified.
+pub fn verify_id_token(id_token: &str, client_id: &str) -> Result<Value> {
+    let claims = decode_id_token_claims(id_token)?;
+    verify_audience(&claims, client_id)?;
+    verify_not_expired(&claims, now_secs())?;
+    Ok(claims)
+}
+
+/// `aud` must include the client; with several audiences `azp` is required and
+/// must equal the client, and whenever `azp` is present it must match
+pub fn verify_audience(claims: &Value, client_id: &str) -> Result<()> {
+    let audiences: Vec<&str> = match claims.get("aud") {
+        Some(Value::String(aud)) => vec![aud.as_str()],
+        Some(Value::Array(values)) => values.iter().filter_map(Value::as_str).collect(),
+        _ => return Err(OidcError::Auth("ID token has no aud claim".to_string())),
+    };
+
+    if !audiences.contains(&client_id) {
+        return Err(OidcError::Auth(format!(
+            "ID token audience [{}] does not include client '{client_id}'",
+            audiences.join(", ")
+        )));
+    }
+
+    match claims.get("azp").and_then(Value::as_str) {
+        Some(azp) if azp != client_id => Err(OidcError::Auth(format!(
+            "ID token azp '{azp}' does not match client '{client_id}'"
+        ))),
+        None if audiences.len() > 1 => Err(OidcError::Auth(
+            "ID token has multiple audiences but no azp claim".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Reject tokens whose `exp` is in the past
+pub fn verify_not_expired(claims: &Value, now: u64) -> Result<()> {
+    let exp = claims
+        .get("exp")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| OidcError::Auth("ID token has no exp claim".to_string()))?;
+
+    if exp <= now {
+        return Err(OidcError::Auth(format!(
+            "ID token expired {}s ago",
+            now - exp
+        )));
+    }
+
+    Ok(())
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Require the `acr` claim to equal the given value
+pub fn assert_acr(claims: &Value, required: &str) -> Result<()> {
+    match claims.get("acr").and_then(Value::as_str) {
+        Some(acr) if acr == required => Ok(()),
+        Some(acr) => Err(OidcError::Auth(format!(
+            "ID token acr '{acr}' does not match required '{required}'"
+        ))),
+        None => Err(OidcError::Auth(format!(
+            "ID token has no acr claim (required '{required}')"
+        ))),
+    }
+}
+
+/// Require the `amr` claim to contain the given method
+pub fn assert_amr(claims: &Value, required: &str) -> Result<()> {
+    let methods: Vec<&str> = match claims.get("amr").and_then(Value::as_array) {
+        Some(values) => values.iter().filter_map(Value::as_str).collect(),
+        None => {
+            return Err(OidcError::Auth(format!(
+                "ID token has no amr claim (required '{required}')"
+            )))
+        }
+    };
+
+    if methods.contains(&required) {
+        Ok(())
+    } else {
+        Err(OidcError::Auth(format!(
+            "ID token amr [{}] does not contain required '{required}'",
+            methods.join(", ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+    use serde_json::json;
+
+    fn token_with_claims(claims: Value) -> TokenResponse {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+        TokenResponse {
+            access_token: "access".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: Some(3600),
+            refresh_token: None,
+            id_token: Some(format!("{header}.{payload}.sig")),
+            scope: None,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_claims_present_and_matching() {
+        let tokens = token_with_claims(json!({"acr": "urn:mfa", "amr": ["pwd", "mfa"]}));
+        let requirements = ClaimRequirements {
+            acr: Some("urn:mfa".to_string()),
+            amr: Some("mfa".to_string()),
+        };
+        assert!(requirements.check(&tokens).is_ok());
+    }
+
+    #[test]
+    fn test_claims_absent() {
+        let claims = json!({"sub": "user"});
+        assert!(assert_acr(&claims, "urn:mfa").is_err());
+        assert!(assert_amr(&claims, "mfa").is_err());
+
+        let mut tokens = token_with_claims(claims);
+        tokens.id_token = None;
+        let requirements = ClaimRequirements {
+            acr: None,
+            amr: Some("mfa".to_string()),
+        };
+        assert!(requirements.check(&tokens).is_err());
+    }
+
+    #[test]
+    fn test_claims_mismatched() {
+        let claims = json!({"acr": "urn:basic", "amr": ["pwd"]});
+        assert!(assert_acr(&claims, "urn:mfa").is_err());
+        assert!(assert_amr(&claims, "mfa").is_err());
+    }
+
+    #[test]
+    fn test_no_requirements_skips_decoding() {
+        let mut tokens = token_with_claims(json!({}));
+        tokens.id_token = Some("opaque".to_string());
+        assert!(ClaimRequirements::default().check(&tokens).is_ok());
+    }
+
+    #[test]
+    fn test_verify_audience_azp() {
+        let multi_ok = json!({"aud": ["client", "api"], "azp": "client"});
+        assert!(verify_audience(&multi_ok, "client").is_ok());
+
+        let multi_wrong_azp = json!({"aud": ["client", "api"], "azp": "api"});
+        assert!(verify_audience(&multi_wrong_azp, "client").is_err());
+
+        let multi_no_azp = json!({"aud": ["client", "api"]});
+        assert!(verify_audience(&multi_no_azp, "client").is_err());
+
+        let single_no_azp = json!({"aud": "client"});
+        assert!(verify_audience(&single_no_azp, "client").is_ok());
+
+        let single_array_no_azp = json!({"aud": ["client"]});
+        assert!(verify_audience(&single_array_no_azp, "client").is_ok());
+
+        let other_audience = json!({"aud": "someone-else"});
+        assert!(verify_audience(&other_audience, "client").is_err());
+    }
+
+    #[test]
+    fn test_verify_not_expired() {
+        assert!(verify_not_expired(&json!({"exp": 2000}), 1000).is_ok());
+        assert!(verify_not_expired(&json!({"exp": 1000}), 2000).is_err());
+        assert!(verify_not_expired(&json!({}), 1000).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::error::{OidcError, Result};
+
+/// Claims requested by each built-in `--claims-preset`
+pub const BUILTIN_CLAIMS_PRESETS: &[(&str, &[&str])] = &[
+    ("email", &["email", "email_verified"]),
+    (
+        "profile",
+        &[
+            "name",
+            "family_name",
+            "given_name",
+            "middle_name",
+            "nickname",
+            "preferred_username",
+            "profile",
+            "picture",
+            "website",
+            "gender",
+            "birthdate",
+            "zoneinfo",
+            "locale",
+            "updated_at",
+        ],
+    ),
+    ("address", &["address"]),
+];
+
+/// Expand a preset into an OIDC `claims` request asking for its claims in both
+/// the id_token and userinfo. Custom presets from the config shadow built-ins.
+pub fn expand_claims_preset(name: &str, custom: &HashMap<String, Vec<String>>) -> Result<Value> {
+    let claims: Vec<&str> = match custom.get(name) {
+        Some(claims) => claims.iter().map(String::as_str).collect(),
+        None => BUILTIN_CLAIMS_PRESETS
+            .iter()
+            .find(|(preset, _)| *preset == name)
+            .map(|(_, claims)| claims.to_vec())
+            .ok_or_else(|| {
+                let mut known: Vec<&str> = BUILTIN_CLAIMS_PRESETS
+                    .iter()
+                    .map(|(preset, _)| *preset)
+                    .chain(custom.keys().map(String::as_str))
+                    .collect();
+                known.sort_unstable();
+                known.dedup();
+                OidcError::Config(format!(
+                    "Unknown claims preset '{name}' (available: {})",
+                    known.join(", ")
+                ))
+            })?,
+    };
+
+    let requested: Map<String, Value> = claims
+        .into_iter()
+        .map(|claim| (claim.to_string(), Value::Null))
+        .collect();
+    Ok(serde_json::json!({
+        "id_token": requested.clone(),
+        "userinfo": requested,
+    }))
+}
+
+/// Recursively merge `overlay` into `base`; on conflicts the overlay wins
+pub fn merge_claims(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_claims(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Combine presets (in order) with an explicit `claims` JSON object, which takes
+/// precedence. Returns `None` when nothing was requested.
+pub fn build_claims_request(
+    presets: &[String],
+    explicit: Option<&str>,
+    custom: &HashMap<String, Vec<String>>,
+) -> Result<Option<Value>> {
+    let mut request: Option<Value> = None;
+
+    for name in presets {
+        let expanded = expand_claims_preset(name, custom)?;
+        match request {
+            Some(ref mut request) => merge_claims(request, expanded),
+            None => request = Some(expanded),
+        }
+    }
+
+    if let Some(explicit) = explicit {
+        let explicit: Value = serde_json::from_str(explicit)
+            .map_err(|e| OidcError::Config(format!("--claims is not valid JSON: {e}")))?;
+        if !explicit.is_object() {
+            return Err(OidcError::Config(
+                "--claims must be a JSON object".to_string(),
+            ));
+        }
+        match request {
+            Some(ref mut request) => merge_claims(request, explicit),
+            None => request = Some(explicit),
+        }
+    }
+
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_preset_expansion() {
+        let email = expand_claims_preset("email", &HashMap::new()).unwrap();
+        assert_eq!(
+            email,
+            json!({
+                "id_token": {"email": null, "email_verified": null},
+                "userinfo": {"email": null, "email_verified": null},
+            })
+        );
+
+        let custom = HashMap::from([("email".to_string(), vec!["work_email".to_string()])]);
+        let shadowed = expand_claims_preset("email", &custom).unwrap();
+        assert_eq!(shadowed["id_token"], json!({"work_email": null}));
+
+        let err = expand_claims_preset("nope", &custom).unwrap_err();
+        assert!(err.to_string().contains("address, email, profile"));
+    }
+
+    #[test]
+    fn test_presets_merge_with_explicit_claims() {
+        let request = build_claims_request(
+            &["email".to_string(), "address".to_string()],
+            Some(r#"{"id_token": {"email": {"essential": true}, "acr": {"values": ["urn:mfa"]}}}"#),
+            &HashMap::new(),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(request["id_token"]["email"], json!({"essential": true}));
+        assert_eq!(request["id_token"]["email_verified"], Value::Null);
+        assert_eq!(request["id_token"]["acr"]["values"][0], "urn:mfa");
+        assert!(request["id_token"]
+            .as_object()
+            .unwrap()
+            .contains_key("address"));
+        assert!(request["userinfo"]
+            .as_object()
+            .unwrap()
+            .contains_key("address"));
+
+        assert!(build_claims_request(&[], None, &HashMap::new())
+            .unwrap()
+            .is_none());
+        assert!(build_claims_request(&[], Some("[1]"), &HashMap::new()).is_err());
+    }
+}
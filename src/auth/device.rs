@@ -0,0 +1,109 @@
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::auth::TokenResponse;
+
+/// Poll interval used when the device authorization response omits `interval`
+pub const DEFAULT_DEVICE_POLL_INTERVAL_SECS: u64 = 5;
+
+/// Amount added to the poll interval on each `slow_down` response (RFC 8628 §3.5)
+pub const SLOW_DOWN_INCREMENT_SECS: u64 = 5;
+
+pub const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Response from the device authorization endpoint
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    pub user_code: String,
+    #[serde(alias = "verification_url")]
+    pub verification_uri: String,
+    pub verification_uri_complete: Option<String>,
+    pub expires_in: u64,
+    #[serde(default = "default_poll_interval")]
+    pub interval: u64,
+}
+
+fn default_poll_interval() -> u64 {
+    DEFAULT_DEVICE_POLL_INTERVAL_SECS
+}
+
+/// Outcome of a single device token poll
+#[derive(Debug)]
+pub enum DevicePoll {
+    Pending,
+    SlowDown,
+    Complete(TokenResponse),
+}
+
+/// Adaptive poll schedule: starts at the server interval, grows on `slow_down`,
+/// and stops once the device code expires.
+#[derive(Debug)]
+pub struct DeviceBackoff {
+    interval: Duration,
+    step: Duration,
+    deadline: Instant,
+}
+
+impl DeviceBackoff {
+    pub fn new(interval_secs: u64, expires_in_secs: u64) -> Self {
+        Self {
+            interval: Duration::from_secs(interval_secs),
+            step: Duration::from_secs(SLOW_DOWN_INCREMENT_SECS),
+            deadline: Instant::now() + Duration::from_secs(expires_in_secs),
+        }
+    }
+
+    pub fn for_authorization(device: &DeviceAuthorization) -> Self {
+        Self::new(device.interval, device.expires_in)
+    }
+
+    /// Override the `slow_down` increment
+    #[cfg(test)]
+    pub fn with_step(mut self, step: Duration) -> Self {
+        self.step = step;
+        self
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn slow_down(&mut self) {
+        self.interval += self.step;
+    }
+
+    /// Time left before the device code expires, or `None` once it has
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .checked_duration_since(Instant::now())
+            .filter(|d| !d.is_zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_authorization_defaults_interval() {
+        let device: DeviceAuthorization = serde_json::from_str(
+            r#"{"device_code":"dc","user_code":"ABCD-EFGH","verification_url":"https://example.com/device","expires_in":600}"#,
+        )
+        .unwrap();
+        assert_eq!(device.interval, DEFAULT_DEVICE_POLL_INTERVAL_SECS);
+        assert_eq!(device.verification_uri, "https://example.com/device");
+    }
+
+    #[tokio::test]
+    async fn test_backoff_slow_down_and_expiry() {
+        let mut backoff = DeviceBackoff::new(5, 600);
+        backoff.slow_down();
+        backoff.slow_down();
+        assert_eq!(backoff.interval(), Duration::from_secs(15));
+        assert!(backoff.remaining().is_some());
+
+        assert!(DeviceBackoff::new(5, 0).remaining().is_none());
+    }
+}
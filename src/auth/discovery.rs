@@ -2,12 +2,17 @@
 
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use url::Url;
 
+use crate::auth::discovery_cache::{
+    cache_ttl_from_headers, now_secs, DiscoveryCache, DEFAULT_DISCOVERY_CACHE_TTL_SECS,
+};
 use crate::error::{OidcError, Result};
+use crate::http::{max_response_bytes, read_body_capped, shared_http_client};
+use crate::settings;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct DiscoveryDocument {
     pub authorization_endpoint: String,
     pub token_endpoint: String,
@@ -20,8 +25,23 @@ pub struct DiscoveryDocument {
     pub scopes_supported: Option<Vec<String>>,
     pub token_endpoint_auth_methods_supported: Option<Vec<String>>,
     pub code_challenge_methods_supported: Option<Vec<String>>,
+    pub device_authorization_endpoint: Option<String>,
+    pub end_session_endpoint: Option<String>,
+    /// RFC 7009 token revocation endpoint
+    pub revocation_endpoint: Option<String>,
+    /// RFC 7662 token introspection endpoint
+    pub introspection_endpoint: Option<String>,
+    /// `Date` header of the discovery response, used for clock drift checks
+    #[serde(skip)]
+    pub server_date: Option<SystemTime>,
 }
 
+/// `Accept` header sent with discovery requests (overridden by `--discovery-accept`)
+pub const DEFAULT_DISCOVERY_ACCEPT: &str = "application/json";
+
+/// Clock drift beyond which `--check-clock` warns
+pub const MAX_CLOCK_DRIFT_SECS: u64 = 60;
+
 impl DiscoveryDocument {
     pub fn supports_pkce(&self) -> bool {
         self.code_challenge_methods_supported
@@ -37,16 +57,138 @@ impl DiscoveryDocument {
 }
 
 pub async fn discover_endpoints(discovery_uri: &str) -> Result<DiscoveryDocument> {
+    discover_endpoints_with(&shared_http_client(&[])?, discovery_uri).await
+}
+
+/// Fetch the discovery document with a caller-configured client (e.g. one with pinned keys)
+pub async fn discover_endpoints_with(
+    client: &Client,
+    discovery_uri: &str,
+) -> Result<DiscoveryDocument> {
+    fetch_discovery(client, discovery_uri)
+        .await?
+        .parse(discovery_uri)
+}
+
+/// Like [`discover_endpoints_with`], but reuse a cached document while it is fresh.
+///
+/// The cache lifetime follows the response's `Cache-Control`/`Expires` headers,
+/// falling back to [`DEFAULT_DISCOVERY_CACHE_TTL_SECS`]. Cached content that no
+/// longer parses or validates (e.g. a truncated copy served by a CDN) is dropped
+/// and fetched again once.
+pub async fn discover_endpoints_cached(
+    client: &Client,
+    discovery_uri: &str,
+    cache: &DiscoveryCache,
+) -> Result<DiscoveryDocument> {
+    if let Some(cached) = cache.get_fresh(discovery_uri, now_secs())? {
+        match parse_discovery_document(cached.document.as_bytes(), None, discovery_uri) {
+            Ok(doc) => return Ok(doc),
+            Err(e) => {
+                crate::ui::warn(format!(
+                    "cached discovery document is unusable ({e}); fetching it again"
+                ));
+                cache.invalidate(discovery_uri)?;
+            }
+        }
+    }
+
+    let fetched = fetch_discovery(client, discovery_uri).await?;
+    let doc = fetched.parse(discovery_uri)?;
+
+    let ttl = fetched
+        .cache_ttl
+        .unwrap_or(Duration::from_secs(DEFAULT_DISCOVERY_CACHE_TTL_SECS));
+    if let Ok(document) = std::str::from_utf8(&fetched.body) {
+        cache.store(discovery_uri, document, ttl)?;
+    }
+
+    Ok(doc)
+}
+
+struct FetchedDiscovery {
+    body: Vec<u8>,
+    server_date: Option<SystemTime>,
+    cache_ttl: Option<Duration>,
+    content_type: Option<String>,
+}
+
+impl FetchedDiscovery {
+    /// Parse the body regardless of the declared content type; some providers serve
+    /// discovery as `application/jrd+json` or `text/plain`. The content type only
+    /// explains a failure, e.g. an HTML login page returned by a proxy.
+    fn parse(&self, discovery_uri: &str) -> Result<DiscoveryDocument> {
+        parse_discovery_document(&self.body, self.server_date, discovery_uri).map_err(
+            |e| match self.content_type.as_deref() {
+                Some(content_type) if !is_json_content_type(content_type) => OidcError::Discovery(
+                    format!("{e} (the endpoint returned '{content_type}', not JSON)"),
+                ),
+                _ => e,
+            },
+        )
+    }
+}
+
+/// `application/json`, `text/json` and structured `+json` types such as `application/jrd+json`
+pub fn is_json_content_type(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let Some((_, subtype)) = mime.split_once('/') else {
+        return false;
+    };
+    subtype == "json" || subtype.ends_with("+json")
+}
+
+fn discovery_accept() -> String {
+    settings::get()
+        .discovery_accept
+        .clone()
+        .filter(|value| !value.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_DISCOVERY_ACCEPT.to_string())
+}
+
+/// Fetch the discovery document, bounded by `--discovery-timeout` when set. Unlike
+/// the client's per-request timeout, the deadline covers DNS, TLS, redirects and
+/// reading the body.
+async fn fetch_discovery(client: &Client, discovery_uri: &str) -> Result<FetchedDiscovery> {
+    with_deadline(
+        settings::get().discovery_timeout,
+        fetch_discovery_unbounded(client, discovery_uri),
+    )
+    .await
+}
+
+async fn with_deadline<T>(
+    deadline: Option<Duration>,
+    discovery: impl std::future::Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(deadline) = deadline else {
+        return discovery.await;
+    };
+    tokio::time::timeout(deadline, discovery)
+        .await
+        .unwrap_or_else(|_| {
+            Err(OidcError::Discovery(format!(
+                "no response within the {deadline:?} discovery timeout"
+            )))
+        })
+}
+
+async fn fetch_discovery_unbounded(
+    client: &Client,
+    discovery_uri: &str,
+) -> Result<FetchedDiscovery> {
     let url = Url::parse(discovery_uri)
         .map_err(|_| OidcError::Discovery(format!("Invalid discovery URI: {discovery_uri}")))?;
 
-    let client = Client::builder().timeout(Duration::from_secs(30)).build()?;
-
-    let response = client
-        .get(url)
-        .header("Accept", "application/json")
-        .send()
-        .await?;
+    let accept = discovery_accept();
+    let accept = reqwest::header::HeaderValue::from_str(&accept)
+        .map_err(|_| OidcError::Config(format!("Invalid discovery Accept header: {accept}")))?;
+    let response = client.get(url).header("Accept", accept).send().await?;
 
     if !response.status().is_success() {
         return Err(OidcError::Discovery(format!(
@@ -55,16 +197,107 @@ pub async fn discover_endpoints(discovery_uri: &str) -> Result<DiscoveryDocument
         )));
     }
 
-    let discovery_doc: DiscoveryDocument = response
-        .json()
-        .await
+    let server_date = response
+        .headers()
+        .get(reqwest::header::DATE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok());
+    let cache_ttl = cache_ttl_from_headers(response.headers(), SystemTime::now());
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let body = read_body_capped(response, max_response_bytes()).await?;
+    Ok(FetchedDiscovery {
+        body,
+        server_date,
+        cache_ttl,
+        content_type,
+    })
+}
+
+fn parse_discovery_document(
+    body: &[u8],
+    server_date: Option<SystemTime>,
+    discovery_uri: &str,
+) -> Result<DiscoveryDocument> {
+    let mut discovery_doc: DiscoveryDocument = serde_json::from_slice(body)
         .map_err(|e| OidcError::Discovery(format!("Failed to parse discovery document: {e}")))?;
+    discovery_doc.server_date = server_date;
 
     validate_discovery_document(&discovery_doc)?;
+    check_issuer_host(
+        &discovery_doc.issuer,
+        discovery_uri,
+        settings::get().strict_issuer,
+    )?;
 
     Ok(discovery_doc)
 }
 
+/// Signed drift of the local clock relative to the server in seconds;
+/// positive when the local clock is ahead
+pub fn clock_drift_secs(server_date: SystemTime, local: SystemTime) -> i64 {
+    match local.duration_since(server_date) {
+        Ok(ahead) => ahead.as_secs() as i64,
+        Err(behind) => -(behind.duration().as_secs() as i64),
+    }
+}
+
+/// Describe the drift if it exceeds [`MAX_CLOCK_DRIFT_SECS`]
+pub fn clock_drift_warning(server_date: SystemTime, local: SystemTime) -> Option<String> {
+    let drift = clock_drift_secs(server_date, local);
+    if drift.unsigned_abs() <= MAX_CLOCK_DRIFT_SECS {
+        return None;
+    }
+
+    let direction = if drift > 0 { "ahead of" } else { "behind" };
+    Some(format!(
+        "Local clock is {}s {direction} the identity provider; token expiry and validation may be wrong",
+        drift.unsigned_abs()
+    ))
+}
+
+/// OIDC Discovery derives the discovery URL from the issuer, so an issuer on another
+/// host (or port) usually means a misconfigured or spoofed provider. Returns the
+/// problem, if any.
+pub fn issuer_host_mismatch(issuer: &str, discovery_uri: &str) -> Option<String> {
+    let issuer_url = match Url::parse(issuer) {
+        Ok(url) => url,
+        Err(_) => return Some(format!("issuer '{issuer}' is not a valid URL")),
+    };
+    let discovery_url = Url::parse(discovery_uri).ok()?;
+
+    let authority = |url: &Url| {
+        (
+            url.host_str().map(str::to_ascii_lowercase),
+            url.port_or_known_default(),
+        )
+    };
+    if authority(&issuer_url) == authority(&discovery_url) {
+        return None;
+    }
+    Some(format!(
+        "discovery issuer '{issuer}' is not on the host of the discovery URL '{discovery_uri}'"
+    ))
+}
+
+/// Warn about an issuer/host mismatch, or reject it when `strict`
+fn check_issuer_host(issuer: &str, discovery_uri: &str, strict: bool) -> Result<()> {
+    match issuer_host_mismatch(issuer, discovery_uri) {
+        Some(problem) if strict => Err(OidcError::Discovery(format!(
+            "{problem} (rejected by --strict-issuer)"
+        ))),
+        Some(problem) => {
+            crate::ui::security_warning(problem);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
 fn validate_discovery_document(doc: &DiscoveryDocument) -> Result<()> {
     if doc.authorization_endpoint.is_empty() {
         return Err(OidcError::Discovery(
@@ -102,6 +335,7 @@ fn validate_discovery_document(doc: &DiscoveryDocument) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::UNIX_EPOCH;
 
     #[test]
     fn test_discovery_document_validation() {
@@ -117,6 +351,7 @@ mod tests {
             scopes_supported: None,
             token_endpoint_auth_methods_supported: None,
             code_challenge_methods_supported: Some(vec!["S256".to_string()]),
+            ..Default::default()
         };
 
         assert!(validate_discovery_document(&doc).is_ok());
@@ -124,6 +359,22 @@ mod tests {
         assert!(doc.supports_authorization_code());
     }
 
+    #[test]
+    fn test_issuer_host_mismatch() {
+        let uri = "https://login.example.com/realms/dev/.well-known/openid-configuration";
+        assert!(issuer_host_mismatch("https://login.example.com/realms/dev", uri).is_none());
+        assert!(issuer_host_mismatch("https://LOGIN.example.com:443/realms/dev", uri).is_none());
+
+        assert!(issuer_host_mismatch("https://evil.example.net/realms/dev", uri).is_some());
+        assert!(issuer_host_mismatch("https://login.example.com:8443/realms/dev", uri).is_some());
+        assert!(issuer_host_mismatch("not a url", uri).is_some());
+
+        assert!(check_issuer_host("https://evil.example.net", uri, false).is_ok());
+        let err = check_issuer_host("https://evil.example.net", uri, true).unwrap_err();
+        assert!(err.to_string().contains("strict-issuer"), "{err}");
+        assert!(check_issuer_host("https://login.example.com/realms/dev", uri, true).is_ok());
+    }
+
     #[test]
     fn test_discovery_document_missing_endpoints() {
         let doc = DiscoveryDocument {
@@ -138,8 +389,199 @@ mod tests {
             scopes_supported: None,
             token_endpoint_auth_methods_supported: None,
             code_challenge_methods_supported: None,
+            ..Default::default()
         };
 
         assert!(validate_discovery_document(&doc).is_err());
     }
+
+    #[tokio::test]
+    async fn test_discovery_decodes_gzip_response() {
+        use crate::test_support::{MockResponse, MockServer};
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let document = r#"{
+            "issuer": "https://example.com",
+            "authorization_endpoint": "https://example.com/auth",
+            "token_endpoint": "https://example.com/token"
+        }"#;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(document.as_bytes()).unwrap();
+
+        let mut response = MockResponse::json(200, "").with_header("Content-Encoding", "gzip");
+        response.body = encoder.finish().unwrap();
+        let server = MockServer::start(vec![response]).await;
+
+        let doc = discover_endpoints(&server.url("/.well-known/openid-configuration"))
+            .await
+            .unwrap();
+        assert_eq!(doc.token_endpoint, "https://example.com/token");
+        assert!(server.requests()[0]
+            .headers
+            .get("accept-encoding")
+            .is_some_and(|v| v.contains("gzip")));
+    }
+
+    #[tokio::test]
+    async fn test_discovery_accepts_nonstandard_json_content_type() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let document = r#"{
+            "issuer": "https://example.com",
+            "authorization_endpoint": "https://example.com/auth",
+            "token_endpoint": "https://example.com/token"
+        }"#;
+        let response = MockResponse::json(200, document)
+            .with_header("Content-Type", "application/jrd+json; charset=utf-8");
+        let server = MockServer::start(vec![
+            response,
+            MockResponse::json(200, "<html>Sign in</html>")
+                .with_header("Content-Type", "text/html"),
+        ])
+        .await;
+        let uri = server.url("/.well-known/openid-configuration");
+
+        let doc = discover_endpoints(&uri).await.unwrap();
+        assert_eq!(doc.token_endpoint, "https://example.com/token");
+        assert_eq!(
+            server.requests()[0]
+                .headers
+                .get("accept")
+                .map(String::as_str),
+            Some(DEFAULT_DISCOVERY_ACCEPT)
+        );
+
+        let err = discover_endpoints(&uri).await.unwrap_err().to_string();
+        assert!(err.contains("'text/html', not JSON"), "{err}");
+    }
+
+    #[test]
+    fn test_is_json_content_type() {
+        assert!(is_json_content_type("application/json"));
+        assert!(is_json_content_type("Application/JRD+JSON; charset=utf-8"));
+        assert!(is_json_content_type("text/json"));
+        assert!(!is_json_content_type("text/html"));
+        assert!(!is_json_content_type("json"));
+    }
+
+    #[tokio::test]
+    async fn test_discovery_captures_server_date() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let document = r#"{
+            "issuer": "https://example.com",
+            "authorization_endpoint": "https://example.com/auth",
+            "token_endpoint": "https://example.com/token"
+        }"#;
+        let response =
+            MockResponse::json(200, document).with_header("Date", "Sun, 06 Nov 1994 08:49:37 GMT");
+        let server = MockServer::start(vec![response]).await;
+
+        let doc = discover_endpoints(&server.url("/.well-known/openid-configuration"))
+            .await
+            .unwrap();
+        let server_date = doc.server_date.unwrap();
+        assert_eq!(server_date, UNIX_EPOCH + Duration::from_secs(784111777));
+        assert!(clock_drift_warning(server_date, SystemTime::now())
+            .unwrap()
+            .contains("ahead of"));
+    }
+
+    #[tokio::test]
+    async fn test_cached_discovery_refetches_invalid_content() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let document = r#"{
+            "issuer": "https://example.com",
+            "authorization_endpoint": "https://example.com/auth",
+            "token_endpoint": "https://example.com/token"
+        }"#;
+        let server = MockServer::start(vec![
+            MockResponse::json(200, document).with_header("Cache-Control", "max-age=120")
+        ])
+        .await;
+        let uri = server.url("/.well-known/openid-configuration");
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiscoveryCache::new_with_override(Some(dir.path().to_path_buf())).unwrap();
+        cache
+            .store(
+                &uri,
+                r#"{"issuer": "https://exam"#,
+                Duration::from_secs(600),
+            )
+            .unwrap();
+
+        let client = Client::new();
+        let doc = discover_endpoints_cached(&client, &uri, &cache)
+            .await
+            .unwrap();
+        assert_eq!(doc.token_endpoint, "https://example.com/token");
+        assert_eq!(server.requests().len(), 1);
+
+        // The fresh copy replaced the broken one, with the TTL from Cache-Control
+        let cached = cache.get_fresh(&uri, now_secs()).unwrap().unwrap();
+        assert_eq!(cached.expires_at - cached.fetched_at, 120);
+        discover_endpoints_cached(&client, &uri, &cache)
+            .await
+            .unwrap();
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_clock_drift_threshold() {
+        let server = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let within = server + Duration::from_secs(MAX_CLOCK_DRIFT_SECS);
+        assert_eq!(
+            clock_drift_secs(server, within),
+            MAX_CLOCK_DRIFT_SECS as i64
+        );
+        assert!(clock_drift_warning(server, within).is_none());
+
+        let behind = server - Duration::from_secs(MAX_CLOCK_DRIFT_SECS + 1);
+        assert_eq!(clock_drift_secs(server, behind), -61);
+        assert!(clock_drift_warning(server, behind)
+            .unwrap()
+            .contains("behind"));
+    }
+
+    #[tokio::test]
+    async fn test_discovery_deadline_cuts_off_slow_server() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let document = r#"{
+            "issuer": "https://example.com",
+            "authorization_endpoint": "https://example.com/auth",
+            "token_endpoint": "https://example.com/token"
+        }"#;
+        let server = MockServer::start(vec![
+            MockResponse::json(200, document).with_delay(Duration::from_secs(5))
+        ])
+        .await;
+        let client = shared_http_client(&[]).unwrap();
+        let uri = server.url("/.well-known/openid-configuration");
+
+        let started = std::time::Instant::now();
+        let err = with_deadline(
+            Some(Duration::from_millis(100)),
+            fetch_discovery_unbounded(&client, &uri),
+        )
+        .await
+        .err()
+        .unwrap();
+        assert!(matches!(err, OidcError::Discovery(_)), "{err}");
+        assert!(err.to_string().contains("discovery timeout"), "{err}");
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        let fast = MockServer::start(vec![MockResponse::json(200, document)]).await;
+        let fetched = with_deadline(
+            Some(Duration::from_secs(5)),
+            fetch_discovery_unbounded(&client, &fast.url("/.well-known/openid-configuration")),
+        )
+        .await
+        .unwrap();
+        assert!(fetched.parse(&uri).is_ok());
+    }
 }
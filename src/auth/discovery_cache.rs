@@ -0,0 +1,218 @@
+use reqwest::header::{HeaderMap, CACHE_CONTROL, DATE, EXPIRES};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::get_config_dir_with_override;
+use crate::error::{OidcError, Result};
+use crate::utils::fs::{write_atomic, FileLock};
+
+/// How long a discovery document is reused when the response sets no caching headers
+pub const DEFAULT_DISCOVERY_CACHE_TTL_SECS: u64 = 3600;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedDiscovery {
+    pub fetched_at: u64,
+    pub expires_at: u64,
+    /// Raw response body, re-parsed and re-validated on every use
+    pub document: String,
+}
+
+/// Discovery documents keyed by discovery URI, stored as `discovery.json` in the
+/// config directory with the same locking as the token cache
+pub struct DiscoveryCache {
+    path: PathBuf,
+}
+
+impl DiscoveryCache {
+    pub fn new_with_override(override_dir: Option<PathBuf>) -> Result<Self> {
+        let mut path = get_config_dir_with_override(override_dir)?;
+        path.push("discovery.json");
+        Ok(Self { path })
+    }
+
+    /// Every cached entry, expired or not
+    pub fn load(&self) -> Result<HashMap<String, CachedDiscovery>> {
+        let _lock = FileLock::shared(&self.path)?;
+        self.read_unlocked()
+    }
+
+    /// Drop every entry, returning how many there were
+    pub fn clear(&self) -> Result<usize> {
+        let mut removed = 0;
+        self.update(|entries| {
+            removed = entries.len();
+            entries.clear();
+        })?;
+        Ok(removed)
+    }
+
+    /// The cached document for `uri` if it has not expired at `now`
+    pub fn get_fresh(&self, uri: &str, now: u64) -> Result<Option<CachedDiscovery>> {
+        let _lock = FileLock::shared(&self.path)?;
+        Ok(self
+            .read_unlocked()?
+            .remove(uri)
+            .filter(|cached| cached.expires_at > now))
+    }
+
+    /// Cache `document` for `ttl`; a zero TTL (e.g. `no-store`) drops any entry instead
+    pub fn store(&self, uri: &str, document: &str, ttl: Duration) -> Result<()> {
+        let now = now_secs();
+        self.update(|entries| {
+            if ttl.is_zero() {
+                entries.remove(uri);
+            } else {
+                entries.insert(
+                    uri.to_string(),
+                    CachedDiscovery {
+                        fetched_at: now,
+                        expires_at: now + ttl.as_secs(),
+                        document: document.to_string(),
+                    },
+                );
+            }
+        })
+    }
+
+    pub fn invalidate(&self, uri: &str) -> Result<()> {
+        self.update(|entries| {
+            entries.remove(uri);
+        })
+    }
+
+    fn update<F: FnOnce(&mut HashMap<String, CachedDiscovery>)>(&self, f: F) -> Result<()> {
+        let _lock = FileLock::exclusive(&self.path)?;
+        let mut entries = self.read_unlocked()?;
+        f(&mut entries);
+
+        let json = serde_json::to_string_pretty(&entries)
+            .map_err(|e| OidcError::Config(format!("Failed to serialize discovery cache: {e}")))?;
+        write_atomic(&self.path, json.as_bytes())
+            .map_err(|e| OidcError::Config(format!("Failed to write discovery cache: {e}")))
+    }
+
+    fn read_unlocked(&self) -> Result<HashMap<String, CachedDiscovery>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => {
+                return Err(OidcError::Config(format!(
+                    "Failed to read discovery cache: {e}"
+                )))
+            }
+        };
+
+        // A corrupt cache file is only a cache miss
+        Ok(serde_json::from_str(&content).unwrap_or_default())
+    }
+}
+
+/// Cache lifetime requested by the response: `no-store`/`no-cache` give zero,
+/// `max-age` wins over `Expires`, which is measured against the `Date` header
+/// (or `now`). `None` when the headers say nothing usable.
+pub fn cache_ttl_from_headers(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if let Some(cache_control) = header(CACHE_CONTROL) {
+        for directive in cache_control.split(',').map(str::trim) {
+            let (name, value) = directive
+                .split_once('=')
+                .map_or((directive, None), |(name, value)| (name, Some(value)));
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" | "no-cache" => return Some(Duration::ZERO),
+                "max-age" => {
+                    if let Some(secs) = value.and_then(|v| v.trim_matches('"').parse().ok()) {
+                        return Some(Duration::from_secs(secs));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let expires = header(EXPIRES)?;
+    // Invalid dates such as "0" mean "already expired"
+    let Ok(expires) = httpdate::parse_http_date(expires) else {
+        return Some(Duration::ZERO);
+    };
+    let date = header(DATE)
+        .and_then(|value| httpdate::parse_http_date(value).ok())
+        .unwrap_or(now);
+    Some(expires.duration_since(date).unwrap_or(Duration::ZERO))
+}
+
+pub(crate) fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(reqwest::header::HeaderName, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn test_ttl_from_headers() {
+        let now = UNIX_EPOCH + Duration::from_secs(784_111_777);
+
+        let max_age = headers(&[(CACHE_CONTROL, "public, max-age=120")]);
+        assert_eq!(
+            cache_ttl_from_headers(&max_age, now),
+            Some(Duration::from_secs(120))
+        );
+
+        let no_store = headers(&[(CACHE_CONTROL, "no-store, max-age=600")]);
+        assert_eq!(cache_ttl_from_headers(&no_store, now), Some(Duration::ZERO));
+
+        let expires = headers(&[
+            (DATE, "Sun, 06 Nov 1994 08:49:37 GMT"),
+            (EXPIRES, "Sun, 06 Nov 1994 08:54:37 GMT"),
+        ]);
+        assert_eq!(
+            cache_ttl_from_headers(&expires, now),
+            Some(Duration::from_secs(300))
+        );
+
+        let both = headers(&[
+            (CACHE_CONTROL, "max-age=30"),
+            (EXPIRES, "Sun, 06 Nov 1994 08:54:37 GMT"),
+        ]);
+        assert_eq!(
+            cache_ttl_from_headers(&both, now),
+            Some(Duration::from_secs(30))
+        );
+
+        let past = headers(&[(EXPIRES, "0")]);
+        assert_eq!(cache_ttl_from_headers(&past, now), Some(Duration::ZERO));
+
+        assert_eq!(cache_ttl_from_headers(&HeaderMap::new(), now), None);
+    }
+
+    #[test]
+    fn test_store_and_expiry() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DiscoveryCache::new_with_override(Some(dir.path().to_path_buf())).unwrap();
+        let uri = "https://idp.example.com/.well-known/openid-configuration";
+
+        cache.store(uri, "{}", Duration::from_secs(60)).unwrap();
+        let now = now_secs();
+        assert!(cache.get_fresh(uri, now).unwrap().is_some());
+        assert!(cache.get_fresh(uri, now + 61).unwrap().is_none());
+
+        cache.store(uri, "{}", Duration::ZERO).unwrap();
+        assert!(cache.get_fresh(uri, now).unwrap().is_none());
+    }
+}
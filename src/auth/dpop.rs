@@ -0,0 +1,141 @@
+//! DPoP proofs (RFC 9449): an ephemeral P-256 key signs a short-lived JWT for each
+//! token request so the provider binds the issued access token to that key.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+use crate::crypto::generate_state;
+use crate::error::{OidcError, Result};
+
+/// Key pair used for every proof in one login; it is never written to disk
+pub struct DpopKey {
+    pair: EcdsaKeyPair,
+    rng: SystemRandom,
+}
+
+impl DpopKey {
+    pub fn generate() -> Result<Self> {
+        let rng = SystemRandom::new();
+        let failed = || OidcError::Config("Failed to generate a DPoP key".to_string());
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            .map_err(|_| failed())?;
+        let pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+            .map_err(|_| failed())?;
+        Ok(Self { pair, rng })
+    }
+
+    /// Base64url `x` and `y` coordinates of the public key
+    fn coordinates(&self) -> (String, String) {
+        // Uncompressed SEC1 point: 0x04 || x || y
+        let point = self.pair.public_key().as_ref();
+        (
+            URL_SAFE_NO_PAD.encode(&point[1..33]),
+            URL_SAFE_NO_PAD.encode(&point[33..]),
+        )
+    }
+
+    /// The public key as a JWK, embedded in every proof's header
+    pub fn public_jwk(&self) -> serde_json::Value {
+        let (x, y) = self.coordinates();
+        json!({ "kty": "EC", "crv": "P-256", "x": x, "y": y })
+    }
+
+    /// RFC 7638 JWK thumbprint, sent as `dpop_jkt` and reported as the token's `cnf.jkt`
+    pub fn thumbprint(&self) -> String {
+        let (x, y) = self.coordinates();
+        // Required members only, in lexicographic order and without whitespace
+        let canonical = format!(r#"{{"crv":"P-256","kty":"EC","x":"{x}","y":"{y}"}}"#);
+        URL_SAFE_NO_PAD.encode(Sha256::digest(canonical.as_bytes()))
+    }
+
+    /// A signed proof for an HTTP request. `htu` drops the query and fragment;
+    /// `nonce` is the value from a server's `DPoP-Nonce` challenge.
+    pub fn proof(&self, method: &str, url: &str, nonce: Option<&str>) -> Result<String> {
+        let mut htu = Url::parse(url)?;
+        htu.set_query(None);
+        htu.set_fragment(None);
+        let iat = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        let header = json!({ "typ": "dpop+jwt", "alg": "ES256", "jwk": self.public_jwk() });
+        let mut claims = json!({
+            "jti": generate_state()?,
+            "htm": method,
+            "htu": htu.as_str(),
+            "iat": iat,
+        });
+        if let Some(nonce) = nonce {
+            claims["nonce"] = json!(nonce);
+        }
+
+        let signed = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature = self
+            .pair
+            .sign(&self.rng, signed.as_bytes())
+            .map_err(|_| OidcError::Config("Failed to sign the DPoP proof".to_string()))?;
+        Ok(format!(
+            "{signed}.{}",
+            URL_SAFE_NO_PAD.encode(signature.as_ref())
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::Jwks;
+    use crate::crypto::decode_jwt_segments;
+
+    #[test]
+    fn test_proof_structure() {
+        let key = DpopKey::generate().unwrap();
+        let proof = key
+            .proof(
+                "POST",
+                "https://auth.example.com/token?x=1#frag",
+                Some("n-1"),
+            )
+            .unwrap();
+        let (header, claims) = decode_jwt_segments(&proof).unwrap();
+
+        assert_eq!(header["typ"], "dpop+jwt");
+        assert_eq!(header["alg"], "ES256");
+        assert_eq!(header["jwk"], key.public_jwk());
+        assert!(header["jwk"].get("d").is_none(), "no private key material");
+
+        assert_eq!(claims["htm"], "POST");
+        assert_eq!(claims["htu"], "https://auth.example.com/token");
+        assert_eq!(claims["nonce"], "n-1");
+        assert!(claims["iat"].as_u64().unwrap() > 0);
+        let other = key.proof("POST", "https://auth.example.com/token", None);
+        let (_, other) = decode_jwt_segments(&other.unwrap()).unwrap();
+        assert_ne!(claims["jti"], other["jti"], "jti is unique per proof");
+        assert!(other.get("nonce").is_none());
+
+        // The signature verifies with the embedded public key
+        let jwks: Jwks =
+            serde_json::from_value(json!({ "keys": [header["jwk"].clone()] })).unwrap();
+        jwks.verify(&proof).unwrap();
+    }
+
+    #[test]
+    fn test_thumbprint() {
+        let key = DpopKey::generate().unwrap();
+        let thumbprint = key.thumbprint();
+        assert_eq!(thumbprint.len(), 43, "base64url SHA-256");
+        assert_eq!(thumbprint, key.thumbprint());
+        assert_ne!(thumbprint, DpopKey::generate().unwrap().thumbprint());
+    }
+}
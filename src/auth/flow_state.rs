@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::auth::AuthorizationRequest;
+use crate::error::{OidcError, Result};
+use crate::utils::fs::write_atomic;
+
+/// Saved flows older than this are discarded instead of resumed
+pub const FLOW_STATE_MAX_AGE_SECS: u64 = 600;
+
+const FLOW_FILE_PREFIX: &str = "login-";
+const FLOW_FILE_SUFFIX: &str = ".json";
+
+/// An in-progress authorization request written to disk so `login --resume`
+/// can finish the code exchange after the original process has gone away.
+///
+/// Contains the PKCE verifier, so the file is owner-only and removed as soon
+/// as the login completes.
+#[derive(Serialize, Deserialize)]
+pub struct SavedFlow {
+    pub profile: String,
+    /// Redirect URI sent in the authorization request; the exchange must repeat it
+    pub redirect_uri: String,
+    pub created_at: u64,
+    pub request: AuthorizationRequest,
+}
+
+impl SavedFlow {
+    pub fn new(profile: &str, redirect_uri: &str, request: AuthorizationRequest) -> Self {
+        Self {
+            profile: profile.to_string(),
+            redirect_uri: redirect_uri.to_string(),
+            created_at: now_secs(),
+            request,
+        }
+    }
+
+    pub fn path(state_dir: &Path, profile: &str) -> PathBuf {
+        state_dir.join(format!("{FLOW_FILE_PREFIX}{profile}{FLOW_FILE_SUFFIX}"))
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now.saturating_sub(self.created_at) > FLOW_STATE_MAX_AGE_SECS
+    }
+
+    /// Write the flow with 0600 permissions, pruning any stale flows first
+    pub fn save(&self, state_dir: &Path) -> Result<PathBuf> {
+        fs::create_dir_all(state_dir)?;
+        prune_expired(state_dir, now_secs())?;
+
+        let path = Self::path(state_dir, &self.profile);
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| OidcError::Config(format!("Failed to serialize login state: {e}")))?;
+        write_atomic(&path, json.as_bytes())
+            .map_err(|e| OidcError::Config(format!("Failed to write login state: {e}")))?;
+        Ok(path)
+    }
+
+    /// Load the saved flow for a profile; an expired flow is deleted and reported as an error
+    pub fn load(state_dir: &Path, profile: &str) -> Result<Self> {
+        let path = Self::path(state_dir, profile);
+        let content = fs::read_to_string(&path).map_err(|_| {
+            OidcError::Config(format!(
+                "No saved login for profile '{profile}' in {state_dir:?}"
+            ))
+        })?;
+        let flow: SavedFlow = serde_json::from_str(&content)
+            .map_err(|e| OidcError::Config(format!("Invalid saved login state {path:?}: {e}")))?;
+
+        if flow.is_expired(now_secs()) {
+            let _ = fs::remove_file(&path);
+            return Err(OidcError::Auth(format!(
+                "Saved login for profile '{profile}' has expired; start a new login"
+            )));
+        }
+
+        Ok(flow)
+    }
+
+    pub fn remove(state_dir: &Path, profile: &str) -> Result<()> {
+        match fs::remove_file(Self::path(state_dir, profile)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Delete saved flows that are too old to resume (or no longer parse)
+pub fn prune_expired(state_dir: &Path, now: u64) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    for entry in fs::read_dir(state_dir)? {
+        let path = entry?.path();
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if !file_name.starts_with(FLOW_FILE_PREFIX) || !file_name.ends_with(FLOW_FILE_SUFFIX) {
+            continue;
+        }
+
+        let stale = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<SavedFlow>(&content).ok())
+            .is_none_or(|flow| flow.is_expired(now));
+        if stale && fs::remove_file(&path).is_ok() {
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{PkceChallenge, PkceMethod};
+
+    fn flow(profile: &str) -> SavedFlow {
+        SavedFlow::new(
+            profile,
+            "https://app.example.com/callback",
+            AuthorizationRequest {
+                authorization_url: "https://idp.example.com/authorize?state=abc".to_string(),
+                state: "abc".to_string(),
+                pkce_challenge: PkceChallenge::new(PkceMethod::S256).unwrap(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_save_load_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let saved = flow("dev");
+        let path = saved.save(dir.path()).unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let loaded = SavedFlow::load(dir.path(), "dev").unwrap();
+        assert_eq!(loaded.request.state, "abc");
+        assert_eq!(
+            loaded.request.pkce_challenge.verifier,
+            saved.request.pkce_challenge.verifier
+        );
+        assert_eq!(loaded.redirect_uri, "https://app.example.com/callback");
+
+        SavedFlow::remove(dir.path(), "dev").unwrap();
+        assert!(!path.exists());
+        assert!(SavedFlow::load(dir.path(), "dev").is_err());
+    }
+
+    #[test]
+    fn test_expired_flows_are_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut stale = flow("old");
+        stale.created_at -= FLOW_STATE_MAX_AGE_SECS + 1;
+        let stale_path = stale.save(dir.path()).unwrap();
+
+        assert!(SavedFlow::load(dir.path(), "old").is_err());
+        assert!(!stale_path.exists());
+
+        let mut stale = flow("old");
+        stale.created_at -= FLOW_STATE_MAX_AGE_SECS + 1;
+        let stale_path = stale.save(dir.path()).unwrap();
+        flow("fresh").save(dir.path()).unwrap();
+        assert!(!stale_path.exists());
+        assert!(SavedFlow::load(dir.path(), "fresh").is_ok());
+    }
+}
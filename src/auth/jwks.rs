@@ -0,0 +1,208 @@
+//! JSON Web Key Sets and offline JWT signature verification (RS256/384/512, ES256/384).
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::crypto::decode_jwt_segments;
+use crate::error::{OidcError, Result};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+/// The public key members needed for verification; other members are ignored
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    pub kid: Option<String>,
+    pub alg: Option<String>,
+    #[serde(rename = "use")]
+    pub key_use: Option<String>,
+    pub n: Option<String>,
+    pub e: Option<String>,
+    pub crv: Option<String>,
+    pub x: Option<String>,
+    pub y: Option<String>,
+}
+
+impl Jwks {
+    /// Read a JWKS document (`{"keys": [...]}`) from disk
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| OidcError::Config(format!("Failed to read JWKS file {path:?}: {e}")))?;
+        serde_json::from_str(&content)
+            .map_err(|e| OidcError::Config(format!("Failed to parse JWKS file {path:?}: {e}")))
+    }
+
+    /// Verify a compact JWT's signature against the keys in this set. Keys are
+    /// matched on the token's `kid` (when it has one) and algorithm.
+    pub fn verify(&self, token: &str) -> Result<()> {
+        let (header, _) = decode_jwt_segments(token)?;
+        let alg = header["alg"].as_str().unwrap_or_default();
+        let kid = header["kid"].as_str();
+
+        let token = token.trim();
+        let (signed, signature) = token
+            .rsplit_once('.')
+            .ok_or_else(|| OidcError::InvalidJwt("missing signature".to_string()))?;
+        let signature = b64(signature, "signature")?;
+
+        let candidates: Vec<&Jwk> = self
+            .keys
+            .iter()
+            .filter(|key| kid.is_none() || key.kid.as_deref() == kid)
+            .filter(|key| {
+                key.key_use
+                    .as_deref()
+                    .is_none_or(|key_use| key_use == "sig")
+            })
+            .filter(|key| key.alg.as_deref().is_none_or(|key_alg| key_alg == alg))
+            .collect();
+        if candidates.is_empty() {
+            return Err(OidcError::Auth(format!(
+                "No key in the JWKS matches the token (alg {alg}, kid {})",
+                kid.unwrap_or("none")
+            )));
+        }
+
+        for key in candidates {
+            if key.verify(alg, signed.as_bytes(), &signature)? {
+                return Ok(());
+            }
+        }
+        Err(OidcError::Auth("ID token signature is invalid".to_string()))
+    }
+}
+
+impl Jwk {
+    /// Whether `signature` over `message` verifies with this key. Errors when the
+    /// algorithm is unsupported or does not fit the key type.
+    fn verify(&self, alg: &str, message: &[u8], signature: &[u8]) -> Result<bool> {
+        let member = |value: &Option<String>, name: &str| {
+            value
+                .as_deref()
+                .ok_or_else(|| OidcError::Auth(format!("JWK is missing '{name}'")))
+                .and_then(|value| b64(value, name))
+        };
+
+        match (alg, self.kty.as_str()) {
+            ("RS256" | "RS384" | "RS512", "RSA") => {
+                let params = match alg {
+                    "RS256" => &signature::RSA_PKCS1_2048_8192_SHA256,
+                    "RS384" => &signature::RSA_PKCS1_2048_8192_SHA384,
+                    _ => &signature::RSA_PKCS1_2048_8192_SHA512,
+                };
+                let key = RsaPublicKeyComponents {
+                    n: member(&self.n, "n")?,
+                    e: member(&self.e, "e")?,
+                };
+                Ok(key.verify(params, message, signature).is_ok())
+            }
+            ("ES256" | "ES384", "EC") => {
+                let (params, crv) = match alg {
+                    "ES256" => (&signature::ECDSA_P256_SHA256_FIXED, "P-256"),
+                    _ => (&signature::ECDSA_P384_SHA384_FIXED, "P-384"),
+                };
+                if self.crv.as_deref() != Some(crv) {
+                    return Ok(false);
+                }
+                // Uncompressed SEC1 point: 0x04 || x || y
+                let mut point = vec![0x04];
+                point.extend(member(&self.x, "x")?);
+                point.extend(member(&self.y, "y")?);
+                Ok(UnparsedPublicKey::new(params, point)
+                    .verify(message, signature)
+                    .is_ok())
+            }
+            ("RS256" | "RS384" | "RS512" | "ES256" | "ES384", _) => Ok(false),
+            _ => Err(OidcError::Auth(format!(
+                "Unsupported signature algorithm '{alg}'"
+            ))),
+        }
+    }
+}
+
+fn b64(value: &str, name: &str) -> Result<Vec<u8>> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|e| OidcError::InvalidJwt(format!("{name} is not valid base64url: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+
+    fn encode(value: &[u8]) -> String {
+        URL_SAFE_NO_PAD.encode(value)
+    }
+
+    /// A fresh P-256 key, its JWKS and an ES256 token signed with it
+    fn signed_token(kid: &str) -> (Jwks, String) {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+            .unwrap();
+        let point = pair.public_key().as_ref();
+        let jwks = Jwks {
+            keys: vec![Jwk {
+                kty: "EC".to_string(),
+                kid: Some(kid.to_string()),
+                crv: Some("P-256".to_string()),
+                x: Some(encode(&point[1..33])),
+                y: Some(encode(&point[33..])),
+                ..Default::default()
+            }],
+        };
+
+        let header = encode(format!(r#"{{"alg":"ES256","kid":"{kid}"}}"#).as_bytes());
+        let payload = encode(br#"{"sub":"user","aud":"client"}"#);
+        let signed = format!("{header}.{payload}");
+        let signature = pair.sign(&rng, signed.as_bytes()).unwrap();
+        (jwks, format!("{signed}.{}", encode(signature.as_ref())))
+    }
+
+    #[test]
+    fn test_verify_against_file_jwks() {
+        let (jwks, token) = signed_token("key-1");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("jwks.json");
+        let key = &jwks.keys[0];
+        std::fs::write(
+            &path,
+            format!(
+                r#"{{"keys":[{{"kty":"EC","kid":"key-1","use":"sig","crv":"P-256","x":"{}","y":"{}"}}]}}"#,
+                key.x.as_deref().unwrap(),
+                key.y.as_deref().unwrap()
+            ),
+        )
+        .unwrap();
+
+        Jwks::from_file(&path).unwrap().verify(&token).unwrap();
+    }
+
+    #[test]
+    fn test_verify_rejects_tampering_and_unknown_keys() {
+        let (jwks, token) = signed_token("key-1");
+
+        let (signed, signature) = token.rsplit_once('.').unwrap();
+        let (header, _) = signed.split_once('.').unwrap();
+        let forged = format!(
+            "{header}.{}.{signature}",
+            encode(br#"{"sub":"admin","aud":"client"}"#)
+        );
+        let err = jwks.verify(&forged).unwrap_err();
+        assert!(err.to_string().contains("signature is invalid"), "{err}");
+
+        let (_, other_kid) = signed_token("key-2");
+        let err = jwks.verify(&other_kid).unwrap_err();
+        assert!(err.to_string().contains("No key"), "{err}");
+
+        assert!(Jwks::default().verify(&token).is_err());
+    }
+}
@@ -1,7 +1,21 @@
+pub mod claims;
+pub mod claims_request;
+pub mod device;
 pub mod discovery;
+pub mod discovery_cache;
+pub mod dpop;
+pub mod flow_state;
+pub mod jwks;
 pub mod oauth;
 pub mod pkce;
 
+pub use claims::*;
+pub use claims_request::*;
+pub use device::*;
 pub use discovery::*;
+pub use discovery_cache::*;
+pub use dpop::*;
+pub use flow_state::*;
+pub use jwks::*;
 pub use oauth::*;
 pub use pkce::*;
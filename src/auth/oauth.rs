@@ -1,21 +1,93 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::time::Duration;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use url::Url;
 
-use crate::auth::{discover_endpoints, generate_state, PkceChallenge};
+use crate::auth::{
+    decode_id_token_claims, discover_endpoints_cached, discover_endpoints_with, generate_state,
+    negotiate_pkce_method, DeviceAuthorization, DeviceBackoff, DevicePoll, DiscoveryCache, DpopKey,
+    PkceChallenge, PkceMethod, DEVICE_CODE_GRANT_TYPE,
+};
 use crate::config::Profile;
 use crate::error::{OidcError, Result};
+use crate::http::{max_response_bytes, read_body_capped, read_error_text, shared_http_client};
+use crate::profile::validation::CLIENT_CREDENTIALS_GRANT;
+use crate::utils::scope::{enforce_required_scopes, merge_scopes};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TokenResponse {
     pub access_token: String,
     pub token_type: String,
     pub expires_in: Option<u64>,
     pub refresh_token: Option<String>,
+    /// Refresh token lifetime in seconds, when the provider reports it (e.g. Keycloak)
+    #[serde(
+        default,
+        alias = "refresh_token_expires_in",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub refresh_expires_in: Option<u64>,
     pub id_token: Option<String>,
     pub scope: Option<String>,
+    /// OIDC session management state, from the token response or the callback
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_state: Option<String>,
+}
+
+impl TokenResponse {
+    /// The id_token's `exp` claim, decoded without verifying the signature
+    pub fn id_token_exp(&self) -> Option<u64> {
+        let claims = decode_id_token_claims(self.id_token.as_deref()?).ok()?;
+        claims.get("exp")?.as_u64()
+    }
+
+    /// Absolute expiry for tokens obtained at `obtained_at`: from `expires_in` when
+    /// present, otherwise the id_token's `exp`. The fallback is the id_token's
+    /// lifetime, which may differ from the access token's.
+    pub fn expires_at(&self, obtained_at: u64) -> Option<u64> {
+        match self.expires_in {
+            Some(expires_in) => Some(obtained_at + expires_in),
+            None => self.id_token_exp(),
+        }
+    }
+}
+
+/// Token response fields sent as numbers in JSON, which form encoding carries as strings
+const NUMERIC_TOKEN_FIELDS: &[&str] = &[
+    "expires_in",
+    "refresh_expires_in",
+    "refresh_token_expires_in",
+];
+
+/// Parse a token endpoint response: JSON, or `application/x-www-form-urlencoded`
+/// for legacy gateways that ignore `Accept: application/json`
+pub fn parse_token_response(body: &[u8], content_type: Option<&str>) -> Result<TokenResponse> {
+    let form_encoded = content_type.is_some_and(|content_type| {
+        content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+    });
+    if !form_encoded {
+        return serde_json::from_slice(body)
+            .map_err(|e| OidcError::Auth(format!("Failed to parse token response: {e}")));
+    }
+
+    let fields = url::form_urlencoded::parse(body)
+        .map(|(key, value)| {
+            let value = match value.parse::<u64>() {
+                Ok(number) if NUMERIC_TOKEN_FIELDS.contains(&key.as_ref()) => number.into(),
+                _ => serde_json::Value::String(value.into_owned()),
+            };
+            (key.into_owned(), value)
+        })
+        .collect();
+    serde_json::from_value(serde_json::Value::Object(fields))
+        .map_err(|e| OidcError::Auth(format!("Failed to parse form-encoded token response: {e}")))
 }
 
 /// JSON export format for tokens with absolute expiration timestamp
@@ -28,6 +100,8 @@ pub struct TokenExport {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub refresh_token: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_expires_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub id_token: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub scope: Option<String>,
@@ -36,25 +110,154 @@ pub struct TokenExport {
 impl TokenExport {
     /// Create a TokenExport from a TokenResponse, converting expires_in to expires_at
     pub fn from_response(response: &TokenResponse) -> Self {
-        let expires_at = response.expires_in.map(|secs| {
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .expect("System time before UNIX epoch")
-                .as_secs()
-                + secs
-        });
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("System time before UNIX epoch")
+            .as_secs();
+        let expires_at = response.expires_at(now);
 
         Self {
             access_token: response.access_token.clone(),
             token_type: response.token_type.clone(),
             expires_at,
             refresh_token: response.refresh_token.clone(),
+            refresh_expires_at: response.refresh_expires_in.map(|secs| now + secs),
             id_token: response.id_token.clone(),
             scope: response.scope.clone(),
         }
     }
 }
 
+/// Token kinds chosen with `login --show`; all are selected by default
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenSelection {
+    pub access: bool,
+    pub id: bool,
+    pub refresh: bool,
+}
+
+impl Default for TokenSelection {
+    fn default() -> Self {
+        Self {
+            access: true,
+            id: true,
+            refresh: true,
+        }
+    }
+}
+
+impl TokenSelection {
+    /// Parse a comma list of `access`, `id` and `refresh`
+    pub fn parse(list: &str) -> Result<Self> {
+        let mut selection = Self {
+            access: false,
+            id: false,
+            refresh: false,
+        };
+
+        for kind in list.split(',').map(str::trim).filter(|k| !k.is_empty()) {
+            match kind {
+                "access" => selection.access = true,
+                "id" => selection.id = true,
+                "refresh" => selection.refresh = true,
+                other => {
+                    return Err(OidcError::Config(format!(
+                        "Unknown token type '{other}' (expected access, id or refresh)"
+                    )))
+                }
+            }
+        }
+
+        if !(selection.access || selection.id || selection.refresh) {
+            return Err(OidcError::Config(
+                "--show needs at least one of access, id, refresh".to_string(),
+            ));
+        }
+
+        Ok(selection)
+    }
+
+    /// Selected token kinds the response did not include
+    pub fn missing(&self, response: &TokenResponse) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        if self.id && response.id_token.is_none() {
+            missing.push("id");
+        }
+        if self.refresh && response.refresh_token.is_none() {
+            missing.push("refresh");
+        }
+        missing
+    }
+
+    /// Remove unselected `access_token`/`id_token`/`refresh_token` keys from serialized output
+    pub fn strip_unselected(&self, value: &mut serde_json::Value) {
+        if let Some(object) = value.as_object_mut() {
+            for (selected, key) in [
+                (self.access, "access_token"),
+                (self.id, "id_token"),
+                (self.refresh, "refresh_token"),
+                (self.refresh, "refresh_expires_in"),
+                (self.refresh, "refresh_expires_at"),
+            ] {
+                if !selected {
+                    object.remove(key);
+                }
+            }
+        }
+    }
+}
+
+/// Where an [`OAuthClient`]'s endpoints came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EndpointSource {
+    /// The discovery document at this URI (fetched or cached)
+    Discovery(String),
+    /// The profile's manual endpoints
+    Manual,
+    /// The profile's manual endpoints after discovery at this URI failed
+    ManualFallback(String),
+}
+
+impl EndpointSource {
+    /// The source a login would use for `profile` when discovery succeeds
+    pub fn configured(profile: &Profile) -> Self {
+        match profile.discovery_uri {
+            Some(ref uri) => EndpointSource::Discovery(uri.clone()),
+            None => EndpointSource::Manual,
+        }
+    }
+}
+
+impl std::fmt::Display for EndpointSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EndpointSource::Discovery(uri) => write!(f, "discovered from {uri}"),
+            EndpointSource::Manual => f.write_str("manual"),
+            EndpointSource::ManualFallback(uri) => {
+                write!(f, "manual (discovery from {uri} failed)")
+            }
+        }
+    }
+}
+
+/// Which kind of token is being revoked, sent as RFC 7009 `token_type_hint`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum TokenTypeHint {
+    AccessToken,
+    RefreshToken,
+}
+
+impl TokenTypeHint {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenTypeHint::AccessToken => "access_token",
+            TokenTypeHint::RefreshToken => "refresh_token",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct AuthorizationRequest {
     pub authorization_url: String,
     pub state: String,
@@ -67,53 +270,296 @@ pub struct OAuthClient {
     profile: Profile,
     authorization_endpoint: String,
     token_endpoint: String,
+    device_authorization_endpoint: Option<String>,
+    end_session_endpoint: Option<String>,
+    revocation_endpoint: Option<String>,
+    introspection_endpoint: Option<String>,
+    endpoint_source: EndpointSource,
+    registered_redirect_uri: Option<String>,
+    server_date: Option<SystemTime>,
+    trace_pkce: bool,
+    /// Negotiated from discovery unless the profile pins one
+    pkce_method: PkceMethod,
+    required_scopes: Vec<String>,
+    id_token_hint: Option<String>,
+    prompt: Vec<String>,
+    claims: Option<serde_json::Value>,
+    /// Key that DPoP-binds issued tokens, when requested
+    dpop: Option<Arc<DpopKey>>,
+    /// `audience` sent with the authorization and token requests
+    audience: Option<String>,
+    /// Codes already sent to the token endpoint, shared between clones
+    used_codes: Arc<Mutex<HashSet<String>>>,
 }
 
 impl OAuthClient {
     pub async fn new(profile: Profile) -> Result<Self> {
-        let client = Client::builder().timeout(Duration::from_secs(30)).build()?;
+        Self::new_with_discovery_cache(profile, None).await
+    }
 
-        let (authorization_endpoint, token_endpoint) =
-            if let Some(ref discovery_uri) = profile.discovery_uri {
-                let discovery_doc = discover_endpoints(discovery_uri).await?;
-                (
-                    discovery_doc.authorization_endpoint,
-                    discovery_doc.token_endpoint,
-                )
-            } else {
-                let auth_endpoint = profile.authorization_endpoint.as_ref().ok_or_else(|| {
-                    OidcError::Config("Missing authorization endpoint".to_string())
-                })?;
-                let token_endpoint = profile
-                    .token_endpoint
-                    .as_ref()
-                    .ok_or_else(|| OidcError::Config("Missing token endpoint".to_string()))?;
-                (auth_endpoint.clone(), token_endpoint.clone())
+    /// Like [`new`](Self::new), reusing discovery documents from `cache` while they are fresh
+    pub async fn new_with_discovery_cache(
+        profile: Profile,
+        cache: Option<&DiscoveryCache>,
+    ) -> Result<Self> {
+        let client = shared_http_client(&profile.pin_sha256)?;
+
+        let mut endpoint_source = EndpointSource::configured(&profile);
+        let discovered = match profile.discovery_uri {
+            Some(ref discovery_uri) => {
+                let result = match cache {
+                    Some(cache) => discover_endpoints_cached(&client, discovery_uri, cache).await,
+                    None => discover_endpoints_with(&client, discovery_uri).await,
+                };
+                match result {
+                    Ok(doc) => Some(doc),
+                    Err(e) if profile.discovery_fallback && profile.has_manual_endpoints() => {
+                        crate::ui::warn(format!(
+                            "discovery failed ({e}); falling back to the profile's manual endpoints"
+                        ));
+                        endpoint_source = EndpointSource::ManualFallback(discovery_uri.clone());
+                        None
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            None => None,
+        };
+
+        let pkce_method = negotiate_pkce_method(
+            profile.pinned_pkce_method(),
+            discovered
+                .as_ref()
+                .filter(|_| !profile.no_pkce)
+                .and_then(|doc| doc.code_challenge_methods_supported.as_deref()),
+        )?;
+
+        let (
+            authorization_endpoint,
+            token_endpoint,
+            device_authorization_endpoint,
+            end_session_endpoint,
+            revocation_endpoint,
+            introspection_endpoint,
+            server_date,
+        ) = if let Some(discovery_doc) = discovered {
+            (
+                discovery_doc.authorization_endpoint,
+                discovery_doc.token_endpoint,
+                discovery_doc.device_authorization_endpoint,
+                discovery_doc.end_session_endpoint,
+                discovery_doc.revocation_endpoint,
+                discovery_doc.introspection_endpoint,
+                discovery_doc.server_date,
+            )
+        } else {
+            let auth_endpoint = match profile.authorization_endpoint {
+                Some(ref endpoint) => endpoint.clone(),
+                // The client credentials grant never sends anyone to authorize
+                None if profile.uses_client_credentials() => String::new(),
+                None => {
+                    return Err(OidcError::Config(
+                        "Missing authorization endpoint".to_string(),
+                    ))
+                }
             };
+            let token_endpoint = profile
+                .token_endpoint
+                .as_ref()
+                .ok_or_else(|| OidcError::Config("Missing token endpoint".to_string()))?;
+            (
+                auth_endpoint,
+                token_endpoint.clone(),
+                profile.device_authorization_endpoint.clone(),
+                None,
+                None,
+                None,
+                None,
+            )
+        };
 
         Ok(OAuthClient {
             client,
             profile,
             authorization_endpoint,
             token_endpoint,
+            device_authorization_endpoint,
+            end_session_endpoint,
+            revocation_endpoint,
+            introspection_endpoint,
+            endpoint_source,
+            registered_redirect_uri: None,
+            server_date,
+            trace_pkce: false,
+            pkce_method,
+            required_scopes: Vec::new(),
+            id_token_hint: None,
+            prompt: Vec::new(),
+            claims: None,
+            dpop: None,
+            audience: None,
+            used_codes: Arc::default(),
         })
     }
 
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Whether the endpoints in use came from discovery or the profile
+    pub fn endpoint_source(&self) -> &EndpointSource {
+        &self.endpoint_source
+    }
+
+    /// PKCE method used for authorization requests
+    pub fn pkce_method(&self) -> PkceMethod {
+        self.pkce_method
+    }
+
+    /// `Date` reported by the provider during discovery, if any
+    pub fn server_date(&self) -> Option<SystemTime> {
+        self.server_date
+    }
+
+    /// Send this redirect URI to the provider instead of the profile's locally-bound one,
+    /// e.g. when a reverse proxy forwards the registered URI to the local callback server
+    pub fn with_registered_redirect_uri(mut self, redirect_uri: Option<String>) -> Self {
+        self.registered_redirect_uri = redirect_uri;
+        self
+    }
+
+    /// Print the PKCE computation when building authorization requests
+    pub fn with_pkce_trace(mut self, trace: bool) -> Self {
+        self.trace_pkce = trace;
+        self
+    }
+
+    /// Scopes that every request must include, per configuration policy
+    pub fn with_required_scopes(mut self, required_scopes: Vec<String>) -> Self {
+        self.required_scopes = required_scopes;
+        self
+    }
+
+    /// Send a previously issued id_token as `id_token_hint` so the provider can pre-select the account
+    pub fn with_id_token_hint(mut self, id_token_hint: Option<String>) -> Self {
+        self.id_token_hint = id_token_hint;
+        self
+    }
+
+    /// `prompt` values to request, e.g. `login` and `consent`; sent space-separated
+    pub fn with_prompt(mut self, prompt: Vec<String>) -> Self {
+        self.prompt = prompt;
+        self
+    }
+
+    /// OIDC `claims` request object sent with the authorization request
+    pub fn with_claims(mut self, claims: Option<serde_json::Value>) -> Self {
+        self.claims = claims;
+        self
+    }
+
+    /// Ask for tokens for a specific API with the `audience` parameter
+    pub fn with_audience(mut self, audience: Option<String>) -> Self {
+        self.audience = audience;
+        self
+    }
+
+    /// Request DPoP-bound tokens (RFC 9449): sends `dpop_jkt` with the authorization
+    /// request and a signed proof with every token request
+    pub fn with_dpop(mut self, key: Option<Arc<DpopKey>>) -> Self {
+        self.dpop = key;
+        self
+    }
+
+    /// The profile scope with any missing required scopes appended; warns when it had to add some.
+    /// Discovery-based (OIDC) profiles also get `openid` unless `no_auto_openid` is set.
+    fn requested_scope(&self) -> String {
+        let (mut scope, added) =
+            enforce_required_scopes(&self.profile.scope, &self.required_scopes);
+        if !added.is_empty() {
+            crate::ui::warn(format!(
+                "adding required scope(s) '{}' to the request",
+                added.join(" ")
+            ));
+        }
+
+        let auto_openid = self.profile.discovery_uri.is_some() && !self.profile.no_auto_openid;
+        if auto_openid && !scope.split_whitespace().any(|s| s == "openid") {
+            crate::ui::note(
+                "adding 'openid' to the scope so an id_token is returned (disable with --no-auto-openid)",
+            );
+            scope = merge_scopes("openid", &scope);
+        }
+
+        scope
+    }
+
+    /// The redirect URI sent in both the authorization and token requests
+    pub fn redirect_uri(&self) -> &str {
+        self.registered_redirect_uri
+            .as_deref()
+            .unwrap_or(&self.profile.redirect_uri)
+    }
+
     pub fn create_authorization_request(&self) -> Result<AuthorizationRequest> {
-        let pkce_challenge = PkceChallenge::new()?;
+        let pkce_challenge = PkceChallenge::new(self.pkce_method)?;
         let state = generate_state()?;
 
         let mut auth_url = Url::parse(&self.authorization_endpoint)?;
 
+        if let Some(ref request_uri) = self.profile.request_uri {
+            // All other parameters live in the externally-hosted request object
+            auth_url
+                .query_pairs_mut()
+                .append_pair("client_id", &self.profile.client_id)
+                .append_pair("request_uri", request_uri);
+
+            return Ok(AuthorizationRequest {
+                authorization_url: auth_url.to_string(),
+                state,
+                pkce_challenge,
+            });
+        }
+
         {
             let mut query_pairs = auth_url.query_pairs_mut();
-            query_pairs.append_pair("response_type", "code");
+            query_pairs.append_pair(
+                "response_type",
+                self.profile.response_type.as_deref().unwrap_or("code"),
+            );
+            if self.profile.is_hybrid() {
+                // Hybrid responses default to the fragment, which the callback server never sees
+                query_pairs.append_pair("response_mode", "query");
+            }
             query_pairs.append_pair("client_id", &self.profile.client_id);
-            query_pairs.append_pair("redirect_uri", &self.profile.redirect_uri);
-            query_pairs.append_pair("scope", &self.profile.scope);
+            query_pairs.append_pair("redirect_uri", self.redirect_uri());
+            query_pairs.append_pair("scope", &self.requested_scope());
             query_pairs.append_pair("state", &state);
-            query_pairs.append_pair("code_challenge", &pkce_challenge.challenge);
-            query_pairs.append_pair("code_challenge_method", "S256");
+            if !self.profile.no_pkce {
+                query_pairs.append_pair("code_challenge", &pkce_challenge.challenge);
+                query_pairs.append_pair("code_challenge_method", self.pkce_method.as_str());
+            }
+            if let Some(ref hint) = self.id_token_hint {
+                query_pairs.append_pair("id_token_hint", hint);
+            }
+            if let Some(prompt) = join_prompt(&self.prompt) {
+                query_pairs.append_pair("prompt", &prompt);
+            }
+            if let Some(ref claims) = self.claims {
+                query_pairs.append_pair("claims", &claims.to_string());
+            }
+            if let Some(ref key) = self.dpop {
+                query_pairs.append_pair("dpop_jkt", &key.thumbprint());
+            }
+            if let Some(ref audience) = self.audience {
+                query_pairs.append_pair("audience", audience);
+            }
+        }
+
+        if self.trace_pkce && !self.profile.no_pkce {
+            for line in pkce_challenge.trace() {
+                crate::ui::progress(line);
+            }
         }
 
         Ok(AuthorizationRequest {
@@ -133,16 +579,163 @@ impl OAuthClient {
         if state != expected_state {
             return Err(OidcError::StateMismatch);
         }
+        self.claim_code(authorization_code)?;
 
         let mut params = HashMap::new();
         params.insert("grant_type", "authorization_code");
         params.insert("code", authorization_code);
-        params.insert("redirect_uri", &self.profile.redirect_uri);
+        params.insert("redirect_uri", self.redirect_uri());
         params.insert("client_id", &self.profile.client_id);
-        params.insert("code_verifier", pkce_verifier);
+        if self.profile.request_uri.is_none() && !self.profile.no_pkce {
+            params.insert("code_verifier", pkce_verifier);
+        }
+        if let Some(ref audience) = self.audience {
+            params.insert("audience", audience);
+        }
+
+        self.send_token_request(&params).await
+    }
+
+    /// Obtain a token for the client itself (RFC 6749 §4.4), authenticating with
+    /// the client secret; no user, browser or callback is involved
+    pub async fn client_credentials_grant(&self) -> Result<TokenResponse> {
+        let mut params = HashMap::new();
+        params.insert("grant_type", CLIENT_CREDENTIALS_GRANT);
+        params.insert("scope", &self.profile.scope);
+        params.insert("client_id", &self.profile.client_id);
+        if let Some(ref audience) = self.audience {
+            params.insert("audience", audience);
+        }
+
+        self.send_token_request(&params).await
+    }
+
+    /// Exchange a refresh token for new tokens (RFC 6749 §6). The provider may
+    /// omit `refresh_token` from the response, in which case the old one stays valid.
+    pub async fn refresh_tokens(&self, refresh_token: &str) -> Result<TokenResponse> {
+        let mut params = HashMap::new();
+        params.insert("grant_type", "refresh_token");
+        params.insert("refresh_token", refresh_token);
+        params.insert("client_id", &self.profile.client_id);
+
+        self.send_token_request(&params).await
+    }
+
+    /// POST `params` to the token endpoint with client authentication and parse the response
+    async fn send_token_request(&self, params: &HashMap<&str, &str>) -> Result<TokenResponse> {
+        let response = self.post_token_request_with_nonce(params).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = read_error_text(response, max_response_bytes()).await;
+            return Err(OidcError::Auth(format!(
+                "Token exchange failed with status {status}: {error_text}"
+            )));
+        }
+
+        read_token_response(response).await
+    }
 
-        let mut request = self.client.post(&self.token_endpoint).form(&params);
+    /// Record a code as exchanged. Servers may burn a code even when the exchange
+    /// fails, so a second attempt is refused rather than answered with `invalid_grant`.
+    fn claim_code(&self, authorization_code: &str) -> Result<()> {
+        let mut used = self.used_codes.lock().unwrap_or_else(|e| e.into_inner());
+        if used.insert(authorization_code.to_string()) {
+            Ok(())
+        } else {
+            Err(OidcError::CodeReused)
+        }
+    }
+
+    /// Build an RP-initiated logout URL from the discovered `end_session_endpoint`
+    pub fn build_end_session_url(
+        &self,
+        id_token_hint: Option<&str>,
+        post_logout_redirect_uri: Option<&str>,
+    ) -> Result<String> {
+        let endpoint = self.end_session_endpoint.as_ref().ok_or_else(|| {
+            OidcError::Config("Provider does not advertise an end_session_endpoint".to_string())
+        })?;
+
+        let mut url = Url::parse(endpoint)?;
+        {
+            let mut query_pairs = url.query_pairs_mut();
+            query_pairs.append_pair("client_id", &self.profile.client_id);
+            if let Some(hint) = id_token_hint {
+                query_pairs.append_pair("id_token_hint", hint);
+            }
+            if let Some(redirect) = post_logout_redirect_uri {
+                query_pairs.append_pair("post_logout_redirect_uri", redirect);
+            }
+        }
+
+        Ok(url.to_string())
+    }
+
+    /// POST `params` to the token endpoint, retrying once when a provider demands a
+    /// server-issued DPoP nonce in the proof (RFC 9449 §8)
+    async fn post_token_request_with_nonce(
+        &self,
+        params: &HashMap<&str, &str>,
+    ) -> Result<reqwest::Response> {
+        let response = self.post_token_request(params, None).await?;
+        match self.dpop_nonce_challenge(&response) {
+            Some(nonce) => self.post_token_request(params, Some(&nonce)).await,
+            None => Ok(response),
+        }
+    }
+
+    async fn post_token_request(
+        &self,
+        params: &HashMap<&str, &str>,
+        dpop_nonce: Option<&str>,
+    ) -> Result<reqwest::Response> {
+        let request = self
+            .client
+            .post(&self.token_endpoint)
+            .header(reqwest::header::ACCEPT, "application/json");
+        let mut request = if self.profile.uses_json_token_requests() {
+            request.json(params)
+        } else {
+            request.form(params)
+        };
 
+        if let Some(ref client_secret) = self.profile.client_secret {
+            request = request.basic_auth(&self.profile.client_id, Some(client_secret));
+        }
+        if let Some(ref key) = self.dpop {
+            request = request.header("DPoP", key.proof("POST", &self.token_endpoint, dpop_nonce)?);
+        }
+
+        Ok(request.send().await?)
+    }
+
+    /// The `DPoP-Nonce` a rejected DPoP token request asks to be retried with
+    fn dpop_nonce_challenge(&self, response: &reqwest::Response) -> Option<String> {
+        if self.dpop.is_none() || !response.status().is_client_error() {
+            return None;
+        }
+        response
+            .headers()
+            .get("DPoP-Nonce")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    }
+
+    /// Revoke `token` at the discovered `revocation_endpoint` (RFC 7009)
+    pub async fn revoke_token(&self, token: &str, hint: Option<TokenTypeHint>) -> Result<()> {
+        let endpoint = self.revocation_endpoint.as_ref().ok_or_else(|| {
+            OidcError::Config("Provider does not advertise a revocation_endpoint".to_string())
+        })?;
+
+        let mut params = HashMap::new();
+        params.insert("token", token);
+        if let Some(hint) = hint {
+            params.insert("token_type_hint", hint.as_str());
+        }
+        params.insert("client_id", &self.profile.client_id);
+
+        let mut request = self.client.post(endpoint).form(&params);
         if let Some(ref client_secret) = self.profile.client_secret {
             request = request.basic_auth(&self.profile.client_id, Some(client_secret));
         }
@@ -151,26 +744,179 @@ impl OAuthClient {
 
         if !response.status().is_success() {
             let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
+            let error_text = read_error_text(response, max_response_bytes()).await;
             return Err(OidcError::Auth(format!(
-                "Token exchange failed with status {status}: {error_text}"
+                "Token revocation failed with status {status}: {error_text}"
             )));
         }
 
-        let token_response: TokenResponse = response
-            .json()
-            .await
-            .map_err(|e| OidcError::Auth(format!("Failed to parse token response: {e}")))?;
+        Ok(())
+    }
+
+    /// Ask the discovered `introspection_endpoint` whether the access token `token`
+    /// is still active (RFC 7662)
+    pub async fn introspect_token(&self, token: &str) -> Result<bool> {
+        let endpoint = self.introspection_endpoint.as_ref().ok_or_else(|| {
+            OidcError::Config("Provider does not advertise an introspection_endpoint".to_string())
+        })?;
+
+        let mut params = HashMap::new();
+        params.insert("token", token);
+        params.insert("token_type_hint", TokenTypeHint::AccessToken.as_str());
+        params.insert("client_id", &self.profile.client_id);
+
+        let mut request = self.client.post(endpoint).form(&params);
+        if let Some(ref client_secret) = self.profile.client_secret {
+            request = request.basic_auth(&self.profile.client_id, Some(client_secret));
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let body = read_body_capped(response, max_response_bytes()).await?;
+        if !status.is_success() {
+            return Err(OidcError::Auth(format!(
+                "Token introspection failed with status {status}: {}",
+                String::from_utf8_lossy(&body)
+            )));
+        }
+
+        let introspection: serde_json::Value = serde_json::from_slice(&body)
+            .map_err(|e| OidcError::Auth(format!("Failed to parse introspection response: {e}")))?;
+        Ok(introspection["active"].as_bool().unwrap_or(false))
+    }
+
+    /// Start a device authorization grant (RFC 8628)
+    pub async fn request_device_authorization(&self) -> Result<DeviceAuthorization> {
+        let endpoint = self.device_authorization_endpoint.as_ref().ok_or_else(|| {
+            OidcError::Config(
+                "Provider does not advertise a device_authorization_endpoint".to_string(),
+            )
+        })?;
+
+        let scope = self.requested_scope();
+        let mut params = HashMap::new();
+        params.insert("client_id", self.profile.client_id.as_str());
+        params.insert("scope", scope.as_str());
+
+        let mut request = self.client.post(endpoint).form(&params);
+        if let Some(ref client_secret) = self.profile.client_secret {
+            request = request.basic_auth(&self.profile.client_id, Some(client_secret));
+        }
+
+        let response = request.send().await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = read_error_text(response, max_response_bytes()).await;
+            return Err(OidcError::Auth(format!(
+                "Device authorization failed with status {status}: {error_text}"
+            )));
+        }
+
+        let body = read_body_capped(response, max_response_bytes()).await?;
+        serde_json::from_slice(&body)
+            .map_err(|e| OidcError::Auth(format!("Failed to parse device authorization: {e}")))
+    }
+
+    /// Make a single device token request
+    pub async fn poll_device_token(&self, device_code: &str) -> Result<DevicePoll> {
+        let mut params = HashMap::new();
+        params.insert("grant_type", DEVICE_CODE_GRANT_TYPE);
+        params.insert("device_code", device_code);
+        params.insert("client_id", &self.profile.client_id);
+
+        let response = self.post_token_request_with_nonce(&params).await?;
+
+        if response.status().is_success() {
+            return Ok(DevicePoll::Complete(read_token_response(response).await?));
+        }
 
-        validate_token_response(&token_response)?;
+        let status = response.status();
+        let error_text = read_error_text(response, max_response_bytes()).await;
+        let error_code = serde_json::from_str::<serde_json::Value>(&error_text)
+            .ok()
+            .and_then(|v| v.get("error").and_then(|e| e.as_str()).map(String::from));
 
-        Ok(token_response)
+        match error_code.as_deref() {
+            Some("authorization_pending") => Ok(DevicePoll::Pending),
+            Some("slow_down") => Ok(DevicePoll::SlowDown),
+            Some("access_denied") => Err(OidcError::Auth(
+                "The authorization request was denied".to_string(),
+            )),
+            Some("expired_token") => Err(OidcError::Auth(
+                "The device code expired before authorization completed".to_string(),
+            )),
+            _ => Err(OidcError::Auth(format!(
+                "Token exchange failed with status {status}: {error_text}"
+            ))),
+        }
+    }
+
+    /// Poll the token endpoint until the user completes the device authorization
+    pub async fn poll_for_device_tokens(
+        &self,
+        device: &DeviceAuthorization,
+        backoff: &mut DeviceBackoff,
+        verbose: bool,
+    ) -> Result<TokenResponse> {
+        loop {
+            let remaining = backoff.remaining().ok_or_else(|| {
+                OidcError::Auth(
+                    "The device code expired before authorization completed".to_string(),
+                )
+            })?;
+            tokio::time::sleep(backoff.interval().min(remaining)).await;
+
+            if verbose {
+                crate::ui::progress(format!(
+                    "Polling token endpoint (interval {}s)...",
+                    backoff.interval().as_secs()
+                ));
+            }
+
+            match self.poll_device_token(&device.device_code).await? {
+                DevicePoll::Pending => {}
+                DevicePoll::SlowDown => {
+                    backoff.slow_down();
+                    if verbose {
+                        crate::ui::progress(format!(
+                            "Server asked to slow down; polling every {}s",
+                            backoff.interval().as_secs()
+                        ));
+                    }
+                }
+                DevicePoll::Complete(token_response) => return Ok(token_response),
+            }
+        }
     }
 }
 
+/// Read and validate a successful token endpoint response, JSON or form-encoded
+async fn read_token_response(response: reqwest::Response) -> Result<TokenResponse> {
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let body = read_body_capped(response, max_response_bytes()).await?;
+    let token_response = parse_token_response(&body, content_type.as_deref())?;
+
+    validate_token_response(&token_response)?;
+
+    Ok(token_response)
+}
+
+/// Space-separated `prompt` value with duplicates dropped, or `None` when empty
+fn join_prompt(values: &[String]) -> Option<String> {
+    let mut unique: Vec<&str> = Vec::new();
+    for value in values {
+        if !unique.contains(&value.as_str()) {
+            unique.push(value);
+        }
+    }
+    (!unique.is_empty()).then(|| unique.join(" "))
+}
+
 fn validate_token_response(response: &TokenResponse) -> Result<()> {
     if response.access_token.is_empty() {
         return Err(OidcError::InvalidTokenResponse);
@@ -187,6 +933,7 @@ fn validate_token_response(response: &TokenResponse) -> Result<()> {
 mod tests {
     use super::*;
     use crate::config::Profile;
+    use crate::test_support::{MockResponse, MockServer};
 
     fn create_test_profile() -> Profile {
         Profile {
@@ -197,6 +944,7 @@ mod tests {
             scope: "openid profile email".to_string(),
             authorization_endpoint: Some("https://example.com/auth".to_string()),
             token_endpoint: Some("https://example.com/token".to_string()),
+            ..Default::default()
         }
     }
 
@@ -207,6 +955,95 @@ mod tests {
         assert!(client.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_discovery_fallback_to_manual_endpoints() {
+        let mut profile = create_test_profile();
+        // Nothing listens on port 1, so discovery fails with a network error
+        profile.discovery_uri =
+            Some("https://127.0.0.1:1/.well-known/openid-configuration".to_string());
+        assert!(OAuthClient::new(profile.clone()).await.is_err());
+
+        profile.discovery_fallback = true;
+        let client = OAuthClient::new(profile.clone()).await.unwrap();
+        assert_eq!(client.authorization_endpoint, "https://example.com/auth");
+        assert_eq!(client.token_endpoint, "https://example.com/token");
+
+        profile.token_endpoint = None;
+        assert!(OAuthClient::new(profile).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_source_recorded_for_each_path() {
+        let manual = OAuthClient::new(create_test_profile()).await.unwrap();
+        assert_eq!(manual.endpoint_source(), &EndpointSource::Manual);
+        assert_eq!(manual.endpoint_source().to_string(), "manual");
+
+        let server = MockServer::start(vec![MockResponse::json(
+            200,
+            r#"{
+                "issuer": "https://example.com",
+                "authorization_endpoint": "https://example.com/discovered-auth",
+                "token_endpoint": "https://example.com/discovered-token"
+            }"#,
+        )])
+        .await;
+        let discovery_uri = server.url("/.well-known/openid-configuration");
+        // Manual endpoints are configured too, but discovery wins
+        let mut profile = create_test_profile();
+        profile.discovery_uri = Some(discovery_uri.clone());
+        let discovered = OAuthClient::new(profile).await.unwrap();
+        assert_eq!(
+            discovered.endpoint_source(),
+            &EndpointSource::Discovery(discovery_uri.clone())
+        );
+        assert_eq!(
+            discovered.token_endpoint,
+            "https://example.com/discovered-token"
+        );
+        assert_eq!(
+            discovered.endpoint_source().to_string(),
+            format!("discovered from {discovery_uri}")
+        );
+
+        let mut profile = create_test_profile();
+        let unreachable = "https://127.0.0.1:1/.well-known/openid-configuration".to_string();
+        profile.discovery_uri = Some(unreachable.clone());
+        profile.discovery_fallback = true;
+        let fallback = OAuthClient::new(profile).await.unwrap();
+        assert_eq!(
+            fallback.endpoint_source(),
+            &EndpointSource::ManualFallback(unreachable)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reused_code_is_refused_before_contacting_server() {
+        let mut profile = create_test_profile();
+        profile.token_endpoint = Some("https://127.0.0.1:1/token".to_string());
+        let client = OAuthClient::new(profile).await.unwrap();
+
+        let first = client
+            .exchange_code_for_tokens("code-1", "s", "s", "verifier")
+            .await;
+        assert!(matches!(first, Err(OidcError::Network(_))));
+
+        let again = client
+            .clone()
+            .exchange_code_for_tokens("code-1", "s", "s", "verifier")
+            .await;
+        assert!(matches!(again, Err(OidcError::CodeReused)));
+
+        // A state mismatch doesn't consume the code
+        let mismatch = client
+            .exchange_code_for_tokens("code-2", "s", "other", "verifier")
+            .await;
+        assert!(matches!(mismatch, Err(OidcError::StateMismatch)));
+        let fresh = client
+            .exchange_code_for_tokens("code-2", "s", "s", "verifier")
+            .await;
+        assert!(matches!(fresh, Err(OidcError::Network(_))));
+    }
+
     #[tokio::test]
     async fn test_authorization_request_creation() {
         let profile = create_test_profile();
@@ -229,6 +1066,7 @@ mod tests {
             refresh_token: None,
             id_token: None,
             scope: None,
+            ..Default::default()
         };
         assert!(validate_token_response(&valid_response).is_ok());
 
@@ -239,7 +1077,657 @@ mod tests {
             refresh_token: None,
             id_token: None,
             scope: None,
+            ..Default::default()
         };
         assert!(validate_token_response(&invalid_response).is_err());
     }
+
+    #[tokio::test]
+    async fn test_authorization_request_with_request_uri() {
+        let mut profile = create_test_profile();
+        profile.request_uri = Some("https://example.com/requests/abc".to_string());
+        let client = OAuthClient::new(profile).await.unwrap();
+
+        let request = client.create_authorization_request().unwrap();
+        let url = Url::parse(&request.authorization_url).unwrap();
+        let params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+
+        assert_eq!(
+            params,
+            vec![
+                ("client_id".to_string(), "test-client".to_string()),
+                (
+                    "request_uri".to_string(),
+                    "https://example.com/requests/abc".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_device_polling_backs_off_on_slow_down() {
+        let server = MockServer::start(vec![
+            MockResponse::json(400, r#"{"error":"authorization_pending"}"#),
+            MockResponse::json(400, r#"{"error":"slow_down"}"#),
+            MockResponse::json(
+                200,
+                r#"{"access_token":"device-token","token_type":"Bearer","expires_in":3600}"#,
+            ),
+        ])
+        .await;
+
+        let mut profile = create_test_profile();
+        profile.token_endpoint = Some(server.url("/token"));
+        let client = OAuthClient::new(profile).await.unwrap();
+
+        let device = DeviceAuthorization {
+            device_code: "device-code".to_string(),
+            user_code: "ABCD-EFGH".to_string(),
+            verification_uri: "https://example.com/device".to_string(),
+            verification_uri_complete: None,
+            expires_in: 60,
+            interval: 0,
+        };
+        let mut backoff = DeviceBackoff::for_authorization(&device)
+            .with_step(std::time::Duration::from_millis(10));
+
+        let tokens = client
+            .poll_for_device_tokens(&device, &mut backoff, false)
+            .await
+            .unwrap();
+
+        assert_eq!(tokens.access_token, "device-token");
+        assert_eq!(backoff.interval(), std::time::Duration::from_millis(10));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0]
+            .body
+            .contains("grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Adevice_code"));
+    }
+
+    #[tokio::test]
+    async fn test_registered_redirect_uri_used_consistently() {
+        let server = MockServer::start(vec![MockResponse::json(
+            200,
+            r#"{"access_token":"token","token_type":"Bearer"}"#,
+        )])
+        .await;
+
+        let mut profile = create_test_profile();
+        profile.token_endpoint = Some(server.url("/token"));
+        let client = OAuthClient::new(profile)
+            .await
+            .unwrap()
+            .with_registered_redirect_uri(Some("https://proxy.example.com/cb".to_string()));
+
+        let request = client.create_authorization_request().unwrap();
+        let url = Url::parse(&request.authorization_url).unwrap();
+        let redirect = url
+            .query_pairs()
+            .find(|(k, _)| k == "redirect_uri")
+            .map(|(_, v)| v.into_owned());
+        assert_eq!(redirect.as_deref(), Some("https://proxy.example.com/cb"));
+
+        client
+            .exchange_code_for_tokens("code", &request.state, &request.state, "verifier")
+            .await
+            .unwrap();
+        let body = &server.requests()[0].body;
+        assert!(body.contains("redirect_uri=https%3A%2F%2Fproxy.example.com%2Fcb"));
+    }
+
+    #[tokio::test]
+    async fn test_token_response_json_and_form_encoded() {
+        let server = MockServer::start(vec![
+            MockResponse::json(
+                200,
+                r#"{"access_token":"json-token","token_type":"Bearer","expires_in":3600}"#,
+            ),
+            MockResponse::json(
+                200,
+                "access_token=form-token&token_type=bearer&expires_in=600&scope=openid+email",
+            )
+            .with_header(
+                "Content-Type",
+                "application/x-www-form-urlencoded; charset=utf-8",
+            ),
+        ])
+        .await;
+
+        let mut profile = create_test_profile();
+        profile.token_endpoint = Some(server.url("/token"));
+        let client = OAuthClient::new(profile).await.unwrap();
+
+        let json = client
+            .exchange_code_for_tokens("code-1", "s", "s", "verifier")
+            .await
+            .unwrap();
+        assert_eq!(json.access_token, "json-token");
+        assert_eq!(json.expires_in, Some(3600));
+
+        let form = client
+            .exchange_code_for_tokens("code-2", "s", "s", "verifier")
+            .await
+            .unwrap();
+        assert_eq!(form.access_token, "form-token");
+        assert_eq!(form.token_type, "bearer");
+        assert_eq!(form.expires_in, Some(600));
+        assert_eq!(form.scope.as_deref(), Some("openid email"));
+
+        // The last response repeats, so device polls see the form-encoded body too
+        let poll = client.poll_device_token("device-code").await.unwrap();
+        assert!(
+            matches!(poll, DevicePoll::Complete(ref tokens) if tokens.access_token == "form-token")
+        );
+
+        for request in server.requests() {
+            assert_eq!(request.headers["accept"], "application/json");
+        }
+
+        assert!(parse_token_response(
+            b"token_type=bearer",
+            Some("application/x-www-form-urlencoded")
+        )
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_token_request_format() {
+        for format in [None, Some("form"), Some("json")] {
+            let server = MockServer::start(vec![MockResponse::json(
+                200,
+                r#"{"access_token":"token","token_type":"Bearer"}"#,
+            )])
+            .await;
+
+            let mut profile = create_test_profile();
+            profile.token_endpoint = Some(server.url("/token"));
+            profile.token_request_format = format.map(str::to_string);
+            let client = OAuthClient::new(profile).await.unwrap();
+            client
+                .exchange_code_for_tokens("the-code", "s", "s", "the-verifier")
+                .await
+                .unwrap();
+
+            let request = &server.requests()[0];
+            let content_type = request.headers["content-type"].as_str();
+            if format == Some("json") {
+                assert_eq!(content_type, "application/json");
+                let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+                assert_eq!(body["grant_type"], "authorization_code");
+                assert_eq!(body["code"], "the-code");
+                assert_eq!(body["code_verifier"], "the-verifier");
+            } else {
+                assert_eq!(content_type, "application/x-www-form-urlencoded");
+                assert!(request.body.contains("grant_type=authorization_code"));
+                assert!(request.body.contains("code=the-code"));
+                assert!(request.body.contains("code_verifier=the-verifier"));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_build_end_session_url() {
+        let mut client = OAuthClient::new(create_test_profile()).await.unwrap();
+        assert!(client.build_end_session_url(None, None).is_err());
+
+        client.end_session_endpoint = Some("https://example.com/logout".to_string());
+        let url = client
+            .build_end_session_url(Some("id.token.hint"), Some("http://localhost:8080/bye"))
+            .unwrap();
+        let url = Url::parse(&url).unwrap();
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+
+        assert_eq!(url.path(), "/logout");
+        assert_eq!(params["client_id"], "test-client");
+        assert_eq!(params["id_token_hint"], "id.token.hint");
+        assert_eq!(
+            params["post_logout_redirect_uri"],
+            "http://localhost:8080/bye"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_credentials_grant() {
+        let server = MockServer::start(vec![MockResponse::json(
+            200,
+            r#"{"access_token":"service-token","token_type":"Bearer","expires_in":300}"#,
+        )])
+        .await;
+
+        let profile = Profile {
+            client_id: "ci-bot".to_string(),
+            client_secret: Some("ci-secret".to_string()),
+            scope: "api:read".to_string(),
+            token_endpoint: Some(server.url("/token")),
+            grant_type: Some(CLIENT_CREDENTIALS_GRANT.to_string()),
+            ..Default::default()
+        };
+        profile.validate().unwrap();
+        let client = OAuthClient::new(profile).await.unwrap();
+
+        let tokens = client
+            .with_audience(Some("https://api.example.com".to_string()))
+            .client_credentials_grant()
+            .await
+            .unwrap();
+        assert_eq!(tokens.access_token, "service-token");
+
+        let request = &server.requests()[0];
+        assert!(request
+            .body
+            .contains("audience=https%3A%2F%2Fapi.example.com"));
+        assert!(request.body.contains("grant_type=client_credentials"));
+        assert!(request.body.contains("scope=api%3Aread"));
+        assert!(!request.body.contains("redirect_uri"));
+        assert!(request.headers["authorization"].starts_with("Basic "));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_tokens() {
+        let server = MockServer::start(vec![
+            MockResponse::json(
+                200,
+                r#"{"access_token":"fresh","token_type":"Bearer","expires_in":300}"#,
+            ),
+            MockResponse::json(400, r#"{"error":"invalid_grant"}"#),
+        ])
+        .await;
+
+        let mut profile = create_test_profile();
+        profile.token_endpoint = Some(server.url("/token"));
+        let client = OAuthClient::new(profile).await.unwrap();
+
+        let tokens = client.refresh_tokens("refresh-1").await.unwrap();
+        assert_eq!(tokens.access_token, "fresh");
+        let err = client.refresh_tokens("refresh-1").await.unwrap_err();
+        assert!(err.to_string().contains("invalid_grant"), "{err}");
+
+        let request = &server.requests()[0];
+        assert!(request.body.contains("grant_type=refresh_token"));
+        assert!(request.body.contains("refresh_token=refresh-1"));
+        assert!(!request.body.contains("code_verifier"));
+    }
+
+    #[tokio::test]
+    async fn test_dpop_proofs_and_nonce_retry() {
+        let server = MockServer::start(vec![
+            MockResponse::json(400, r#"{"error":"use_dpop_nonce"}"#)
+                .with_header("DPoP-Nonce", "server-nonce"),
+            MockResponse::json(
+                200,
+                r#"{"access_token":"bound","token_type":"DPoP","expires_in":300}"#,
+            ),
+        ])
+        .await;
+
+        let mut profile = create_test_profile();
+        profile.token_endpoint = Some(server.url("/token"));
+        let key = Arc::new(DpopKey::generate().unwrap());
+        let client = OAuthClient::new(profile)
+            .await
+            .unwrap()
+            .with_dpop(Some(key.clone()));
+
+        let request = client.create_authorization_request().unwrap();
+        let url = Url::parse(&request.authorization_url).unwrap();
+        assert!(url
+            .query_pairs()
+            .any(|(name, value)| name == "dpop_jkt" && value == key.thumbprint()));
+
+        let tokens = client.refresh_tokens("refresh-1").await.unwrap();
+        assert_eq!(tokens.token_type, "DPoP");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2, "retried once with the nonce");
+        let (_, first) = crate::crypto::decode_jwt_segments(&requests[0].headers["dpop"]).unwrap();
+        assert!(first.get("nonce").is_none());
+        assert_eq!(first["htm"], "POST");
+        assert_eq!(first["htu"], server.url("/token"));
+        let (_, retry) = crate::crypto::decode_jwt_segments(&requests[1].headers["dpop"]).unwrap();
+        assert_eq!(retry["nonce"], "server-nonce");
+    }
+
+    #[tokio::test]
+    async fn test_device_poll_carries_dpop_proof() {
+        let server = MockServer::start(vec![
+            MockResponse::json(400, r#"{"error":"use_dpop_nonce"}"#)
+                .with_header("DPoP-Nonce", "server-nonce"),
+            MockResponse::json(
+                200,
+                r#"{"access_token":"bound","token_type":"DPoP","expires_in":300}"#,
+            ),
+        ])
+        .await;
+
+        let mut profile = create_test_profile();
+        profile.token_endpoint = Some(server.url("/token"));
+        let client = OAuthClient::new(profile)
+            .await
+            .unwrap()
+            .with_dpop(Some(Arc::new(DpopKey::generate().unwrap())));
+
+        let poll = client.poll_device_token("device-code").await.unwrap();
+        assert!(matches!(poll, DevicePoll::Complete(ref tokens) if tokens.token_type == "DPoP"));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2, "retried once with the nonce");
+        assert!(requests[0].body.contains("device_code=device-code"));
+        assert!(requests[0].headers.contains_key("dpop"));
+        let (_, retry) = crate::crypto::decode_jwt_segments(&requests[1].headers["dpop"]).unwrap();
+        assert_eq!(retry["htu"], server.url("/token"));
+        assert_eq!(retry["nonce"], "server-nonce");
+    }
+
+    #[tokio::test]
+    async fn test_revoke_token() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, ""),
+            MockResponse::json(400, r#"{"error":"unsupported_token_type"}"#),
+        ])
+        .await;
+
+        let mut client = OAuthClient::new(create_test_profile()).await.unwrap();
+        assert!(client.revoke_token("token", None).await.is_err());
+
+        client.revocation_endpoint = Some(server.url("/revoke"));
+        client
+            .revoke_token("refresh-1", Some(TokenTypeHint::RefreshToken))
+            .await
+            .unwrap();
+        let err = client.revoke_token("access-1", None).await.unwrap_err();
+        assert!(
+            err.to_string().contains("unsupported_token_type"),
+            "error body is surfaced: {err}"
+        );
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].body.contains("token=refresh-1"));
+        assert!(requests[0].body.contains("token_type_hint=refresh_token"));
+        assert!(requests[0].headers["authorization"].starts_with("Basic "));
+        assert!(!requests[1].body.contains("token_type_hint"));
+    }
+
+    #[tokio::test]
+    async fn test_introspect_token() {
+        let server = MockServer::start(vec![
+            MockResponse::json(200, r#"{"active":true,"scope":"openid"}"#),
+            MockResponse::json(200, r#"{"active":false}"#),
+            MockResponse::json(401, r#"{"error":"invalid_client"}"#),
+        ])
+        .await;
+
+        let mut client = OAuthClient::new(create_test_profile()).await.unwrap();
+        assert!(client.introspect_token("token").await.is_err());
+
+        client.introspection_endpoint = Some(server.url("/introspect"));
+        assert!(client.introspect_token("live").await.unwrap());
+        assert!(!client.introspect_token("revoked").await.unwrap());
+        let err = client.introspect_token("any").await.unwrap_err();
+        assert!(err.to_string().contains("invalid_client"), "{err}");
+
+        let request = &server.requests()[0];
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/introspect");
+        assert!(request.body.contains("token=live"));
+        assert!(request.body.contains("token_type_hint=access_token"));
+    }
+
+    #[tokio::test]
+    async fn test_no_pkce_omits_challenge_and_verifier() {
+        let server = MockServer::start(vec![MockResponse::json(
+            200,
+            r#"{"access_token":"token","token_type":"Bearer"}"#,
+        )])
+        .await;
+
+        let mut profile = create_test_profile();
+        profile.token_endpoint = Some(server.url("/token"));
+        profile.no_pkce = true;
+        let client = OAuthClient::new(profile).await.unwrap();
+
+        let request = client.create_authorization_request().unwrap();
+        let url = Url::parse(&request.authorization_url).unwrap();
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert!(!params.contains_key("code_challenge"));
+        assert!(!params.contains_key("code_challenge_method"));
+        assert_eq!(params["state"], request.state);
+
+        client
+            .exchange_code_for_tokens("code", &request.state, &request.state, "verifier")
+            .await
+            .unwrap();
+        assert!(!server.requests()[0].body.contains("code_verifier"));
+    }
+
+    #[tokio::test]
+    async fn test_required_scopes_injected_into_authorization_request() {
+        let mut profile = create_test_profile();
+        profile.scope = "profile email".to_string();
+        let client = OAuthClient::new(profile)
+            .await
+            .unwrap()
+            .with_required_scopes(vec!["openid".to_string()]);
+
+        let request = client.create_authorization_request().unwrap();
+        let url = Url::parse(&request.authorization_url).unwrap();
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert_eq!(params["scope"], "profile email openid");
+    }
+
+    #[tokio::test]
+    async fn test_id_token_hint_only_sent_when_present() {
+        let params_of = |client: &OAuthClient| -> HashMap<String, String> {
+            let request = client.create_authorization_request().unwrap();
+            Url::parse(&request.authorization_url)
+                .unwrap()
+                .query_pairs()
+                .into_owned()
+                .collect()
+        };
+
+        let client = OAuthClient::new(create_test_profile()).await.unwrap();
+        assert!(!params_of(&client).contains_key("id_token_hint"));
+
+        let client = client.with_id_token_hint(Some("cached.id.token".to_string()));
+        assert_eq!(params_of(&client)["id_token_hint"], "cached.id.token");
+
+        let client = client.with_id_token_hint(None);
+        assert!(!params_of(&client).contains_key("id_token_hint"));
+    }
+
+    #[tokio::test]
+    async fn test_prompt_values_are_space_joined() {
+        let prompt_of = |client: &OAuthClient| -> Option<String> {
+            let request = client.create_authorization_request().unwrap();
+            Url::parse(&request.authorization_url)
+                .unwrap()
+                .query_pairs()
+                .find(|(key, _)| key == "prompt")
+                .map(|(_, value)| value.into_owned())
+        };
+
+        let client = OAuthClient::new(create_test_profile()).await.unwrap();
+        assert_eq!(prompt_of(&client), None);
+
+        let client = client.with_prompt(vec!["consent".to_string()]);
+        assert_eq!(prompt_of(&client).as_deref(), Some("consent"));
+
+        let client = client.with_prompt(vec![
+            "login".to_string(),
+            "consent".to_string(),
+            "login".to_string(),
+        ]);
+        assert_eq!(prompt_of(&client).as_deref(), Some("login consent"));
+    }
+
+    #[tokio::test]
+    async fn test_openid_added_for_discovery_profiles() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let server = MockServer::start(vec![MockResponse::json(
+            200,
+            r#"{
+                "issuer": "https://example.com",
+                "authorization_endpoint": "https://example.com/auth",
+                "token_endpoint": "https://example.com/token"
+            }"#,
+        )])
+        .await;
+
+        let mut profile = create_test_profile();
+        profile.discovery_uri = Some(server.url("/.well-known/openid-configuration"));
+        profile.scope = "profile email".to_string();
+
+        let scope_of = |client: &OAuthClient| {
+            let request = client.create_authorization_request().unwrap();
+            let url = Url::parse(&request.authorization_url).unwrap();
+            let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+            params["scope"].clone()
+        };
+
+        let client = OAuthClient::new(profile.clone()).await.unwrap();
+        assert_eq!(scope_of(&client), "openid profile email");
+
+        profile.no_auto_openid = true;
+        let client = OAuthClient::new(profile.clone()).await.unwrap();
+        assert_eq!(scope_of(&client), "profile email");
+
+        // Manual-endpoint profiles are not assumed to be OIDC
+        let mut manual = create_test_profile();
+        manual.scope = "api".to_string();
+        let client = OAuthClient::new(manual).await.unwrap();
+        assert_eq!(scope_of(&client), "api");
+    }
+
+    #[tokio::test]
+    async fn test_pkce_method_negotiated_from_discovery() {
+        async fn negotiate(methods: Option<&str>, pinned: Option<&str>) -> Result<PkceMethod> {
+            let methods = methods
+                .map(|m| format!(r#", "code_challenge_methods_supported": {m}"#))
+                .unwrap_or_default();
+            let server = MockServer::start(vec![MockResponse::json(
+                200,
+                &format!(
+                    r#"{{
+                        "issuer": "https://example.com",
+                        "authorization_endpoint": "https://example.com/auth",
+                        "token_endpoint": "https://example.com/token"{methods}
+                    }}"#
+                ),
+            )])
+            .await;
+            let mut profile = create_test_profile();
+            profile.discovery_uri = Some(server.url("/.well-known/openid-configuration"));
+            profile.pkce_method = pinned.map(str::to_string);
+            OAuthClient::new(profile)
+                .await
+                .map(|client| client.pkce_method())
+        }
+
+        assert_eq!(
+            negotiate(Some(r#"["plain", "S256"]"#), None).await.unwrap(),
+            PkceMethod::S256
+        );
+        assert_eq!(
+            negotiate(Some(r#"["plain"]"#), None).await.unwrap(),
+            PkceMethod::Plain
+        );
+        assert_eq!(negotiate(None, None).await.unwrap(), PkceMethod::S256);
+        let err = negotiate(Some(r#"["S512"]"#), None).await.unwrap_err();
+        assert!(err.to_string().contains("S512"), "{err}");
+        // A pinned method wins over discovery
+        assert_eq!(
+            negotiate(Some(r#"["S256"]"#), Some("plain")).await.unwrap(),
+            PkceMethod::Plain
+        );
+        assert_eq!(
+            negotiate(Some(r#"["S512"]"#), Some("S256")).await.unwrap(),
+            PkceMethod::S256
+        );
+    }
+
+    #[tokio::test]
+    async fn test_plain_pkce_challenge_is_the_verifier() {
+        let mut profile = create_test_profile();
+        profile.pkce_method = Some("plain".to_string());
+        let client = OAuthClient::new(profile).await.unwrap();
+        let request = client.create_authorization_request().unwrap();
+        let url = Url::parse(&request.authorization_url).unwrap();
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert_eq!(params["code_challenge_method"], "plain");
+        assert_eq!(params["code_challenge"], request.pkce_challenge.verifier);
+    }
+
+    #[test]
+    fn test_token_selection() {
+        let selection = TokenSelection::parse("id, refresh").unwrap();
+        assert!(!selection.access && selection.id && selection.refresh);
+        assert!(TokenSelection::parse("access,bogus").is_err());
+        assert!(TokenSelection::parse(" , ").is_err());
+
+        let response = TokenResponse {
+            access_token: "at".to_string(),
+            token_type: "Bearer".to_string(),
+            id_token: Some("idt".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(selection.missing(&response), vec!["refresh"]);
+
+        let mut value = serde_json::to_value(&response).unwrap();
+        selection.strip_unselected(&mut value);
+        assert!(value.get("access_token").is_none());
+        assert_eq!(value["id_token"], "idt");
+        assert_eq!(value["token_type"], "Bearer");
+    }
+
+    #[test]
+    fn test_expires_at_falls_back_to_id_token_exp() {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"sub":"user","exp":1700003600}"#);
+        let mut response = TokenResponse {
+            access_token: "at".to_string(),
+            token_type: "Bearer".to_string(),
+            id_token: Some(format!("e30.{payload}.sig")),
+            ..Default::default()
+        };
+        assert_eq!(response.id_token_exp(), Some(1700003600));
+        assert_eq!(response.expires_at(1700000000), Some(1700003600));
+
+        // expires_in wins when the provider sends it
+        response.expires_in = Some(60);
+        assert_eq!(response.expires_at(1700000000), Some(1700000060));
+
+        // Neither source: unknown expiry
+        response.expires_in = None;
+        response.id_token = Some("not-a-jwt".to_string());
+        assert_eq!(response.expires_at(1700000000), None);
+        response.id_token = None;
+        assert_eq!(response.expires_at(1700000000), None);
+    }
+
+    #[test]
+    fn test_token_response_refresh_expires_in() {
+        let response: TokenResponse = serde_json::from_str(
+            r#"{"access_token":"at","token_type":"Bearer","expires_in":300,
+                "refresh_token":"rt","refresh_expires_in":1800}"#,
+        )
+        .unwrap();
+        assert_eq!(response.refresh_expires_in, Some(1800));
+
+        let response: TokenResponse = serde_json::from_str(
+            r#"{"access_token":"at","token_type":"Bearer","refresh_token_expires_in":86400}"#,
+        )
+        .unwrap();
+        assert_eq!(response.refresh_expires_in, Some(86400));
+
+        let response: TokenResponse =
+            serde_json::from_str(r#"{"access_token":"at","token_type":"Bearer"}"#).unwrap();
+        assert_eq!(response.refresh_expires_in, None);
+        let value = serde_json::to_value(&response).unwrap();
+        assert!(value.get("refresh_expires_in").is_none());
+    }
 }
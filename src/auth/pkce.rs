@@ -1 +1,31 @@
-pub use crate::crypto::{generate_state, PkceChallenge};
+pub use crate::crypto::{generate_state, PkceChallenge, PkceMethod};
+
+use crate::error::{OidcError, Result};
+
+/// Pick the PKCE method for a login: the profile's pinned method if any, otherwise
+/// `S256` when the provider supports it or does not say, and `plain` only when
+/// that is all it advertises.
+pub fn negotiate_pkce_method(
+    pinned: Option<PkceMethod>,
+    supported: Option<&[String]>,
+) -> Result<PkceMethod> {
+    if let Some(method) = pinned {
+        return Ok(method);
+    }
+
+    let Some(supported) = supported else {
+        return Ok(PkceMethod::S256);
+    };
+    let offers = |method: PkceMethod| supported.iter().any(|m| m == method.as_str());
+    if offers(PkceMethod::S256) {
+        Ok(PkceMethod::S256)
+    } else if offers(PkceMethod::Plain) {
+        Ok(PkceMethod::Plain)
+    } else {
+        Err(OidcError::Config(format!(
+            "The provider supports no usable PKCE method (code_challenge_methods_supported: {}); \
+             set pkce_method in the profile to override",
+            supported.join(", ")
+        )))
+    }
+}
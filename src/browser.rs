@@ -1,14 +1,149 @@
 use crate::error::{OidcError, Result};
+use crate::ui::{json_diagnostics, progress, warn};
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
 
 pub trait BrowserOpener {
     fn open_with_fallback(&self, url: &str, quiet: bool) -> Result<()>;
 }
 
-pub struct WebBrowserOpener;
+#[derive(Default)]
+pub struct WebBrowserOpener {
+    /// Offer to retry when the browser fails to open on an interactive terminal
+    pub retry: bool,
+    /// Try a private/incognito window first, falling back to a normal open
+    pub incognito: bool,
+}
 
 impl BrowserOpener for WebBrowserOpener {
     fn open_with_fallback(&self, url: &str, quiet: bool) -> Result<()> {
-        open_browser_with_fallback(url, quiet)
+        if self.incognito {
+            if open_private_window(url).is_ok() {
+                if !quiet {
+                    progress("Opening private browser window for authentication...");
+                }
+                return Ok(());
+            }
+            warn("could not open a private browser window; opening the default browser instead");
+        }
+        open_browser_with_fallback(url, quiet, self.retry)
+    }
+}
+
+/// Browsers tried in order for a private window; `$BROWSER` is tried before these
+#[cfg(target_os = "macos")]
+const PRIVATE_WINDOW_BROWSERS: &[&str] = &[
+    "Google Chrome",
+    "Firefox",
+    "Microsoft Edge",
+    "Brave Browser",
+    "Chromium",
+];
+
+#[cfg(windows)]
+const PRIVATE_WINDOW_BROWSERS: &[&str] = &["chrome", "msedge", "firefox", "brave"];
+
+#[cfg(not(any(target_os = "macos", windows)))]
+const PRIVATE_WINDOW_BROWSERS: &[&str] = &[
+    "google-chrome",
+    "google-chrome-stable",
+    "chromium",
+    "chromium-browser",
+    "firefox",
+    "microsoft-edge",
+    "brave-browser",
+];
+
+/// A program and arguments that open a URL in a private window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaunchCommand {
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+/// The private-window flag for a browser, recognised by its executable or app name
+pub fn private_window_flag(browser: &str) -> Option<&'static str> {
+    let name = std::path::Path::new(browser)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(browser)
+        .to_ascii_lowercase();
+
+    if name.contains("firefox") {
+        Some("--private-window")
+    } else if name.contains("edge") {
+        Some("--inprivate")
+    } else if ["chrome", "chromium", "brave"]
+        .iter()
+        .any(|family| name.contains(family))
+    {
+        Some("--incognito")
+    } else {
+        None
+    }
+}
+
+/// The command opening `url` privately in `browser`, or `None` for browsers without a
+/// known flag. On macOS `browser` is an application name launched through `open`.
+pub fn private_window_command(browser: &str, url: &str) -> Option<LaunchCommand> {
+    let flag = private_window_flag(browser)?;
+    let (program, args) = if cfg!(target_os = "macos") {
+        ("open", vec!["-na", browser, "--args", flag, url])
+    } else {
+        (browser, vec![flag, url])
+    };
+    Some(LaunchCommand {
+        program: program.to_string(),
+        args: args.into_iter().map(str::to_string).collect(),
+    })
+}
+
+/// Launch the first available browser that supports private windows
+pub fn open_private_window(url: &str) -> Result<()> {
+    let preferred = std::env::var("BROWSER").ok();
+    let candidates = preferred
+        .iter()
+        .map(String::as_str)
+        .chain(PRIVATE_WINDOW_BROWSERS.iter().copied());
+
+    for browser in candidates {
+        let Some(command) = private_window_command(browser, url) else {
+            continue;
+        };
+        let mut process = Command::new(&command.program);
+        process
+            .args(&command.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        // `open` exits once the app is launched and fails for unknown apps; a browser
+        // started directly keeps running, so only a failed spawn counts there
+        let launched = if cfg!(target_os = "macos") {
+            process.status().is_ok_and(|status| status.success())
+        } else {
+            process.spawn().is_ok()
+        };
+        if launched {
+            return Ok(());
+        }
+    }
+
+    Err(OidcError::BrowserFailed)
+}
+
+/// Why a browser is likely to open from this session, or `None` when it probably
+/// cannot (e.g. a headless Linux host); used by `doctor`
+pub fn browser_hint() -> Option<&'static str> {
+    let set = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+    if set("BROWSER") {
+        Some("BROWSER is set")
+    } else if cfg!(any(target_os = "macos", target_os = "windows")) {
+        Some("system default")
+    } else if set("DISPLAY") || set("WAYLAND_DISPLAY") {
+        Some("graphical session")
+    } else {
+        None
     }
 }
 
@@ -22,23 +157,53 @@ pub fn open_browser(url: &str) -> Result<()> {
     }
 }
 
-pub fn open_browser_with_fallback(url: &str, quiet: bool) -> Result<()> {
-    match open_browser(url) {
-        Ok(_) => {
-            if !quiet {
-                println!("Opening browser for authentication...");
+pub fn open_browser_with_fallback(url: &str, quiet: bool, retry: bool) -> Result<()> {
+    loop {
+        match open_browser(url) {
+            Ok(_) => {
+                if !quiet {
+                    progress("Opening browser for authentication...");
+                }
+                return Ok(());
             }
-            Ok(())
-        }
-        Err(_) => {
-            if !quiet {
-                println!("Unable to open browser automatically.");
-                println!("Please manually open the following URL in your browser:");
-                println!();
-                println!("{url}");
-                println!();
+            Err(_) => {
+                if should_offer_retry(retry, quiet, io::stdin().is_terminal()) && confirm_retry()? {
+                    continue;
+                }
+
+                print_manual_open_instructions(url, quiet);
+                return Ok(());
             }
-            Ok(())
+        }
+    }
+}
+
+/// Retrying needs someone to answer the prompt, so only offer it on an interactive terminal
+fn should_offer_retry(retry: bool, quiet: bool, is_tty: bool) -> bool {
+    retry && !quiet && is_tty
+}
+
+fn confirm_retry() -> Result<bool> {
+    // On stderr, like the other prompts, so stdout only carries the command's output
+    eprint!("Retry opening browser? (y/N): ");
+    io::stderr().flush().unwrap();
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+fn print_manual_open_instructions(url: &str, quiet: bool) {
+    if !quiet {
+        progress("Unable to open browser automatically.");
+        progress("Please manually open the following URL in your browser:");
+        if !json_diagnostics() {
+            println!();
+        }
+        progress(url);
+        if !json_diagnostics() {
+            println!();
         }
     }
 }
@@ -83,6 +248,51 @@ impl BrowserOpener for MockBrowserOpener {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_retry_only_offered_interactively() {
+        assert!(should_offer_retry(true, false, true));
+        assert!(!should_offer_retry(false, false, true));
+        assert!(!should_offer_retry(true, true, true));
+        assert!(!should_offer_retry(true, false, false));
+    }
+
+    #[test]
+    fn test_private_window_flags() {
+        assert_eq!(private_window_flag("google-chrome"), Some("--incognito"));
+        assert_eq!(private_window_flag("Google Chrome"), Some("--incognito"));
+        assert_eq!(
+            private_window_flag("/usr/bin/chromium"),
+            Some("--incognito")
+        );
+        assert_eq!(private_window_flag("brave-browser"), Some("--incognito"));
+        assert_eq!(private_window_flag("firefox"), Some("--private-window"));
+        assert_eq!(private_window_flag("msedge"), Some("--inprivate"));
+        assert_eq!(private_window_flag("microsoft-edge"), Some("--inprivate"));
+        assert_eq!(private_window_flag("lynx"), None);
+    }
+
+    #[test]
+    fn test_private_window_command() {
+        let url = "https://idp.example.com/authorize?client_id=cli&state=abc";
+        assert!(private_window_command("w3m", url).is_none());
+
+        let command = private_window_command("firefox", url).unwrap();
+        if cfg!(target_os = "macos") {
+            assert_eq!(command.program, "open");
+            assert_eq!(
+                command.args,
+                vec!["-na", "firefox", "--args", "--private-window", url]
+            );
+        } else {
+            assert_eq!(command.program, "firefox");
+            assert_eq!(command.args, vec!["--private-window", url]);
+        }
+
+        for browser in PRIVATE_WINDOW_BROWSERS {
+            assert!(private_window_command(browser, url).is_some(), "{browser}");
+        }
+    }
+
     #[test]
     fn test_mock_browser_opener_with_invalid_url() {
         let mock = MockBrowserOpener::new();
@@ -1,5 +1,15 @@
-use clap::{ArgAction, Parser, Subcommand};
+use crate::auth::TokenTypeHint;
+use crate::error::Result;
+use crate::http::{Correlation, MAX_RESPONSE_BYTES_ENV, PROXY_ENV};
+use crate::profile::foreign::ImportFormat;
+use crate::profile::validation::OAUTH21_ENV;
+use crate::server::BrowserTokenDisplay;
+use crate::settings::{self, Settings};
+use crate::ui;
+use crate::utils::env::load_env_file;
+use clap::{ArgAction, CommandFactory, Parser, Subcommand};
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "oidc-cli")]
@@ -9,20 +19,225 @@ pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
 
-    #[arg(short, long, global = true)]
-    pub verbose: bool,
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = ArgAction::Count,
+        help = "Show detailed output (-vv for protocol traces)"
+    )]
+    pub verbose: u8,
 
     #[arg(short, long, global = true)]
     pub quiet: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        help = "Load KEY=VALUE pairs from a .env file (real environment variables take precedence)"
+    )]
+    pub env_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "Store profiles and tokens in DIR instead of the default config directory"
+    )]
+    pub config_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        help = "Profiles file name inside the config directory (default profiles.json, or OIDC_CLI_PROFILES_FILE)"
+    )]
+    pub profiles_file: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        conflicts_with = "profiles_file",
+        help = "Read and write profiles in this JSON or YAML file instead of the config directory"
+    )]
+    pub config: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "URL",
+        help = "Send discovery and token requests through this proxy (hosts in NO_PROXY bypass it)"
+    )]
+    pub proxy: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "MIME",
+        help = "Accept header for discovery requests, e.g. application/jrd+json (default application/json)"
+    )]
+    pub discovery_accept: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "SECS",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Give up on discovery (including DNS, TLS and redirects) after this many seconds"
+    )]
+    pub discovery_timeout: Option<u64>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Reject discovery documents whose issuer is on a different host than the discovery URL (default: warn)"
+    )]
+    pub strict_issuer: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Enforce OAuth 2.1: require PKCE, reject hybrid response types, mismatched redirect URIs and non-loopback http URLs"
+    )]
+    pub oauth21: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Tag discovery and token requests with a per-invocation correlation ID header"
+    )]
+    pub correlation_id: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        requires = "correlation_id",
+        help = "Header carrying the correlation ID (default X-Correlation-ID)"
+    )]
+    pub correlation_header: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Accept http redirect URIs on *.localhost loopback aliases such as app.localhost"
+    )]
+    pub allow_insecure_localhost_http: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Suppress non-fatal warnings while keeping normal output; security warnings still show"
+    )]
+    pub quiet_warnings: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "LANG",
+        help = "Language for prompts and errors, e.g. de (default from LC_ALL/LC_MESSAGES/LANG; English fallback)"
+    )]
+    pub lang: Option<String>,
 }
 
+// Parsed once per run, so the size of the Login variant does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 pub enum Commands {
     #[command(about = "Authenticate using a profile")]
     Login {
-        #[arg(help = "Profile name to use for authentication")]
+        #[arg(
+            help = "Profile name to use for authentication",
+            conflicts_with = "adhoc"
+        )]
         profile: Option<String>,
 
+        #[arg(
+            long,
+            conflicts_with_all = [
+                "profile", "adhoc", "explain", "resume", "state_dir", "callback_file",
+                "output_fd", "output_template", "copy", "claims_out", "metrics_json",
+                "access_token_file", "id_token_file", "refresh_token_file",
+            ],
+            help = "Log in to every profile in turn and print all tokens as one JSON object keyed by profile"
+        )]
+        all: bool,
+
+        #[arg(
+            long,
+            requires = "client_id",
+            help = "Log in with the connection flags below without saving a profile"
+        )]
+        adhoc: bool,
+
+        #[arg(
+            long,
+            conflicts_with_all = ["profile", "adhoc", "all", "resume", "choose_scopes"],
+            help = "Read {\"profile\": {...}, \"options\": {...}} JSON from stdin and log in without saving anything"
+        )]
+        stdin_profile: bool,
+
+        #[arg(long, requires = "adhoc", help = "Client ID (ad-hoc login)")]
+        client_id: Option<String>,
+
+        #[arg(long, requires = "adhoc", help = "Client secret (ad-hoc login)")]
+        client_secret: Option<String>,
+
+        #[arg(
+            long,
+            help = "Redirect URI to use instead of the profile's (ad-hoc default http://localhost:8080/callback)"
+        )]
+        redirect_uri: Option<String>,
+
+        #[arg(
+            long,
+            help = "OAuth scope to request instead of the profile's (ad-hoc default openid)"
+        )]
+        scope: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "Request a named scope preset (merged with --scope)"
+        )]
+        scope_preset: Option<String>,
+
+        #[arg(
+            long = "for",
+            value_name = "API",
+            conflicts_with_all = ["scope", "scope_preset", "choose_scopes", "use_cache", "all"],
+            help = "Request the scope and audience configured for API in the profile's 'apis'"
+        )]
+        for_api: Option<String>,
+
+        #[arg(
+            long,
+            help = "Pick scopes for this login from the provider's scopes_supported (interactive)"
+        )]
+        choose_scopes: bool,
+
+        #[arg(
+            long,
+            conflicts_with_all = ["device", "resume", "callback_file"],
+            help = "Print the authorization URL's parameters (or JSON with --json) instead of logging in"
+        )]
+        explain: bool,
+
+        #[arg(long, requires = "adhoc", help = "OIDC discovery URI (ad-hoc login)")]
+        discovery_uri: Option<String>,
+
+        #[arg(
+            long,
+            requires = "adhoc",
+            help = "Authorization endpoint (ad-hoc login)"
+        )]
+        auth_endpoint: Option<String>,
+
+        #[arg(long, requires = "adhoc", help = "Token endpoint (ad-hoc login)")]
+        token_endpoint: Option<String>,
+
         #[arg(short, long, help = "Port for the callback server")]
         port: Option<u16>,
 
@@ -39,6 +254,457 @@ pub enum Commands {
             help = "Write token output to file (implies --json)"
         )]
         output: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "FD",
+            conflicts_with = "output",
+            help = "Write JSON token output to this inherited file descriptor (Unix; implies --json)"
+        )]
+        output_fd: Option<i32>,
+
+        #[arg(
+            long,
+            value_name = "TEMPLATE",
+            conflicts_with_all = ["json", "output"],
+            help = "Render tokens with a template using {access_token}, {id_token}, {refresh_token}, {token_type}, {expires_in} and {scope}"
+        )]
+        output_template: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "VALUE",
+            help = "Fail unless the id_token 'acr' claim equals this value"
+        )]
+        require_acr: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "VALUE",
+            help = "Fail unless the id_token 'amr' claim contains this value (e.g. mfa)"
+        )]
+        require_amr: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "URL",
+            help = "Pass an externally-hosted request object via request_uri (state and PKCE come from the request object)"
+        )]
+        request_uri: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "STATE",
+            help = "The state set in the request_uri request object, so the callback can be checked against it"
+        )]
+        request_state: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "URI",
+            help = "Redirect URI sent to the provider when it differs from the locally-bound one (e.g. behind a reverse proxy)"
+        )]
+        registered_redirect_uri: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with = "port",
+            help = "Serve the callback on a Unix domain socket behind a reverse proxy (Unix only)"
+        )]
+        uds: Option<PathBuf>,
+
+        #[arg(
+            long,
+            help = "Use the device authorization grant instead of a browser redirect",
+            conflicts_with_all = ["port", "request_uri", "uds"]
+        )]
+        device: bool,
+
+        #[arg(
+            long,
+            value_name = "GRANT",
+            value_parser = ["authorization_code", "client_credentials"],
+            conflicts_with_all = ["device", "resume", "callback_file"],
+            help = "Grant to use instead of the profile's grant_type; client_credentials gets a token for the client itself, without a browser"
+        )]
+        grant: Option<String>,
+
+        #[arg(
+            long,
+            conflicts_with_all = ["state_dir", "use_cache"],
+            help = "Request DPoP-bound tokens (RFC 9449) using a key generated for this login only"
+        )]
+        dpop: bool,
+
+        #[arg(
+            long,
+            help = "Offer to retry when the browser cannot be opened (interactive terminals only)"
+        )]
+        retry_browser: bool,
+
+        #[arg(
+            long,
+            conflicts_with_all = ["device", "callback_file"],
+            help = "Open the authorization URL in a private/incognito window (Chrome, Firefox, Edge, Brave), e.g. to log in as another account"
+        )]
+        incognito: bool,
+
+        #[arg(
+            long,
+            default_value_t = 0,
+            value_name = "N",
+            conflicts_with_all = ["device", "callback_file", "state_dir"],
+            help = "On callback timeout, start a fresh authorization up to N times, doubling the wait each time"
+        )]
+        retries: u32,
+
+        #[arg(
+            long,
+            value_name = "SECS",
+            conflicts_with_all = ["device", "callback_file"],
+            help = "Also give up once the callback server has received no request for SECS seconds"
+        )]
+        idle_timeout: Option<u64>,
+
+        #[arg(
+            long,
+            value_name = "MODE",
+            num_args = 0..=1,
+            default_missing_value = "full",
+            conflicts_with_all = ["device", "callback_file"],
+            help = "Show the tokens on the browser success page: full (default) or preview. Off unless given or set in the profile"
+        )]
+        show_token_in_browser: Option<BrowserTokenDisplay>,
+
+        #[arg(
+            long,
+            help = "Warn if the local clock drifts from the provider's Date header"
+        )]
+        check_clock: bool,
+
+        #[arg(
+            long,
+            help = "Omit PKCE parameters (insecure; confidential clients only)"
+        )]
+        no_pkce: bool,
+
+        #[arg(
+            long,
+            help = "Do not add 'openid' to the scope of discovery-based profiles"
+        )]
+        no_auto_openid: bool,
+
+        #[arg(long, help = "Use the profile's manual endpoints if discovery fails")]
+        discovery_fallback: bool,
+
+        #[arg(
+            long,
+            help = "Send the cached id_token as id_token_hint so the provider can pre-select the account"
+        )]
+        hint: bool,
+
+        #[arg(
+            long,
+            help = "Send prompt=login to force re-authentication at the provider"
+        )]
+        fresh: bool,
+
+        #[arg(
+            long,
+            help = "Send prompt=consent to make the provider show the consent screen again"
+        )]
+        consent: bool,
+
+        #[arg(
+            long,
+            help = "Check the id_token's aud, azp and exp claims (the signature only with --jwks-file)"
+        )]
+        verify: bool,
+
+        #[arg(
+            long,
+            requires = "verify",
+            help = "With --verify, check the id_token signature without network access (needs --jwks-file)"
+        )]
+        offline: bool,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            requires = "verify",
+            help = "With --verify, check the id_token signature against the keys in this JWKS file"
+        )]
+        jwks_file: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Write the decoded id_token claims as JSON to PATH (checked first with --verify)"
+        )]
+        claims_out: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Write the raw access token to PATH (mode 0600)"
+        )]
+        access_token_file: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Write the raw id_token to PATH (mode 0600), if one was returned"
+        )]
+        id_token_file: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Write the raw refresh token to PATH (mode 0600), if one was returned"
+        )]
+        refresh_token_file: Option<PathBuf>,
+
+        #[arg(
+            long = "pin-sha256",
+            value_name = "BASE64",
+            help = "Only accept provider TLS certificates with this SPKI SHA-256 (repeatable; overrides the profile's pins)"
+        )]
+        pin_sha256: Vec<String>,
+
+        #[arg(
+            long,
+            value_name = "LIST",
+            help = "Print only these tokens: comma list of access, id, refresh (default: all returned)"
+        )]
+        show: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "CMD",
+            help = "Run a shell command after login with the tokens in OIDC_ACCESS_TOKEN etc."
+        )]
+        on_success: Option<String>,
+
+        #[arg(
+            long,
+            help = "Run the profile's post_login_hook without asking for confirmation"
+        )]
+        run_hook: bool,
+
+        #[arg(
+            long = "cached",
+            help = "Cache tokens in tokens.json and reuse a valid cached token obtained for the same scopes instead of authenticating; also caches discovery documents"
+        )]
+        use_cache: bool,
+
+        #[arg(
+            long,
+            value_name = "DIR",
+            help = "Save the in-progress authorization request (incl. PKCE verifier, mode 0600) so it can be resumed"
+        )]
+        state_dir: Option<PathBuf>,
+
+        #[arg(
+            long,
+            requires = "state_dir",
+            conflicts_with = "device",
+            help = "Finish a login saved with --state-dir by pasting the callback URL"
+        )]
+        resume: bool,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Write a JSON record of the outcome, phase timings and bytes received"
+        )]
+        metrics_json: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with_all = ["device", "resume", "uds", "state_dir"],
+            help = "Print the authorization URL and wait (up to 5 minutes) for another tool to write the callback URL to this file"
+        )]
+        callback_file: Option<PathBuf>,
+
+        #[arg(
+            long,
+            value_name = "JSON",
+            help = "OIDC claims request object, e.g. '{\"id_token\":{\"email\":{\"essential\":true}}}'"
+        )]
+        claims: Option<String>,
+
+        #[arg(
+            long = "claims-preset",
+            value_name = "NAME",
+            help = "Request a claims preset (email, profile, address or a custom one); repeatable, merged under --claims"
+        )]
+        claims_preset: Vec<String>,
+    },
+
+    #[command(about = "End the provider session via RP-initiated logout")]
+    EndSession {
+        #[arg(help = "Profile name to log out of")]
+        profile: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "ID_TOKEN",
+            help = "id_token from the session to end"
+        )]
+        id_token_hint: Option<String>,
+
+        #[arg(
+            long,
+            value_name = "URI",
+            help = "Where the provider redirects after logout"
+        )]
+        post_logout_redirect_uri: Option<String>,
+    },
+
+    #[command(about = "Exchange the cached refresh token for new tokens")]
+    Refresh {
+        #[arg(help = "Profile whose cached refresh token to use")]
+        profile: Option<String>,
+    },
+
+    #[command(about = "Revoke a token at the provider's revocation endpoint (RFC 7009)")]
+    Revoke {
+        #[arg(help = "Profile whose client and provider to use")]
+        profile: Option<String>,
+
+        #[arg(
+            long,
+            conflicts_with = "token_stdin",
+            help = "Token to revoke (visible in shell history; prefer --token-stdin). Default: the profile's cached refresh token, else its access token"
+        )]
+        token: Option<String>,
+
+        #[arg(
+            long,
+            requires = "profile",
+            help = "Read the token to revoke from stdin (no echo on a terminal)"
+        )]
+        token_stdin: bool,
+
+        #[arg(
+            long,
+            value_enum,
+            value_name = "TYPE",
+            help = "token_type_hint to send; also picks which cached token to revoke"
+        )]
+        token_type_hint: Option<TokenTypeHint>,
+    },
+
+    #[command(about = "Decode a JWT's header and payload without verifying it")]
+    Decode {
+        #[arg(
+            long,
+            conflicts_with = "token_stdin",
+            help = "Token to decode (visible in shell history; prefer --token-stdin)"
+        )]
+        token: Option<String>,
+
+        #[arg(long, help = "Read the token from stdin (no echo on a terminal)")]
+        token_stdin: bool,
+
+        #[arg(
+            long,
+            help = "Print the header and payload as JSON instead of an annotated tree"
+        )]
+        raw: bool,
+    },
+
+    #[command(about = "Show recorded login attempts")]
+    Audit {
+        #[arg(
+            long,
+            value_name = "DURATION",
+            help = "Only entries newer than this, e.g. 90m, 12h, 7d or 2w"
+        )]
+        since: Option<String>,
+
+        #[arg(long, value_name = "NAME", help = "Only entries for this profile")]
+        profile: Option<String>,
+
+        #[arg(long, help = "Output as JSON")]
+        json: bool,
+    },
+
+    #[command(about = "List or clear cached discovery documents and tokens")]
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+
+    #[command(about = "Show whether profiles have a valid cached token")]
+    Status {
+        #[arg(help = "Profile name", conflicts_with = "all")]
+        profile: Option<String>,
+
+        #[arg(long, help = "Show the status of every profile")]
+        all: bool,
+
+        #[arg(long, help = "Output the status as a JSON array")]
+        json: bool,
+
+        #[arg(
+            long,
+            conflicts_with = "json",
+            help = "Print one JSON object per profile as it is checked"
+        )]
+        json_lines: bool,
+
+        #[arg(
+            long,
+            help = "Also ask the provider's introspection endpoint whether each valid token is still active"
+        )]
+        introspect: bool,
+    },
+
+    #[command(about = "Print a redacted environment report to paste into bug reports")]
+    Doctor {
+        #[arg(help = "Profile whose discovery host to check for reachability")]
+        profile: Option<String>,
+
+        #[arg(long, help = "Print the report as JSON")]
+        json: bool,
+    },
+
+    #[command(
+        about = "Obtain and verify a token for a profile, exiting non-zero on failure (for monitoring)"
+    )]
+    Selftest {
+        #[arg(help = "Profile to check")]
+        profile: Option<String>,
+
+        #[arg(long, help = "Print the result as one JSON object")]
+        json: bool,
+
+        #[arg(
+            long,
+            default_value_t = 30,
+            value_parser = clap::value_parser!(u64).range(1..),
+            help = "Seconds allowed for the whole check"
+        )]
+        timeout: u64,
+    },
+
+    #[command(about = "Start the callback server and wait for a test request")]
+    TestCallback {
+        #[arg(help = "Redirect URI to serve, e.g. http://localhost:8080/callback")]
+        redirect_uri: String,
+
+        #[arg(
+            short,
+            long,
+            help = "Port override (defaults to the redirect URI's port)"
+        )]
+        port: Option<u16>,
+
+        #[arg(long, default_value_t = 120, help = "Seconds to wait for a request")]
+        timeout: u64,
     },
 
     #[command(about = "List all available profiles")]
@@ -61,6 +727,13 @@ pub enum Commands {
         #[arg(long, help = "OAuth scope")]
         scope: Option<String>,
 
+        #[arg(
+            long,
+            value_name = "NAME",
+            help = "Use a named scope preset (merged with --scope)"
+        )]
+        scope_preset: Option<String>,
+
         #[arg(long, help = "OIDC discovery URI")]
         discovery_uri: Option<String>,
 
@@ -72,12 +745,41 @@ pub enum Commands {
 
         #[arg(long, help = "Non-interactive mode (requires all parameters)")]
         non_interactive: bool,
+
+        #[arg(
+            long,
+            conflicts_with = "non_interactive",
+            help = "Use the given flags and prompt only for missing required values"
+        )]
+        fill_missing: bool,
+
+        #[arg(
+            long,
+            default_value_t = true,
+            action = clap::ArgAction::Set,
+            num_args = 0..=1,
+            default_missing_value = "true",
+            help = "Hide the client secret while typing it in interactive mode (pass =false to echo it)"
+        )]
+        prompt_secret_always: bool,
+
+        #[arg(
+            long,
+            help = "Keep the scope string exactly as given (no deduplication)"
+        )]
+        no_normalize_scopes: bool,
     },
 
     #[command(about = "Edit an existing profile")]
     Edit {
         #[arg(help = "Name of the profile to edit")]
         name: String,
+
+        #[arg(
+            long,
+            help = "Keep the scope string exactly as given (no deduplication)"
+        )]
+        no_normalize_scopes: bool,
     },
 
     #[command(about = "Delete a profile")]
@@ -98,6 +800,42 @@ pub enum Commands {
         new_name: String,
     },
 
+    #[command(about = "Show a profile's settings (secrets masked)")]
+    Show {
+        #[arg(help = "Profile name")]
+        name: String,
+
+        #[arg(
+            long,
+            help = "Print an importable JSON snippet without the client secret"
+        )]
+        share: bool,
+    },
+
+    #[command(about = "Show the differences between two profiles")]
+    Diff {
+        #[arg(help = "First profile name")]
+        a: String,
+
+        #[arg(help = "Second profile name")]
+        b: String,
+
+        #[arg(long, help = "Output the diff as JSON")]
+        json: bool,
+    },
+
+    #[command(about = "Manage named scope presets")]
+    ScopePreset {
+        #[command(subcommand)]
+        action: ScopePresetAction,
+    },
+
+    #[command(about = "Print the config as JSON with client secrets masked")]
+    Dump {
+        #[arg(long, help = "Show client secrets instead of masking them")]
+        reveal: bool,
+    },
+
     #[command(about = "Export profiles to a file")]
     Export {
         #[arg(help = "Output file path")]
@@ -105,6 +843,9 @@ pub enum Commands {
 
         #[arg(help = "Specific profile names to export (exports all if not specified)")]
         profiles: Vec<String>,
+
+        #[arg(long, help = "Encrypt the export with a passphrase (prompted twice)")]
+        encrypt: bool,
     },
 
     #[command(about = "Import profiles from a file")]
@@ -112,25 +853,184 @@ pub enum Commands {
         #[arg(help = "Input file path")]
         file: PathBuf,
 
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = ImportFormat::Native,
+            help = "Format of the input file"
+        )]
+        from: ImportFormat,
+
         #[arg(short, long, help = "Overwrite existing profiles")]
         overwrite: bool,
+
+        #[arg(long, help = "Import profiles that carry a post_login_hook")]
+        allow_hook: bool,
+
+        #[arg(long, help = "Print one JSON object per imported profile")]
+        json_lines: bool,
     },
+
+    #[command(about = "Delete all stored profiles, cached tokens and lock files")]
+    Reset {
+        #[arg(short, long, help = "Skip confirmation prompt")]
+        force: bool,
+    },
+
+    #[command(about = "Print the JSON Schema of the profiles and import/export files")]
+    Schema,
+
+    #[command(about = "Print the version with build metadata (git commit, build date, features)")]
+    Version {
+        #[arg(long, help = "Output as JSON")]
+        json: bool,
+    },
+
+    #[command(about = "Print a roff man page to stdout", hide = true)]
+    Man,
+}
+
+#[derive(Subcommand)]
+pub enum CacheAction {
+    #[command(
+        about = "Show cached entries with their size, age and expiry (token values are never shown)"
+    )]
+    List {
+        #[arg(long, help = "Output as JSON")]
+        json: bool,
+    },
+
+    #[command(about = "Remove cached entries")]
+    #[command(group(clap::ArgGroup::new("caches").required(true).multiple(true)))]
+    Clear {
+        #[arg(long, group = "caches", help = "Clear cached discovery documents")]
+        discovery: bool,
+
+        #[arg(
+            long,
+            group = "caches",
+            help = "Clear cached tokens (profiles will need to log in again)"
+        )]
+        tokens: bool,
+
+        #[arg(long, group = "caches", help = "Clear every cache")]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ScopePresetAction {
+    #[command(about = "Add or replace a scope preset")]
+    Add {
+        #[arg(help = "Preset name")]
+        name: String,
+
+        #[arg(help = "Space-separated scope string")]
+        scope: String,
+    },
+
+    #[command(about = "Remove a scope preset")]
+    Remove {
+        #[arg(help = "Preset name")]
+        name: String,
+    },
+
+    #[command(about = "List scope presets")]
+    List,
 }
 
 impl Cli {
+    /// Global options for the lower layers, with the documented environment
+    /// fallbacks for `--proxy` and `--oauth21` and the response size limit
+    pub fn settings(&self) -> Settings {
+        let env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        Settings {
+            proxy: self.proxy.clone().or_else(|| env(PROXY_ENV)),
+            discovery_accept: self.discovery_accept.clone(),
+            discovery_timeout: self.discovery_timeout.map(Duration::from_secs),
+            strict_issuer: self.strict_issuer,
+            oauth21: self.oauth21 || env(OAUTH21_ENV).is_some(),
+            correlation: self
+                .correlation_id
+                .then(|| Correlation::new(self.correlation_header.clone())),
+            allow_insecure_localhost_http: self.allow_insecure_localhost_http,
+            quiet_warnings: self.quiet_warnings,
+            lang: self.lang.clone(),
+            json_diagnostics: self.wants_json(),
+            max_response_bytes: env(MAX_RESPONSE_BYTES_ENV)
+                .and_then(|value| value.trim().parse().ok()),
+        }
+    }
+
+    /// Load `--env-file` and then fix the global settings, so the environment
+    /// fallbacks in [`Cli::settings`] also see variables from the file
+    pub fn prepare(&self) -> Result<()> {
+        let injected = match self.env_file {
+            Some(ref env_file) => Some((env_file, load_env_file(env_file)?)),
+            None => None,
+        };
+        settings::init(self.settings());
+
+        // Diagnostics, not output: with --json these go to stderr
+        if self.is_verbose() {
+            if let Some(ref correlation) = settings::get().correlation {
+                ui::progress(format!("Correlation ID: {}", correlation.id));
+            }
+            if let Some((env_file, injected)) = injected {
+                ui::progress(format!(
+                    "Loaded {} variable(s) from {env_file:?}",
+                    injected.len()
+                ));
+            }
+        }
+        Ok(())
+    }
+
     pub fn is_verbose(&self) -> bool {
-        self.verbose && !self.quiet
+        self.verbose > 0 && !self.quiet
+    }
+
+    pub fn is_very_verbose(&self) -> bool {
+        self.verbose > 1 && !self.quiet
     }
 
     pub fn is_quiet(&self) -> bool {
         self.quiet
     }
+
+    /// Whether the command writes a JSON document, so diagnostics should be JSON too
+    pub fn wants_json(&self) -> bool {
+        match &self.command {
+            Commands::Login {
+                json,
+                output,
+                output_fd,
+                ..
+            } => *json || output.is_some() || output_fd.is_some(),
+            Commands::Audit { json, .. }
+            | Commands::Status { json, .. }
+            | Commands::Selftest { json, .. }
+            | Commands::Doctor { json, .. }
+            | Commands::Diff { json, .. }
+            | Commands::Version { json } => *json,
+            Commands::Cache {
+                action: CacheAction::List { json },
+            } => *json,
+            _ => false,
+        }
+    }
+}
+
+/// Render the man page for the whole CLI in roff format
+pub fn render_man_page() -> std::io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    clap_mangen::Man::new(Cli::command()).render(&mut buffer)?;
+    Ok(buffer)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use clap::CommandFactory;
 
     #[test]
     fn verify_cli_args() {
@@ -163,4 +1063,60 @@ mod tests {
         assert!(!cli.is_verbose());
         assert!(cli.is_quiet());
     }
+
+    #[test]
+    fn test_global_settings() {
+        let cli = Cli::try_parse_from([
+            "oidc-cli",
+            "--strict-issuer",
+            "--discovery-timeout",
+            "5",
+            "--correlation-id",
+            "--correlation-header",
+            "X-Request-ID",
+            "--lang",
+            "de",
+            "status",
+            "--json",
+        ])
+        .unwrap();
+        let settings = cli.settings();
+        assert!(settings.strict_issuer);
+        assert_eq!(settings.discovery_timeout, Some(Duration::from_secs(5)));
+        assert_eq!(settings.correlation.unwrap().header, "X-Request-ID");
+        assert_eq!(settings.lang.as_deref(), Some("de"));
+        assert!(settings.json_diagnostics);
+        assert!(!settings.quiet_warnings);
+
+        let cli = Cli::try_parse_from(["oidc-cli", "list"]).unwrap();
+        let settings = cli.settings();
+        assert!(settings.correlation.is_none());
+        assert!(!settings.json_diagnostics);
+    }
+
+    #[test]
+    fn test_revoke_token_stdin() {
+        let cli = Cli::try_parse_from(["oidc-cli", "revoke", "p", "--token-stdin"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Commands::Revoke {
+                token: None,
+                token_stdin: true,
+                ..
+            }
+        ));
+
+        assert!(
+            Cli::try_parse_from(["oidc-cli", "revoke", "p", "--token", "t", "--token-stdin"])
+                .is_err()
+        );
+        assert!(Cli::try_parse_from(["oidc-cli", "revoke", "--token-stdin"]).is_err());
+    }
+
+    #[test]
+    fn test_render_man_page() {
+        let page = String::from_utf8(render_man_page().unwrap()).unwrap();
+        assert!(page.contains(".TH oidc-cli"));
+        assert!(page.contains("login"));
+    }
 }
@@ -0,0 +1,38 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::audit::{parse_duration, AuditFilter, AuditLog};
+use crate::error::Result;
+use crate::profile::ProfileManager;
+
+pub fn handle_audit(
+    profile_manager: ProfileManager,
+    since: Option<String>,
+    profile: Option<String>,
+    json: bool,
+) -> Result<()> {
+    let since = since.as_deref().map(parse_duration).transpose()?;
+    let filter = AuditFilter::new(since, profile, SystemTime::now());
+    let entries =
+        filter.apply(AuditLog::new_with_override(profile_manager.config_dir_override())?.read()?);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+    } else if entries.is_empty() {
+        println!("No matching audit entries.");
+    } else {
+        for entry in &entries {
+            let when = httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(entry.timestamp));
+            let outcome = match entry.error_kind {
+                _ if entry.success => "ok".to_string(),
+                Some(ref kind) => format!("failed ({kind})"),
+                None => "failed".to_string(),
+            };
+            println!(
+                "{when}  {:<6} {:<20} {outcome}",
+                entry.event,
+                entry.profile.as_deref().unwrap_or("-")
+            );
+        }
+    }
+    Ok(())
+}
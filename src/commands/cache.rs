@@ -0,0 +1,260 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::auth::DiscoveryCache;
+use crate::cli::CacheAction;
+use crate::error::Result;
+use crate::profile::ProfileManager;
+use crate::token_cache::TokenCache;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheKind {
+    Discovery,
+    Token,
+}
+
+impl CacheKind {
+    fn label(self) -> &'static str {
+        match self {
+            CacheKind::Discovery => "discovery",
+            CacheKind::Token => "token",
+        }
+    }
+}
+
+/// One cached item, described without its (possibly secret) contents
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheEntry {
+    pub kind: CacheKind,
+    /// Discovery URI or profile name
+    pub key: String,
+    /// Size of the stored entry as JSON
+    pub size_bytes: usize,
+    pub age_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    pub expired: bool,
+    /// Which tokens a token entry holds, e.g. `access_token`, `refresh_token`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub contents: Vec<&'static str>,
+}
+
+/// Which caches `cache clear` empties
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheSelection {
+    pub discovery: bool,
+    pub tokens: bool,
+}
+
+/// Every discovery document and token cached in the config directory, sorted by kind and key
+pub fn list_cache_entries(config_dir: Option<PathBuf>) -> Result<Vec<CacheEntry>> {
+    let now = now_secs();
+    let mut entries = Vec::new();
+
+    for (uri, cached) in DiscoveryCache::new_with_override(config_dir.clone())?.load()? {
+        entries.push(CacheEntry {
+            kind: CacheKind::Discovery,
+            key: uri,
+            size_bytes: serde_json::to_vec(&cached)?.len(),
+            age_secs: now.saturating_sub(cached.fetched_at),
+            expires_at: Some(cached.expires_at),
+            expired: cached.expires_at <= now,
+            contents: Vec::new(),
+        });
+    }
+
+    for (profile, cached) in TokenCache::new_with_override(config_dir)?.load()? {
+        let tokens = &cached.tokens;
+        let contents = [
+            ("access_token", true),
+            ("id_token", tokens.id_token.is_some()),
+            ("refresh_token", tokens.refresh_token.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, present)| present.then_some(name))
+        .collect();
+        let expires_at = cached.expires_at();
+        entries.push(CacheEntry {
+            kind: CacheKind::Token,
+            key: profile,
+            size_bytes: serde_json::to_vec(&cached)?.len(),
+            age_secs: now.saturating_sub(cached.obtained_at),
+            expires_at,
+            expired: expires_at.is_some_and(|at| at <= now),
+            contents,
+        });
+    }
+
+    entries.sort_by(|a, b| (a.kind.label(), &a.key).cmp(&(b.kind.label(), &b.key)));
+    Ok(entries)
+}
+
+/// Empty the selected caches, returning how many discovery and token entries were dropped
+pub fn clear_caches(
+    config_dir: Option<PathBuf>,
+    selection: CacheSelection,
+) -> Result<(usize, usize)> {
+    let discovery = if selection.discovery {
+        DiscoveryCache::new_with_override(config_dir.clone())?.clear()?
+    } else {
+        0
+    };
+    let tokens = if selection.tokens {
+        TokenCache::new_with_override(config_dir)?.clear()?
+    } else {
+        0
+    };
+    Ok((discovery, tokens))
+}
+
+pub fn handle_cache(
+    profile_manager: ProfileManager,
+    action: CacheAction,
+    quiet: bool,
+) -> Result<()> {
+    let config_dir = profile_manager.config_dir_override();
+    match action {
+        CacheAction::List { json } => {
+            let entries = list_cache_entries(config_dir)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else if quiet {
+                for entry in &entries {
+                    println!("{}\t{}", entry.kind.label(), entry.key);
+                }
+            } else if entries.is_empty() {
+                println!("Nothing cached.");
+            } else {
+                let now = now_secs();
+                for entry in &entries {
+                    let expiry = match entry.expires_at {
+                        Some(at) if at > now => format!("expires in {}", format_secs(at - now)),
+                        Some(at) => format!("expired {} ago", format_secs(now - at)),
+                        None => "no expiry".to_string(),
+                    };
+                    let contents = if entry.contents.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" [{}]", entry.contents.join(", "))
+                    };
+                    println!(
+                        "{:<9}  {}{contents}\n           {} bytes, age {}, {expiry}",
+                        entry.kind.label(),
+                        entry.key,
+                        entry.size_bytes,
+                        format_secs(entry.age_secs)
+                    );
+                }
+            }
+        }
+        CacheAction::Clear {
+            discovery,
+            tokens,
+            all,
+        } => {
+            let selection = CacheSelection {
+                discovery: discovery || all,
+                tokens: tokens || all,
+            };
+            let (discovery, tokens) = clear_caches(config_dir, selection)?;
+            if !quiet {
+                println!(
+                    "✓ Cleared {discovery} discovery document(s) and {tokens} cached token(s)."
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Coarse human-readable duration: `45s`, `12m`, `3h`, `2d`
+fn format_secs(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3_599 => format!("{}m", secs / 60),
+        3_600..=86_399 => format!("{}h", secs / 3_600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::TokenResponse;
+    use std::time::Duration;
+
+    #[test]
+    fn test_list_and_clear_caches() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_dir = Some(dir.path().to_path_buf());
+
+        let discovery = DiscoveryCache::new_with_override(config_dir.clone()).unwrap();
+        discovery
+            .store(
+                "https://idp.example.com/.well-known/openid-configuration",
+                r#"{"issuer":"https://idp.example.com"}"#,
+                Duration::from_secs(600),
+            )
+            .unwrap();
+        let tokens = TokenCache::new_with_override(config_dir.clone()).unwrap();
+        let response = TokenResponse {
+            access_token: "secret-access-token".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: Some(3600),
+            refresh_token: Some("secret-refresh-token".to_string()),
+            ..Default::default()
+        };
+        tokens.store("work", &response, "openid").unwrap();
+
+        let entries = list_cache_entries(config_dir.clone()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].kind, CacheKind::Discovery);
+        assert!(!entries[0].expired);
+        assert_eq!(entries[1].key, "work");
+        assert_eq!(entries[1].contents, vec!["access_token", "refresh_token"]);
+        assert!(entries[1].size_bytes > 0);
+        let json = serde_json::to_string(&entries).unwrap();
+        assert!(!json.contains("secret-"), "token values must not be listed");
+
+        let cleared = clear_caches(
+            config_dir.clone(),
+            CacheSelection {
+                discovery: true,
+                tokens: false,
+            },
+        )
+        .unwrap();
+        assert_eq!(cleared, (1, 0));
+        let entries = list_cache_entries(config_dir.clone()).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].kind, CacheKind::Token);
+
+        let cleared = clear_caches(
+            config_dir.clone(),
+            CacheSelection {
+                discovery: true,
+                tokens: true,
+            },
+        )
+        .unwrap();
+        assert_eq!(cleared, (0, 1));
+        assert!(list_cache_entries(config_dir).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_format_secs() {
+        assert_eq!(format_secs(5), "5s");
+        assert_eq!(format_secs(125), "2m");
+        assert_eq!(format_secs(7_200), "2h");
+        assert_eq!(format_secs(200_000), "2d");
+    }
+}
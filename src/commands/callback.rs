@@ -0,0 +1,59 @@
+use crate::error::{OidcError, Result};
+use crate::profile::validation::validate_redirect_uri;
+use crate::server::CallbackServer;
+use crate::utils::url::{extract_port_from_redirect_uri, is_localhost_redirect_uri};
+use tokio::time::{timeout, Duration};
+
+/// Start the callback server for a redirect URI and wait for a single manual request
+pub async fn handle_test_callback(
+    redirect_uri: String,
+    port: Option<u16>,
+    timeout_secs: u64,
+    quiet: bool,
+) -> Result<()> {
+    validate_redirect_uri(&redirect_uri)?;
+    if !is_localhost_redirect_uri(&redirect_uri) {
+        return Err(OidcError::InvalidRedirectUri(format!(
+            "{redirect_uri} is not a localhost URI; the callback server only binds locally"
+        )));
+    }
+
+    let port = port
+        .or_else(|| extract_port_from_redirect_uri(&redirect_uri))
+        .unwrap_or(8080);
+
+    let mut server = CallbackServer::new(port, &redirect_uri)?;
+    let mut receiver = server.start().await?;
+
+    if !quiet {
+        println!("Callback server listening on {}", server.get_redirect_uri());
+        println!(
+            "Open {}?code=test&state=test to check it responds",
+            server.get_redirect_uri()
+        );
+        println!("Waiting up to {timeout_secs}s (press Ctrl+C to cancel)...");
+    }
+
+    let received = timeout(Duration::from_secs(timeout_secs), receiver.recv()).await;
+    server.shutdown();
+
+    let result = received
+        .map_err(|_| OidcError::Server(format!("No callback received within {timeout_secs}s")))?
+        .ok_or_else(|| OidcError::Server("Callback server stopped unexpectedly".to_string()))?;
+
+    println!("✓ Callback received");
+    if let Some(ref error) = result.error {
+        println!("  error:             {error}");
+        if let Some(ref description) = result.error_description {
+            println!("  error_description: {description}");
+        }
+    } else {
+        println!("  code:              {}", result.code);
+    }
+    println!("  state:             {}", result.state);
+    if let Some(ref session_state) = result.session_state {
+        println!("  session_state:     {session_state}");
+    }
+
+    Ok(())
+}
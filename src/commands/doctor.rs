@@ -0,0 +1,280 @@
+use serde::Serialize;
+use std::time::Instant;
+use url::Url;
+
+use crate::auth::discover_endpoints;
+use crate::browser::browser_hint;
+use crate::commands::version::{build_info, BuildInfo};
+use crate::config::get_config_dir_with_override;
+use crate::error::{OidcError, Result};
+use crate::profile::ProfileManager;
+
+/// Environment summary for bug reports. Holds no secrets, tokens or full URLs, so
+/// it can be pasted into a public issue as is.
+#[derive(Debug, Serialize)]
+pub struct DoctorReport {
+    pub build: BuildInfo,
+    pub os: &'static str,
+    pub arch: &'static str,
+    /// With the home directory shown as `~`
+    pub config_dir: String,
+    pub config_dir_exists: bool,
+    pub profiles: usize,
+    /// Why a browser should open, or `None` when none was detected
+    pub browser: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub discovery: Option<DiscoveryCheck>,
+}
+
+/// Reachability of a profile's discovery host
+#[derive(Debug, Serialize)]
+pub struct DiscoveryCheck {
+    /// Host (and port) only; the path and query are left out
+    pub host: String,
+    pub reachable: bool,
+    pub elapsed_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Gather the report, fetching `profile`'s discovery document when one is named
+pub async fn collect_report(
+    profile_manager: &ProfileManager,
+    profile: Option<&str>,
+) -> Result<DoctorReport> {
+    let config_dir = get_config_dir_with_override(profile_manager.config_dir_override())?;
+    let discovery = match profile {
+        Some(name) => check_discovery(profile_manager, name).await?,
+        None => None,
+    };
+
+    Ok(DoctorReport {
+        build: build_info(),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        config_dir: home_relative(&config_dir.display().to_string()),
+        config_dir_exists: config_dir.is_dir(),
+        profiles: profile_manager.list_profiles().len(),
+        browser: browser_hint(),
+        discovery,
+    })
+}
+
+async fn check_discovery(
+    profile_manager: &ProfileManager,
+    name: &str,
+) -> Result<Option<DiscoveryCheck>> {
+    let profile = profile_manager.get_profile(name)?;
+    let Some(ref discovery_uri) = profile.discovery_uri else {
+        return Ok(None);
+    };
+    // Values from the profile that must not reach the report through an error message
+    let secrets: Vec<&str> = [
+        Some(discovery_uri.as_str()),
+        profile.client_secret.as_deref(),
+        Some(profile.client_id.as_str()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    let started = Instant::now();
+    let result = discover_endpoints(discovery_uri).await;
+    Ok(Some(DiscoveryCheck {
+        host: redact_url(discovery_uri),
+        reachable: result.is_ok(),
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        error: result.err().map(|e| redact(&e.to_string(), &secrets)),
+    }))
+}
+
+/// `https://host:port/…` for a URL, keeping only what identifies the server
+fn redact_url(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(url) => {
+            let host = url.host_str().unwrap_or("unknown");
+            match url.port() {
+                Some(port) => format!("{}://{host}:{port}/…", url.scheme()),
+                None => format!("{}://{host}/…", url.scheme()),
+            }
+        }
+        Err(_) => "[redacted]".to_string(),
+    }
+}
+
+/// Replace every URL in `text` with its redacted form and every value in `secrets`
+/// with a placeholder
+fn redact(text: &str, secrets: &[&str]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = ["https://", "http://"]
+        .iter()
+        .filter_map(|scheme| rest.find(scheme))
+        .min()
+    {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find(|c: char| c.is_whitespace() || matches!(c, ')' | '"' | '\'' | '>'))
+            .map_or(rest.len(), |len| start + len);
+        out.push_str(&redact_url(&rest[start..end]));
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+
+    secrets
+        .iter()
+        .filter(|secret| !secret.is_empty())
+        .fold(out, |text, secret| text.replace(secret, "[redacted]"))
+}
+
+fn home_relative(path: &str) -> String {
+    match dirs::home_dir().map(|home| home.display().to_string()) {
+        Some(home) if !home.is_empty() && path.starts_with(&home) => {
+            format!("~{}", &path[home.len()..])
+        }
+        _ => path.to_string(),
+    }
+}
+
+pub fn render_report(report: &DoctorReport) -> String {
+    let build = &report.build;
+    let features = if build.features.is_empty() {
+        "none".to_string()
+    } else {
+        build.features.join(", ")
+    };
+    let mut lines = vec![
+        format!(
+            "oidc-cli:   {} ({} {})",
+            build.version, build.git_commit, build.build_date
+        ),
+        format!("target:     {} ({})", build.target, build.profile),
+        format!("os:         {} ({})", report.os, report.arch),
+        format!("features:   {features}"),
+        format!(
+            "config dir: {} ({})",
+            report.config_dir,
+            if report.config_dir_exists {
+                "exists"
+            } else {
+                "missing"
+            }
+        ),
+        format!("profiles:   {}", report.profiles),
+        format!(
+            "browser:    {}",
+            report
+                .browser
+                .map_or("not detected".to_string(), |hint| format!(
+                    "detected ({hint})"
+                ))
+        ),
+    ];
+    if let Some(ref discovery) = report.discovery {
+        lines.push(match discovery.error {
+            None => format!(
+                "discovery:  {} reachable in {}ms",
+                discovery.host, discovery.elapsed_ms
+            ),
+            Some(ref error) => format!("discovery:  {} failed: {error}", discovery.host),
+        });
+    }
+    lines.join("\n")
+}
+
+pub async fn handle_doctor(
+    profile_manager: ProfileManager,
+    profile: Option<String>,
+    json: bool,
+) -> Result<()> {
+    let report = collect_report(&profile_manager, profile.as_deref()).await?;
+    if json {
+        let output = serde_json::to_string_pretty(&report)
+            .map_err(|e| OidcError::Config(format!("Failed to serialize report: {e}")))?;
+        println!("{output}");
+    } else {
+        println!("{}", render_report(&report));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Profile;
+    use crate::test_support::{MockResponse, MockServer};
+
+    const SECRET: &str = "s3cr3t-client-secret";
+
+    async fn report_for(discovery_uri: String) -> DoctorReport {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager =
+            ProfileManager::new_with_test_dir(Some(dir.path().to_path_buf())).unwrap();
+        manager
+            .add_profile(
+                "corp",
+                Profile {
+                    client_id: "corp-client-id".to_string(),
+                    client_secret: Some(SECRET.to_string()),
+                    redirect_uri: "http://localhost:8080/callback".to_string(),
+                    scope: "openid".to_string(),
+                    discovery_uri: Some(discovery_uri),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        collect_report(&manager, Some("corp")).await.unwrap()
+    }
+
+    fn assert_redacted(report: &DoctorReport) {
+        let text = render_report(report);
+        let json = serde_json::to_string(report).unwrap();
+        for output in [&text, &json] {
+            for secret in [SECRET, "corp-client-id", "/realms/acme", "tenant=42"] {
+                assert!(!output.contains(secret), "{secret} leaked into {output}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_contains_no_secrets() {
+        let server = MockServer::start(vec![MockResponse::json(500, "down")]).await;
+        let report =
+            report_for(server.url("/realms/acme/.well-known/openid-configuration?tenant=42")).await;
+
+        let discovery = report.discovery.as_ref().unwrap();
+        assert!(!discovery.reachable);
+        assert!(discovery.host.starts_with("http://127.0.0.1:"));
+        assert!(discovery.host.ends_with("/…"));
+        assert_redacted(&report);
+
+        // Connection errors quote the full URL, which must be cut down too
+        let unreachable = report_for(
+            "https://127.0.0.1:1/realms/acme/.well-known/openid-configuration?tenant=42"
+                .to_string(),
+        )
+        .await;
+        let error = unreachable
+            .discovery
+            .as_ref()
+            .unwrap()
+            .error
+            .clone()
+            .unwrap();
+        assert!(error.contains("https://127.0.0.1:1/…"), "{error}");
+        assert_redacted(&unreachable);
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact(
+                "request for url (https://idp.example.com/t/a?x=1): secret-1 rejected",
+                &["secret-1"]
+            ),
+            "request for url (https://idp.example.com/…): [redacted] rejected"
+        );
+        assert_eq!(redact("no urls here", &[""]), "no urls here");
+        assert_eq!(redact_url("not a url"), "[redacted]");
+    }
+}
@@ -1,5 +1,10 @@
+use crate::config::Config;
 use crate::error::{OidcError, Result};
+use crate::profile::foreign::ImportFormat;
+use crate::profile::storage::ProfileStorage;
 use crate::profile::ProfileManager;
+use crate::ui::{prompt_passphrase, JsonLinesWriter};
+use crate::utils::mask::mask_secret;
 
 pub fn handle_list(profile_manager: ProfileManager, quiet: bool) -> Result<()> {
     let profiles = profile_manager.list_profiles();
@@ -29,6 +34,7 @@ pub fn handle_export(
     profile_manager: ProfileManager,
     file: std::path::PathBuf,
     profiles: Vec<String>,
+    encrypt: bool,
     quiet: bool,
 ) -> Result<()> {
     let profile_names = if profiles.is_empty() {
@@ -40,7 +46,13 @@ pub fn handle_export(
         Some(profiles)
     };
 
-    profile_manager.export_profiles(&file, profile_names)?;
+    let passphrase = if encrypt {
+        Some(prompt_passphrase(true)?)
+    } else {
+        None
+    };
+
+    profile_manager.export_profiles(&file, profile_names, passphrase.as_deref())?;
 
     if !quiet {
         println!("✓ Profiles exported to {file:?} successfully.");
@@ -52,7 +64,10 @@ pub fn handle_export(
 pub fn handle_import(
     profile_manager: &mut ProfileManager,
     file: std::path::PathBuf,
+    from: ImportFormat,
     overwrite: bool,
+    allow_hook: bool,
+    json_lines: bool,
     quiet: bool,
 ) -> Result<()> {
     if !file.exists() {
@@ -61,9 +76,26 @@ pub fn handle_import(
         )));
     }
 
-    let imported_names = profile_manager.import_profiles(&file, overwrite)?;
+    let imported_names = if from == ImportFormat::Native {
+        let passphrase = if ProfileStorage::is_encrypted_export(&file)? {
+            if !quiet {
+                println!("{file:?} is encrypted.");
+            }
+            Some(prompt_passphrase(false)?)
+        } else {
+            None
+        };
+        profile_manager.import_profiles(&file, overwrite, passphrase.as_deref(), allow_hook)?
+    } else {
+        profile_manager.import_foreign_profiles(&file, from, overwrite)?
+    };
 
-    if !quiet {
+    if json_lines {
+        let mut lines = JsonLinesWriter::stdout();
+        for name in &imported_names {
+            lines.emit(&serde_json::json!({ "profile": name, "imported": true }))?;
+        }
+    } else if !quiet {
         println!(
             "✓ Imported {} profile(s) from {:?}:",
             imported_names.len(),
@@ -76,3 +108,49 @@ pub fn handle_import(
 
     Ok(())
 }
+
+/// Print the loaded config as pretty JSON, masking client secrets unless `reveal` is set
+pub fn handle_dump(profile_manager: ProfileManager, reveal: bool) -> Result<()> {
+    println!("{}", dump_config(profile_manager.config(), reveal)?);
+    Ok(())
+}
+
+pub fn dump_config(config: &Config, reveal: bool) -> Result<String> {
+    let mut config = config.clone();
+    if !reveal {
+        for profile in config.profiles.values_mut() {
+            profile.client_secret = mask_secret(profile.client_secret.as_deref());
+        }
+    }
+
+    serde_json::to_string_pretty(&config)
+        .map_err(|e| OidcError::Profile(format!("Failed to serialize config: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Profile;
+
+    #[test]
+    fn test_dump_masks_secrets_by_default() {
+        let mut config = Config::new();
+        config.profiles.insert(
+            "dev".to_string(),
+            Profile {
+                client_id: "client".to_string(),
+                client_secret: Some("super-secret".to_string()),
+                redirect_uri: "http://localhost:8080/callback".to_string(),
+                scope: "openid".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let masked = dump_config(&config, false).unwrap();
+        assert!(!masked.contains("super-secret"));
+        assert!(masked.contains("\"client_secret\": \"****\""));
+        assert!(masked.contains("\"client_id\": \"client\""));
+
+        assert!(dump_config(&config, true).unwrap().contains("super-secret"));
+    }
+}
@@ -1,26 +1,432 @@
-use crate::auth::{OAuthClient, TokenExport};
+use crate::audit::AuditLog;
+use crate::auth::{
+    build_claims_request, clock_drift_warning, decode_id_token_claims, discover_endpoints_cached,
+    discover_endpoints_with, verify_id_token, AuthorizationRequest, ClaimRequirements,
+    DeviceBackoff, DiscoveryCache, DpopKey, Jwks, OAuthClient, PkceMethod, SavedFlow, TokenExport,
+    TokenResponse, TokenSelection,
+};
 use crate::browser::{BrowserOpener, WebBrowserOpener};
+use crate::config::Profile;
 use crate::error::{OidcError, Result};
+use crate::http::shared_http_client;
+use crate::metrics::{Metrics, MetricsRecord};
+use crate::profile::validation::{
+    oauth21_enforced, validate_endpoint_url, validate_oauth21_pkce_method,
+    validate_oauth21_redirect_match, validate_redirect_uri, validate_scope,
+    CLIENT_CREDENTIALS_GRANT,
+};
 use crate::profile::ProfileManager;
-use crate::server::CallbackServer;
-use crate::ui::{display_tokens, handle_manual_code_entry, select_profile};
+use crate::server::{BrowserTokenDisplay, CallbackResult, CallbackServer};
+use crate::token_cache::{TokenCache, DEFAULT_EXPIRY_LEEWAY_SECS};
+use crate::ui::{
+    display_tokens, explain_url, format_url_breakdown, handle_manual_code_entry, json_diagnostics,
+    parse_callback_response, progress, prompt_scope_selection, read_callback_url,
+    render_output_template, security_warning, select_profile, warn,
+};
+use crate::utils::fs::{open_output_fd, write_atomic};
 use crate::utils::url::{extract_port_from_redirect_uri, is_localhost_redirect_uri};
-use std::path::PathBuf;
-use tokio::time::{timeout, Duration};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::future::Future;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{sleep_until, Duration, Instant};
+
+/// Connection settings for a one-off login that is never written to `profiles.json`
+#[derive(Clone)]
+pub struct AdhocProfile {
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub discovery_uri: Option<String>,
+    pub authorization_endpoint: Option<String>,
+    pub token_endpoint: Option<String>,
+}
+
+impl AdhocProfile {
+    pub const DEFAULT_REDIRECT_URI: &'static str = "http://localhost:8080/callback";
+    pub const DEFAULT_SCOPE: &'static str = "openid";
+
+    /// Build and validate a transient profile
+    pub fn into_profile(self) -> Result<Profile> {
+        let profile = Profile {
+            client_id: self.client_id,
+            client_secret: self.client_secret,
+            redirect_uri: self
+                .redirect_uri
+                .unwrap_or_else(|| Self::DEFAULT_REDIRECT_URI.to_string()),
+            scope: self
+                .scope
+                .unwrap_or_else(|| Self::DEFAULT_SCOPE.to_string()),
+            discovery_uri: self.discovery_uri,
+            authorization_endpoint: self.authorization_endpoint,
+            token_endpoint: self.token_endpoint,
+            ..Default::default()
+        };
+        profile.validate()?;
+        Ok(profile)
+    }
+}
+
+/// `login --stdin-profile` input: a complete profile plus login options, so an
+/// orchestrator can run one login without any stored state
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StdinInvocation {
+    pub profile: Profile,
+    #[serde(default)]
+    pub options: StdinLoginOptions,
+}
+
+/// Login options accepted in a `--stdin-profile` document; set values override the flags
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StdinLoginOptions {
+    pub scope: Option<String>,
+    pub port: Option<u16>,
+    pub redirect_uri: Option<String>,
+    pub device: bool,
+    pub fresh: bool,
+    pub consent: bool,
+    pub verify: bool,
+    /// Same values as `--show`
+    pub show: Option<String>,
+    pub retries: Option<u32>,
+    /// Seconds, like `--idle-timeout`
+    pub idle_timeout: Option<u64>,
+}
+
+impl StdinInvocation {
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        serde_json::from_reader(reader)
+            .map_err(|e| OidcError::Config(format!("Invalid --stdin-profile document: {e}")))
+    }
+
+    /// Validate the profile and fold the document into `options` as an ad-hoc login
+    pub fn into_login_options(self, mut options: LoginOptions) -> Result<LoginOptions> {
+        self.profile.validate_for_use()?;
+        let StdinLoginOptions {
+            scope,
+            port,
+            redirect_uri,
+            device,
+            fresh,
+            consent,
+            verify,
+            show,
+            retries,
+            idle_timeout,
+        } = self.options;
+
+        if let Some(ref scope) = scope {
+            validate_scope(scope)?;
+        }
+        if let Some(show) = show {
+            options.show = TokenSelection::parse(&show)?;
+        }
+        options.adhoc = Some(self.profile);
+        options.scope = scope.or(options.scope);
+        options.port = port.or(options.port);
+        options.redirect_uri = redirect_uri.or(options.redirect_uri);
+        options.device |= device;
+        options.fresh |= fresh;
+        options.consent |= consent;
+        options.verify |= verify;
+        options.retries = retries.unwrap_or(options.retries);
+        options.idle_timeout = idle_timeout
+            .map(Duration::from_secs)
+            .or(options.idle_timeout);
+        Ok(options)
+    }
+}
 
 /// Options for the login command
+#[derive(Clone, Default)]
 pub struct LoginOptions {
     pub profile_name: Option<String>,
+    /// Log in to every profile in turn and emit the tokens as one object keyed by profile
+    pub all: bool,
+    /// Use this transient, validated profile instead of a saved one
+    pub adhoc: Option<Profile>,
+    /// Read the profile and options as JSON from stdin (see [`StdinInvocation`])
+    pub stdin_profile: bool,
     pub port: Option<u16>,
     pub copy: bool,
     pub quiet: bool,
     pub verbose: bool,
+    /// Print protocol-level traces such as the PKCE computation
+    pub very_verbose: bool,
     pub json: bool,
     pub output: Option<PathBuf>,
+    /// Write the JSON token output to this inherited file descriptor (Unix)
+    pub output_fd: Option<i32>,
+    pub output_template: Option<String>,
+    pub claim_requirements: ClaimRequirements,
+    pub request_uri: Option<String>,
+    /// Overrides the profile's `request_state`
+    pub request_state: Option<String>,
+    pub registered_redirect_uri: Option<String>,
+    pub uds: Option<PathBuf>,
+    pub device: bool,
+    /// Overrides the profile's `grant_type`
+    pub grant: Option<String>,
+    /// Bind the tokens to an ephemeral key with DPoP (RFC 9449)
+    pub dpop: bool,
+    pub retry_browser: bool,
+    /// Open the authorization URL in a private/incognito window when possible
+    pub incognito: bool,
+    /// Start a fresh authorization request this many times when the callback times out
+    pub retries: u32,
+    /// Also time out once the callback server has seen no request for this long
+    pub idle_timeout: Option<Duration>,
+    /// Show the tokens on the success page, overriding the profile's `show_token_in_browser`
+    pub show_token_in_browser: Option<BrowserTokenDisplay>,
+    /// Redirect URI override; otherwise chosen from the profile's candidates
+    pub redirect_uri: Option<String>,
+    /// Scope override, merged with `scope_preset` when both are given
+    pub scope: Option<String>,
+    pub scope_preset: Option<String>,
+    /// Print the authorization request's parameters instead of logging in
+    pub explain: bool,
+    /// Use the scope and audience of this entry in the profile's `apis`
+    pub for_api: Option<String>,
+    /// Pick the scopes interactively from the provider's `scopes_supported`
+    pub choose_scopes: bool,
+    pub check_clock: bool,
+    pub no_pkce: bool,
+    pub no_auto_openid: bool,
+    /// Fall back to the profile's manual endpoints if discovery fails
+    pub discovery_fallback: bool,
+    /// Send the cached id_token as `id_token_hint`
+    pub hint: bool,
+    /// Send `prompt=login` to force re-authentication
+    pub fresh: bool,
+    /// Send `prompt=consent` to force the consent screen
+    pub consent: bool,
+    /// Check id_token claims (aud/azp/exp) after login
+    pub verify: bool,
+    /// With `verify`, check the signature without network access (needs `jwks_file`)
+    pub offline: bool,
+    /// With `verify`, check the id_token signature against this JWKS file
+    pub jwks_file: Option<PathBuf>,
+    /// Write the decoded id_token claims here
+    pub claims_out: Option<PathBuf>,
+    pub token_files: TokenFiles,
+    /// SPKI pins replacing the profile's `pin_sha256`
+    pub pin_sha256: Vec<String>,
+    /// Which tokens to print
+    pub show: TokenSelection,
+    /// Hook command given on the command line (an explicit opt-in)
+    pub on_success: Option<String>,
+    /// Run the profile's stored hook without confirmation
+    pub run_hook: bool,
+    /// Reuse a valid cached token, and cache the tokens this login obtains
+    pub use_cache: bool,
+    /// Persist the authorization request here so the login can be resumed
+    pub state_dir: Option<PathBuf>,
+    /// Complete a saved login from `state_dir` with a pasted callback URL
+    pub resume: bool,
+    /// Write a JSON timing/outcome record here when the command finishes
+    pub metrics_json: Option<PathBuf>,
+    /// Print the authorization URL and wait for an external tool to write the response here
+    pub callback_file: Option<PathBuf>,
+    /// Explicit OIDC `claims` request JSON
+    pub claims: Option<String>,
+    /// Claims presets merged (under `claims`) into the claims request
+    pub claims_presets: Vec<String>,
 }
 
 pub async fn handle_login(profile_manager: ProfileManager, options: LoginOptions) -> Result<()> {
-    handle_login_with_browser_opener(profile_manager, options, &WebBrowserOpener).await
+    let options = if options.stdin_profile {
+        StdinInvocation::from_reader(std::io::stdin().lock())?.into_login_options(options)?
+    } else {
+        options
+    };
+    let browser_opener = WebBrowserOpener {
+        retry: options.retry_browser,
+        incognito: options.incognito,
+    };
+    if options.all {
+        return handle_login_batch(profile_manager, options, &browser_opener).await;
+    }
+    handle_login_with_browser_opener(profile_manager, options, &browser_opener).await
+}
+
+/// Client credentials logins in a batch run at most this many at a time
+const BATCH_CONCURRENCY: usize = 4;
+
+/// Log in to every profile and emit all tokens as one JSON object keyed by profile
+/// name. Client credentials profiles are logged in concurrently; browser flows follow
+/// one after another, confirming before each after the first. A failed profile is
+/// reported under an `error` key and fails the command at the end.
+pub async fn handle_login_batch<B: BrowserOpener>(
+    profile_manager: ProfileManager,
+    options: LoginOptions,
+    browser_opener: &B,
+) -> Result<()> {
+    let mut names: Vec<String> = profile_manager
+        .list_profiles()
+        .into_iter()
+        .cloned()
+        .collect();
+    names.sort();
+    if names.is_empty() {
+        return Err(OidcError::Profile("No profiles to log in to".to_string()));
+    }
+
+    let (machine, interactive_names): (Vec<String>, Vec<String>) = names
+        .into_iter()
+        .partition(|name| is_client_credentials_login(&profile_manager, name, &options));
+    let mut results = login_concurrently(&profile_manager, &options, machine).await;
+
+    let interactive = !options.quiet && std::io::stdin().is_terminal();
+    for (index, name) in interactive_names.into_iter().enumerate() {
+        if index > 0 && interactive && !confirm_next_profile(&name) {
+            results.push((name, Err(OidcError::Cancelled)));
+            continue;
+        }
+        if !options.quiet {
+            eprintln!("==> {name}");
+        }
+        let result = login_batch_profile(&profile_manager, &options, &name, browser_opener).await;
+        results.push((name, result));
+    }
+    results.sort_by(|(left, _), (right, _)| left.cmp(right));
+
+    let json = serde_json::to_string_pretty(&batch_tokens_json(&results, options.show))?;
+    match options.output {
+        Some(ref path) => {
+            write_atomic(path, json.as_bytes())?;
+            if !options.quiet {
+                progress(format!("Tokens written to {}", path.display()));
+            }
+        }
+        None => println!("{json}"),
+    }
+
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    if failed > 0 {
+        return Err(OidcError::Auth(format!(
+            "{failed} of {} profile login(s) did not complete",
+            results.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Whether `name` logs in with the client credentials grant, which needs no browser
+fn is_client_credentials_login(
+    profile_manager: &ProfileManager,
+    name: &str,
+    options: &LoginOptions,
+) -> bool {
+    match options.grant.as_deref() {
+        Some(grant) => grant == CLIENT_CREDENTIALS_GRANT,
+        None => profile_manager
+            .get_profile(name)
+            .is_ok_and(|profile| profile.uses_client_credentials()),
+    }
+}
+
+/// Log in to each of `names` with the client credentials grant, at most
+/// [`BATCH_CONCURRENCY`] at a time. Results come back in completion order.
+async fn login_concurrently(
+    profile_manager: &ProfileManager,
+    options: &LoginOptions,
+    names: Vec<String>,
+) -> Vec<(String, Result<TokenResponse>)> {
+    let permits = Arc::new(Semaphore::new(BATCH_CONCURRENCY));
+    let mut tasks = JoinSet::new();
+    for name in names {
+        let permits = permits.clone();
+        let profile_manager = profile_manager.clone();
+        let options = options.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await.expect("never closed");
+            // The client credentials grant never opens a browser
+            let browser_opener = WebBrowserOpener::default();
+            let result =
+                login_batch_profile(&profile_manager, &options, &name, &browser_opener).await;
+            (name, result)
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok(result) => results.push(result),
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        }
+    }
+    results
+}
+
+/// One profile's login within a batch, recorded in the audit log; failures are warned about
+async fn login_batch_profile<B: BrowserOpener>(
+    profile_manager: &ProfileManager,
+    options: &LoginOptions,
+    name: &str,
+    browser_opener: &B,
+) -> Result<TokenResponse> {
+    let profile_options = LoginOptions {
+        profile_name: Some(name.to_string()),
+        all: false,
+        ..options.clone()
+    };
+    let mut metrics = Metrics::new("login");
+    let result = run_login(
+        profile_manager.clone(),
+        profile_options,
+        browser_opener,
+        &mut metrics,
+        false,
+    )
+    .await;
+    record_login_attempt(
+        profile_manager.config_dir_override(),
+        &metrics.finish(&result),
+    );
+    let result = result.and_then(|tokens| {
+        tokens.ok_or_else(|| OidcError::Auth("No tokens were obtained".to_string()))
+    });
+    if let Err(ref e) = result {
+        warn(format!("login for '{name}' failed: {e}"));
+    }
+    result
+}
+
+/// `{ "profile": {tokens...} }`, with `{ "error": ... }` for profiles that failed
+pub fn batch_tokens_json(
+    results: &[(String, Result<TokenResponse>)],
+    show: TokenSelection,
+) -> serde_json::Value {
+    let entries = results.iter().map(|(name, result)| {
+        let value = match result {
+            Ok(tokens) => {
+                let mut export = serde_json::to_value(TokenExport::from_response(tokens))
+                    .expect("token export serializes");
+                show.strip_unselected(&mut export);
+                export
+            }
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        };
+        (name.clone(), value)
+    });
+    serde_json::Value::Object(entries.collect())
+}
+
+/// Ask before starting the next browser flow; anything but an empty line or `y` skips it
+fn confirm_next_profile(name: &str) -> bool {
+    eprint!("Log in to '{name}' next? [Y/n]: ");
+    let _ = std::io::Write::flush(&mut std::io::stderr());
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok()
+        && matches!(answer.trim().to_lowercase().as_str(), "" | "y" | "yes")
 }
 
 pub async fn handle_login_with_browser_opener<B: BrowserOpener>(
@@ -28,175 +434,1978 @@ pub async fn handle_login_with_browser_opener<B: BrowserOpener>(
     options: LoginOptions,
     browser_opener: &B,
 ) -> Result<()> {
+    let metrics_path = options.metrics_json.clone();
+    let config_dir = profile_manager.config_dir_override();
+    // Ad-hoc logins leave no trace in the config directory, including the audit log
+    let audit = options.adhoc.is_none();
+    let mut metrics = Metrics::new("login");
+    let result = run_login(profile_manager, options, browser_opener, &mut metrics, true).await;
+    let record = metrics.finish(&result);
+
+    // --explain stops before logging in, so there is no attempt to audit
+    if audit && !matches!(result, Ok(None)) {
+        record_login_attempt(config_dir, &record);
+    }
+    // The record is written on failure too; a write error never masks the login result
+    if let Some(path) = metrics_path {
+        if let Err(e) = record.write(&path) {
+            warn(e);
+        }
+    }
+    result.map(|_| ())
+}
+
+/// Append the attempt to the audit log; failing to do so only warns
+fn record_login_attempt(config_dir: Option<PathBuf>, record: &MetricsRecord) {
+    let appended =
+        AuditLog::new_with_override(config_dir).and_then(|log| log.append(&record.into()));
+    if let Err(e) = appended {
+        warn(format!("could not write the audit log: {e}"));
+    }
+}
+
+/// Run one login and return the tokens (`None` for `--explain`). With `emit` unset
+/// the tokens are only returned, for callers that print them differently.
+async fn run_login<B: BrowserOpener>(
+    profile_manager: ProfileManager,
+    options: LoginOptions,
+    browser_opener: &B,
+    metrics: &mut Metrics,
+    emit: bool,
+) -> Result<Option<TokenResponse>> {
+    metrics.phase("resolve");
     let LoginOptions {
         profile_name,
+        adhoc,
         port,
         copy,
         quiet,
         verbose,
+        very_verbose,
         json,
         output,
+        output_fd,
+        output_template,
+        claim_requirements,
+        request_uri,
+        request_state,
+        registered_redirect_uri,
+        uds,
+        device,
+        grant,
+        dpop,
+        retries,
+        idle_timeout,
+        show_token_in_browser,
+        redirect_uri,
+        scope,
+        scope_preset,
+        for_api,
+        choose_scopes,
+        explain,
+        check_clock,
+        no_pkce,
+        no_auto_openid,
+        discovery_fallback,
+        hint,
+        fresh,
+        consent,
+        verify,
+        offline,
+        jwks_file,
+        claims_out,
+        token_files,
+        pin_sha256,
+        show,
+        on_success,
+        run_hook,
+        use_cache,
+        state_dir,
+        resume,
+        callback_file,
+        claims,
+        claims_presets,
+        ..
     } = options;
 
-    // --output implies --json
-    let json_output = json || output.is_some();
-    let profile_name = match profile_name {
-        Some(name) => name,
-        None => select_profile(&profile_manager, quiet)?,
+    // Opened up front so a bad descriptor fails before the user is sent to log in
+    let output_fd = output_fd.map(open_output_fd).transpose()?;
+    let jwks = match jwks_file {
+        Some(ref path) => Some(Jwks::from_file(path)?),
+        None if offline => {
+            return Err(OidcError::Config(
+                "--offline verification needs signing keys; pass --jwks-file <PATH>".to_string(),
+            ))
+        }
+        None => None,
+    };
+    // --output and --output-fd imply --json
+    let json_output = match (&output_fd, &output) {
+        (Some(file), _) => Some(JsonOutput::Fd(file)),
+        (None, Some(path)) => Some(JsonOutput::File(path)),
+        (None, None) => json.then_some(JsonOutput::Stdout),
     };
+    // Ad-hoc logins never read or write stored state (profiles or token cache)
+    let persist = adhoc.is_none();
+    let (profile_name, mut profile) = match adhoc {
+        Some(adhoc) => ("ad-hoc".to_string(), adhoc.resolve_env()?),
+        None => {
+            let name = match profile_name {
+                Some(name) => name,
+                None => select_profile(&profile_manager, quiet)?,
+            };
+            let profile = profile_manager.get_profile(&name)?.resolve_env()?;
+            (name, profile)
+        }
+    };
+    metrics.set_profile(&profile_name);
 
-    let profile = profile_manager.get_profile(&profile_name)?.clone();
+    if let Some(scope) = profile_manager.resolve_scope(scope_preset.as_deref(), scope.as_deref())? {
+        validate_scope(&scope)?;
+        profile.scope = scope;
+    }
+    let audience = apply_api_target(&mut profile, for_api.as_deref())?;
 
-    let oauth_client = OAuthClient::new(profile.clone()).await?;
-    let auth_request = oauth_client.create_authorization_request()?;
+    if let Some(grant) = grant {
+        profile.grant_type = Some(grant);
+        profile.validate()?;
+    }
+    let client_credentials = profile.uses_client_credentials();
 
-    if !quiet {
-        println!("Initiating OAuth 2.0 authorization flow...");
+    if !client_credentials {
+        let chosen_redirect_uri = select_redirect_uri(
+            &profile,
+            redirect_uri.as_deref(),
+            std::io::stdin().is_terminal(),
+        )?;
+        if verbose && (!profile.redirect_uris.is_empty() || redirect_uri.is_some()) {
+            progress(format!("Using redirect URI {chosen_redirect_uri}"));
+        }
+        profile.redirect_uri = chosen_redirect_uri;
     }
 
-    browser_opener.open_with_fallback(&auth_request.authorization_url, quiet)?;
+    if let Some(request_uri) = request_uri {
+        validate_endpoint_url(&request_uri, "request URI")?;
+        profile.request_uri = Some(request_uri);
+    }
+    if request_state.is_some() {
+        profile.request_state = request_state;
+    }
 
-    let (code, state, server_opt) = if is_localhost_redirect_uri(&profile.redirect_uri) {
-        // Use callback server for localhost URLs
-        let port = port
-            .or_else(|| extract_port_from_redirect_uri(&profile.redirect_uri))
-            .unwrap_or(8080);
+    if no_pkce {
+        profile.no_pkce = true;
+        profile.validate()?;
+    }
 
-        let mut server = CallbackServer::new(port, &profile.redirect_uri)?;
+    if no_auto_openid {
+        profile.no_auto_openid = true;
+    }
 
+    if discovery_fallback {
+        profile.discovery_fallback = true;
+    }
+
+    if !pin_sha256.is_empty() {
+        profile.pin_sha256 = pin_sha256;
+        profile.validate()?;
+    }
+
+    let token_display = match show_token_in_browser {
+        Some(display) => Some(display),
+        None => profile
+            .show_token_in_browser
+            .as_deref()
+            .map(|value| BrowserTokenDisplay::from_str(value, true))
+            .transpose()
+            .map_err(OidcError::Config)?,
+    };
+
+    // A cached document carries no Date header to compare against
+    let discovery_cache = if use_cache && persist && !check_clock {
+        Some(DiscoveryCache::new_with_override(
+            profile_manager.config_dir_override(),
+        )?)
+    } else {
+        None
+    };
+
+    if choose_scopes {
+        if let Some(scope) = choose_login_scopes(&profile, discovery_cache.as_ref()).await? {
+            validate_scope(&scope)?;
+            profile.scope = scope;
+        }
+    }
+
+    if oauth21_enforced() {
+        profile.validate_oauth21()?;
+    }
+
+    if profile.no_pkce && !device {
+        security_warning(
+            "PKCE is disabled for this login. Authorization codes are not bound\n\
+             to this client and could be replayed if intercepted.",
+        );
+    }
+
+    if let Some(ref uri) = registered_redirect_uri {
+        validate_redirect_uri(uri)?;
+        if oauth21_enforced() {
+            validate_oauth21_redirect_match(uri, &profile.redirect_uri)?;
+        }
         if verbose {
-            println!("Starting callback server on port {port}");
+            progress(format!(
+                "Sending registered redirect URI {uri} (local callback: {})",
+                profile.redirect_uri
+            ));
         }
+    }
 
-        let mut receiver = server.start().await?;
+    let resumed = if resume {
+        let dir = state_dir
+            .as_deref()
+            .ok_or_else(|| OidcError::Config("--resume requires --state-dir".to_string()))?;
+        let flow = SavedFlow::load(dir, &profile_name)?;
+        // The exchange must repeat the redirect URI sent with the original request
+        profile.redirect_uri = flow.redirect_uri.clone();
+        Some(flow)
+    } else {
+        None
+    };
 
-        if !quiet {
-            println!("Waiting for authentication callback...");
-            println!("Press Ctrl+C to cancel");
+    // Space-joined into a single `prompt` parameter, e.g. `prompt=login consent`
+    let prompt: Vec<String> = [(fresh, "login"), (consent, "consent")]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, value)| value.to_string())
+        .collect();
+
+    // A forced login or consent screen is pointless if a cached token is returned instead
+    if use_cache && persist && resumed.is_none() && prompt.is_empty() && !explain {
+        let cache = TokenCache::new_with_override(profile_manager.config_dir_override())?;
+        let leeway = Duration::from_secs(DEFAULT_EXPIRY_LEEWAY_SECS);
+        if let Some(cached) = cache.get_valid_for_scope(&profile_name, &profile.scope, leeway)? {
+            if verbose {
+                progress(format!("Using cached token for profile '{profile_name}'"));
+            }
+            claim_requirements.check(&cached.tokens)?;
+            if verify {
+                verify_tokens(&cached.tokens, &profile.client_id, jwks.as_ref(), verbose)?;
+            }
+            if let Some(ref path) = claims_out {
+                write_claims_file(path, &cached.tokens)?;
+            }
+            token_files.write(&cached.tokens)?;
+            if emit {
+                emit_tokens(
+                    &cached.tokens,
+                    output_template.as_deref(),
+                    json_output,
+                    quiet,
+                    copy,
+                    show,
+                )?;
+            }
+            return Ok(Some(cached.tokens));
+        }
+        if verbose {
+            progress("No cached token for the requested scopes, authenticating...");
         }
+    }
 
-        let callback_result = timeout(Duration::from_secs(300), receiver.recv())
-            .await
-            .map_err(|_| OidcError::Auth("Authentication timeout (5 minutes)".to_string()))?
-            .ok_or_else(|| OidcError::Auth("Failed to receive callback".to_string()))?;
+    let id_token_hint = if hint && persist {
+        let cached = TokenCache::new_with_override(profile_manager.config_dir_override())?
+            .cached_id_token(&profile_name)?;
+        if verbose {
+            match cached {
+                Some(_) => progress("Sending cached id_token as id_token_hint"),
+                None => progress(format!(
+                    "No cached id_token for '{profile_name}'; sending no hint"
+                )),
+            }
+        }
+        cached
+    } else {
+        None
+    };
 
-        if let Some(error) = callback_result.error {
-            return Err(OidcError::Auth(format!(
-                "Authentication failed: {} - {}",
-                error,
-                callback_result.error_description.unwrap_or_default()
-            )));
+    let claims_request = build_claims_request(
+        &claims_presets,
+        claims.as_deref(),
+        profile_manager.claims_presets(),
+    )?;
+    if verbose {
+        if let Some(ref request) = claims_request {
+            progress(format!("Requesting claims: {request}"));
         }
+    }
 
-        (callback_result.code, callback_result.state, Some(server))
+    let dpop_key = if dpop {
+        Some(Arc::new(DpopKey::generate()?))
     } else {
-        let code = handle_manual_code_entry(quiet).await?;
-        (code, auth_request.state.clone(), None)
+        None
     };
 
-    if let Some(server) = server_opt {
-        // Exchange tokens in background while browser shows success page
-        let server_clone = server.clone();
-        let oauth_client_clone = oauth_client.clone();
-        let code_clone = code.clone();
-        let state_clone = state.clone();
-        let auth_state_clone = auth_request.state.clone();
-        let verifier_clone = auth_request.pkce_challenge.verifier.clone();
-        let output_clone = output.clone();
+    metrics.phase("discovery");
+    let oauth_client =
+        OAuthClient::new_with_discovery_cache(profile.clone(), discovery_cache.as_ref())
+            .await?
+            .with_registered_redirect_uri(registered_redirect_uri.filter(|_| resumed.is_none()))
+            .with_pkce_trace(very_verbose)
+            .with_required_scopes(profile_manager.required_scopes().to_vec())
+            .with_id_token_hint(id_token_hint)
+            .with_prompt(prompt)
+            .with_claims(claims_request)
+            .with_dpop(dpop_key.clone())
+            .with_audience(audience);
 
-        tokio::spawn(async move {
+    if verbose {
+        progress(format!("Endpoints: {}", oauth_client.endpoint_source()));
+    }
+
+    if !profile.no_pkce && !device && !client_credentials && resumed.is_none() {
+        let method = oauth_client.pkce_method();
+        if verbose {
+            let source = if profile.pkce_method.is_some() {
+                "pinned by the profile"
+            } else if profile.discovery_uri.is_some() {
+                "negotiated from discovery"
+            } else {
+                "default"
+            };
+            progress(format!("Using PKCE method {method} ({source})"));
+        }
+        if oauth21_enforced() {
+            validate_oauth21_pkce_method(method)?;
+        }
+        if method == PkceMethod::Plain {
+            security_warning(
+                "using the plain PKCE method; the challenge does not hide the verifier",
+            );
+        }
+    }
+
+    if check_clock {
+        match oauth_client.server_date() {
+            Some(server_date) => {
+                if let Some(warning) = clock_drift_warning(server_date, SystemTime::now()) {
+                    warn(warning);
+                } else if verbose {
+                    progress("Local clock is within tolerance of the provider");
+                }
+            }
+            None => warn("cannot check clock drift without a Date header from discovery"),
+        }
+    }
+
+    if explain {
+        if client_credentials {
+            return Err(OidcError::Config(
+                "--explain shows an authorization request, which the client_credentials grant does not make"
+                    .to_string(),
+            ));
+        }
+        let request = oauth_client.create_authorization_request()?;
+        explain_authorization_url(&request.authorization_url, json)?;
+        return Ok(None);
+    }
+
+    metrics.phase("authorize");
+    let (token_response, server_opt) = if let Some(flow) = resumed {
+        (resume_saved_flow(&oauth_client, flow, verbose).await?, None)
+    } else if client_credentials {
+        if verbose {
+            progress("Requesting a token with the client credentials grant...");
+        }
+        (oauth_client.client_credentials_grant().await?, None)
+    } else if device {
+        (
+            authorize_with_device_code(&oauth_client, quiet, verbose).await?,
+            None,
+        )
+    } else if let Some(ref path) = callback_file {
+        (
+            authorize_with_callback_file(&oauth_client, path, quiet, verbose).await?,
+            None,
+        )
+    } else {
+        let mut auth_request = oauth_client.create_authorization_request()?;
+        if let Some(ref dir) = state_dir {
+            let flow = SavedFlow::new(&profile_name, oauth_client.redirect_uri(), auth_request);
+            let path = flow.save(dir)?;
+            security_warning(format!(
+                "login state, including the PKCE verifier, was saved to {path:?}.\n\
+                 keep it private; it is removed once the login completes."
+            ));
+            auth_request = flow.request;
+        }
+        authorize_with_browser(
+            &oauth_client,
+            auth_request,
+            CallbackListener {
+                port,
+                uds: uds.as_deref(),
+                retries,
+                idle_timeout,
+                token_display,
+            },
+            quiet,
+            verbose,
+            browser_opener,
+        )
+        .await?
+    };
+
+    if let Some(ref dir) = state_dir {
+        SavedFlow::remove(dir, &profile_name)?;
+    }
+
+    metrics.phase("output");
+    claim_requirements.check(&token_response)?;
+    if verify {
+        verify_tokens(&token_response, &profile.client_id, jwks.as_ref(), verbose)?;
+    }
+    if let Some(ref path) = claims_out {
+        write_claims_file(path, &token_response)?;
+        if verbose {
+            progress(format!("ID token claims written to {path:?}"));
+        }
+    }
+    for path in token_files.write(&token_response)? {
+        if verbose {
+            progress(format!("Token written to {path:?}"));
+        }
+    }
+
+    if let Some(ref key) = dpop_key {
+        report_dpop_binding(&token_response, key);
+    }
+
+    // Tokens only reach the disk with --cached. A DPoP-bound token is useless once this
+    // process and its key are gone, and the cache holds one token per profile, which an
+    // API-specific token must not replace.
+    if use_cache && persist && dpop_key.is_none() && for_api.is_none() {
+        if let Err(e) = TokenCache::new_with_override(profile_manager.config_dir_override())
+            .and_then(|cache| cache.store(&profile_name, &token_response, &profile.scope))
+        {
+            if !quiet {
+                warn(format!("failed to cache tokens: {e}"));
+            }
+        }
+    }
+
+    if emit {
+        emit_tokens(
+            &token_response,
+            output_template.as_deref(),
+            json_output,
+            quiet,
+            copy,
+            show,
+        )?;
+    }
+
+    let hook = match on_success {
+        Some(command) => Some(command),
+        None => profile
+            .post_login_hook
+            .clone()
+            .filter(|command| run_hook || confirm_hook(command)),
+    };
+    if let Some(command) = hook {
+        run_post_login_hook(&command, &profile_name, &token_response, verbose).await?;
+    }
+
+    if let Some(server) = server_opt.filter(|server| server.token_display().is_some()) {
+        server.set_tokens(token_response.clone()).await;
+
+        if !quiet && json_output.is_none() {
+            println!();
+            println!("Token is now available in the browser.");
+        }
+
+        // Keep the callback server alive long enough for the browser to fetch the tokens
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+
+    Ok(Some(token_response))
+}
+
+/// Pick the redirect URI for this login: an explicit override wins, otherwise an
+/// interactive session prefers the first localhost candidate so the callback server
+/// can receive the code; anything else falls back to the profile's primary URI.
+pub fn select_redirect_uri(
+    profile: &Profile,
+    override_uri: Option<&str>,
+    interactive: bool,
+) -> Result<String> {
+    if let Some(uri) = override_uri {
+        validate_redirect_uri(uri)?;
+        return Ok(uri.to_string());
+    }
+
+    if interactive {
+        if let Some(local) = profile
+            .redirect_uri_candidates()
+            .find(|uri| is_localhost_redirect_uri(uri))
+        {
+            return Ok(local.clone());
+        }
+    }
+
+    Ok(profile.redirect_uri.clone())
+}
+
+fn verify_tokens(
+    token_response: &TokenResponse,
+    client_id: &str,
+    jwks: Option<&Jwks>,
+    verbose: bool,
+) -> Result<()> {
+    match token_response.id_token.as_deref() {
+        Some(id_token) => {
+            if let Some(jwks) = jwks {
+                jwks.verify(id_token)?;
+                if verbose {
+                    progress("✓ ID token signature verified against the JWKS file");
+                }
+            }
+            verify_id_token(id_token, client_id)?;
             if verbose {
-                println!("Received authorization code, exchanging for tokens...");
+                progress("✓ ID token aud, azp and exp claims verified");
             }
+        }
+        // A signature check that was asked for but cannot happen must not pass silently
+        None if jwks.is_some() => {
+            return Err(OidcError::Auth(
+                "--jwks-file given but no id_token was returned to verify".to_string(),
+            ))
+        }
+        None => warn("--verify requested but no id_token was returned"),
+    }
+    Ok(())
+}
 
-            match oauth_client_clone
-                .exchange_code_for_tokens(
-                    &code_clone,
-                    &state_clone,
-                    &auth_state_clone,
-                    &verifier_clone,
-                )
-                .await
-            {
-                Ok(token_response) => {
-                    // Handle JSON output
-                    if json_output {
-                        output_tokens_json(&token_response, output_clone.as_ref(), quiet);
-                    } else if quiet {
-                        println!("{}", serde_json::to_string(&token_response).unwrap());
-                    } else {
-                        display_tokens(&token_response, copy).unwrap_or_else(|e| {
-                            eprintln!("Error displaying tokens: {e}");
-                        });
-                    }
-
-                    server_clone.set_tokens(token_response.clone()).await;
-
-                    if !quiet && !json_output {
-                        println!();
-                        println!("Token is now available in the browser.");
-                    }
+/// Finish a saved flow from the callback URL the user pastes after authorizing
+async fn resume_saved_flow(
+    oauth_client: &OAuthClient,
+    flow: SavedFlow,
+    verbose: bool,
+) -> Result<TokenResponse> {
+    progress(format!("Resuming login for profile '{}'.", flow.profile));
+    progress("If you have not authorized yet, open this URL first:");
+    progress(format!("  {}", flow.request.authorization_url));
+
+    let callback = read_callback_url().await?;
+    redeem_callback(
+        oauth_client,
+        callback,
+        &flow.request.state,
+        &flow.request.pkce_challenge.verifier,
+        verbose,
+    )
+    .await
+}
+
+/// Turn the callback answering a login into tokens: reject an error response or a
+/// foreign `state`, take the access token straight from the query in the hybrid
+/// flow, and otherwise exchange the code. The callback's `session_state` fills in
+/// when the token response has none.
+async fn redeem_callback(
+    oauth_client: &OAuthClient,
+    callback: CallbackResult,
+    expected_state: &str,
+    pkce_verifier: &str,
+    verbose: bool,
+) -> Result<TokenResponse> {
+    if let Some(error) = callback.error {
+        return Err(OidcError::Auth(format!(
+            "Authentication failed: {} - {}",
+            error,
+            callback.error_description.unwrap_or_default()
+        )));
+    }
+    if callback.state != expected_state {
+        return Err(OidcError::StateMismatch);
+    }
+
+    if oauth_client.profile().is_hybrid() {
+        if let Some(token_response) = callback.query_token() {
+            if verbose {
+                progress("Access token returned in the callback, skipping code exchange");
+            }
+            return Ok(token_response);
+        }
+    }
+
+    if verbose {
+        progress("Received authorization code, exchanging for tokens...");
+    }
+
+    let mut token_response = oauth_client
+        .exchange_code_for_tokens(
+            &callback.code,
+            &callback.state,
+            expected_state,
+            pkce_verifier,
+        )
+        .await?;
+
+    if token_response.session_state.is_none() {
+        token_response.session_state = callback.session_state;
+    }
+
+    Ok(token_response)
+}
+
+/// How long `--callback-file` waits for the authorization response
+const CALLBACK_FILE_TIMEOUT: Duration = Duration::from_secs(300);
+const CALLBACK_FILE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Print the authorization URL without starting a callback server, then poll `path`
+/// until another process writes the callback URL (or query string) into it
+async fn authorize_with_callback_file(
+    oauth_client: &OAuthClient,
+    path: &Path,
+    quiet: bool,
+    verbose: bool,
+) -> Result<TokenResponse> {
+    if path.exists() {
+        return Err(OidcError::Config(format!(
+            "Callback file {path:?} already exists; remove it so a stale response is not used"
+        )));
+    }
+
+    let auth_request = oauth_client.create_authorization_request()?;
+    if quiet {
+        progress(&auth_request.authorization_url);
+    } else {
+        progress("Open this URL to authorize:");
+        progress(format!("  {}", auth_request.authorization_url));
+        if !json_diagnostics() {
+            println!();
+        }
+        progress(format!(
+            "Waiting for the authorization response in {path:?}..."
+        ));
+    }
+
+    let callback =
+        poll_callback_file(path, CALLBACK_FILE_TIMEOUT, CALLBACK_FILE_POLL_INTERVAL).await?;
+    // The response is single-use; don't leave the code lying around
+    let _ = std::fs::remove_file(path);
+
+    redeem_callback(
+        oauth_client,
+        callback,
+        &auth_request.state,
+        &auth_request.pkce_challenge.verifier,
+        verbose,
+    )
+    .await
+}
+
+/// Wait for `path` to hold a parseable authorization response. Partially written
+/// or unparseable content is retried until `limit` elapses.
+pub async fn poll_callback_file(
+    path: &Path,
+    limit: Duration,
+    interval: Duration,
+) -> Result<CallbackResult> {
+    let deadline = tokio::time::Instant::now() + limit;
+    loop {
+        let content = std::fs::read_to_string(path).ok();
+        if let Some(callback) = content.as_deref().and_then(parse_callback_response) {
+            return Ok(callback);
+        }
+
+        if tokio::time::Instant::now() >= deadline {
+            return Err(OidcError::Auth(match content {
+                Some(_) => format!(
+                    "Callback file {path:?} does not contain a callback URL with 'code' and 'state'"
+                ),
+                None => format!("Timed out after {}s waiting for {path:?}", limit.as_secs()),
+            }));
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Switch `profile` to the scope of its `apis` entry for `api`, returning the audience to request
+fn apply_api_target(profile: &mut Profile, api: Option<&str>) -> Result<Option<String>> {
+    let Some(api) = api else {
+        return Ok(None);
+    };
+    let target = profile.api_target(api)?.clone();
+    validate_scope(&target.scope)?;
+    profile.scope = target.scope;
+    Ok(target.audience)
+}
+
+/// Files that each receive one raw token, for tools that read them separately
+#[derive(Debug, Clone, Default)]
+pub struct TokenFiles {
+    pub access: Option<PathBuf>,
+    pub id: Option<PathBuf>,
+    pub refresh: Option<PathBuf>,
+}
+
+impl TokenFiles {
+    /// Write each requested token that the response contains (owner-only, atomically)
+    /// and return the paths written; absent tokens leave their file untouched
+    pub fn write(&self, token_response: &TokenResponse) -> Result<Vec<PathBuf>> {
+        let files = [
+            (&self.access, Some(&token_response.access_token)),
+            (&self.id, token_response.id_token.as_ref()),
+            (&self.refresh, token_response.refresh_token.as_ref()),
+        ];
+
+        let mut written = Vec::new();
+        for (path, token) in files {
+            if let (Some(path), Some(token)) = (path, token) {
+                write_atomic(path, token.as_bytes()).map_err(|e| {
+                    OidcError::Config(format!("Failed to write token to {path:?}: {e}"))
+                })?;
+                written.push(path.clone());
+            }
+        }
+        Ok(written)
+    }
+}
+
+/// Write the id_token claims as pretty-printed JSON readable only by the owner
+pub fn write_claims_file(path: &Path, token_response: &TokenResponse) -> Result<()> {
+    let id_token = token_response.id_token.as_deref().ok_or_else(|| {
+        OidcError::Auth(
+            "--claims-out was given but no id_token was returned (is 'openid' in the scope?)"
+                .to_string(),
+        )
+    })?;
+
+    let claims = decode_id_token_claims(id_token)?;
+    let json = serde_json::to_string_pretty(&claims)
+        .map_err(|e| OidcError::Config(format!("Failed to serialize claims: {e}")))?;
+    write_atomic(path, json.as_bytes())
+        .map_err(|e| OidcError::Config(format!("Failed to write claims to {path:?}: {e}")))
+}
+
+/// Let the user adjust the scopes for this login from the provider's
+/// `scopes_supported`. Returns `None` (keep the profile's scopes) when stdin is
+/// not a terminal or the provider does not advertise any.
+async fn choose_login_scopes(
+    profile: &Profile,
+    discovery_cache: Option<&DiscoveryCache>,
+) -> Result<Option<String>> {
+    if !std::io::stdin().is_terminal() {
+        warn("--choose-scopes needs an interactive terminal; using the profile's scopes");
+        return Ok(None);
+    }
+    let Some(ref discovery_uri) = profile.discovery_uri else {
+        warn("--choose-scopes needs a discovery URI; using the profile's scopes");
+        return Ok(None);
+    };
+
+    let client = shared_http_client(&profile.pin_sha256)?;
+    let doc = match discovery_cache {
+        Some(cache) => discover_endpoints_cached(&client, discovery_uri, cache).await?,
+        None => discover_endpoints_with(&client, discovery_uri).await?,
+    };
+    match doc.scopes_supported.filter(|scopes| !scopes.is_empty()) {
+        Some(available) => prompt_scope_selection(&available, &profile.scope).map(Some),
+        None => {
+            warn("the provider does not advertise scopes_supported; using the profile's scopes");
+            Ok(None)
+        }
+    }
+}
+
+/// Ask before running a hook stored in the profile; never runs unattended without `--run-hook`
+fn confirm_hook(command: &str) -> bool {
+    if !std::io::stdin().is_terminal() {
+        warn("skipping post_login_hook (pass --run-hook to run it non-interactively)");
+        return false;
+    }
+
+    eprint!("Run post-login hook `{command}`? [y/N]: ");
+    let _ = std::io::Write::flush(&mut std::io::stderr());
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok()
+        && matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Run a post-login hook through the shell. Tokens are passed in environment
+/// variables rather than arguments so they do not show up in process listings;
+/// the hook's stdout goes to stderr to keep token output on stdout parseable.
+pub async fn run_post_login_hook(
+    command: &str,
+    profile_name: &str,
+    token_response: &TokenResponse,
+    verbose: bool,
+) -> Result<()> {
+    #[cfg(unix)]
+    let mut cmd = {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = tokio::process::Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    };
+
+    cmd.env("OIDC_PROFILE", profile_name)
+        .env("OIDC_ACCESS_TOKEN", &token_response.access_token)
+        .env("OIDC_TOKEN_TYPE", &token_response.token_type)
+        .stdout(std::io::stderr());
+    for (name, value) in [
+        ("OIDC_ID_TOKEN", &token_response.id_token),
+        ("OIDC_REFRESH_TOKEN", &token_response.refresh_token),
+    ] {
+        if let Some(value) = value {
+            cmd.env(name, value);
+        }
+    }
+    if let Some(expires_in) = token_response.expires_in {
+        cmd.env("OIDC_EXPIRES_IN", expires_in.to_string());
+    }
+
+    if verbose {
+        progress(format!("Running post-login hook: {command}"));
+    }
+
+    let status = cmd
+        .status()
+        .await
+        .map_err(|e| OidcError::Hook(format!("could not start `{command}`: {e}")))?;
+
+    if !status.success() {
+        return Err(OidcError::Hook(match status.code() {
+            Some(code) => format!("`{command}` exited with status {code}"),
+            None => format!("`{command}` was terminated by a signal"),
+        }));
+    }
+
+    Ok(())
+}
+
+/// Destination of JSON token output
+#[derive(Clone, Copy)]
+enum JsonOutput<'a> {
+    Stdout,
+    File(&'a PathBuf),
+    Fd(&'a std::fs::File),
+}
+
+/// Print each authorization URL parameter (the URL carries no client secret)
+fn explain_authorization_url(url: &str, json: bool) -> Result<()> {
+    let breakdown = explain_url(url)?;
+    if json {
+        let json = serde_json::to_string_pretty(&breakdown)
+            .map_err(|e| OidcError::Config(format!("Failed to serialize URL breakdown: {e}")))?;
+        println!("{json}");
+    } else {
+        print!("{}", format_url_breakdown(&breakdown));
+    }
+    Ok(())
+}
+
+/// Print tokens using the selected output mode; JSON output skips the human-readable display
+fn emit_tokens(
+    token_response: &TokenResponse,
+    output_template: Option<&str>,
+    json_output: Option<JsonOutput>,
+    quiet: bool,
+    copy: bool,
+    show: TokenSelection,
+) -> Result<()> {
+    for kind in show.missing(token_response) {
+        warn(format!(
+            "--show requested the {kind} token but the provider did not return one"
+        ));
+    }
+
+    if let Some(template) = output_template {
+        let (rendered, unknown) = render_output_template(template, token_response);
+        for name in unknown {
+            warn(format!("unknown placeholder '{{{name}}}' left unchanged"));
+        }
+        println!("{rendered}");
+    } else if let Some(destination) = json_output {
+        output_tokens_json(token_response, destination, quiet, show)?;
+    } else if quiet {
+        let mut value = serde_json::to_value(token_response).unwrap();
+        show.strip_unselected(&mut value);
+        println!("{value}");
+    } else {
+        display_tokens(token_response, copy, show)?;
+    }
+
+    Ok(())
+}
+
+/// Wait for the callback to `request`. After each timeout, while `retries` remain,
+/// `new_attempt` issues a replacement request and the window doubles. A late
+/// callback answering an abandoned request is ignored rather than treated as a
+/// state mismatch. With `idle`, an attempt also ends once the callback server has
+/// seen no request at all for the idle timeout. Returns the callback with the
+/// request it answers.
+async fn await_callback<F>(
+    receiver: &mut mpsc::Receiver<CallbackResult>,
+    mut request: AuthorizationRequest,
+    retries: u32,
+    base_timeout: Duration,
+    mut idle: Option<IdleTimeout<'_>>,
+    mut new_attempt: F,
+) -> Result<(CallbackResult, AuthorizationRequest)>
+where
+    F: FnMut() -> Result<AuthorizationRequest>,
+{
+    let mut abandoned_states = Vec::new();
+    let mut window = base_timeout;
+    let mut waited = Duration::ZERO;
+    let mut idled = false;
+
+    for attempt in 0..=retries {
+        let started = Instant::now();
+        let deadline = started + window;
+        let mut idle_deadline = idle.as_ref().map(|idle| started + idle.timeout);
+        loop {
+            let wake = idle_deadline.map_or(deadline, |idle_at| idle_at.min(deadline));
+            tokio::select! {
+                callback = receiver.recv() => match callback {
+                    Some(callback) if abandoned_states.contains(&callback.state) => continue,
+                    Some(callback) => return Ok((callback, request)),
+                    None => return Err(OidcError::Auth("Failed to receive callback".to_string())),
+                },
+                Some(()) = next_activity(idle.as_mut()) => {
+                    idle_deadline = idle.as_ref().map(|idle| Instant::now() + idle.timeout);
                 }
-                Err(e) => {
-                    eprintln!("Error exchanging code for tokens: {e}");
+                _ = sleep_until(wake) => {
+                    idled = wake < deadline;
+                    break;
                 }
             }
-        });
+        }
+        waited += started.elapsed();
+
+        if attempt < retries {
+            abandoned_states.push(std::mem::replace(&mut request, new_attempt()?).state);
+            window = window.saturating_mul(2);
+        }
+    }
+
+    match idle {
+        Some(idle) if idled => Err(OidcError::Auth(format!(
+            "Authentication timeout: no activity on the callback server for {}s",
+            idle.timeout.as_secs()
+        ))),
+        _ => Err(OidcError::Auth(format!(
+            "Authentication timeout: no callback received in {}s",
+            waited.as_secs()
+        ))),
+    }
+}
+
+/// Ends a callback wait early when the server sees no requests for `timeout`
+struct IdleTimeout<'a> {
+    timeout: Duration,
+    activity: &'a mut mpsc::Receiver<()>,
+}
+
+/// The next activity signal, or never without an idle timeout
+async fn next_activity(idle: Option<&mut IdleTimeout<'_>>) -> Option<()> {
+    match idle {
+        Some(idle) => idle.activity.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// How the local callback server listens and how long it waits
+struct CallbackListener<'a> {
+    port: Option<u16>,
+    uds: Option<&'a Path>,
+    /// Fresh authorization attempts allowed after a timeout
+    retries: u32,
+    /// Shorter limit on inactivity, reset by any request to the server
+    idle_timeout: Option<Duration>,
+    token_display: Option<BrowserTokenDisplay>,
+}
+
+/// Window for the first callback attempt; each retry doubles it
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Run the authorization code flow in the browser and exchange the resulting code
+async fn authorize_with_browser<B: BrowserOpener>(
+    oauth_client: &OAuthClient,
+    mut auth_request: AuthorizationRequest,
+    listener: CallbackListener<'_>,
+    quiet: bool,
+    verbose: bool,
+    browser_opener: &B,
+) -> Result<(TokenResponse, Option<CallbackServer>)> {
+    let profile = oauth_client.profile();
+
+    if !quiet {
+        progress("Initiating OAuth 2.0 authorization flow...");
+    }
+
+    browser_opener.open_with_fallback(&auth_request.authorization_url, quiet)?;
+
+    let (callback, server_opt) = if listener.uds.is_some()
+        || is_localhost_redirect_uri(&profile.redirect_uri)
+    {
+        // Use callback server for localhost URLs, or a Unix socket fronted by a proxy
+        let mut server = match listener.uds {
+            Some(socket_path) => {
+                if verbose {
+                    progress(format!(
+                        "Starting callback server on Unix socket {socket_path:?}"
+                    ));
+                }
+                new_unix_callback_server(socket_path, &profile.redirect_uri)?
+                    .with_token_display(listener.token_display)
+            }
+            None => {
+                let port = listener
+                    .port
+                    .or_else(|| extract_port_from_redirect_uri(&profile.redirect_uri))
+                    .unwrap_or(8080);
+
+                if verbose {
+                    progress(format!("Starting callback server on port {port}"));
+                }
+
+                CallbackServer::new(port, &profile.redirect_uri)?
+                    .with_token_display(listener.token_display)
+            }
+        };
+
+        let mut activity = listener.idle_timeout.map(|_| server.track_activity());
+        let mut receiver = server.start().await?;
 
         if !quiet {
-            println!("Authentication successful! Check your browser for the access token.");
-            println!("Tokens will be displayed in the browser once ready...");
+            progress("Waiting for authentication callback...");
+            progress("Press Ctrl+C to cancel");
         }
 
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+        // Codes are single-use, so each retry sends a new request with fresh PKCE and state
+        let (callback_result, answered) = await_callback(
+            &mut receiver,
+            auth_request,
+            listener.retries,
+            CALLBACK_TIMEOUT,
+            listener
+                .idle_timeout
+                .zip(activity.as_mut())
+                .map(|(timeout, activity)| IdleTimeout { timeout, activity }),
+            || {
+                if !quiet {
+                    progress("No callback received; starting a new authorization attempt...");
+                }
+                let request = oauth_client.create_authorization_request()?;
+                browser_opener.open_with_fallback(&request.authorization_url, quiet)?;
+                Ok(request)
+            },
+        )
+        .await?;
+        auth_request = answered;
+        (callback_result, Some(server))
     } else {
-        if verbose {
-            println!("Received authorization code, exchanging for tokens...");
+        let mut instructed = quiet;
+        let token_response = redeem_manual_entries(oauth_client, &auth_request, verbose, || {
+            // Explain the manual step once, not on every retry
+            handle_manual_code_entry(std::mem::replace(&mut instructed, true))
+        })
+        .await?;
+        return Ok((token_response, None));
+    };
+
+    let expected_state = expected_callback_state(profile, &auth_request, &callback)?;
+    let token_response = redeem_callback(
+        oauth_client,
+        callback,
+        &expected_state,
+        &auth_request.pkce_challenge.verifier,
+        verbose,
+    )
+    .await?;
+
+    Ok((token_response, server_opt))
+}
+
+/// Codes the user may paste before a manual login gives up
+const MANUAL_ENTRY_ATTEMPTS: u32 = 3;
+
+/// Redeem codes pasted by the user until one is exchanged. A failed attempt asks again
+/// with the same client, so pasting a code that was already tried is refused as
+/// [`OidcError::CodeReused`] instead of being sent to the provider a second time.
+async fn redeem_manual_entries<F, Fut>(
+    oauth_client: &OAuthClient,
+    auth_request: &AuthorizationRequest,
+    verbose: bool,
+    mut read_entry: F,
+) -> Result<TokenResponse>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(String, Option<String>)>>,
+{
+    let mut attempt = 1;
+    loop {
+        let (code, state) = read_entry().await?;
+        let callback = CallbackResult {
+            code,
+            // A callback URL without a state never matches, so it is rejected
+            state: state.unwrap_or_default(),
+            error: None,
+            error_description: None,
+            access_token: None,
+            token_type: None,
+            expires_in: None,
+            session_state: None,
+        };
+        let result = match expected_callback_state(oauth_client.profile(), auth_request, &callback)
+        {
+            Ok(expected_state) => {
+                redeem_callback(
+                    oauth_client,
+                    callback,
+                    &expected_state,
+                    &auth_request.pkce_challenge.verifier,
+                    verbose,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Err(e) if attempt < MANUAL_ENTRY_ATTEMPTS && !matches!(e, OidcError::Cancelled) => {
+                progress(format!(
+                    "{e}. Authorize again in the browser and paste the new callback URL or code."
+                ));
+                attempt += 1;
+            }
+            result => return result,
         }
+    }
+}
 
-        let token_response = oauth_client
-            .exchange_code_for_tokens(
-                &code,
-                &state,
-                &auth_request.state,
-                &auth_request.pkce_challenge.verifier,
-            )
-            .await?;
+/// The `state` a callback must carry. With `request_uri` the state lives in the hosted
+/// request object, so it is the profile's `request_state`; without one the callback's
+/// state can't be verified, which is warned about, and a callback without any state
+/// is still refused.
+fn expected_callback_state(
+    profile: &Profile,
+    auth_request: &AuthorizationRequest,
+    callback: &CallbackResult,
+) -> Result<String> {
+    if profile.request_uri.is_none() {
+        return Ok(auth_request.state.clone());
+    }
+    if let Some(ref request_state) = profile.request_state {
+        return Ok(request_state.clone());
+    }
+    if callback.state.is_empty() && callback.error.is_none() {
+        return Err(OidcError::Auth(
+            "The callback has no state parameter; the request object must set one".to_string(),
+        ));
+    }
+    security_warning(
+        "The callback state can't be verified because the request object's state is unknown; \
+         pass --request-state or set request_state in the profile",
+    );
+    Ok(callback.state.clone())
+}
+
+#[cfg(unix)]
+fn new_unix_callback_server(socket_path: &Path, redirect_uri: &str) -> Result<CallbackServer> {
+    CallbackServer::new_unix(socket_path, redirect_uri)
+}
+
+#[cfg(not(unix))]
+fn new_unix_callback_server(_socket_path: &Path, _redirect_uri: &str) -> Result<CallbackServer> {
+    Err(OidcError::Config(
+        "--uds is only supported on Unix platforms".to_string(),
+    ))
+}
 
-        // Handle JSON output
-        if json_output {
-            output_tokens_json(&token_response, output.as_ref(), quiet);
-        } else if quiet {
-            println!("{}", serde_json::to_string(&token_response).unwrap());
-        } else {
-            display_tokens(&token_response, copy)?;
+/// Say whether the provider actually bound the tokens to the DPoP key
+fn report_dpop_binding(token_response: &TokenResponse, key: &DpopKey) {
+    if token_response.token_type.eq_ignore_ascii_case("DPoP") {
+        progress(format!(
+            "✓ DPoP-bound access token (key thumbprint {})",
+            key.thumbprint()
+        ));
+    } else {
+        warn(format!(
+            "requested DPoP but the provider issued a '{}' token, which is not key-bound",
+            token_response.token_type
+        ));
+    }
+}
+
+/// Run the device authorization grant, polling until the user approves on another device
+async fn authorize_with_device_code(
+    oauth_client: &OAuthClient,
+    quiet: bool,
+    verbose: bool,
+) -> Result<TokenResponse> {
+    let device = oauth_client.request_device_authorization().await?;
+
+    // The user code is required to continue, so it is shown even in quiet mode,
+    // on stderr to keep stdout parseable
+    eprintln!(
+        "To sign in, visit {} and enter the code: {}",
+        device.verification_uri, device.user_code
+    );
+    if !quiet {
+        if let Some(ref complete) = device.verification_uri_complete {
+            eprintln!("Or open: {complete}");
         }
+        eprintln!(
+            "Waiting for authorization (code expires in {}s)...",
+            device.expires_in
+        );
     }
 
-    Ok(())
+    let mut backoff = DeviceBackoff::for_authorization(&device);
+    oauth_client
+        .poll_for_device_tokens(&device, &mut backoff, verbose)
+        .await
 }
 
 /// Output tokens as JSON to stdout or file
 fn output_tokens_json(
-    token_response: &crate::auth::TokenResponse,
-    output_path: Option<&PathBuf>,
+    token_response: &TokenResponse,
+    destination: JsonOutput,
     quiet: bool,
-) {
-    let export = TokenExport::from_response(token_response);
+    show: TokenSelection,
+) -> Result<()> {
+    let mut export = serde_json::to_value(TokenExport::from_response(token_response)).unwrap();
+    show.strip_unselected(&mut export);
     let json_str = serde_json::to_string_pretty(&export).unwrap();
 
-    if let Some(path) = output_path {
-        match std::fs::write(path, &json_str) {
-            Ok(_) => {
-                if !quiet {
-                    println!("Tokens written to {}", path.display());
-                }
+    match destination {
+        JsonOutput::Stdout => println!("{json_str}"),
+        // Owner-only and atomic, like the token cache; a failed write fails the login
+        JsonOutput::File(path) => {
+            write_atomic(path, json_str.as_bytes()).map_err(|e| {
+                OidcError::Config(format!("Failed to write tokens to {path:?}: {e}"))
+            })?;
+            if !quiet {
+                progress(format!("Tokens written to {}", path.display()));
             }
-            Err(e) => {
-                eprintln!("Error writing tokens to file: {e}");
+        }
+        // The wrapping process reads until EOF, which it sees once the descriptor is closed
+        JsonOutput::Fd(mut file) => {
+            writeln!(file, "{json_str}")?;
+            file.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth_request(state: &str) -> AuthorizationRequest {
+        AuthorizationRequest {
+            authorization_url: format!("https://example.com/auth?state={state}"),
+            state: state.to_string(),
+            pkce_challenge: crate::crypto::PkceChallenge::new(PkceMethod::S256).unwrap(),
+        }
+    }
+
+    fn callback(state: &str) -> CallbackResult {
+        CallbackResult {
+            code: format!("code-{state}"),
+            state: state.to_string(),
+            error: None,
+            error_description: None,
+            access_token: None,
+            token_type: None,
+            expires_in: None,
+            session_state: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_manual_entry_refuses_a_code_already_tried() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let server = MockServer::start(vec![
+            MockResponse::json(400, r#"{"error":"invalid_grant"}"#),
+            MockResponse::json(
+                200,
+                r#"{"access_token":"user-token","token_type":"Bearer","expires_in":300}"#,
+            ),
+        ])
+        .await;
+        let profile = Profile {
+            client_id: "client".to_string(),
+            redirect_uri: "https://app.example.com/callback".to_string(),
+            scope: "openid".to_string(),
+            authorization_endpoint: Some("https://example.com/auth".to_string()),
+            token_endpoint: Some(server.url("/token")),
+            ..Default::default()
+        };
+        let client = OAuthClient::new(profile).await.unwrap();
+        let request = auth_request("s");
+
+        // The first exchange fails, the same code is pasted again, then a fresh one
+        let mut entries = vec!["fresh", "first", "first"];
+        let mut outcomes = Vec::new();
+        let tokens = redeem_manual_entries(&client, &request, false, || {
+            let code = entries.pop().unwrap().to_string();
+            outcomes.push(code.clone());
+            async move { Ok((code, Some("s".to_string()))) }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(tokens.access_token, "user-token");
+        assert_eq!(outcomes, ["first", "first", "fresh"]);
+        // The repeated code never reached the provider
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].body.contains("code=fresh"));
+
+        let reused = client
+            .exchange_code_for_tokens("fresh", "s", "s", "verifier")
+            .await;
+        assert!(matches!(reused, Err(OidcError::CodeReused)));
+
+        let mut attempts = 0;
+        let reused = redeem_manual_entries(&client, &request, false, || {
+            attempts += 1;
+            async { Ok(("fresh".to_string(), Some("s".to_string()))) }
+        })
+        .await;
+        assert!(matches!(reused, Err(OidcError::CodeReused)));
+        assert_eq!(attempts, MANUAL_ENTRY_ATTEMPTS);
+    }
+
+    #[test]
+    fn test_expected_callback_state() {
+        let mut profile = Profile::default();
+        let request = auth_request("ours");
+        assert_eq!(
+            expected_callback_state(&profile, &request, &callback("theirs")).unwrap(),
+            "ours"
+        );
+
+        profile.request_uri = Some("https://example.com/requests/abc".to_string());
+        assert_eq!(
+            expected_callback_state(&profile, &request, &callback("theirs")).unwrap(),
+            "theirs"
+        );
+        assert!(expected_callback_state(&profile, &request, &callback("")).is_err());
+
+        profile.request_state = Some("hosted".to_string());
+        assert_eq!(
+            expected_callback_state(&profile, &request, &callback("theirs")).unwrap(),
+            "hosted"
+        );
+    }
+
+    #[test]
+    fn test_verify_tokens_requires_id_token_with_jwks() {
+        let tokens = TokenResponse {
+            access_token: "access".to_string(),
+            token_type: "Bearer".to_string(),
+            ..Default::default()
+        };
+        assert!(verify_tokens(&tokens, "client", None, false).is_ok());
+        let result = verify_tokens(&tokens, "client", Some(&Jwks::default()), false);
+        assert!(matches!(result, Err(OidcError::Auth(ref msg)) if msg.contains("no id_token")));
+    }
+
+    #[tokio::test]
+    async fn test_redeem_callback() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let server = MockServer::start(vec![MockResponse::json(
+            200,
+            r#"{"access_token":"user-token","token_type":"Bearer","expires_in":300}"#,
+        )])
+        .await;
+        let profile = Profile {
+            client_id: "client".to_string(),
+            redirect_uri: "https://app.example.com/callback".to_string(),
+            scope: "openid".to_string(),
+            authorization_endpoint: Some("https://example.com/auth".to_string()),
+            token_endpoint: Some(server.url("/token")),
+            ..Default::default()
+        };
+        let client = OAuthClient::new(profile).await.unwrap();
+
+        let mut denied = callback("s");
+        denied.error = Some("access_denied".to_string());
+        let result = redeem_callback(&client, denied, "s", "verifier", false).await;
+        assert!(matches!(result, Err(OidcError::Auth(ref msg)) if msg.contains("access_denied")));
+
+        let result = redeem_callback(&client, callback("other"), "s", "verifier", false).await;
+        assert!(matches!(result, Err(OidcError::StateMismatch)));
+        assert!(server.requests().is_empty());
+
+        let mut answered = callback("s");
+        answered.session_state = Some("session".to_string());
+        let tokens = redeem_callback(&client, answered, "s", "verifier", false)
+            .await
+            .unwrap();
+        assert_eq!(tokens.access_token, "user-token");
+        assert_eq!(tokens.session_state.as_deref(), Some("session"));
+        assert!(server.requests()[0].body.contains("code=code-s"));
+    }
+
+    #[tokio::test]
+    async fn test_await_callback_retries_after_timeout() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let mut attempts = 0;
+
+        let (result, request) = await_callback(
+            &mut rx,
+            auth_request("first"),
+            1,
+            Duration::from_millis(50),
+            None,
+            || {
+                attempts += 1;
+                // A late answer to the abandoned request must not end the wait
+                tx.try_send(callback("first")).unwrap();
+                tx.try_send(callback("second")).unwrap();
+                Ok(auth_request("second"))
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(attempts, 1);
+        assert_eq!(request.state, "second");
+        assert_eq!(result.code, "code-second");
+    }
+
+    #[tokio::test]
+    async fn test_await_callback_times_out_without_retries() {
+        let (_tx, mut rx) = mpsc::channel::<CallbackResult>(1);
+        let result = await_callback(
+            &mut rx,
+            auth_request("only"),
+            0,
+            Duration::from_millis(20),
+            None,
+            || panic!("no retry expected"),
+        )
+        .await;
+        assert!(matches!(result, Err(OidcError::Auth(ref msg)) if msg.contains("timeout")));
+    }
+
+    #[tokio::test]
+    async fn test_await_callback_activity_resets_idle_deadline() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let (activity_tx, mut activity) = mpsc::channel(1);
+
+        // Requests every 20ms keep a 100ms idle timeout from firing until the callback at 200ms
+        tokio::spawn(async move {
+            for _ in 0..10 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                activity_tx.send(()).await.unwrap();
             }
+            tx.send(callback("only")).await.unwrap();
+            // Keep the activity channel open, as the server does
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+        let (result, _) = await_callback(
+            &mut rx,
+            auth_request("only"),
+            0,
+            Duration::from_secs(5),
+            Some(IdleTimeout {
+                timeout: Duration::from_millis(100),
+                activity: &mut activity,
+            }),
+            || panic!("no retry expected"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.code, "code-only");
+
+        // Without requests the idle timeout ends the wait long before the overall one
+        let (_tx, mut rx) = mpsc::channel::<CallbackResult>(1);
+        let (_activity_tx, mut activity) = mpsc::channel(1);
+        let started = Instant::now();
+        let result = await_callback(
+            &mut rx,
+            auth_request("idle"),
+            0,
+            Duration::from_secs(5),
+            Some(IdleTimeout {
+                timeout: Duration::from_millis(20),
+                activity: &mut activity,
+            }),
+            || panic!("no retry expected"),
+        )
+        .await;
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(matches!(result, Err(OidcError::Auth(ref msg)) if msg.contains("no activity")));
+    }
+
+    #[tokio::test]
+    async fn test_login_batch_collects_tokens_per_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_dir = Some(dir.path().to_path_buf());
+        let mut manager = ProfileManager::new_with_test_dir(config_dir.clone()).unwrap();
+        let cache = TokenCache::new_with_override(config_dir).unwrap();
+        for name in ["beta", "alpha"] {
+            manager
+                .create_profile(crate::profile::ProfileParams {
+                    name: name.to_string(),
+                    client_id: format!("{name}-client"),
+                    client_secret: None,
+                    redirect_uri: "http://localhost:8080/callback".to_string(),
+                    scope: "openid".to_string(),
+                    discovery_uri: Some(
+                        "https://example.com/.well-known/openid-configuration".to_string(),
+                    ),
+                    authorization_endpoint: None,
+                    token_endpoint: None,
+                })
+                .unwrap();
+            let tokens = TokenResponse {
+                access_token: format!("{name}-access"),
+                token_type: "Bearer".to_string(),
+                expires_in: Some(3600),
+                ..Default::default()
+            };
+            cache.store(name, &tokens, "openid").unwrap();
         }
-    } else {
-        println!("{json_str}");
+
+        let output = dir.path().join("tokens.json");
+        let options = LoginOptions {
+            all: true,
+            use_cache: true,
+            quiet: true,
+            output: Some(output.clone()),
+            ..Default::default()
+        };
+        let browser = crate::browser::MockBrowserOpener::new();
+        handle_login_batch(manager, options, &browser)
+            .await
+            .unwrap();
+        assert!(browser.get_opened_urls().is_empty());
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(output).unwrap()).unwrap();
+        assert_eq!(json["alpha"]["access_token"], "alpha-access");
+        assert_eq!(json["beta"]["access_token"], "beta-access");
+
+        let results = vec![
+            ("ok".to_string(), Ok(TokenResponse::default())),
+            ("bad".to_string(), Err(OidcError::Cancelled)),
+        ];
+        let json = batch_tokens_json(&results, TokenSelection::default());
+        assert!(json["ok"].get("access_token").is_some());
+        assert_eq!(json["bad"]["error"], "Operation cancelled by user");
+    }
+
+    fn adhoc() -> AdhocProfile {
+        AdhocProfile {
+            client_id: "client".to_string(),
+            client_secret: None,
+            redirect_uri: None,
+            scope: None,
+            discovery_uri: Some("https://example.com/.well-known/openid-configuration".to_string()),
+            authorization_endpoint: None,
+            token_endpoint: None,
+        }
+    }
+
+    fn service_profile(token_endpoint: String) -> Profile {
+        Profile {
+            client_id: "ci-bot".to_string(),
+            client_secret: Some("ci-secret".to_string()),
+            scope: "api:read".to_string(),
+            token_endpoint: Some(token_endpoint),
+            grant_type: Some(CLIENT_CREDENTIALS_GRANT.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_login_batch_runs_client_credentials_concurrently() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        // Hold each response open so overlapping requests are seen in flight together
+        let server = MockServer::start(vec![MockResponse::json(
+            200,
+            r#"{"access_token":"service-token","token_type":"Bearer","expires_in":300}"#,
+        )
+        .with_delay(Duration::from_millis(200))])
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager =
+            ProfileManager::new_with_test_dir(Some(dir.path().to_path_buf())).unwrap();
+        let names = ["svc-a", "svc-b", "svc-c"];
+        for name in names {
+            manager
+                .add_profile(name, service_profile(server.url("/token")))
+                .unwrap();
+        }
+
+        let output = dir.path().join("all.json");
+        let options = LoginOptions {
+            all: true,
+            quiet: true,
+            output: Some(output.clone()),
+            ..Default::default()
+        };
+        let browser = crate::browser::MockBrowserOpener::new();
+        handle_login_batch(manager, options, &browser)
+            .await
+            .unwrap();
+
+        assert!(
+            server.max_concurrent_requests() > 1,
+            "the token requests overlapped"
+        );
+        assert_eq!(server.requests().len(), names.len());
+        assert!(browser.get_opened_urls().is_empty());
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(output).unwrap()).unwrap();
+        for name in names {
+            assert_eq!(json[name]["access_token"], "service-token");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tokens_are_cached_only_with_cached() {
+        use crate::test_support::{MockResponse, MockServer};
+
+        let server = MockServer::start(vec![MockResponse::json(
+            200,
+            r#"{"access_token":"service-token","token_type":"Bearer","expires_in":300}"#,
+        )])
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let config_dir = Some(dir.path().to_path_buf());
+        let mut manager = ProfileManager::new_with_test_dir(config_dir.clone()).unwrap();
+        manager
+            .add_profile("svc", service_profile(server.url("/token")))
+            .unwrap();
+        let browser = crate::browser::MockBrowserOpener::new();
+        let login = |use_cache| LoginOptions {
+            profile_name: Some("svc".to_string()),
+            output: Some(dir.path().join("out.json")),
+            quiet: true,
+            use_cache,
+            ..Default::default()
+        };
+        let cached = || {
+            TokenCache::new_with_override(config_dir.clone())
+                .unwrap()
+                .load()
+                .unwrap()
+                .remove("svc")
+        };
+
+        handle_login_with_browser_opener(manager.clone(), login(false), &browser)
+            .await
+            .unwrap();
+        assert!(cached().is_none(), "nothing is persisted without --cached");
+
+        handle_login_with_browser_opener(manager, login(true), &browser)
+            .await
+            .unwrap();
+        assert_eq!(cached().unwrap().tokens.access_token, "service-token");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_post_login_hook_gets_token_env_and_reports_status() {
+        let tokens = TokenResponse {
+            access_token: "hook-access".to_string(),
+            token_type: "Bearer".to_string(),
+            ..Default::default()
+        };
+
+        run_post_login_hook(
+            r#"test "$OIDC_ACCESS_TOKEN" = hook-access && test "$OIDC_PROFILE" = dev && test -z "$OIDC_ID_TOKEN""#,
+            "dev",
+            &tokens,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let err = run_post_login_hook("exit 3", "dev", &tokens, false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("status 3"));
+    }
+
+    #[test]
+    fn test_select_redirect_uri() {
+        let profile = Profile {
+            redirect_uri: "https://app.example.com/callback".to_string(),
+            redirect_uris: vec![
+                "https://other.example.com/cb".to_string(),
+                "http://localhost:9000/callback".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            select_redirect_uri(&profile, None, true).unwrap(),
+            "http://localhost:9000/callback"
+        );
+        assert_eq!(
+            select_redirect_uri(&profile, None, false).unwrap(),
+            "https://app.example.com/callback"
+        );
+        assert_eq!(
+            select_redirect_uri(&profile, Some("http://127.0.0.1:7000/cb"), true).unwrap(),
+            "http://127.0.0.1:7000/cb"
+        );
+        assert!(select_redirect_uri(&profile, Some("not a uri"), true).is_err());
+    }
+
+    #[test]
+    fn test_adhoc_profile_defaults_and_validation() {
+        let profile = adhoc().into_profile().unwrap();
+        assert_eq!(profile.redirect_uri, AdhocProfile::DEFAULT_REDIRECT_URI);
+        assert_eq!(profile.scope, AdhocProfile::DEFAULT_SCOPE);
+
+        let mut missing_endpoints = adhoc();
+        missing_endpoints.discovery_uri = None;
+        assert!(missing_endpoints.into_profile().is_err());
+    }
+
+    #[test]
+    fn test_stdin_invocation_builds_adhoc_login() {
+        let document = r#"{
+            "profile": {
+                "client_id": "orchestrated",
+                "redirect_uri": "http://localhost:8080/callback",
+                "scope": "openid",
+                "discovery_uri": "https://idp.example.com/.well-known/openid-configuration",
+                "pkce_method": "S256"
+            },
+            "options": { "scope": "openid email", "port": 9123, "verify": true, "show": "access" }
+        }"#;
+        let base = LoginOptions {
+            json: true,
+            retries: 2,
+            ..Default::default()
+        };
+        let options = StdinInvocation::from_reader(document.as_bytes())
+            .unwrap()
+            .into_login_options(base)
+            .unwrap();
+
+        let profile = options.adhoc.as_ref().unwrap();
+        assert_eq!(profile.client_id, "orchestrated");
+        assert_eq!(profile.pkce_method.as_deref(), Some("S256"));
+        assert_eq!(options.scope.as_deref(), Some("openid email"));
+        assert_eq!(options.port, Some(9123));
+        assert!(options.verify && options.json);
+        assert_eq!(options.retries, 2);
+        assert_eq!(options.show, TokenSelection::parse("access").unwrap());
+
+        let invalid_profile =
+            r#"{ "profile": { "client_id": "x", "redirect_uri": "nope", "scope": "openid" } }"#;
+        assert!(StdinInvocation::from_reader(invalid_profile.as_bytes())
+            .unwrap()
+            .into_login_options(LoginOptions::default())
+            .is_err());
+        let unknown_option = r#"{
+            "profile": { "client_id": "x", "redirect_uri": "http://localhost/cb", "scope": "openid",
+                         "discovery_uri": "https://idp.example.com/.well-known/openid-configuration" },
+            "options": { "output": "/tmp/x" }
+        }"#;
+        assert!(StdinInvocation::from_reader(unknown_option.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_write_claims_file() {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("claims.json");
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"sub":"user-1","email":"a@example.com"}"#);
+        let mut tokens = TokenResponse {
+            access_token: "access".to_string(),
+            token_type: "Bearer".to_string(),
+            id_token: Some(format!("eyJhbGciOiJub25lIn0.{payload}.sig")),
+            ..Default::default()
+        };
+
+        write_claims_file(&path, &tokens).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written["sub"], "user-1");
+        assert_eq!(written["email"], "a@example.com");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        tokens.id_token = None;
+        assert!(write_claims_file(&path, &tokens).is_err());
+    }
+
+    #[test]
+    fn test_for_api_selects_scope_and_audience() {
+        let mut profile = Profile {
+            scope: "openid".to_string(),
+            ..Default::default()
+        };
+        profile.apis.insert(
+            "billing".to_string(),
+            crate::config::ApiTarget {
+                scope: "openid billing:read billing:write".to_string(),
+                audience: Some("https://billing.example.com".to_string()),
+            },
+        );
+
+        let mut unchanged = profile.clone();
+        assert_eq!(apply_api_target(&mut unchanged, None).unwrap(), None);
+        assert_eq!(unchanged.scope, "openid");
+
+        let audience = apply_api_target(&mut profile, Some("billing")).unwrap();
+        assert_eq!(audience.as_deref(), Some("https://billing.example.com"));
+        assert_eq!(profile.scope, "openid billing:read billing:write");
+
+        assert!(apply_api_target(&mut profile, Some("crm")).is_err());
+
+        profile.apis.insert(
+            "broken".to_string(),
+            crate::config::ApiTarget {
+                scope: "reports\"read".to_string(),
+                audience: None,
+            },
+        );
+        assert!(apply_api_target(&mut profile, Some("broken")).is_err());
+    }
+
+    #[test]
+    fn test_json_output_file_is_one_private_object() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tokens.json");
+        let tokens = TokenResponse {
+            access_token: "access".to_string(),
+            token_type: "Bearer".to_string(),
+            refresh_token: Some("refresh".to_string()),
+            ..Default::default()
+        };
+
+        output_tokens_json(
+            &tokens,
+            JsonOutput::File(&path),
+            true,
+            TokenSelection::default(),
+        )
+        .unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["access_token"], "access");
+        assert_eq!(json["refresh_token"], "refresh");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let unwritable = dir.path().join("missing").join("tokens.json");
+        assert!(output_tokens_json(
+            &tokens,
+            JsonOutput::File(&unwritable),
+            true,
+            TokenSelection::default(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_token_files_hold_exactly_the_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = TokenFiles {
+            access: Some(dir.path().join("access")),
+            id: Some(dir.path().join("id")),
+            refresh: Some(dir.path().join("refresh")),
+        };
+        let tokens = TokenResponse {
+            access_token: "access-token".to_string(),
+            token_type: "Bearer".to_string(),
+            id_token: Some("id.token.sig".to_string()),
+            ..Default::default()
+        };
+
+        let written = files.write(&tokens).unwrap();
+        assert_eq!(written.len(), 2);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("access")).unwrap(),
+            "access-token"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("id")).unwrap(),
+            "id.token.sig"
+        );
+        assert!(
+            !dir.path().join("refresh").exists(),
+            "no file for a token that was not returned"
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for name in ["access", "id"] {
+                let mode = std::fs::metadata(dir.path().join(name))
+                    .unwrap()
+                    .permissions()
+                    .mode();
+                assert_eq!(mode & 0o777, 0o600, "{name}");
+            }
+        }
+
+        assert!(TokenFiles::default().write(&tokens).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_poll_callback_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("callback.txt");
+
+        let missing =
+            poll_callback_file(&path, Duration::from_millis(50), Duration::from_millis(10)).await;
+        assert!(matches!(missing, Err(e) if e.to_string().contains("Timed out")));
+
+        std::fs::write(&path, "code=abc").unwrap();
+        let no_state =
+            poll_callback_file(&path, Duration::from_millis(50), Duration::from_millis(10)).await;
+        assert!(no_state.is_err());
+
+        let writer_path = path.clone();
+        let writer = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            std::fs::write(
+                writer_path,
+                "https://app.example.com/callback?code=abc&state=xyz\n",
+            )
+            .unwrap();
+        });
+        let callback = poll_callback_file(&path, Duration::from_secs(5), Duration::from_millis(10))
+            .await
+            .unwrap();
+        writer.await.unwrap();
+        assert_eq!(callback.code, "abc");
+        assert_eq!(callback.state, "xyz");
     }
 }
@@ -1,7 +1,33 @@
+pub mod audit;
+pub mod cache;
+pub mod callback;
+pub mod doctor;
 pub mod import_export;
 pub mod login;
 pub mod profile;
+pub mod refresh;
+pub mod reset;
+pub mod revoke;
+pub mod schema;
+pub mod selftest;
+pub mod session;
+pub mod status;
+pub mod token;
+pub mod version;
 
+pub use audit::*;
+pub use cache::*;
+pub use callback::*;
+pub use doctor::*;
 pub use import_export::*;
 pub use login::*;
 pub use profile::*;
+pub use refresh::*;
+pub use reset::*;
+pub use revoke::*;
+pub use schema::*;
+pub use selftest::*;
+pub use session::*;
+pub use status::*;
+pub use token::*;
+pub use version::*;
@@ -1,6 +1,12 @@
+use crate::auth::EndpointSource;
+use crate::cli::ScopePresetAction;
+use crate::config::Profile;
 use crate::error::{OidcError, Result};
+use crate::profile::diff::diff_profiles;
+use crate::profile::share::shareable_config;
 use crate::profile::{ProfileManager, ProfileParams};
 use crate::ui::prompts::*;
+use crate::utils::mask::mask_secret;
 use std::io::{self, Write};
 
 pub struct CreateParams {
@@ -9,26 +15,126 @@ pub struct CreateParams {
     pub client_secret: Option<String>,
     pub redirect_uri: Option<String>,
     pub scope: Option<String>,
+    pub scope_preset: Option<String>,
     pub discovery_uri: Option<String>,
     pub auth_endpoint: Option<String>,
     pub token_endpoint: Option<String>,
     pub non_interactive: bool,
+    /// Use the given flags and prompt only for required values that are missing
+    pub fill_missing: bool,
+    /// Read the client secret without echo when prompting for it
+    pub prompt_secret: bool,
     pub quiet: bool,
 }
 
+/// A required `create` value that was not supplied on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingField {
+    ClientId,
+    RedirectUri,
+    Scope,
+    /// Neither a discovery URI nor an authorization endpoint
+    AuthEndpoint,
+    /// Neither a discovery URI nor a token endpoint
+    TokenEndpoint,
+}
+
+impl CreateParams {
+    /// Required fields absent from the flags, given the already-resolved scope.
+    /// Endpoints only count as missing when no discovery URI was given either.
+    pub fn missing_fields(&self, scope: Option<&str>) -> Vec<MissingField> {
+        let mut missing = Vec::new();
+        if self.client_id.is_none() {
+            missing.push(MissingField::ClientId);
+        }
+        if self.redirect_uri.is_none() {
+            missing.push(MissingField::RedirectUri);
+        }
+        if scope.is_none() {
+            missing.push(MissingField::Scope);
+        }
+        if self.discovery_uri.is_none() {
+            if self.auth_endpoint.is_none() {
+                missing.push(MissingField::AuthEndpoint);
+            }
+            if self.token_endpoint.is_none() {
+                missing.push(MissingField::TokenEndpoint);
+            }
+        }
+        missing
+    }
+}
+
+/// Prompt for just the missing required values, leaving everything given on the command line as is
+fn prompt_missing_fields(params: &mut CreateParams, scope: &mut Option<String>) -> Result<()> {
+    let missing = params.missing_fields(scope.as_deref());
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    if !params.quiet {
+        println!(
+            "Completing profile '{}' ({} value(s) missing)",
+            params.name,
+            missing.len()
+        );
+    }
+
+    // With no endpoint flags at all, discovery is the likelier intent
+    let no_endpoints = missing.contains(&MissingField::AuthEndpoint)
+        && missing.contains(&MissingField::TokenEndpoint);
+    if no_endpoints {
+        params.discovery_uri =
+            prompt_optional_input("Discovery URI (leave empty to enter endpoints)")?;
+    }
+
+    for field in missing {
+        match field {
+            MissingField::ClientId => params.client_id = Some(prompt_input("Client ID", true)?),
+            MissingField::RedirectUri => {
+                params.redirect_uri = Some(prompt_input_with_default(
+                    "Redirect URI",
+                    "http://localhost:8080/callback",
+                )?)
+            }
+            MissingField::Scope => {
+                *scope = Some(prompt_input_with_default("Scope", "openid profile email")?)
+            }
+            MissingField::AuthEndpoint if params.discovery_uri.is_none() => {
+                params.auth_endpoint = Some(prompt_input("Authorization Endpoint", true)?)
+            }
+            MissingField::TokenEndpoint if params.discovery_uri.is_none() => {
+                params.token_endpoint = Some(prompt_input("Token Endpoint", true)?)
+            }
+            MissingField::AuthEndpoint | MissingField::TokenEndpoint => {}
+        }
+    }
+
+    Ok(())
+}
+
 pub async fn handle_create(
     profile_manager: &mut ProfileManager,
-    params: CreateParams,
+    mut params: CreateParams,
 ) -> Result<()> {
-    if params.non_interactive {
+    let mut scope =
+        profile_manager.resolve_scope(params.scope_preset.as_deref(), params.scope.as_deref())?;
+
+    if params.fill_missing {
+        prompt_missing_fields(&mut params, &mut scope)?;
+    }
+
+    if params.non_interactive || params.fill_missing {
         let client_id = params.client_id.ok_or_else(|| {
             OidcError::Config("--client-id is required in non-interactive mode".to_string())
         })?;
         let redirect_uri = params.redirect_uri.ok_or_else(|| {
             OidcError::Config("--redirect-uri is required in non-interactive mode".to_string())
         })?;
-        let scope = params.scope.ok_or_else(|| {
-            OidcError::Config("--scope is required in non-interactive mode".to_string())
+        let scope = scope.ok_or_else(|| {
+            OidcError::Config(
+                "--scope or --scope-preset is required in non-interactive mode".to_string(),
+            )
         })?;
 
         if params.discovery_uri.is_none()
@@ -52,7 +158,14 @@ pub async fn handle_create(
             println!("Profile '{}' created successfully.", params.name);
         }
     } else {
-        create_profile_interactive(profile_manager, params.name, params.quiet).await?;
+        create_profile_interactive(
+            profile_manager,
+            params.name,
+            scope,
+            params.prompt_secret,
+            params.quiet,
+        )
+        .await?;
     }
 
     Ok(())
@@ -61,6 +174,8 @@ pub async fn handle_create(
 async fn create_profile_interactive(
     profile_manager: &mut ProfileManager,
     name: String,
+    default_scope: Option<String>,
+    hide_secret: bool,
     quiet: bool,
 ) -> Result<()> {
     if !quiet {
@@ -70,9 +185,16 @@ async fn create_profile_interactive(
     }
 
     let client_id = prompt_input("Client ID", true)?;
-    let client_secret = prompt_optional_input("Client Secret (optional)")?;
+    let client_secret = if hide_secret {
+        prompt_secret("Client Secret (optional)")?
+    } else {
+        prompt_optional_input("Client Secret (optional)")?
+    };
     let redirect_uri = prompt_input_with_default("Redirect URI", "http://localhost:8080/callback")?;
-    let scope = prompt_input_with_default("Scope", "openid profile email")?;
+    let scope = prompt_input_with_default(
+        "Scope",
+        default_scope.as_deref().unwrap_or("openid profile email"),
+    )?;
 
     println!();
     println!("Choose configuration method:");
@@ -221,3 +343,220 @@ pub fn handle_rename(
 
     Ok(())
 }
+
+pub fn handle_show(profile_manager: ProfileManager, name: String, share: bool) -> Result<()> {
+    let profile = profile_manager.get_profile(&name)?;
+
+    if share {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&shareable_config(&name, profile))?
+        );
+        return Ok(());
+    }
+
+    println!("Profile '{name}':");
+    println!("  client_id:              {}", profile.client_id);
+    println!(
+        "  client_secret:          {}",
+        mask_secret(profile.client_secret.as_deref()).unwrap_or_else(|| "(not set)".to_string())
+    );
+    println!("  redirect_uri:           {}", profile.redirect_uri);
+    println!("  scope:                  {}", profile.scope);
+    let optional = [
+        ("discovery_uri", &profile.discovery_uri),
+        ("authorization_endpoint", &profile.authorization_endpoint),
+        ("token_endpoint", &profile.token_endpoint),
+        ("request_uri", &profile.request_uri),
+        ("request_state", &profile.request_state),
+    ];
+    for (field, value) in optional {
+        if let Some(value) = value {
+            println!("  {:<23} {value}", format!("{field}:"));
+        }
+    }
+    println!("  endpoints:              {}", describe_endpoints(profile));
+
+    Ok(())
+}
+
+/// Where a login would take the endpoints from, noting manual endpoints that
+/// discovery overrides
+fn describe_endpoints(profile: &Profile) -> String {
+    let source = EndpointSource::configured(profile);
+    let has_manual = profile.authorization_endpoint.is_some() || profile.token_endpoint.is_some();
+    match source {
+        EndpointSource::Discovery(_) if profile.discovery_fallback && has_manual => {
+            format!("{source} (manual endpoints as fallback)")
+        }
+        EndpointSource::Discovery(_) if has_manual => format!("{source} (manual endpoints unused)"),
+        _ => source.to_string(),
+    }
+}
+
+pub fn handle_diff(
+    profile_manager: ProfileManager,
+    left: String,
+    right: String,
+    json: bool,
+    quiet: bool,
+) -> Result<()> {
+    let left_profile = profile_manager.get_profile(&left)?;
+    let right_profile = profile_manager.get_profile(&right)?;
+    let fields = diff_profiles(left_profile, right_profile);
+    let identical = fields.iter().all(|f| !f.differs);
+
+    if json {
+        let output = serde_json::json!({
+            "left": left,
+            "right": right,
+            "identical": identical,
+            "fields": fields,
+        });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    if quiet {
+        for field in fields.iter().filter(|f| f.differs) {
+            println!("{}", field.field);
+        }
+        return Ok(());
+    }
+
+    if identical {
+        println!("Profiles '{left}' and '{right}' are identical.");
+        return Ok(());
+    }
+
+    println!("Differences between '{left}' and '{right}':");
+    for field in fields.iter().filter(|f| f.differs) {
+        println!("  {}:", field.field);
+        println!(
+            "    - {left}: {}",
+            field.left.as_deref().unwrap_or("(not set)")
+        );
+        println!(
+            "    + {right}: {}",
+            field.right.as_deref().unwrap_or("(not set)")
+        );
+    }
+
+    let same = fields.iter().filter(|f| !f.differs).count();
+    if same > 0 {
+        println!();
+        println!("{same} other field(s) are identical.");
+    }
+
+    Ok(())
+}
+
+pub fn handle_scope_preset(
+    profile_manager: &mut ProfileManager,
+    action: ScopePresetAction,
+    quiet: bool,
+) -> Result<()> {
+    match action {
+        ScopePresetAction::Add { name, scope } => {
+            profile_manager.add_scope_preset(&name, &scope)?;
+            if !quiet {
+                println!("✓ Scope preset '{name}' saved.");
+            }
+        }
+        ScopePresetAction::Remove { name } => {
+            profile_manager.remove_scope_preset(&name)?;
+            if !quiet {
+                println!("✓ Scope preset '{name}' removed.");
+            }
+        }
+        ScopePresetAction::List => {
+            let mut presets: Vec<_> = profile_manager.scope_presets().iter().collect();
+            presets.sort();
+
+            if presets.is_empty() {
+                if !quiet {
+                    println!("No scope presets defined.");
+                }
+            } else if quiet {
+                for (name, scope) in presets {
+                    println!("{name}\t{scope}");
+                }
+            } else {
+                println!("Scope presets:");
+                for (name, scope) in presets {
+                    println!("  • {name}: {scope}");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> CreateParams {
+        CreateParams {
+            name: "dev".to_string(),
+            client_id: Some("client".to_string()),
+            client_secret: None,
+            redirect_uri: Some("http://localhost:8080/callback".to_string()),
+            scope: Some("openid".to_string()),
+            scope_preset: None,
+            discovery_uri: Some("https://example.com/.well-known/openid-configuration".to_string()),
+            auth_endpoint: None,
+            token_endpoint: None,
+            non_interactive: false,
+            fill_missing: true,
+            prompt_secret: true,
+            quiet: true,
+        }
+    }
+
+    #[test]
+    fn test_missing_fields() {
+        assert!(params().missing_fields(Some("openid")).is_empty());
+
+        let mut partial = params();
+        partial.client_id = None;
+        assert_eq!(
+            partial.missing_fields(None),
+            vec![MissingField::ClientId, MissingField::Scope]
+        );
+
+        let mut manual = params();
+        manual.discovery_uri = None;
+        manual.auth_endpoint = Some("https://example.com/authorize".to_string());
+        assert_eq!(
+            manual.missing_fields(Some("openid")),
+            vec![MissingField::TokenEndpoint]
+        );
+
+        manual.auth_endpoint = None;
+        assert_eq!(
+            manual.missing_fields(Some("openid")),
+            vec![MissingField::AuthEndpoint, MissingField::TokenEndpoint]
+        );
+    }
+
+    #[test]
+    fn test_describe_endpoints() {
+        let mut profile = Profile {
+            authorization_endpoint: Some("https://idp.example.com/auth".to_string()),
+            token_endpoint: Some("https://idp.example.com/token".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(describe_endpoints(&profile), "manual");
+
+        profile.discovery_uri =
+            Some("https://idp.example.com/.well-known/openid-configuration".to_string());
+        assert_eq!(
+            describe_endpoints(&profile),
+            "discovered from https://idp.example.com/.well-known/openid-configuration (manual endpoints unused)"
+        );
+        profile.discovery_fallback = true;
+        assert!(describe_endpoints(&profile).ends_with("(manual endpoints as fallback)"));
+    }
+}
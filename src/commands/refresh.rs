@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use crate::auth::{OAuthClient, TokenResponse};
+use crate::error::{OidcError, Result};
+use crate::profile::ProfileManager;
+use crate::token_cache::{CachedToken, TokenCache, DEFAULT_EXPIRY_LEEWAY_SECS};
+use crate::ui::select_profile;
+
+/// Exchange the profile's cached refresh token for new tokens and cache them
+pub async fn handle_refresh(
+    profile_manager: ProfileManager,
+    profile_name: Option<String>,
+    quiet: bool,
+) -> Result<()> {
+    let profile_name = match profile_name {
+        Some(name) => name,
+        None => select_profile(&profile_manager, quiet)?,
+    };
+    let profile = profile_manager.get_profile(&profile_name)?.resolve_env()?;
+    let relogin = |reason: &str| {
+        OidcError::Auth(format!(
+            "{reason}; run 'oidc-cli login {profile_name} --cached' to sign in again"
+        ))
+    };
+
+    let cache = TokenCache::new_with_override(profile_manager.config_dir_override())?;
+    let cached = cache
+        .load()?
+        .remove(&profile_name)
+        .filter(|cached| cached.tokens.refresh_token.is_some())
+        .ok_or_else(|| relogin("No cached refresh token for this profile"))?;
+    if !cached.can_refresh(Duration::from_secs(DEFAULT_EXPIRY_LEEWAY_SECS)) {
+        return Err(relogin("The cached refresh token has expired"));
+    }
+    let refresh_token = cached.tokens.refresh_token.as_deref().unwrap_or_default();
+
+    let oauth_client = OAuthClient::new(profile.clone()).await?;
+    let refreshed = match oauth_client.refresh_tokens(refresh_token).await {
+        Ok(tokens) => tokens,
+        Err(OidcError::Auth(message)) => {
+            return Err(relogin(&format!(
+                "The refresh token was rejected ({message})"
+            )))
+        }
+        Err(e) => return Err(e),
+    };
+
+    let tokens = keep_refresh_token(refreshed, &cached);
+    let requested_scope = cached.requested_scope.unwrap_or(profile.scope);
+    cache.store(&profile_name, &tokens, &requested_scope)?;
+
+    if !quiet {
+        println!("✓ Refreshed tokens for profile '{profile_name}'");
+    }
+    Ok(())
+}
+
+/// Carry the previous refresh token over when the provider did not rotate it, along
+/// with whatever remains of its lifetime
+pub fn keep_refresh_token(mut refreshed: TokenResponse, previous: &CachedToken) -> TokenResponse {
+    if refreshed.refresh_token.is_none() {
+        refreshed.refresh_token = previous.tokens.refresh_token.clone();
+        refreshed.refresh_expires_in = previous.refresh_lifetime_left();
+    }
+    refreshed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(access_token: &str, refresh_token: Option<&str>) -> TokenResponse {
+        TokenResponse {
+            access_token: access_token.to_string(),
+            token_type: "Bearer".to_string(),
+            refresh_token: refresh_token.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_keep_refresh_token() {
+        let mut previous = CachedToken::new(tokens("old", Some("refresh-1")), "openid");
+
+        let kept = keep_refresh_token(tokens("new", None), &previous);
+        assert_eq!(kept.access_token, "new");
+        assert_eq!(kept.refresh_token.as_deref(), Some("refresh-1"));
+        assert_eq!(kept.refresh_expires_in, None);
+
+        let mut rotated_tokens = tokens("new", Some("refresh-2"));
+        rotated_tokens.refresh_expires_in = Some(3600);
+        let rotated = keep_refresh_token(rotated_tokens, &previous);
+        assert_eq!(rotated.refresh_token.as_deref(), Some("refresh-2"));
+        assert_eq!(rotated.refresh_expires_in, Some(3600));
+
+        // Obtained 600s into a 1800s refresh lifetime
+        previous.tokens.refresh_expires_in = Some(1800);
+        previous.obtained_at -= 600;
+        let kept = keep_refresh_token(tokens("new", None), &previous);
+        let left = kept.refresh_expires_in.unwrap();
+        assert!((1199..=1200).contains(&left), "{left}");
+
+        previous.obtained_at -= 3600;
+        let kept = keep_refresh_token(tokens("new", None), &previous);
+        assert_eq!(kept.refresh_expires_in, Some(0));
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::config::{get_config_dir_with_override, profiles_file_name, DATA_FILES};
+use crate::error::Result;
+use crate::utils::fs::lock_path_for;
+
+pub fn handle_reset(
+    config_dir_override: Option<PathBuf>,
+    profiles_file: Option<&str>,
+    force: bool,
+    quiet: bool,
+) -> Result<()> {
+    let config_dir = get_config_dir_with_override(config_dir_override)?;
+    let profiles_file = profiles_file_name(profiles_file)?;
+    let files = stored_files(&config_dir, &profiles_file)?;
+
+    if files.is_empty() {
+        if !quiet {
+            println!("Nothing to remove in {config_dir:?}.");
+        }
+        return Ok(());
+    }
+
+    // Only --force skips the confirmation; --quiet just shortens it
+    if !force {
+        if !quiet {
+            println!("This will delete:");
+            for file in &files {
+                println!("  {}", file.display());
+            }
+        }
+        print!("Remove all stored profiles and tokens? (y/N): ");
+        io::stdout().flush().unwrap();
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
+            if !quiet {
+                println!("Operation cancelled.");
+            }
+            return Ok(());
+        }
+    }
+
+    let removed = remove_stored_files(&config_dir, &profiles_file)?;
+
+    if !quiet {
+        for file in &removed {
+            println!("Removed {}", file.display());
+        }
+        println!("✓ Removed {} file(s).", removed.len());
+    }
+
+    Ok(())
+}
+
+/// Delete every known data file (plus lock files and interrupted atomic writes)
+/// and the directory itself if that leaves it empty
+pub fn remove_stored_files(config_dir: &Path, profiles_file: &str) -> Result<Vec<PathBuf>> {
+    let files = stored_files(config_dir, profiles_file)?;
+    for file in &files {
+        fs::remove_file(file)?;
+    }
+
+    // Only succeeds when nothing unrelated lives in the directory
+    let _ = fs::remove_dir(config_dir);
+
+    Ok(files)
+}
+
+/// Known data files that exist under the config directory
+pub fn stored_files(config_dir: &Path, profiles_file: &str) -> Result<Vec<PathBuf>> {
+    if !config_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let names: Vec<&str> = std::iter::once(profiles_file)
+        .chain(DATA_FILES.iter().copied())
+        .collect();
+
+    let mut files = Vec::new();
+    for name in &names {
+        let path = config_dir.join(name);
+        files.push(lock_path_for(&path));
+        files.push(path);
+    }
+    files.retain(|path| path.exists());
+
+    // Leftovers from interrupted atomic writes: `<name>.tmp-<pid>`
+    for entry in fs::read_dir(config_dir)? {
+        let path = entry?.path();
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        if names
+            .iter()
+            .any(|name| file_name.starts_with(&format!("{name}.tmp-")))
+        {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::TokenResponse;
+    use crate::profile::{ProfileManager, ProfileParams};
+    use crate::token_cache::TokenCache;
+
+    #[test]
+    fn test_reset_removes_known_files_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_dir = dir.path().to_path_buf();
+
+        let mut manager = ProfileManager::new_with_test_dir(Some(config_dir.clone())).unwrap();
+        manager
+            .create_profile(ProfileParams {
+                name: "dev".to_string(),
+                client_id: "client".to_string(),
+                client_secret: None,
+                redirect_uri: "http://localhost:8080/callback".to_string(),
+                scope: "openid".to_string(),
+                discovery_uri: Some(
+                    "https://example.com/.well-known/openid-configuration".to_string(),
+                ),
+                authorization_endpoint: None,
+                token_endpoint: None,
+            })
+            .unwrap();
+        TokenCache::new_with_override(Some(config_dir.clone()))
+            .unwrap()
+            .store("dev", &TokenResponse::default(), "openid")
+            .unwrap();
+        fs::write(config_dir.join("tokens.json.tmp-1234"), "{").unwrap();
+        fs::write(config_dir.join("notes.txt"), "keep me").unwrap();
+
+        handle_reset(Some(config_dir.clone()), Some("profiles.json"), true, true).unwrap();
+
+        assert!(!config_dir.join("profiles.json").exists());
+        assert!(!config_dir.join("tokens.json").exists());
+        assert!(!config_dir.join("tokens.json.lock").exists());
+        assert!(!config_dir.join("tokens.json.tmp-1234").exists());
+        assert!(config_dir.join("notes.txt").exists());
+        assert!(stored_files(&config_dir, "profiles.json")
+            .unwrap()
+            .is_empty());
+    }
+}
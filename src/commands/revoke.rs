@@ -0,0 +1,118 @@
+use crate::auth::{OAuthClient, TokenTypeHint};
+use crate::error::{OidcError, Result};
+use crate::profile::ProfileManager;
+use crate::token_cache::{CachedToken, TokenCache};
+use crate::ui::{read_token_input, select_profile};
+
+/// Options for the revoke command
+pub struct RevokeOptions {
+    pub profile_name: Option<String>,
+    /// Token to revoke; the profile's cached token when neither this nor
+    /// `token_stdin` is given
+    pub token: Option<String>,
+    pub token_stdin: bool,
+    pub token_type_hint: Option<TokenTypeHint>,
+    pub quiet: bool,
+}
+
+pub async fn handle_revoke(profile_manager: ProfileManager, options: RevokeOptions) -> Result<()> {
+    let RevokeOptions {
+        profile_name,
+        token,
+        token_stdin,
+        token_type_hint,
+        quiet,
+    } = options;
+
+    let profile_name = match profile_name {
+        Some(name) => name,
+        None => select_profile(&profile_manager, quiet)?,
+    };
+    let profile = profile_manager.get_profile(&profile_name)?.resolve_env()?;
+
+    let cache = TokenCache::new_with_override(profile_manager.config_dir_override())?;
+    let (token, hint, from_cache) = if token.is_some() || token_stdin {
+        (
+            read_token_input(token, token_stdin)?,
+            token_type_hint,
+            false,
+        )
+    } else {
+        let cached = cache.load()?.remove(&profile_name).ok_or_else(|| {
+            OidcError::Config(format!(
+                "No cached token for profile '{profile_name}'; pass --token-stdin"
+            ))
+        })?;
+        let (token, hint) = cached_token_to_revoke(cached, token_type_hint)?;
+        (token, Some(hint), true)
+    };
+
+    let oauth_client = OAuthClient::new(profile).await?;
+    oauth_client.revoke_token(&token, hint).await?;
+
+    // The cached tokens belong to the revoked grant, so they would only fail later
+    if from_cache {
+        cache.remove(&profile_name)?;
+    }
+
+    if !quiet {
+        let what = hint.map_or("token", TokenTypeHint::as_str);
+        println!("✓ Revoked {what} for profile '{profile_name}'");
+    }
+    Ok(())
+}
+
+/// The cached token matching `hint`, or the refresh token (falling back to the
+/// access token) when there is no hint, since revoking it ends the whole grant
+pub fn cached_token_to_revoke(
+    cached: CachedToken,
+    hint: Option<TokenTypeHint>,
+) -> Result<(String, TokenTypeHint)> {
+    let tokens = cached.tokens;
+    match (hint, tokens.refresh_token) {
+        (Some(TokenTypeHint::AccessToken), _) | (None, None) => {
+            Ok((tokens.access_token, TokenTypeHint::AccessToken))
+        }
+        (_, Some(refresh_token)) => Ok((refresh_token, TokenTypeHint::RefreshToken)),
+        (Some(TokenTypeHint::RefreshToken), None) => Err(OidcError::Config(
+            "The cached token has no refresh token to revoke".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::TokenResponse;
+
+    fn cached(refresh_token: Option<&str>) -> CachedToken {
+        CachedToken::new(
+            TokenResponse {
+                access_token: "access".to_string(),
+                token_type: "Bearer".to_string(),
+                refresh_token: refresh_token.map(str::to_string),
+                ..Default::default()
+            },
+            "openid",
+        )
+    }
+
+    #[test]
+    fn test_cached_token_to_revoke() {
+        let pick = |refresh, hint| cached_token_to_revoke(cached(refresh), hint);
+
+        assert_eq!(
+            pick(Some("refresh"), None).unwrap(),
+            ("refresh".to_string(), TokenTypeHint::RefreshToken)
+        );
+        assert_eq!(
+            pick(None, None).unwrap(),
+            ("access".to_string(), TokenTypeHint::AccessToken)
+        );
+        assert_eq!(
+            pick(Some("refresh"), Some(TokenTypeHint::AccessToken)).unwrap(),
+            ("access".to_string(), TokenTypeHint::AccessToken)
+        );
+        assert!(pick(None, Some(TokenTypeHint::RefreshToken)).is_err());
+    }
+}
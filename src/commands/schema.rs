@@ -0,0 +1,37 @@
+use crate::config::Config;
+use crate::error::{OidcError, Result};
+
+/// JSON Schema for the profiles file, which is also the import/export format
+pub fn config_schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(Config)).expect("schema serializes to JSON")
+}
+
+pub fn handle_schema() -> Result<()> {
+    let schema = serde_json::to_string_pretty(&config_schema())
+        .map_err(|e| OidcError::Config(format!("Failed to serialize schema: {e}")))?;
+    println!("{schema}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_requires_profile_fields() {
+        let schema = config_schema();
+        let profile = &schema["definitions"]["Profile"];
+        let required: Vec<&str> = profile["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect();
+
+        assert!(required.contains(&"client_id"));
+        assert!(required.contains(&"redirect_uri"));
+        assert!(!required.contains(&"redirect_uris"));
+        assert!(profile["properties"]["pin_sha256"].is_object());
+        assert_eq!(schema["required"], serde_json::json!(["profiles"]));
+    }
+}
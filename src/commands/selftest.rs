@@ -0,0 +1,243 @@
+use serde::Serialize;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::auth::{verify_id_token, OAuthClient, TokenResponse};
+use crate::error::{OidcError, Result};
+use crate::profile::ProfileManager;
+use crate::token_cache::{TokenCache, DEFAULT_EXPIRY_LEEWAY_SECS};
+use crate::ui::select_profile;
+
+/// How the health check obtained the token it verified
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelftestMethod {
+    /// A fresh token from the client credentials grant
+    ClientCredentials,
+    /// The profile's cached token, after checking the provider is reachable
+    CachedToken,
+}
+
+impl SelftestMethod {
+    fn label(self) -> &'static str {
+        match self {
+            SelftestMethod::ClientCredentials => "client credentials",
+            SelftestMethod::CachedToken => "cached token",
+        }
+    }
+}
+
+/// Outcome of one health check, printed as a single JSON line with `--json`
+#[derive(Debug, Clone, Serialize)]
+pub struct SelftestReport {
+    pub profile: String,
+    pub healthy: bool,
+    pub method: SelftestMethod,
+    pub elapsed_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Obtain and verify a token for `name`, giving up after `timeout`. Never fails;
+/// problems are reported in the returned report.
+pub async fn run_selftest(
+    profile_manager: &ProfileManager,
+    name: &str,
+    timeout: Duration,
+) -> SelftestReport {
+    let started = Instant::now();
+    let method = match profile_manager.get_profile(name) {
+        Ok(profile) if profile.uses_client_credentials() => SelftestMethod::ClientCredentials,
+        _ => SelftestMethod::CachedToken,
+    };
+
+    let outcome = match tokio::time::timeout(timeout, check(profile_manager, name, method)).await {
+        Ok(outcome) => outcome,
+        Err(_) => Err(OidcError::Auth(format!(
+            "no result within the {timeout:?} timeout"
+        ))),
+    };
+
+    let (expires_at, error) = match outcome {
+        Ok(expires_at) => (expires_at, None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    SelftestReport {
+        profile: name.to_string(),
+        healthy: error.is_none(),
+        method,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+        expires_at,
+        error,
+    }
+}
+
+/// Run the check for `method`, returning when the verified token expires
+async fn check(
+    profile_manager: &ProfileManager,
+    name: &str,
+    method: SelftestMethod,
+) -> Result<Option<u64>> {
+    let profile = profile_manager.get_profile(name)?.resolve_env()?;
+    profile.validate()?;
+    let client_id = profile.client_id.clone();
+    // Discovery runs here too, so an unreachable provider fails the check either way
+    let oauth_client = OAuthClient::new(profile).await?;
+
+    let (tokens, obtained_at) = match method {
+        SelftestMethod::ClientCredentials => {
+            (oauth_client.client_credentials_grant().await?, now_secs())
+        }
+        SelftestMethod::CachedToken => {
+            let cached = TokenCache::new_with_override(profile_manager.config_dir_override())?
+                .get_valid(name, Duration::from_secs(DEFAULT_EXPIRY_LEEWAY_SECS))?
+                .ok_or_else(|| {
+                    OidcError::Auth(format!(
+                        "no valid cached token; run 'oidc-cli login {name} --cached'"
+                    ))
+                })?;
+            (cached.tokens, cached.obtained_at)
+        }
+    };
+
+    verify_tokens(&tokens, &client_id, obtained_at)
+}
+
+/// The token must not have expired, and any id_token must be issued to this client
+fn verify_tokens(tokens: &TokenResponse, client_id: &str, obtained_at: u64) -> Result<Option<u64>> {
+    if let Some(ref id_token) = tokens.id_token {
+        verify_id_token(id_token, client_id)?;
+    }
+    let expires_at = tokens.expires_at(obtained_at);
+    if expires_at.is_some_and(|expires_at| expires_at <= now_secs()) {
+        return Err(OidcError::Auth("the token has already expired".to_string()));
+    }
+    Ok(expires_at)
+}
+
+pub async fn handle_selftest(
+    profile_manager: ProfileManager,
+    profile: Option<String>,
+    json: bool,
+    timeout: Duration,
+    quiet: bool,
+) -> Result<()> {
+    let name = match profile {
+        Some(name) => name,
+        None => select_profile(&profile_manager, quiet)?,
+    };
+
+    let report = run_selftest(&profile_manager, &name, timeout).await;
+    if json {
+        println!("{}", serde_json::to_string(&report)?);
+    } else if report.healthy && !quiet {
+        println!(
+            "✓ {name}: healthy ({}, {}ms)",
+            report.method.label(),
+            report.elapsed_ms
+        );
+    }
+
+    match report.error {
+        Some(error) => Err(OidcError::Auth(format!(
+            "Health check failed for '{name}': {error}"
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Profile;
+    use crate::profile::validation::CLIENT_CREDENTIALS_GRANT;
+    use crate::test_support::{MockResponse, MockServer};
+
+    fn manager_with(dir: &tempfile::TempDir, profile: Profile) -> ProfileManager {
+        let mut manager =
+            ProfileManager::new_with_test_dir(Some(dir.path().to_path_buf())).unwrap();
+        manager.add_profile("svc", profile).unwrap();
+        manager
+    }
+
+    fn service_profile(token_endpoint: String) -> Profile {
+        Profile {
+            client_id: "ci-bot".to_string(),
+            client_secret: Some("ci-secret".to_string()),
+            scope: "api:read".to_string(),
+            token_endpoint: Some(token_endpoint),
+            grant_type: Some(CLIENT_CREDENTIALS_GRANT.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_selftest_client_credentials_round_trip() {
+        let server = MockServer::start(vec![
+            MockResponse::json(
+                200,
+                r#"{"access_token":"service-token","token_type":"Bearer","expires_in":300}"#,
+            ),
+            MockResponse::json(401, r#"{"error":"invalid_client"}"#),
+        ])
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let manager = manager_with(&dir, service_profile(server.url("/token")));
+
+        let report = run_selftest(&manager, "svc", Duration::from_secs(5)).await;
+        assert!(report.healthy, "{report:?}");
+        assert_eq!(report.method, SelftestMethod::ClientCredentials);
+        assert!(report.expires_at.unwrap() > now_secs());
+
+        let report = run_selftest(&manager, "svc", Duration::from_secs(5)).await;
+        assert!(!report.healthy);
+        assert!(report.error.unwrap().contains("invalid_client"));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_selftest_timeout_and_missing_cache() {
+        let server = MockServer::start(vec![MockResponse::json(
+            200,
+            r#"{"access_token":"late","token_type":"Bearer"}"#,
+        )
+        .with_delay(Duration::from_secs(5))])
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let manager = manager_with(&dir, service_profile(server.url("/token")));
+
+        let report = run_selftest(&manager, "svc", Duration::from_millis(200)).await;
+        assert!(!report.healthy);
+        assert!(report.error.unwrap().contains("timeout"));
+
+        let mut interactive = service_profile(server.url("/token"));
+        interactive.grant_type = None;
+        interactive.redirect_uri = "http://localhost:8080/callback".to_string();
+        interactive.authorization_endpoint = Some(server.url("/authorize"));
+        let dir = tempfile::tempdir().unwrap();
+        let manager = manager_with(&dir, interactive);
+        let report = run_selftest(&manager, "svc", Duration::from_secs(5)).await;
+        assert_eq!(report.method, SelftestMethod::CachedToken);
+        assert!(report.error.unwrap().contains("no valid cached token"));
+    }
+
+    #[test]
+    fn test_verify_tokens_rejects_expired() {
+        let tokens = TokenResponse {
+            access_token: "at".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: Some(60),
+            ..Default::default()
+        };
+        assert!(verify_tokens(&tokens, "client", now_secs()).is_ok());
+        assert!(verify_tokens(&tokens, "client", now_secs() - 120).is_err());
+    }
+}
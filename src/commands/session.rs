@@ -0,0 +1,59 @@
+use crate::auth::OAuthClient;
+use crate::browser::{BrowserOpener, WebBrowserOpener};
+use crate::error::Result;
+use crate::profile::validation::validate_redirect_uri;
+use crate::profile::ProfileManager;
+use crate::ui::select_profile;
+
+/// Options for the end-session command
+pub struct EndSessionOptions {
+    pub profile_name: Option<String>,
+    pub id_token_hint: Option<String>,
+    pub post_logout_redirect_uri: Option<String>,
+    pub quiet: bool,
+}
+
+pub async fn handle_end_session(
+    profile_manager: ProfileManager,
+    options: EndSessionOptions,
+) -> Result<()> {
+    handle_end_session_with_browser_opener(profile_manager, options, &WebBrowserOpener::default())
+        .await
+}
+
+pub async fn handle_end_session_with_browser_opener<B: BrowserOpener>(
+    profile_manager: ProfileManager,
+    options: EndSessionOptions,
+    browser_opener: &B,
+) -> Result<()> {
+    let EndSessionOptions {
+        profile_name,
+        id_token_hint,
+        post_logout_redirect_uri,
+        quiet,
+    } = options;
+
+    if let Some(ref redirect) = post_logout_redirect_uri {
+        validate_redirect_uri(redirect)?;
+    }
+
+    let profile_name = match profile_name {
+        Some(name) => name,
+        None => select_profile(&profile_manager, quiet)?,
+    };
+    let profile = profile_manager.get_profile(&profile_name)?.resolve_env()?;
+
+    let oauth_client = OAuthClient::new(profile).await?;
+    let logout_url = oauth_client.build_end_session_url(
+        id_token_hint.as_deref(),
+        post_logout_redirect_uri.as_deref(),
+    )?;
+
+    if !quiet {
+        println!("Ending session for profile '{profile_name}'...");
+    }
+
+    browser_opener.open_with_fallback(&logout_url, quiet)?;
+
+    Ok(())
+}
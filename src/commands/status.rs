@@ -0,0 +1,345 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::auth::OAuthClient;
+use crate::error::{OidcError, Result};
+use crate::profile::ProfileManager;
+use crate::token_cache::{CachedToken, TokenCache, DEFAULT_EXPIRY_LEEWAY_SECS};
+use crate::ui::{select_profile, JsonLinesWriter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenState {
+    Valid,
+    /// Unexpired, but the provider's introspection endpoint reports it inactive
+    Inactive,
+    Expired,
+    Missing,
+    InvalidProfile,
+}
+
+impl TokenState {
+    fn label(self) -> &'static str {
+        match self {
+            TokenState::Valid => "valid",
+            TokenState::Inactive => "inactive",
+            TokenState::Expired => "expired",
+            TokenState::Missing => "no token",
+            TokenState::InvalidProfile => "invalid profile",
+        }
+    }
+}
+
+/// Cached-token status of a single profile
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileStatus {
+    pub profile: String,
+    pub state: TokenState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Introspection result, when `--introspect` was given and the provider answered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// How many introspection requests `status --introspect` keeps in flight
+const INTROSPECTION_CONCURRENCY: usize = 4;
+
+pub fn profile_status(
+    profile_manager: &ProfileManager,
+    cached: &HashMap<String, CachedToken>,
+    name: &str,
+) -> ProfileStatus {
+    let mut status = ProfileStatus {
+        profile: name.to_string(),
+        state: TokenState::Missing,
+        expires_at: None,
+        active: None,
+        detail: None,
+    };
+
+    if let Err(e) = profile_manager
+        .get_profile(name)
+        .and_then(|profile| profile.validate())
+    {
+        status.state = TokenState::InvalidProfile;
+        status.detail = Some(e.to_string());
+        return status;
+    }
+
+    if let Some(token) = cached.get(name) {
+        status.expires_at = token.expires_at();
+        status.state = if token.is_valid(Duration::from_secs(DEFAULT_EXPIRY_LEEWAY_SECS)) {
+            TokenState::Valid
+        } else {
+            TokenState::Expired
+        };
+    }
+
+    status
+}
+
+/// Ask each profile's provider whether its locally valid token is still active, a few
+/// profiles at a time. A failed request leaves the state alone and is noted in `detail`.
+async fn introspect_statuses(
+    profile_manager: &ProfileManager,
+    cached: &HashMap<String, CachedToken>,
+    statuses: &mut [ProfileStatus],
+) {
+    let permits = Arc::new(Semaphore::new(INTROSPECTION_CONCURRENCY));
+    let mut tasks = JoinSet::new();
+    for (index, status) in statuses.iter().enumerate() {
+        if status.state != TokenState::Valid {
+            continue;
+        }
+        let (Ok(profile), Some(token)) = (
+            profile_manager.get_profile(&status.profile),
+            cached.get(&status.profile),
+        ) else {
+            continue;
+        };
+        let profile = profile.clone();
+        let access_token = token.tokens.access_token.clone();
+        let permits = permits.clone();
+        tasks.spawn(async move {
+            let _permit = permits.acquire_owned().await.expect("never closed");
+            let result = match profile.resolve_env() {
+                Ok(profile) => match OAuthClient::new(profile).await {
+                    Ok(client) => client.introspect_token(&access_token).await,
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            };
+            (index, result)
+        });
+    }
+
+    while let Some(joined) = tasks.join_next().await {
+        let (index, result) = match joined {
+            Ok(result) => result,
+            Err(e) => std::panic::resume_unwind(e.into_panic()),
+        };
+        let status = &mut statuses[index];
+        match result {
+            Ok(active) => {
+                status.active = Some(active);
+                if !active {
+                    status.state = TokenState::Inactive;
+                }
+            }
+            Err(e) => status.detail = Some(format!("introspection failed: {e}")),
+        }
+    }
+}
+
+pub async fn handle_status(
+    profile_manager: ProfileManager,
+    profile: Option<String>,
+    all: bool,
+    json: bool,
+    json_lines: bool,
+    introspect: bool,
+    quiet: bool,
+) -> Result<()> {
+    let mut names: Vec<String> = if all {
+        profile_manager
+            .list_profiles()
+            .into_iter()
+            .cloned()
+            .collect()
+    } else {
+        match profile {
+            Some(name) => vec![name],
+            None => vec![select_profile(&profile_manager, quiet)?],
+        }
+    };
+    names.sort();
+
+    let cached = TokenCache::new_with_override(profile_manager.config_dir_override())?.load()?;
+    // Introspection results arrive out of order, so lines are written once they are in
+    let mut lines = (json_lines && !introspect).then(JsonLinesWriter::stdout);
+    let mut statuses = Vec::with_capacity(names.len());
+    for name in &names {
+        let status = profile_status(&profile_manager, &cached, name);
+        if let Some(ref mut lines) = lines {
+            lines.emit(&status)?;
+        }
+        statuses.push(status);
+    }
+    if introspect {
+        introspect_statuses(&profile_manager, &cached, &mut statuses).await;
+        if json_lines {
+            let mut lines = JsonLinesWriter::stdout();
+            for status in &statuses {
+                lines.emit(status)?;
+            }
+        }
+    }
+
+    if json_lines {
+        // Already streamed
+    } else if json {
+        println!("{}", serde_json::to_string_pretty(&statuses)?);
+    } else if quiet {
+        for status in &statuses {
+            println!("{}\t{}", status.profile, status.state.label());
+        }
+    } else if statuses.is_empty() {
+        println!("No profiles found.");
+    } else {
+        let width = statuses.iter().map(|s| s.profile.len()).max().unwrap_or(0);
+        for status in &statuses {
+            let marker = if status.state == TokenState::Valid {
+                "✓"
+            } else {
+                "✗"
+            };
+            let extra = match (status.expires_at, &status.detail) {
+                (_, Some(detail)) => format!(" ({detail})"),
+                (Some(expires_at), None) => format!(" (expires at {expires_at})"),
+                (None, None) => String::new(),
+            };
+            println!(
+                "{marker} {:<width$}  {}{extra}",
+                status.profile,
+                status.state.label()
+            );
+        }
+    }
+
+    let failing = statuses
+        .iter()
+        .filter(|s| s.state != TokenState::Valid)
+        .count();
+    if failing > 0 {
+        return Err(OidcError::Auth(format!(
+            "{failing} profile(s) without a valid token"
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::TokenResponse;
+    use crate::profile::ProfileParams;
+
+    #[test]
+    fn test_profile_status_states() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager =
+            ProfileManager::new_with_test_dir(Some(dir.path().to_path_buf())).unwrap();
+        for name in ["fresh", "stale", "never"] {
+            manager
+                .create_profile(ProfileParams {
+                    name: name.to_string(),
+                    client_id: "client".to_string(),
+                    client_secret: None,
+                    redirect_uri: "http://localhost:8080/callback".to_string(),
+                    scope: "openid".to_string(),
+                    discovery_uri: Some(
+                        "https://example.com/.well-known/openid-configuration".to_string(),
+                    ),
+                    authorization_endpoint: None,
+                    token_endpoint: None,
+                })
+                .unwrap();
+        }
+
+        let token = |expires_in| TokenResponse {
+            access_token: "access".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: Some(expires_in),
+            ..Default::default()
+        };
+        let cache = TokenCache::new_with_override(Some(dir.path().to_path_buf())).unwrap();
+        cache.store("fresh", &token(3600), "openid").unwrap();
+        cache.store("stale", &token(0), "openid").unwrap();
+        let cached = cache.load().unwrap();
+
+        assert_eq!(
+            profile_status(&manager, &cached, "fresh").state,
+            TokenState::Valid
+        );
+        assert_eq!(
+            profile_status(&manager, &cached, "stale").state,
+            TokenState::Expired
+        );
+        assert_eq!(
+            profile_status(&manager, &cached, "never").state,
+            TokenState::Missing
+        );
+        assert_eq!(
+            profile_status(&manager, &cached, "unknown").state,
+            TokenState::InvalidProfile
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_introspects_valid_tokens() {
+        use crate::config::Profile;
+        use crate::test_support::{MockResponse, MockServer};
+
+        let introspection =
+            MockServer::start(vec![MockResponse::json(200, r#"{"active":false}"#)
+                .with_delay(Duration::from_millis(100))])
+            .await;
+        let discovery_body = format!(
+            r#"{{
+                "issuer": "https://example.com",
+                "authorization_endpoint": "https://example.com/auth",
+                "token_endpoint": "https://example.com/token",
+                "introspection_endpoint": "{}"
+            }}"#,
+            introspection.url("/introspect")
+        );
+        let discovery = MockServer::start(vec![MockResponse::json(200, &discovery_body)]).await;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager =
+            ProfileManager::new_with_test_dir(Some(dir.path().to_path_buf())).unwrap();
+        let cache = TokenCache::new_with_override(Some(dir.path().to_path_buf())).unwrap();
+        let token = TokenResponse {
+            access_token: "access".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: Some(3600),
+            ..Default::default()
+        };
+        for name in ["revoked-a", "revoked-b", "no-token"] {
+            let profile = Profile {
+                client_id: "client".to_string(),
+                redirect_uri: "http://localhost:8080/callback".to_string(),
+                scope: "openid".to_string(),
+                discovery_uri: Some(discovery.url("/.well-known/openid-configuration")),
+                ..Default::default()
+            };
+            manager.add_profile(name, profile).unwrap();
+            if name != "no-token" {
+                cache.store(name, &token, "openid").unwrap();
+            }
+        }
+
+        let cached = cache.load().unwrap();
+        let mut statuses: Vec<ProfileStatus> = ["no-token", "revoked-a", "revoked-b"]
+            .iter()
+            .map(|name| profile_status(&manager, &cached, name))
+            .collect();
+        introspect_statuses(&manager, &cached, &mut statuses).await;
+
+        assert_eq!(statuses[0].state, TokenState::Missing);
+        assert_eq!(statuses[0].active, None);
+        for status in &statuses[1..] {
+            assert_eq!(status.state, TokenState::Inactive, "{status:?}");
+            assert_eq!(status.active, Some(false));
+        }
+        assert_eq!(introspection.requests().len(), 2);
+    }
+}
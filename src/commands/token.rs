@@ -0,0 +1,43 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::crypto::decode_jwt_segments;
+use crate::error::Result;
+use crate::ui::{read_token_input, render_claims_tree};
+
+/// Print the decoded header and payload of a JWT without verifying it, as an
+/// annotated tree or, with `raw`, as pretty-printed JSON
+pub fn handle_decode(
+    token: Option<String>,
+    token_stdin: bool,
+    raw: bool,
+    quiet: bool,
+) -> Result<()> {
+    let token = read_token_input(token, token_stdin)?;
+    let (header, payload) = decode_jwt_segments(&token)?;
+
+    if quiet {
+        println!("{}", serde_json::to_string(&payload)?);
+        return Ok(());
+    }
+
+    if raw {
+        println!("Header:");
+        println!("{}", serde_json::to_string_pretty(&header)?);
+        println!();
+        println!("Payload:");
+        println!("{}", serde_json::to_string_pretty(&payload)?);
+        return Ok(());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    println!("Header:");
+    print!("{}", render_claims_tree(&header, now));
+    println!();
+    println!("Payload:");
+    print!("{}", render_claims_tree(&payload, now));
+
+    Ok(())
+}
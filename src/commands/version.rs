@@ -0,0 +1,75 @@
+use serde::Serialize;
+
+use crate::error::{OidcError, Result};
+
+/// Build metadata for bug reports, recorded by `build.rs`
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    pub build_date: &'static str,
+    pub target: &'static str,
+    pub profile: &'static str,
+    /// Optional cargo features compiled in
+    pub features: Vec<&'static str>,
+}
+
+pub fn build_info() -> BuildInfo {
+    let mut features = Vec::new();
+    if cfg!(feature = "clipboard") {
+        features.push("clipboard");
+    }
+
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("OIDC_CLI_GIT_COMMIT"),
+        build_date: env!("OIDC_CLI_BUILD_DATE"),
+        target: env!("OIDC_CLI_TARGET"),
+        profile: env!("OIDC_CLI_BUILD_PROFILE"),
+        features,
+    }
+}
+
+pub fn handle_version(json: bool) -> Result<()> {
+    let info = build_info();
+    if json {
+        let output = serde_json::to_string_pretty(&info)
+            .map_err(|e| OidcError::Config(format!("Failed to serialize build info: {e}")))?;
+        println!("{output}");
+        return Ok(());
+    }
+
+    println!(
+        "oidc-cli {} ({} {})",
+        info.version, info.git_commit, info.build_date
+    );
+    println!("target:   {} ({})", info.target, info.profile);
+    let features = if info.features.is_empty() {
+        "none".to_string()
+    } else {
+        info.features.join(", ")
+    };
+    println!("features: {features}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_info() {
+        let info = build_info();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.git_commit.is_empty());
+        assert_eq!(info.build_date.len(), "YYYY-MM-DD".len());
+        assert_eq!(
+            info.features.contains(&"clipboard"),
+            cfg!(feature = "clipboard")
+        );
+
+        let json = serde_json::to_value(&info).unwrap();
+        assert!(json["features"].is_array());
+        assert_eq!(json["target"], info.target);
+    }
+}
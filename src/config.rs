@@ -1,11 +1,19 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use url::Url;
 
+use crate::crypto::PkceMethod;
 use crate::error::{OidcError, Result};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::pinning::validate_pin;
+use crate::profile::validation::{
+    oauth21_enforced, validate_client_credentials_configuration, validate_oauth21_url,
+    validate_scope, validate_scope_syntax, AUTHORIZATION_CODE_GRANT, CLIENT_CREDENTIALS_GRANT,
+};
+use crate::utils::env::expand_env_vars;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct Profile {
     pub discovery_uri: Option<String>,
     pub client_id: String,
@@ -14,15 +22,125 @@ pub struct Profile {
     pub scope: String,
     pub authorization_endpoint: Option<String>,
     pub token_endpoint: Option<String>,
+    /// Externally-hosted authorization request object passed as `request_uri`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_uri: Option<String>,
+    /// The `state` inside the `request_uri` request object, checked against the callback
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_state: Option<String>,
+    /// Device authorization endpoint for profiles without discovery
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_authorization_endpoint: Option<String>,
+    /// Additional redirect URIs registered for the client; login picks one per environment
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redirect_uris: Vec<String>,
+    /// Shell command run after a successful login, with tokens in `OIDC_*` env vars
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_login_hook: Option<String>,
+    /// Omit PKCE parameters for providers that reject them (confidential clients only)
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub no_pkce: bool,
+    /// `code` (default) or the `code token` hybrid, whose access token arrives in the callback query
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response_type: Option<String>,
+    /// Base64 SHA-256 SPKI pins; when set, TLS connections to the provider must match one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pin_sha256: Vec<String>,
+    /// Do not add `openid` to the scope of discovery-based profiles
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub no_auto_openid: bool,
+    /// Use the manual endpoints when discovery fails (requires both to be set)
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub discovery_fallback: bool,
+    /// Token request body encoding: `form` (default, per spec) or `json` for gateways that require it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_request_format: Option<String>,
+    /// Show the tokens on the browser success page: `preview` or `full` (off by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub show_token_in_browser: Option<String>,
+    /// PKCE method to use regardless of discovery: `S256` or `plain` (negotiated by default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pkce_method: Option<String>,
+    /// `authorization_code` (default) or `client_credentials` for service accounts,
+    /// which need only a client secret and token endpoint (no redirect URI)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grant_type: Option<String>,
+    /// Scopes and audience per API, chosen with `login --for <api>`, so one client
+    /// can get tokens for several APIs
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub apis: HashMap<String, ApiTarget>,
+}
+
+/// What to request when logging in for one API
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ApiTarget {
+    /// Requested instead of the profile's `scope`
+    pub scope: String,
+    /// Sent as the `audience` parameter of the authorization and token requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
 }
 
 impl Profile {
+    /// Whether the profile uses the `code token` hybrid response type
+    pub fn is_hybrid(&self) -> bool {
+        self.response_type
+            .as_deref()
+            .is_some_and(|rt| rt.split_whitespace().any(|part| part == "token"))
+    }
+
+    /// The PKCE method pinned by the profile, if any
+    pub fn pinned_pkce_method(&self) -> Option<PkceMethod> {
+        self.pkce_method.as_deref().and_then(PkceMethod::parse)
+    }
+
+    /// Whether token requests should send a JSON body instead of form encoding
+    pub fn uses_json_token_requests(&self) -> bool {
+        self.token_request_format.as_deref() == Some("json")
+    }
+
+    /// Whether logins use the client credentials grant instead of a browser flow
+    pub fn uses_client_credentials(&self) -> bool {
+        self.grant_type.as_deref() == Some(CLIENT_CREDENTIALS_GRANT)
+    }
+
+    /// The `apis` entry for `api`; the error lists the configured names
+    pub fn api_target(&self, api: &str) -> Result<&ApiTarget> {
+        self.apis.get(api).ok_or_else(|| {
+            let mut names: Vec<&str> = self.apis.keys().map(String::as_str).collect();
+            names.sort_unstable();
+            OidcError::Config(if names.is_empty() {
+                format!("Unknown API '{api}': the profile has no 'apis' entries")
+            } else {
+                format!("Unknown API '{api}' (configured: {})", names.join(", "))
+            })
+        })
+    }
+
+    /// Whether both manual endpoints are set, so the profile works without discovery
+    pub fn has_manual_endpoints(&self) -> bool {
+        self.authorization_endpoint.is_some() && self.token_endpoint.is_some()
+    }
+
     pub fn validate(&self) -> Result<()> {
         if self.client_id.is_empty() {
             return Err(OidcError::MissingField("client_id".to_string()));
         }
 
-        if self.redirect_uri.is_empty() {
+        if let Some(ref grant_type) = self.grant_type {
+            if grant_type != AUTHORIZATION_CODE_GRANT && grant_type != CLIENT_CREDENTIALS_GRANT {
+                return Err(OidcError::Config(format!(
+                    "Unsupported grant_type '{grant_type}' (expected '{AUTHORIZATION_CODE_GRANT}' or '{CLIENT_CREDENTIALS_GRANT}')"
+                )));
+            }
+        }
+        let client_credentials = self.uses_client_credentials();
+
+        if self.redirect_uri.is_empty() && !client_credentials {
             return Err(OidcError::MissingField("redirect_uri".to_string()));
         }
 
@@ -30,8 +148,9 @@ impl Profile {
             return Err(OidcError::MissingField("scope".to_string()));
         }
 
-        Url::parse(&self.redirect_uri)
-            .map_err(|_| OidcError::InvalidRedirectUri(self.redirect_uri.clone()))?;
+        for candidate in self.redirect_uri_candidates() {
+            Url::parse(candidate).map_err(|_| OidcError::InvalidRedirectUri(candidate.clone()))?;
+        }
 
         if let Some(ref discovery_uri) = self.discovery_uri {
             Url::parse(discovery_uri).map_err(|_| {
@@ -51,7 +170,93 @@ impl Profile {
             })?;
         }
 
-        if self.discovery_uri.is_none()
+        if let Some(ref device_endpoint) = self.device_authorization_endpoint {
+            Url::parse(device_endpoint).map_err(|_| {
+                OidcError::Config(format!(
+                    "Invalid device authorization endpoint: {device_endpoint}"
+                ))
+            })?;
+        }
+
+        if let Some(ref request_uri) = self.request_uri {
+            let url = Url::parse(request_uri)
+                .map_err(|_| OidcError::Config(format!("Invalid request URI: {request_uri}")))?;
+            if url.scheme() != "https" {
+                return Err(OidcError::Config("Request URI must use HTTPS".to_string()));
+            }
+        }
+
+        if let Some(ref response_type) = self.response_type {
+            let mut parts: Vec<&str> = response_type.split_whitespace().collect();
+            parts.sort_unstable();
+            if parts != ["code"] && parts != ["code", "token"] {
+                return Err(OidcError::Config(format!(
+                    "Unsupported response_type '{response_type}' (expected 'code' or 'code token')"
+                )));
+            }
+        }
+
+        for pin in &self.pin_sha256 {
+            validate_pin(pin)?;
+        }
+
+        if let Some(ref format) = self.token_request_format {
+            if format != "form" && format != "json" {
+                return Err(OidcError::Config(format!(
+                    "Unsupported token_request_format '{format}' (expected 'form' or 'json')"
+                )));
+            }
+        }
+
+        if let Some(ref method) = self.pkce_method {
+            if PkceMethod::parse(method).is_none() {
+                return Err(OidcError::Config(format!(
+                    "Unsupported pkce_method '{method}' (expected 'S256' or 'plain')"
+                )));
+            }
+        }
+
+        if let Some(ref display) = self.show_token_in_browser {
+            if display != "preview" && display != "full" {
+                return Err(OidcError::Config(format!(
+                    "Unsupported show_token_in_browser '{display}' (expected 'preview' or 'full')"
+                )));
+            }
+        }
+
+        for (name, target) in &self.apis {
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(OidcError::Config(format!(
+                    "Invalid API name '{name}' in 'apis' (must be non-empty without spaces)"
+                )));
+            }
+            validate_scope_syntax(&target.scope).map_err(|e| {
+                OidcError::Config(format!("API '{name}' has an invalid scope: {e}"))
+            })?;
+            if target
+                .audience
+                .as_deref()
+                .is_some_and(|aud| aud.trim().is_empty())
+            {
+                return Err(OidcError::Config(format!(
+                    "API '{name}' has an empty audience"
+                )));
+            }
+        }
+
+        if self.no_pkce && self.client_secret.is_none() {
+            return Err(OidcError::Config(
+                "PKCE cannot be disabled for public clients (no client_secret)".to_string(),
+            ));
+        }
+
+        if client_credentials {
+            validate_client_credentials_configuration(
+                self.client_secret.as_deref(),
+                self.discovery_uri.as_deref(),
+                self.token_endpoint.as_deref(),
+            )?;
+        } else if self.discovery_uri.is_none()
             && (self.authorization_endpoint.is_none() || self.token_endpoint.is_none())
         {
             return Err(OidcError::Config(
@@ -61,11 +266,94 @@ impl Profile {
 
         Ok(())
     }
+
+    /// [`Profile::validate`], plus the OAuth 2.1 rules when `--oauth21` is in effect.
+    /// Used where a profile is created, changed or logged in with, so existing
+    /// profiles still load in strict mode.
+    pub fn validate_for_use(&self) -> Result<()> {
+        self.validate()?;
+        if oauth21_enforced() {
+            self.validate_oauth21()?;
+        }
+        Ok(())
+    }
+
+    /// OAuth 2.1 rules on top of [`Profile::validate`]: PKCE stays on, no hybrid
+    /// response type, and every URL uses https unless it points at loopback
+    pub fn validate_oauth21(&self) -> Result<()> {
+        if self.no_pkce {
+            return Err(OidcError::Config(
+                "OAuth 2.1 mode: PKCE is required and cannot be disabled".to_string(),
+            ));
+        }
+
+        if self.pinned_pkce_method() == Some(PkceMethod::Plain) {
+            return Err(OidcError::Config(
+                "OAuth 2.1 mode: the plain PKCE method is not allowed (use S256)".to_string(),
+            ));
+        }
+
+        if self.is_hybrid() {
+            return Err(OidcError::Config(format!(
+                "OAuth 2.1 mode: response_type '{}' is not allowed (only 'code')",
+                self.response_type.as_deref().unwrap_or_default()
+            )));
+        }
+
+        for uri in self.redirect_uri_candidates() {
+            validate_oauth21_url(uri, "redirect URI")?;
+        }
+
+        let endpoints = [
+            (&self.discovery_uri, "discovery URI"),
+            (&self.authorization_endpoint, "authorization endpoint"),
+            (&self.token_endpoint, "token endpoint"),
+            (
+                &self.device_authorization_endpoint,
+                "device authorization endpoint",
+            ),
+        ];
+        for (url, what) in endpoints {
+            if let Some(url) = url.as_deref() {
+                validate_oauth21_url(url, what)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The primary redirect URI followed by any additional candidates
+    pub fn redirect_uri_candidates(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.redirect_uri)
+            .chain(self.redirect_uris.iter())
+            .filter(|uri| !uri.is_empty())
+    }
+
+    /// Return a copy with `${VAR}` references in the client credentials expanded
+    pub fn resolve_env(&self) -> Result<Profile> {
+        let mut resolved = self.clone();
+        resolved.client_id = expand_env_vars(&self.client_id)?;
+        resolved.client_secret = self
+            .client_secret
+            .as_deref()
+            .map(expand_env_vars)
+            .transpose()?;
+        Ok(resolved)
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     pub profiles: HashMap<String, Profile>,
+    /// Named scope bundles usable with `--scope-preset`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub scope_presets: HashMap<String, String>,
+    /// Scopes added to every authorization request when missing
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_scopes: Vec<String>,
+    /// Custom `--claims-preset` names mapped to the claims they request
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub claims_presets: HashMap<String, Vec<String>>,
 }
 
 impl Default for Config {
@@ -78,11 +366,14 @@ impl Config {
     pub fn new() -> Self {
         Config {
             profiles: HashMap::new(),
+            scope_presets: HashMap::new(),
+            required_scopes: Vec::new(),
+            claims_presets: HashMap::new(),
         }
     }
 
     pub fn add_profile(&mut self, name: String, profile: Profile) -> Result<()> {
-        profile.validate()?;
+        profile.validate_for_use()?;
 
         if self.profiles.contains_key(&name) {
             return Err(OidcError::ProfileExists(name));
@@ -105,7 +396,7 @@ impl Config {
     }
 
     pub fn update_profile(&mut self, name: String, profile: Profile) -> Result<()> {
-        profile.validate()?;
+        profile.validate_for_use()?;
 
         if !self.profiles.contains_key(&name) {
             return Err(OidcError::ProfileNotFound(name));
@@ -128,6 +419,24 @@ impl Config {
     pub fn list_profiles(&self) -> Vec<&String> {
         self.profiles.keys().collect()
     }
+
+    pub fn add_scope_preset(&mut self, name: String, scope: String) -> Result<()> {
+        validate_scope(&scope)?;
+        self.scope_presets.insert(name, scope);
+        Ok(())
+    }
+
+    pub fn get_scope_preset(&self, name: &str) -> Result<&String> {
+        self.scope_presets
+            .get(name)
+            .ok_or_else(|| OidcError::Config(format!("Scope preset not found: {name}")))
+    }
+
+    pub fn remove_scope_preset(&mut self, name: &str) -> Result<String> {
+        self.scope_presets
+            .remove(name)
+            .ok_or_else(|| OidcError::Config(format!("Scope preset not found: {name}")))
+    }
 }
 
 pub fn get_config_dir_with_override(override_dir: Option<PathBuf>) -> Result<PathBuf> {
@@ -150,9 +459,48 @@ pub fn get_config_dir_with_override(override_dir: Option<PathBuf>) -> Result<Pat
         .ok_or_else(|| OidcError::Config("Could not determine config directory".to_string()))
 }
 
-pub fn get_config_file_path_with_override(override_dir: Option<PathBuf>) -> Result<PathBuf> {
+/// Profiles file name used when neither `--profiles-file` nor the env var is set
+pub const DEFAULT_PROFILES_FILE: &str = "profiles.json";
+
+/// Environment variable selecting an alternate profiles file in the config dir
+pub const PROFILES_FILE_ENV: &str = "OIDC_CLI_PROFILES_FILE";
+
+/// Data files oidc-cli writes into its config directory besides the profiles file
+pub const DATA_FILES: &[&str] = &["tokens.json", "discovery.json", "audit.log"];
+
+/// Resolve the profiles file name: an explicit name, then `OIDC_CLI_PROFILES_FILE`,
+/// then `profiles.json`. Only bare file names inside the config dir are accepted,
+/// other than the token cache, discovery cache, audit log and `*.lock` files.
+pub fn profiles_file_name(explicit: Option<&str>) -> Result<String> {
+    let name = match explicit {
+        Some(name) => name.to_string(),
+        None => std::env::var(PROFILES_FILE_ENV)
+            .ok()
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_PROFILES_FILE.to_string()),
+    };
+
+    let is_bare = !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\'])
+        && !DATA_FILES.contains(&name.as_str())
+        && !name.ends_with(".lock");
+    if !is_bare {
+        return Err(OidcError::Config(format!(
+            "Invalid profiles file name '{name}': expected a file name inside the config directory"
+        )));
+    }
+
+    Ok(name)
+}
+
+pub fn get_config_file_path_with_override(
+    override_dir: Option<PathBuf>,
+    profiles_file: Option<&str>,
+) -> Result<PathBuf> {
     let mut path = get_config_dir_with_override(override_dir)?;
-    path.push("profiles.json");
+    path.push(profiles_file_name(profiles_file)?);
     Ok(path)
 }
 
@@ -169,6 +517,7 @@ mod tests {
             scope: "openid profile email".to_string(),
             authorization_endpoint: None,
             token_endpoint: None,
+            ..Default::default()
         }
     }
 
@@ -192,6 +541,16 @@ mod tests {
         assert!(profile.validate().is_err());
     }
 
+    #[test]
+    fn test_profile_validation_request_uri_requires_https() {
+        let mut profile = create_test_profile();
+        profile.request_uri = Some("https://example.com/requests/abc".to_string());
+        assert!(profile.validate().is_ok());
+
+        profile.request_uri = Some("http://example.com/requests/abc".to_string());
+        assert!(profile.validate().is_err());
+    }
+
     #[test]
     fn test_config_add_profile() {
         let mut config = Config::new();
@@ -211,4 +570,209 @@ mod tests {
             .unwrap();
         assert!(config.add_profile("test".to_string(), profile).is_err());
     }
+
+    #[test]
+    fn test_profile_validation_no_pkce_requires_secret() {
+        let mut profile = create_test_profile();
+        profile.no_pkce = true;
+        assert!(profile.validate().is_ok());
+
+        profile.client_secret = None;
+        assert!(profile.validate().is_err());
+    }
+
+    #[test]
+    fn test_client_credentials_profile_validation() {
+        let mut profile = Profile {
+            client_id: "ci-bot".to_string(),
+            client_secret: Some("ci-secret".to_string()),
+            scope: "api:read".to_string(),
+            token_endpoint: Some("https://example.com/token".to_string()),
+            grant_type: Some(CLIENT_CREDENTIALS_GRANT.to_string()),
+            ..Default::default()
+        };
+        assert!(
+            profile.validate().is_ok(),
+            "no redirect URI or auth endpoint needed"
+        );
+
+        profile.client_secret = None;
+        assert!(profile.validate().is_err());
+        profile.client_secret = Some("ci-secret".to_string());
+
+        profile.token_endpoint = None;
+        assert!(profile.validate().is_err());
+        profile.token_endpoint = Some("https://example.com/token".to_string());
+
+        profile.grant_type = Some(AUTHORIZATION_CODE_GRANT.to_string());
+        assert!(
+            profile.validate().is_err(),
+            "authorization code needs a redirect URI"
+        );
+
+        profile.grant_type = Some("password".to_string());
+        assert!(profile.validate().is_err());
+    }
+
+    #[test]
+    fn test_api_targets() {
+        let mut profile = create_test_profile();
+        assert!(profile
+            .api_target("billing")
+            .unwrap_err()
+            .to_string()
+            .contains("no 'apis' entries"));
+
+        let billing = ApiTarget {
+            scope: "openid billing:read".to_string(),
+            audience: Some("https://billing.example.com".to_string()),
+        };
+        profile.apis.insert("billing".to_string(), billing.clone());
+        profile.apis.insert(
+            "reports".to_string(),
+            ApiTarget {
+                scope: "reports".to_string(),
+                audience: None,
+            },
+        );
+        assert!(profile.validate().is_ok());
+        assert_eq!(profile.api_target("billing").unwrap(), &billing);
+        let err = profile.api_target("crm").unwrap_err().to_string();
+        assert!(err.contains("configured: billing, reports"), "{err}");
+
+        for (name, scope, audience) in [
+            ("two words", "scope", None),
+            ("empty-scope", " ", None),
+            ("bad-scope", "billing read!", None),
+            ("empty-audience", "scope", Some("")),
+        ] {
+            let mut invalid = create_test_profile();
+            invalid.apis.insert(
+                name.to_string(),
+                ApiTarget {
+                    scope: scope.to_string(),
+                    audience: audience.map(str::to_string),
+                },
+            );
+            assert!(invalid.validate().is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_oauth21_rejects_disallowed_configurations() {
+        let profile = create_test_profile();
+        assert!(profile.validate_oauth21().is_ok());
+
+        let mut no_pkce = profile.clone();
+        no_pkce.no_pkce = true;
+        let err = no_pkce.validate_oauth21().unwrap_err().to_string();
+        assert!(err.contains("PKCE is required"), "{err}");
+
+        let mut plain = profile.clone();
+        plain.pkce_method = Some("plain".to_string());
+        assert!(plain.validate().is_ok());
+        assert!(plain.validate_oauth21().is_err());
+        plain.pkce_method = Some("S512".to_string());
+        assert!(plain.validate().is_err());
+
+        let mut hybrid = profile.clone();
+        hybrid.response_type = Some("code token".to_string());
+        assert!(hybrid.validate_oauth21().is_err());
+
+        let mut insecure_redirect = profile.clone();
+        insecure_redirect.redirect_uri = "http://app.example.com/callback".to_string();
+        assert!(insecure_redirect.validate_oauth21().is_err());
+
+        let mut alias_redirect = profile.clone();
+        alias_redirect.redirect_uris = vec!["http://app.localhost:8080/callback".to_string()];
+        assert!(alias_redirect.validate_oauth21().is_err());
+
+        let mut insecure_endpoint = profile.clone();
+        insecure_endpoint.discovery_uri = None;
+        insecure_endpoint.authorization_endpoint = Some("https://idp.example.com/auth".to_string());
+        insecure_endpoint.token_endpoint = Some("http://idp.example.com/token".to_string());
+        let err = insecure_endpoint
+            .validate_oauth21()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("token endpoint"), "{err}");
+
+        let mut loopback_idp = insecure_endpoint.clone();
+        loopback_idp.token_endpoint = Some("http://127.0.0.1:9000/token".to_string());
+        assert!(loopback_idp.validate_oauth21().is_ok());
+    }
+
+    #[test]
+    fn test_profile_validation_response_type() {
+        let mut profile = create_test_profile();
+        assert!(!profile.is_hybrid());
+
+        profile.response_type = Some("token code".to_string());
+        assert!(profile.validate().is_ok());
+        assert!(profile.is_hybrid());
+
+        profile.response_type = Some("code id_token".to_string());
+        assert!(profile.validate().is_err());
+    }
+
+    #[test]
+    fn test_profile_validation_token_request_format() {
+        let mut profile = create_test_profile();
+        assert!(!profile.uses_json_token_requests());
+
+        profile.token_request_format = Some("json".to_string());
+        assert!(profile.validate().is_ok());
+        assert!(profile.uses_json_token_requests());
+
+        profile.token_request_format = Some("form".to_string());
+        assert!(profile.validate().is_ok());
+
+        profile.token_request_format = Some("xml".to_string());
+        assert!(profile.validate().is_err());
+    }
+
+    #[test]
+    fn test_profiles_file_name() {
+        assert_eq!(profiles_file_name(Some("work.json")).unwrap(), "work.json");
+        assert!(profiles_file_name(Some("../work.json")).is_err());
+        assert!(profiles_file_name(Some("")).is_err());
+        assert!(profiles_file_name(Some("tokens.json")).is_err());
+        assert!(profiles_file_name(Some("discovery.json")).is_err());
+        assert!(profiles_file_name(Some("audit.log")).is_err());
+        assert!(profiles_file_name(Some("profiles.json.lock")).is_err());
+
+        let path =
+            get_config_file_path_with_override(Some(PathBuf::from("/tmp/oidc")), Some("work.json"))
+                .unwrap();
+        assert_eq!(path, PathBuf::from("/tmp/oidc/work.json"));
+    }
+
+    #[test]
+    fn test_scope_presets() {
+        let mut config = Config::new();
+        config
+            .add_scope_preset("api".to_string(), "openid api:read".to_string())
+            .unwrap();
+        assert!(config
+            .add_scope_preset("bad".to_string(), "openid bad!scope".to_string())
+            .is_err());
+
+        assert_eq!(config.get_scope_preset("api").unwrap(), "openid api:read");
+        assert_eq!(
+            config.remove_scope_preset("api").unwrap(),
+            "openid api:read"
+        );
+        assert!(config.get_scope_preset("api").is_err());
+    }
+
+    #[test]
+    fn test_profile_resolve_env() {
+        std::env::set_var("OIDC_CLI_CONFIG_TEST_SECRET", "resolved-secret");
+        let mut profile = create_test_profile();
+        profile.client_secret = Some("${OIDC_CLI_CONFIG_TEST_SECRET}".to_string());
+
+        let resolved = profile.resolve_env().unwrap();
+        assert_eq!(resolved.client_secret.as_deref(), Some("resolved-secret"));
+        assert_eq!(resolved.client_id, "test-client");
+    }
 }
@@ -1,24 +1,95 @@
-use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use argon2::Argon2;
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine as _,
+};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sha2::{Digest, Sha256};
 
-use crate::error::Result;
+use crate::error::{OidcError, Result};
 
+/// PKCE `code_challenge_method`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PkceMethod {
+    #[default]
+    S256,
+    /// The challenge is the verifier itself; only for providers without S256
+    #[serde(rename = "plain")]
+    Plain,
+}
+
+impl PkceMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PkceMethod::S256 => "S256",
+            PkceMethod::Plain => "plain",
+        }
+    }
+
+    /// Parse a method name as it appears in `code_challenge_methods_supported`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "S256" => Some(PkceMethod::S256),
+            "plain" => Some(PkceMethod::Plain),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for PkceMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct PkceChallenge {
     pub verifier: String,
     pub challenge: String,
+    #[serde(default)]
+    pub method: PkceMethod,
 }
 
 impl PkceChallenge {
-    pub fn new() -> Result<Self> {
+    pub fn new(method: PkceMethod) -> Result<Self> {
         let verifier = generate_code_verifier()?;
-        let challenge = create_code_challenge(&verifier)?;
+        let challenge = challenge_for(&verifier, method)?;
 
         Ok(PkceChallenge {
             verifier,
             challenge,
+            method,
         })
     }
+
+    /// Recompute the challenge from the verifier and compare it with the stored one
+    pub fn verify(&self) -> bool {
+        challenge_for(&self.verifier, self.method)
+            .is_ok_and(|challenge| challenge == self.challenge)
+    }
+
+    /// Diagnostic description of the challenge computation for debugging `invalid_grant`.
+    /// The values are ephemeral but still sensitive while the flow is in progress.
+    pub fn trace(&self) -> Vec<String> {
+        vec![
+            "PKCE trace (sensitive until this login completes):".to_string(),
+            format!(
+                "  code_verifier ({} chars): {}",
+                self.verifier.len(),
+                self.verifier
+            ),
+            format!("  code_challenge ({}): {}", self.method, self.challenge),
+            format!(
+                "  {}(code_verifier) == code_challenge: {}",
+                self.method,
+                if self.verify() { "yes" } else { "NO" }
+            ),
+        ]
+    }
 }
 
 pub fn generate_code_verifier() -> Result<String> {
@@ -37,6 +108,13 @@ pub fn generate_code_verifier() -> Result<String> {
     Ok(verifier)
 }
 
+fn challenge_for(verifier: &str, method: PkceMethod) -> Result<String> {
+    match method {
+        PkceMethod::S256 => create_code_challenge(verifier),
+        PkceMethod::Plain => Ok(verifier.to_string()),
+    }
+}
+
 pub fn create_code_challenge(verifier: &str) -> Result<String> {
     let mut hasher = Sha256::new();
     hasher.update(verifier.as_bytes());
@@ -53,6 +131,110 @@ pub fn generate_state() -> Result<String> {
     Ok(URL_SAFE_NO_PAD.encode(&bytes))
 }
 
+/// Decode the header and payload of a compact JWT without verifying its signature.
+///
+/// Returns an error for opaque (non-JWT) tokens rather than panicking.
+pub fn decode_jwt_segments(token: &str) -> Result<(Value, Value)> {
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    if parts.len() != 3 {
+        return Err(OidcError::InvalidJwt(
+            "expected three dot-separated segments".to_string(),
+        ));
+    }
+
+    let header = decode_jwt_segment(parts[0], "header")?;
+    let payload = decode_jwt_segment(parts[1], "payload")?;
+
+    Ok((header, payload))
+}
+
+fn decode_jwt_segment(segment: &str, name: &str) -> Result<Value> {
+    let bytes = URL_SAFE_NO_PAD
+        .decode(segment.trim_end_matches('='))
+        .map_err(|e| OidcError::InvalidJwt(format!("{name} is not valid base64url: {e}")))?;
+
+    let value: Value = serde_json::from_slice(&bytes)
+        .map_err(|e| OidcError::InvalidJwt(format!("{name} is not valid JSON: {e}")))?;
+
+    if !value.is_object() {
+        return Err(OidcError::InvalidJwt(format!(
+            "{name} is not a JSON object"
+        )));
+    }
+
+    Ok(value)
+}
+
+/// Passphrase-encrypted payload: an Argon2id-derived key and ChaCha20-Poly1305,
+/// stored as JSON so readers can recognise it before asking for a passphrase
+#[derive(Serialize, Deserialize)]
+pub struct EncryptedBlob {
+    pub format: String,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+impl EncryptedBlob {
+    pub const FORMAT: &'static str = "oidc-cli-encrypted-v1";
+
+    pub fn seal(plaintext: &[u8], passphrase: &str) -> Result<Self> {
+        let mut salt = [0u8; 16];
+        let mut nonce = [0u8; 12];
+        rand::thread_rng().fill(&mut salt);
+        rand::thread_rng().fill(&mut nonce);
+
+        let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| OidcError::Config("Encryption failed".to_string()))?;
+
+        Ok(Self {
+            format: Self::FORMAT.to_string(),
+            salt: STANDARD.encode(salt),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        })
+    }
+
+    /// Decrypt; a wrong passphrase and a tampered blob are indistinguishable
+    pub fn open(&self, passphrase: &str) -> Result<Vec<u8>> {
+        let invalid =
+            |what: &str| OidcError::Config(format!("Encrypted file has an invalid {what}"));
+        let salt = STANDARD.decode(&self.salt).map_err(|_| invalid("salt"))?;
+        let nonce = STANDARD.decode(&self.nonce).map_err(|_| invalid("nonce"))?;
+        let ciphertext = STANDARD
+            .decode(&self.ciphertext)
+            .map_err(|_| invalid("ciphertext"))?;
+        if nonce.len() != 12 {
+            return Err(invalid("nonce"));
+        }
+
+        ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?)
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| {
+                OidcError::Config(
+                    "Decryption failed: wrong passphrase or corrupted file".to_string(),
+                )
+            })
+    }
+
+    /// Parse `content` as an encrypted blob, or `None` if it is anything else
+    pub fn detect(content: &str) -> Option<Self> {
+        serde_json::from_str::<Self>(content)
+            .ok()
+            .filter(|blob| blob.format == Self::FORMAT)
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| OidcError::Config(format!("Key derivation failed: {e}")))?;
+    Ok(key)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,12 +254,32 @@ mod tests {
 
     #[test]
     fn test_pkce_challenge() {
-        let pkce = PkceChallenge::new().unwrap();
+        let pkce = PkceChallenge::new(PkceMethod::S256).unwrap();
         assert!(pkce.verifier.len() >= 43);
         assert!(!pkce.challenge.is_empty());
         assert_ne!(pkce.verifier, pkce.challenge);
     }
 
+    #[test]
+    fn test_pkce_challenge_verify_and_trace() {
+        let mut pkce = PkceChallenge::new(PkceMethod::S256).unwrap();
+        assert!(pkce.verify());
+        assert!(pkce.trace().last().unwrap().ends_with("yes"));
+
+        // RFC 7636 Appendix B
+        pkce.verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string();
+        pkce.challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM".to_string();
+        assert!(pkce.verify());
+
+        pkce.challenge = "tampered".to_string();
+        assert!(!pkce.verify());
+        assert!(pkce.trace().last().unwrap().ends_with("NO"));
+
+        let plain = PkceChallenge::new(PkceMethod::Plain).unwrap();
+        assert_eq!(plain.challenge, plain.verifier);
+        assert!(plain.verify());
+    }
+
     #[test]
     fn test_code_challenge_deterministic() {
         let verifier = "test_verifier_with_sufficient_length_for_pkce_requirements";
@@ -85,4 +287,34 @@ mod tests {
         let challenge2 = create_code_challenge(verifier).unwrap();
         assert_eq!(challenge1, challenge2);
     }
+
+    #[test]
+    fn test_decode_jwt_segments() {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","typ":"JWT"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"sub":"user-1","acr":"urn:mfa"}"#);
+        let token = format!("{header}.{payload}.signature");
+
+        let (header, claims) = decode_jwt_segments(&token).unwrap();
+        assert_eq!(header["alg"], "RS256");
+        assert_eq!(claims["sub"], "user-1");
+    }
+
+    #[test]
+    fn test_decode_jwt_segments_rejects_opaque_tokens() {
+        assert!(decode_jwt_segments("opaque-access-token").is_err());
+        assert!(decode_jwt_segments("a.b.c").is_err());
+        assert!(decode_jwt_segments("").is_err());
+    }
+
+    #[test]
+    fn test_encrypted_blob_round_trip() {
+        let blob = EncryptedBlob::seal(b"secret config", "correct horse").unwrap();
+        let json = serde_json::to_string(&blob).unwrap();
+        assert!(!json.contains("secret config"));
+
+        let detected = EncryptedBlob::detect(&json).unwrap();
+        assert_eq!(detected.open("correct horse").unwrap(), b"secret config");
+        assert!(detected.open("wrong").is_err());
+        assert!(EncryptedBlob::detect(r#"{"profiles":{}}"#).is_none());
+    }
 }
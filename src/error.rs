@@ -31,6 +31,9 @@ pub enum OidcError {
     #[error("State parameter mismatch")]
     StateMismatch,
 
+    #[error("This authorization code was already exchanged; start a new login for a fresh code")]
+    CodeReused,
+
     #[error("Invalid redirect URI: {0}")]
     InvalidRedirectUri(String),
 
@@ -40,12 +43,21 @@ pub enum OidcError {
     #[error("Invalid token response")]
     InvalidTokenResponse,
 
+    #[error("Response body exceeds the {0}-byte limit")]
+    ResponseTooLarge(usize),
+
+    #[error("Invalid JWT: {0}")]
+    InvalidJwt(String),
+
     #[error("Profile not found: {0}")]
     ProfileNotFound(String),
 
     #[error("Profile already exists: {0}")]
     ProfileExists(String),
 
+    #[error("Post-login hook failed: {0}")]
+    Hook(String),
+
     #[error("Discovery failed: {0}")]
     Discovery(String),
 
@@ -56,4 +68,33 @@ pub enum OidcError {
     Cancelled,
 }
 
+impl OidcError {
+    /// Stable snake_case name of the variant, for machine-readable output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            OidcError::Network(_) => "network",
+            OidcError::Json(_) => "json",
+            OidcError::Url(_) => "url",
+            OidcError::Io(_) => "io",
+            OidcError::Config(_) => "config",
+            OidcError::Auth(_) => "auth",
+            OidcError::Profile(_) => "profile",
+            OidcError::Server(_) => "server",
+            OidcError::StateMismatch => "state_mismatch",
+            OidcError::CodeReused => "code_reused",
+            OidcError::InvalidRedirectUri(_) => "invalid_redirect_uri",
+            OidcError::MissingField(_) => "missing_field",
+            OidcError::InvalidTokenResponse => "invalid_token_response",
+            OidcError::ResponseTooLarge(_) => "response_too_large",
+            OidcError::InvalidJwt(_) => "invalid_jwt",
+            OidcError::ProfileNotFound(_) => "profile_not_found",
+            OidcError::ProfileExists(_) => "profile_exists",
+            OidcError::Hook(_) => "hook",
+            OidcError::Discovery(_) => "discovery",
+            OidcError::BrowserFailed => "browser_failed",
+            OidcError::Cancelled => "cancelled",
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, OidcError>;
@@ -0,0 +1,375 @@
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Response};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::error::{OidcError, Result};
+use crate::pinning::pinned_tls_config;
+use crate::settings;
+use crate::utils::proxy::NoProxy;
+
+/// Timeout applied to every outgoing HTTP request
+pub const HTTP_TIMEOUT_SECS: u64 = 30;
+
+/// Largest response body read from discovery and token endpoints
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 2 * 1024 * 1024;
+
+/// Environment variable overriding [`DEFAULT_MAX_RESPONSE_BYTES`]
+pub const MAX_RESPONSE_BYTES_ENV: &str = "OIDC_CLI_MAX_RESPONSE_BYTES";
+
+/// Environment variable naming a proxy, like `--proxy`
+pub const PROXY_ENV: &str = "OIDC_CLI_PROXY";
+
+pub const DEFAULT_CORRELATION_HEADER: &str = "X-Correlation-ID";
+
+/// Header sent on every request of one invocation so it can be found in provider logs
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Correlation {
+    pub header: String,
+    pub id: String,
+}
+
+impl Correlation {
+    /// A fresh ID sent in `header`, or [`DEFAULT_CORRELATION_HEADER`] when `None`
+    pub fn new(header: Option<String>) -> Self {
+        Self {
+            header: header
+                .filter(|header| !header.trim().is_empty())
+                .unwrap_or_else(|| DEFAULT_CORRELATION_HEADER.to_string()),
+            id: new_correlation_id(),
+        }
+    }
+}
+
+/// A random (version 4) UUID
+pub fn new_correlation_id() -> String {
+    let mut bytes: [u8; 16] = rand::random();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Build the HTTP client used for discovery and token requests.
+///
+/// Compressed responses (`Content-Encoding: gzip` or `br`) are decoded transparently.
+/// An explicit proxy (`--proxy` / `OIDC_CLI_PROXY`) is skipped for hosts matching `NO_PROXY`.
+/// When `pins` is non-empty every TLS connection must present a server certificate
+/// whose SPKI SHA-256 matches one of them. With `--correlation-id`, every request
+/// carries the invocation's [`Correlation`] header.
+pub fn build_pinned_http_client(pins: &[String]) -> Result<Client> {
+    let key = ClientKey::new(pins);
+    build_http_client_with_proxy(
+        key.proxy.as_deref(),
+        NoProxy::from_env(),
+        pins,
+        key.correlation.as_ref(),
+    )
+}
+
+/// Process-wide client for the current proxy settings and `pins`.
+///
+/// Clients share a connection pool, so repeated discovery and token requests
+/// reuse connections. Differing pins (or proxy settings) get their own client.
+pub fn shared_http_client(pins: &[String]) -> Result<Client> {
+    static CACHE: OnceLock<ClientCache> = OnceLock::new();
+    CACHE
+        .get_or_init(ClientCache::default)
+        .get_or_build(ClientKey::new(pins), || build_pinned_http_client(pins))
+}
+
+/// Everything that affects how a client is built
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ClientKey {
+    proxy: Option<String>,
+    no_proxy: Option<String>,
+    pins: Vec<String>,
+    correlation: Option<Correlation>,
+}
+
+impl ClientKey {
+    fn new(pins: &[String]) -> Self {
+        let mut pins = pins.to_vec();
+        pins.sort();
+        pins.dedup();
+        let settings = settings::get();
+        Self {
+            proxy: settings
+                .proxy
+                .clone()
+                .filter(|value| !value.trim().is_empty()),
+            no_proxy: std::env::var("NO_PROXY")
+                .or_else(|_| std::env::var("no_proxy"))
+                .ok(),
+            pins,
+            correlation: settings.correlation.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ClientCache {
+    clients: Mutex<HashMap<ClientKey, Client>>,
+}
+
+impl ClientCache {
+    fn get_or_build(
+        &self,
+        key: ClientKey,
+        build: impl FnOnce() -> Result<Client>,
+    ) -> Result<Client> {
+        let mut clients = self.clients.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(client) = clients.get(&key) {
+            return Ok(client.clone());
+        }
+        let client = build()?;
+        clients.insert(key, client.clone());
+        Ok(client)
+    }
+}
+
+fn build_http_client_with_proxy(
+    proxy: Option<&str>,
+    no_proxy: NoProxy,
+    pins: &[String],
+    correlation: Option<&Correlation>,
+) -> Result<Client> {
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(HTTP_TIMEOUT_SECS))
+        .gzip(true)
+        .brotli(true);
+
+    if let Some(correlation) = correlation {
+        let name = HeaderName::from_bytes(correlation.header.as_bytes()).map_err(|_| {
+            OidcError::Config(format!(
+                "Invalid correlation header name '{}'",
+                correlation.header
+            ))
+        })?;
+        let value = HeaderValue::from_str(&correlation.id).map_err(|_| {
+            OidcError::Config(format!("Invalid correlation ID '{}'", correlation.id))
+        })?;
+        builder = builder.default_headers(HeaderMap::from_iter([(name, value)]));
+    }
+
+    if !pins.is_empty() {
+        builder = builder.use_preconfigured_tls(pinned_tls_config(pins)?);
+    }
+
+    if let Some(proxy) = proxy {
+        let proxy_url = url::Url::parse(proxy)
+            .map_err(|e| OidcError::Config(format!("Invalid proxy URL '{proxy}': {e}")))?;
+        builder = builder.proxy(reqwest::Proxy::custom(move |url| match url.host_str() {
+            Some(host) if no_proxy.matches(host) => None,
+            _ => Some(proxy_url.clone()),
+        }));
+    }
+
+    Ok(builder.build()?)
+}
+
+static BYTES_RECEIVED: AtomicU64 = AtomicU64::new(0);
+
+/// Total response body bytes read through [`read_body_capped`] in this process
+pub fn bytes_received() -> u64 {
+    BYTES_RECEIVED.load(Ordering::Relaxed)
+}
+
+/// Response size limit: `OIDC_CLI_MAX_RESPONSE_BYTES` as fixed in the settings, or the default
+pub fn max_response_bytes() -> usize {
+    settings::get()
+        .max_response_bytes
+        .unwrap_or(DEFAULT_MAX_RESPONSE_BYTES)
+}
+
+/// Read a response body, failing once it grows past `limit` bytes instead of
+/// buffering an arbitrarily large (possibly decompressed) payload
+pub async fn read_body_capped(mut response: Response, limit: usize) -> Result<Vec<u8>> {
+    if response
+        .content_length()
+        .is_some_and(|length| length > limit as u64)
+    {
+        return Err(OidcError::ResponseTooLarge(limit));
+    }
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > limit {
+            return Err(OidcError::ResponseTooLarge(limit));
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    BYTES_RECEIVED.fetch_add(body.len() as u64, Ordering::Relaxed);
+
+    Ok(body)
+}
+
+/// Body of a failed response for an error message, read within `limit` bytes; an
+/// oversized body is reported as such rather than buffered
+pub async fn read_error_text(response: Response, limit: usize) -> String {
+    match read_body_capped(response, limit).await {
+        Ok(body) => String::from_utf8_lossy(&body).into_owned(),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockResponse, MockServer};
+
+    #[tokio::test]
+    async fn test_read_body_capped_rejects_oversized_body() {
+        let big = format!(r#"{{"access_token":"{}"}}"#, "x".repeat(4096));
+        let server = MockServer::start(vec![MockResponse::json(200, &big)]).await;
+        let client = build_pinned_http_client(&[]).unwrap();
+
+        let response = client.get(server.url("/token")).send().await.unwrap();
+        assert!(matches!(
+            read_body_capped(response, 1024).await,
+            Err(OidcError::ResponseTooLarge(1024))
+        ));
+
+        let response = client.get(server.url("/token")).send().await.unwrap();
+        assert_eq!(
+            read_body_capped(response, 8192).await.unwrap().len(),
+            big.len()
+        );
+
+        let response = client.get(server.url("/token")).send().await.unwrap();
+        assert_eq!(
+            read_error_text(response, 1024).await,
+            "Response body exceeds the 1024-byte limit"
+        );
+    }
+
+    #[test]
+    fn test_identical_configs_reuse_client() {
+        let cache = ClientCache::default();
+        let builds = std::cell::Cell::new(0);
+        let build = || {
+            builds.set(builds.get() + 1);
+            build_http_client_with_proxy(None, NoProxy::default(), &[], None)
+        };
+        let key = |pins: &[&str]| ClientKey {
+            proxy: None,
+            no_proxy: None,
+            pins: pins.iter().map(|pin| pin.to_string()).collect(),
+            correlation: None,
+        };
+
+        cache.get_or_build(key(&[]), build).unwrap();
+        cache.get_or_build(key(&[]), build).unwrap();
+        assert_eq!(builds.get(), 1);
+
+        cache.get_or_build(key(&["pin-a"]), build).unwrap();
+        assert_eq!(builds.get(), 2);
+
+        let proxied = ClientKey {
+            proxy: Some("http://proxy.example.com:3128".to_string()),
+            ..key(&[])
+        };
+        cache.get_or_build(proxied, build).unwrap();
+        assert_eq!(builds.get(), 3);
+
+        // Pin order does not matter
+        let a = ClientKey::new(&["b".to_string(), "a".to_string()]);
+        let b = ClientKey::new(&["a".to_string(), "b".to_string(), "a".to_string()]);
+        assert_eq!(a, b);
+    }
+
+    #[tokio::test]
+    async fn test_correlation_header_sent_on_requests() {
+        let server = MockServer::start(vec![MockResponse::json(200, "{}")]).await;
+        let correlation = Correlation {
+            header: "X-Request-Trace".to_string(),
+            id: new_correlation_id(),
+        };
+        let client =
+            build_http_client_with_proxy(None, NoProxy::default(), &[], Some(&correlation))
+                .unwrap();
+
+        client.get(server.url("/token")).send().await.unwrap();
+        assert_eq!(
+            server.requests()[0].headers.get("x-request-trace"),
+            Some(&correlation.id)
+        );
+
+        let bad = Correlation {
+            header: "not a header".to_string(),
+            ..correlation
+        };
+        assert!(build_http_client_with_proxy(None, NoProxy::default(), &[], Some(&bad)).is_err());
+    }
+
+    #[test]
+    fn test_new_correlation_id_is_uuid_v4() {
+        let id = new_correlation_id();
+        let parts: Vec<&str> = id.split('-').collect();
+        assert_eq!(
+            parts.iter().map(|p| p.len()).collect::<Vec<_>>(),
+            vec![8, 4, 4, 4, 12]
+        );
+        assert!(parts[2].starts_with('4'));
+        assert!("89ab".contains(&parts[3][..1]));
+        assert_ne!(id, new_correlation_id());
+    }
+
+    #[tokio::test]
+    async fn test_no_proxy_bypasses_explicit_proxy() {
+        let proxy = MockServer::start(vec![MockResponse::json(200, r#"{"via":"proxy"}"#)]).await;
+        let target = MockServer::start(vec![MockResponse::json(200, r#"{"via":"direct"}"#)]).await;
+        let proxy_url = proxy.url("");
+
+        let proxied = build_http_client_with_proxy(
+            Some(&proxy_url),
+            NoProxy::parse("example.com"),
+            &[],
+            None,
+        )
+        .unwrap();
+        let body = proxied
+            .get(target.url("/token"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(body.contains("proxy"));
+        assert!(target.requests().is_empty());
+
+        let bypassed = build_http_client_with_proxy(
+            Some(&proxy_url),
+            NoProxy::parse("127.0.0.0/8"),
+            &[],
+            None,
+        )
+        .unwrap();
+        let body = bypassed
+            .get(target.url("/token"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(body.contains("direct"));
+        assert_eq!(proxy.requests().len(), 1);
+        assert_eq!(target.requests().len(), 1);
+
+        assert!(
+            build_http_client_with_proxy(Some("not a url"), NoProxy::default(), &[], None).is_err()
+        );
+    }
+}
@@ -1,3 +1,4 @@
+pub mod audit;
 pub mod auth;
 pub mod browser;
 pub mod cli;
@@ -5,8 +6,16 @@ pub mod commands;
 pub mod config;
 pub mod crypto;
 pub mod error;
+pub mod http;
+pub mod messages;
+pub mod metrics;
+pub mod pinning;
 pub mod profile;
 pub mod server;
+pub mod settings;
+#[cfg(test)]
+mod test_support;
+pub mod token_cache;
 pub mod ui;
 pub mod utils;
 
@@ -17,10 +26,13 @@ pub use utils::url::{
 };
 
 // Re-export from server.rs for testing
-pub use server::parse_query_params as server_parse_query_params;
-pub use server::{extract_path_from_redirect_uri, CallbackResult, CallbackServer};
+pub use server::{
+    browser_token_json, callback_result_from_params, extract_path_from_redirect_uri,
+    BrowserTokenDisplay, CallbackResult, CallbackServer,
+};
 
 // Re-export profile and browser modules for testing
 #[cfg(test)]
 pub use browser::MockBrowserOpener;
+pub use profile::builder::ProfileBuilder;
 pub use profile::ProfileManager;
@@ -1,3 +1,4 @@
+mod audit;
 mod auth;
 mod browser;
 mod cli;
@@ -5,57 +6,316 @@ mod commands;
 mod config;
 mod crypto;
 mod error;
+mod http;
+mod messages;
+mod metrics;
+mod pinning;
 mod profile;
 mod server;
+mod settings;
+#[cfg(test)]
+mod test_support;
+mod token_cache;
 mod ui;
 mod utils;
 
+use auth::ClaimRequirements;
 use clap::Parser;
 use cli::{Cli, Commands};
 use commands::*;
 use error::{OidcError, Result};
 use profile::ProfileManager;
 
-#[tokio::main]
-async fn main() {
+fn main() {
     let cli = Cli::parse();
 
-    if let Err(e) = run(cli).await {
+    // Everything that touches the process environment happens here, before the
+    // runtime starts any threads
+    let result = cli.prepare().and_then(|()| {
+        tokio::runtime::Runtime::new()
+            .map_err(OidcError::from)
+            .and_then(|runtime| runtime.block_on(run(cli)))
+    });
+
+    if let Err(e) = result {
         if !matches!(e, OidcError::Cancelled) {
-            eprintln!("Error: {e}");
+            eprintln!("{}: {e}", messages::tr(messages::Message::ErrorPrefix));
             std::process::exit(1);
         }
     }
 }
 
 async fn run(cli: Cli) -> Result<()> {
-    let mut profile_manager = ProfileManager::new()?;
+    if let Commands::Schema = cli.command {
+        return handle_schema();
+    }
+
+    if let Commands::Version { json } = cli.command {
+        return handle_version(json);
+    }
+
+    if let Commands::Man = cli.command {
+        use std::io::Write;
+        std::io::stdout().write_all(&cli::render_man_page()?)?;
+        return Ok(());
+    }
+
+    // Reset must work even when profiles.json no longer parses
+    if let Commands::Reset { force } = cli.command {
+        return handle_reset(
+            cli.config_dir.clone(),
+            cli.profiles_file.as_deref(),
+            force,
+            cli.is_quiet(),
+        );
+    }
+
+    let mut profile_manager = match cli.config.clone() {
+        Some(config_file) => {
+            ProfileManager::new_with_config_file(config_file, cli.config_dir.clone())?
+        }
+        None => {
+            ProfileManager::new_with_location(cli.config_dir.clone(), cli.profiles_file.clone())?
+        }
+    };
 
     let is_quiet = cli.is_quiet();
     let is_verbose = cli.is_verbose();
+    let is_very_verbose = cli.is_very_verbose();
 
     match cli.command {
         Commands::Login {
+            all,
             profile,
+            adhoc,
+            stdin_profile,
+            client_id,
+            client_secret,
+            redirect_uri,
+            scope,
+            scope_preset,
+            for_api,
+            choose_scopes,
+            explain,
+            discovery_uri,
+            auth_endpoint,
+            token_endpoint,
             port,
             copy,
             json,
             output,
+            output_fd,
+            output_template,
+            require_acr,
+            require_amr,
+            request_uri,
+            request_state,
+            registered_redirect_uri,
+            uds,
+            device,
+            grant,
+            dpop,
+            retry_browser,
+            incognito,
+            retries,
+            idle_timeout,
+            show_token_in_browser,
+            check_clock,
+            no_pkce,
+            no_auto_openid,
+            discovery_fallback,
+            hint,
+            fresh,
+            consent,
+            verify,
+            offline,
+            jwks_file,
+            claims_out,
+            access_token_file,
+            id_token_file,
+            refresh_token_file,
+            pin_sha256,
+            show,
+            on_success,
+            run_hook,
+            use_cache,
+            state_dir,
+            resume,
+            metrics_json,
+            callback_file,
+            claims,
+            claims_preset,
         } => {
             handle_login(
                 profile_manager,
                 LoginOptions {
                     profile_name: profile,
+                    all,
+                    adhoc: adhoc
+                        .then(|| {
+                            AdhocProfile {
+                                client_id: client_id.unwrap_or_default(),
+                                client_secret,
+                                redirect_uri: redirect_uri.clone(),
+                                scope: scope.clone(),
+                                discovery_uri,
+                                authorization_endpoint: auth_endpoint,
+                                token_endpoint,
+                            }
+                            .into_profile()
+                        })
+                        .transpose()?,
+                    stdin_profile,
                     port,
                     copy,
                     quiet: is_quiet,
                     verbose: is_verbose,
+                    very_verbose: is_very_verbose,
                     json,
                     output,
+                    output_fd,
+                    output_template,
+                    claim_requirements: ClaimRequirements {
+                        acr: require_acr,
+                        amr: require_amr,
+                    },
+                    request_uri,
+                    request_state,
+                    registered_redirect_uri,
+                    uds,
+                    device,
+                    grant,
+                    dpop,
+                    retry_browser,
+                    incognito,
+                    retries,
+                    idle_timeout: idle_timeout.map(std::time::Duration::from_secs),
+                    show_token_in_browser,
+                    redirect_uri,
+                    scope,
+                    scope_preset,
+                    for_api,
+                    choose_scopes,
+                    explain,
+                    check_clock,
+                    no_pkce,
+                    no_auto_openid,
+                    discovery_fallback,
+                    hint,
+                    fresh,
+                    consent,
+                    verify,
+                    offline,
+                    jwks_file,
+                    claims_out,
+                    token_files: TokenFiles {
+                        access: access_token_file,
+                        id: id_token_file,
+                        refresh: refresh_token_file,
+                    },
+                    pin_sha256,
+                    show: show
+                        .as_deref()
+                        .map(auth::TokenSelection::parse)
+                        .transpose()?
+                        .unwrap_or_default(),
+                    on_success,
+                    run_hook,
+                    use_cache,
+                    state_dir,
+                    resume,
+                    metrics_json,
+                    callback_file,
+                    claims,
+                    claims_presets: claims_preset,
                 },
             )
             .await
         }
+        Commands::EndSession {
+            profile,
+            id_token_hint,
+            post_logout_redirect_uri,
+        } => {
+            handle_end_session(
+                profile_manager,
+                EndSessionOptions {
+                    profile_name: profile,
+                    id_token_hint,
+                    post_logout_redirect_uri,
+                    quiet: is_quiet,
+                },
+            )
+            .await
+        }
+        Commands::Doctor { profile, json } => handle_doctor(profile_manager, profile, json).await,
+        Commands::Selftest {
+            profile,
+            json,
+            timeout,
+        } => {
+            handle_selftest(
+                profile_manager,
+                profile,
+                json,
+                std::time::Duration::from_secs(timeout),
+                is_quiet,
+            )
+            .await
+        }
+        Commands::Refresh { profile } => handle_refresh(profile_manager, profile, is_quiet).await,
+        Commands::Revoke {
+            profile,
+            token,
+            token_stdin,
+            token_type_hint,
+        } => {
+            handle_revoke(
+                profile_manager,
+                RevokeOptions {
+                    profile_name: profile,
+                    token,
+                    token_stdin,
+                    token_type_hint,
+                    quiet: is_quiet,
+                },
+            )
+            .await
+        }
+        Commands::Decode {
+            token,
+            token_stdin,
+            raw,
+        } => handle_decode(token, token_stdin, raw, is_quiet),
+        Commands::Audit {
+            since,
+            profile,
+            json,
+        } => handle_audit(profile_manager, since, profile, json),
+        Commands::Cache { action } => handle_cache(profile_manager, action, is_quiet),
+        Commands::Status {
+            profile,
+            all,
+            json,
+            json_lines,
+            introspect,
+        } => {
+            handle_status(
+                profile_manager,
+                profile,
+                all,
+                json,
+                json_lines,
+                introspect,
+                is_quiet,
+            )
+            .await
+        }
+        Commands::TestCallback {
+            redirect_uri,
+            port,
+            timeout,
+        } => handle_test_callback(redirect_uri, port, timeout, is_quiet).await,
         Commands::List => handle_list(profile_manager, is_quiet),
         Commands::Create {
             name,
@@ -63,11 +323,16 @@ async fn run(cli: Cli) -> Result<()> {
             client_secret,
             redirect_uri,
             scope,
+            scope_preset,
             discovery_uri,
             auth_endpoint,
             token_endpoint,
             non_interactive,
+            fill_missing,
+            prompt_secret_always,
+            no_normalize_scopes,
         } => {
+            profile_manager.set_normalize_scopes(!no_normalize_scopes);
             handle_create(
                 &mut profile_manager,
                 CreateParams {
@@ -76,27 +341,59 @@ async fn run(cli: Cli) -> Result<()> {
                     client_secret,
                     redirect_uri,
                     scope,
+                    scope_preset,
                     discovery_uri,
                     auth_endpoint,
                     token_endpoint,
                     non_interactive,
+                    fill_missing,
+                    prompt_secret: prompt_secret_always,
                     quiet: is_quiet,
                 },
             )
             .await
         }
-        Commands::Edit { name } => handle_edit(&mut profile_manager, name, is_quiet).await,
+        Commands::Edit {
+            name,
+            no_normalize_scopes,
+        } => {
+            profile_manager.set_normalize_scopes(!no_normalize_scopes);
+            handle_edit(&mut profile_manager, name, is_quiet).await
+        }
         Commands::Delete { name, force } => {
             handle_delete(&mut profile_manager, name, force, is_quiet)
         }
         Commands::Rename { old_name, new_name } => {
             handle_rename(&mut profile_manager, old_name, new_name, is_quiet)
         }
-        Commands::Export { file, profiles } => {
-            handle_export(profile_manager, file, profiles, is_quiet)
+        Commands::Show { name, share } => handle_show(profile_manager, name, share),
+        Commands::Diff { a, b, json } => handle_diff(profile_manager, a, b, json, is_quiet),
+        Commands::ScopePreset { action } => {
+            handle_scope_preset(&mut profile_manager, action, is_quiet)
         }
-        Commands::Import { file, overwrite } => {
-            handle_import(&mut profile_manager, file, overwrite, is_quiet)
+        Commands::Dump { reveal } => handle_dump(profile_manager, reveal),
+        Commands::Export {
+            file,
+            profiles,
+            encrypt,
+        } => handle_export(profile_manager, file, profiles, encrypt, is_quiet),
+        Commands::Import {
+            file,
+            from,
+            overwrite,
+            allow_hook,
+            json_lines,
+        } => handle_import(
+            &mut profile_manager,
+            file,
+            from,
+            overwrite,
+            allow_hook,
+            json_lines,
+            is_quiet,
+        ),
+        Commands::Man | Commands::Schema | Commands::Version { .. } | Commands::Reset { .. } => {
+            unreachable!("handled before loading profiles")
         }
     }
 }
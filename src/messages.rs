@@ -0,0 +1,206 @@
+//! Catalog of user-facing prompt and error messages, looked up by key in the
+//! language chosen with `--lang` or the usual locale variables. Messages without
+//! a translation fall back to English.
+
+use crate::settings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    English,
+    German,
+}
+
+impl Locale {
+    /// Parse a locale tag such as `de`, `de_DE.UTF-8` or `en-US`; `None` when unsupported
+    pub fn parse(tag: &str) -> Option<Self> {
+        let language = tag
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "en" | "c" | "posix" => Some(Locale::English),
+            "de" => Some(Locale::German),
+            _ => None,
+        }
+    }
+
+    /// The locale from the first non-empty setting among `--lang`, `LC_ALL`,
+    /// `LC_MESSAGES` and `LANG`; English when that setting is unsupported
+    pub fn current() -> Self {
+        let from_env = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok());
+        settings::get()
+            .lang
+            .clone()
+            .into_iter()
+            .chain(from_env)
+            .find(|value| !value.is_empty())
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+}
+
+/// Keys for the localized messages. Placeholders are written `{name}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    /// Prefix for the error printed when a command fails
+    ErrorPrefix,
+    NoProfiles,
+    MultipleProfilesSpecify,
+    MultipleProfilesHeading,
+    /// `{max}`: number of profiles
+    SelectProfile,
+    /// `{max}`: number of profiles
+    InvalidProfileSelection,
+    FieldRequired,
+    SecretShownWarning,
+    PassphrasePrompt,
+    ConfirmPassphrasePrompt,
+    PassphraseEmpty,
+    PassphraseMismatch,
+    ScopesHeading,
+    ToggleScopesPrompt,
+    SelectAtLeastOneScope,
+    /// `{input}`: the rejected entry, `{max}`: number of scopes
+    InvalidScopeSelection,
+}
+
+/// The template for `message` in `locale`, or the English one when it is not translated
+pub fn lookup(message: Message, locale: Locale) -> &'static str {
+    match locale {
+        Locale::English => english(message),
+        Locale::German => german(message).unwrap_or_else(|| english(message)),
+    }
+}
+
+/// `message` in the current locale
+pub fn tr(message: Message) -> String {
+    tr_with(message, &[])
+}
+
+/// `message` in the current locale with its `{name}` placeholders filled from `args`
+pub fn tr_with(message: Message, args: &[(&str, &str)]) -> String {
+    fill(lookup(message, Locale::current()), args)
+}
+
+fn fill(template: &str, args: &[(&str, &str)]) -> String {
+    args.iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{name}}}"), value)
+        })
+}
+
+fn english(message: Message) -> &'static str {
+    match message {
+        Message::ErrorPrefix => "Error",
+        Message::NoProfiles => "No profiles found. Create a profile first using 'create' command.",
+        Message::MultipleProfilesSpecify => {
+            "Multiple profiles available. Please specify a profile name."
+        }
+        Message::MultipleProfilesHeading => "Multiple profiles available:",
+        Message::SelectProfile => "Select a profile (1-{max}): ",
+        Message::InvalidProfileSelection => {
+            "Invalid selection. Please enter a number between 1 and {max}."
+        }
+        Message::FieldRequired => "This field is required. Please enter a value.",
+        Message::SecretShownWarning => {
+            "stdin is not a terminal; reading the secret without hiding input"
+        }
+        Message::PassphrasePrompt => "Passphrase: ",
+        Message::ConfirmPassphrasePrompt => "Confirm passphrase: ",
+        Message::PassphraseEmpty => "Passphrase cannot be empty",
+        Message::PassphraseMismatch => "Passphrases do not match",
+        Message::ScopesHeading => "Scopes:",
+        Message::ToggleScopesPrompt => {
+            "Toggle scopes by number (e.g. 2 5), or press Enter to continue: "
+        }
+        Message::SelectAtLeastOneScope => "Select at least one scope.",
+        Message::InvalidScopeSelection => {
+            "Invalid selection '{input}'. Enter numbers between 1 and {max}."
+        }
+    }
+}
+
+fn german(message: Message) -> Option<&'static str> {
+    Some(match message {
+        Message::ErrorPrefix => "Fehler",
+        Message::NoProfiles => {
+            "Keine Profile gefunden. Legen Sie zuerst mit dem Befehl 'create' ein Profil an."
+        }
+        Message::MultipleProfilesSpecify => {
+            "Mehrere Profile vorhanden. Bitte geben Sie einen Profilnamen an."
+        }
+        Message::MultipleProfilesHeading => "Mehrere Profile vorhanden:",
+        Message::SelectProfile => "Profil auswählen (1-{max}): ",
+        Message::InvalidProfileSelection => {
+            "Ungültige Auswahl. Bitte eine Zahl zwischen 1 und {max} eingeben."
+        }
+        Message::FieldRequired => "Dieses Feld ist erforderlich. Bitte einen Wert eingeben.",
+        Message::PassphrasePrompt => "Passphrase: ",
+        Message::ConfirmPassphrasePrompt => "Passphrase bestätigen: ",
+        Message::PassphraseEmpty => "Die Passphrase darf nicht leer sein",
+        Message::PassphraseMismatch => "Die Passphrasen stimmen nicht überein",
+        Message::ScopesHeading => "Scopes:",
+        Message::ToggleScopesPrompt => {
+            "Scopes per Nummer umschalten (z. B. 2 5) oder Enter zum Fortfahren: "
+        }
+        Message::SelectAtLeastOneScope => "Mindestens einen Scope auswählen.",
+        Message::InvalidScopeSelection => {
+            "Ungültige Auswahl '{input}'. Zahlen zwischen 1 und {max} eingeben."
+        }
+        Message::SecretShownWarning => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locale_parse() {
+        assert_eq!(Locale::parse("de_DE.UTF-8"), Some(Locale::German));
+        assert_eq!(Locale::parse("DE"), Some(Locale::German));
+        assert_eq!(Locale::parse("en-US"), Some(Locale::English));
+        assert_eq!(Locale::parse("C.UTF-8"), Some(Locale::English));
+        assert_eq!(Locale::parse("fr_FR.UTF-8"), None);
+        assert_eq!(Locale::parse(""), None);
+    }
+
+    #[test]
+    fn test_keys_resolve_with_english_fallback() {
+        assert_eq!(lookup(Message::ErrorPrefix, Locale::English), "Error");
+        assert_eq!(lookup(Message::ErrorPrefix, Locale::German), "Fehler");
+        // Untranslated keys use the English text
+        assert_eq!(
+            lookup(Message::SecretShownWarning, Locale::German),
+            lookup(Message::SecretShownWarning, Locale::English)
+        );
+        // An unsupported locale is English
+        let locale = Locale::parse("fr_FR.UTF-8").unwrap_or_default();
+        assert_eq!(
+            lookup(Message::NoProfiles, locale),
+            "No profiles found. Create a profile first using 'create' command."
+        );
+    }
+
+    #[test]
+    fn test_fill_placeholders() {
+        assert_eq!(
+            fill(
+                lookup(Message::InvalidScopeSelection, Locale::English),
+                &[("input", "9"), ("max", "3")]
+            ),
+            "Invalid selection '9'. Enter numbers between 1 and 3."
+        );
+        assert_eq!(
+            fill(
+                lookup(Message::SelectProfile, Locale::German),
+                &[("max", "4")]
+            ),
+            "Profil auswählen (1-4): "
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use serde::Serialize;
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::error::{OidcError, Result};
+use crate::http::bytes_received;
+use crate::utils::fs::write_atomic;
+
+/// Per-invocation timing and outcome record written with `--metrics-json`
+#[derive(Debug, Serialize)]
+pub struct MetricsRecord {
+    pub command: String,
+    pub profile: Option<String>,
+    pub success: bool,
+    pub error_kind: Option<String>,
+    /// Unix timestamp (seconds) when the command started
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub phases: Vec<PhaseTiming>,
+    /// Response body bytes read from the provider
+    pub bytes_received: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PhaseTiming {
+    pub name: String,
+    pub duration_ms: u64,
+}
+
+/// Collects phase durations while a command runs. Starting a phase ends the
+/// previous one; [`finish`](Metrics::finish) ends the last and records the outcome.
+pub struct Metrics {
+    command: String,
+    profile: Option<String>,
+    started_at: u64,
+    started: Instant,
+    bytes_at_start: u64,
+    phases: Vec<PhaseTiming>,
+    current: Option<(String, Instant)>,
+}
+
+impl Metrics {
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            profile: None,
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            started: Instant::now(),
+            bytes_at_start: bytes_received(),
+            phases: Vec::new(),
+            current: None,
+        }
+    }
+
+    pub fn set_profile(&mut self, profile: &str) {
+        self.profile = Some(profile.to_string());
+    }
+
+    pub fn phase(&mut self, name: &str) {
+        self.end_phase();
+        self.current = Some((name.to_string(), Instant::now()));
+    }
+
+    fn end_phase(&mut self) {
+        if let Some((name, started)) = self.current.take() {
+            self.phases.push(PhaseTiming {
+                name,
+                duration_ms: started.elapsed().as_millis() as u64,
+            });
+        }
+    }
+
+    pub fn finish<T>(mut self, result: &Result<T>) -> MetricsRecord {
+        self.end_phase();
+        MetricsRecord {
+            command: self.command,
+            profile: self.profile,
+            success: result.is_ok(),
+            error_kind: result.as_ref().err().map(|e| e.kind().to_string()),
+            started_at: self.started_at,
+            duration_ms: self.started.elapsed().as_millis() as u64,
+            phases: self.phases,
+            bytes_received: bytes_received().saturating_sub(self.bytes_at_start),
+        }
+    }
+}
+
+impl MetricsRecord {
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| OidcError::Config(format!("Failed to serialize metrics: {e}")))?;
+        write_atomic(path, json.as_bytes())
+            .map_err(|e| OidcError::Config(format!("Failed to write metrics to {path:?}: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_json_shape() {
+        let mut metrics = Metrics::new("login");
+        metrics.set_profile("dev");
+        metrics.phase("discovery");
+        metrics.phase("authorize");
+        let record = metrics.finish::<()>(&Err(OidcError::StateMismatch));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.json");
+        record.write(&path).unwrap();
+
+        let value: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(value["command"], "login");
+        assert_eq!(value["profile"], "dev");
+        assert_eq!(value["success"], false);
+        assert_eq!(value["error_kind"], "state_mismatch");
+        assert!(value["started_at"].as_u64().unwrap() > 0);
+        assert!(value["duration_ms"].is_u64());
+        assert!(value["bytes_received"].is_u64());
+
+        let phases = value["phases"].as_array().unwrap();
+        assert_eq!(phases.len(), 2);
+        assert_eq!(phases[0]["name"], "discovery");
+        assert!(phases[1]["duration_ms"].is_u64());
+    }
+}
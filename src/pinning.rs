@@ -0,0 +1,158 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::error::{OidcError, Result};
+
+/// Base64 SHA-256 of the certificate's DER-encoded SubjectPublicKeyInfo
+/// (the usual HPKP-style `pin-sha256` value)
+pub fn spki_sha256(cert_der: &[u8]) -> Result<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert_der)
+        .map_err(|e| OidcError::Config(format!("Failed to parse server certificate: {e}")))?;
+    Ok(STANDARD.encode(Sha256::digest(cert.public_key().raw)))
+}
+
+/// Check that a pin is base64 for a 32-byte SHA-256 digest
+pub fn validate_pin(pin: &str) -> Result<()> {
+    match STANDARD.decode(pin) {
+        Ok(digest) if digest.len() == 32 => Ok(()),
+        _ => Err(OidcError::Config(format!(
+            "Invalid SPKI pin '{pin}': expected a base64-encoded SHA-256 digest"
+        ))),
+    }
+}
+
+/// Normal WebPKI validation followed by a check that the server certificate's
+/// public key matches one of the configured pins
+pub struct PinnedVerifier {
+    inner: WebPkiVerifier,
+    pins: Vec<String>,
+}
+
+impl PinnedVerifier {
+    pub fn new(roots: RootCertStore, pins: Vec<String>) -> Self {
+        Self {
+            inner: WebPkiVerifier::new(roots, None),
+            pins,
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+
+        let actual =
+            spki_sha256(&end_entity.0).map_err(|e| rustls::Error::General(e.to_string()))?;
+        if self.pins.contains(&actual) {
+            Ok(verified)
+        } else {
+            Err(rustls::Error::General(format!(
+                "server public key sha256/{actual} does not match any pinned key"
+            )))
+        }
+    }
+}
+
+/// TLS configuration trusting the bundled Mozilla roots and enforcing `pins`
+pub fn pinned_tls_config(pins: &[String]) -> Result<ClientConfig> {
+    for pin in pins {
+        validate_pin(pin)?;
+    }
+
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            anchor.subject,
+            anchor.spki,
+            anchor.name_constraints,
+        )
+    }));
+
+    Ok(ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(PinnedVerifier::new(roots, pins.to_vec())))
+        .with_no_client_auth())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Self-signed `localhost` certificate; its pin was computed with openssl
+    const FIXTURE_CERT: &str = include_str!("../tests/fixtures/localhost-cert.pem");
+    const FIXTURE_PIN: &str = "C68QzvK4vuSYDyXV7pWrS220St3cynqcFvXE2Irk00Y=";
+
+    fn fixture_der() -> Vec<u8> {
+        let body: String = FIXTURE_CERT
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect();
+        STANDARD.decode(body).unwrap()
+    }
+
+    fn verify_fixture(pins: Vec<String>) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let der = fixture_der();
+        let mut roots = RootCertStore::empty();
+        roots.add(&Certificate(der.clone())).unwrap();
+
+        PinnedVerifier::new(roots, pins).verify_server_cert(
+            &Certificate(der),
+            &[],
+            &ServerName::try_from("localhost").unwrap(),
+            &mut std::iter::empty(),
+            &[],
+            SystemTime::now(),
+        )
+    }
+
+    #[test]
+    fn test_spki_sha256_matches_openssl() {
+        assert_eq!(spki_sha256(&fixture_der()).unwrap(), FIXTURE_PIN);
+    }
+
+    #[test]
+    fn test_pinned_verifier_accepts_matching_pin() {
+        let pins = vec![
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_string(),
+            FIXTURE_PIN.to_string(),
+        ];
+        assert!(verify_fixture(pins).is_ok());
+    }
+
+    #[test]
+    fn test_pinned_verifier_rejects_mismatch() {
+        let err = verify_fixture(vec![
+            "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=".to_string()
+        ])
+        .unwrap_err();
+        assert!(err.to_string().contains(FIXTURE_PIN));
+    }
+
+    #[test]
+    fn test_validate_pin() {
+        assert!(validate_pin(FIXTURE_PIN).is_ok());
+        assert!(validate_pin("not-base64!").is_err());
+        assert!(validate_pin("AAAA").is_err());
+        assert!(pinned_tls_config(&["AAAA".to_string()]).is_err());
+        assert!(pinned_tls_config(&[FIXTURE_PIN.to_string()]).is_ok());
+    }
+}
@@ -0,0 +1,213 @@
+#![allow(dead_code)]
+
+use crate::config::Profile;
+use crate::error::{OidcError, Result};
+use crate::profile::validation::{sanitize_input, validate_profile_input};
+
+/// Fluent construction of a validated [`Profile`] for library users.
+///
+/// `client_id`, `redirect_uri` and `scope` are required, plus either a discovery
+/// URI or both manual endpoints. Values are trimmed and checked like profiles
+/// created with the `create` command.
+///
+/// ```
+/// use oidc_cli::ProfileBuilder;
+///
+/// let profile = ProfileBuilder::new()
+///     .client_id("my-client")
+///     .redirect_uri("http://localhost:8080/callback")
+///     .scope("openid email")
+///     .discovery_uri("https://accounts.example.com/.well-known/openid-configuration")
+///     .build()?;
+/// assert_eq!(profile.client_id, "my-client");
+/// # Ok::<(), oidc_cli::error::OidcError>(())
+/// ```
+///
+/// Missing required values are reported by `build`:
+///
+/// ```
+/// use oidc_cli::ProfileBuilder;
+///
+/// let err = ProfileBuilder::new()
+///     .client_id("my-client")
+///     .scope("openid")
+///     .authorization_endpoint("https://auth.example.com/authorize")
+///     .token_endpoint("https://auth.example.com/token")
+///     .build()
+///     .unwrap_err();
+/// assert!(err.to_string().contains("redirect_uri"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ProfileBuilder {
+    profile: Profile,
+    client_id: Option<String>,
+    redirect_uri: Option<String>,
+    scope: Option<String>,
+}
+
+impl ProfileBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn client_id(mut self, client_id: impl Into<String>) -> Self {
+        self.client_id = Some(client_id.into());
+        self
+    }
+
+    pub fn client_secret(mut self, client_secret: impl Into<String>) -> Self {
+        self.profile.client_secret = Some(client_secret.into());
+        self
+    }
+
+    pub fn redirect_uri(mut self, redirect_uri: impl Into<String>) -> Self {
+        self.redirect_uri = Some(redirect_uri.into());
+        self
+    }
+
+    /// Another registered redirect URI that login may pick instead of the primary one
+    pub fn additional_redirect_uri(mut self, redirect_uri: impl Into<String>) -> Self {
+        self.profile.redirect_uris.push(redirect_uri.into());
+        self
+    }
+
+    pub fn scope(mut self, scope: impl Into<String>) -> Self {
+        self.scope = Some(scope.into());
+        self
+    }
+
+    pub fn discovery_uri(mut self, discovery_uri: impl Into<String>) -> Self {
+        self.profile.discovery_uri = Some(discovery_uri.into());
+        self
+    }
+
+    pub fn authorization_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.profile.authorization_endpoint = Some(endpoint.into());
+        self
+    }
+
+    pub fn token_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.profile.token_endpoint = Some(endpoint.into());
+        self
+    }
+
+    pub fn device_authorization_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.profile.device_authorization_endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// `S256` or `plain`; negotiated from discovery when not set
+    pub fn pkce_method(mut self, method: impl Into<String>) -> Self {
+        self.profile.pkce_method = Some(method.into());
+        self
+    }
+
+    /// Omit PKCE (confidential clients only)
+    pub fn no_pkce(mut self, no_pkce: bool) -> Self {
+        self.profile.no_pkce = no_pkce;
+        self
+    }
+
+    /// Use the manual endpoints when discovery fails
+    pub fn discovery_fallback(mut self, fallback: bool) -> Self {
+        self.profile.discovery_fallback = fallback;
+        self
+    }
+
+    /// Check the required values and return the validated profile
+    pub fn build(self) -> Result<Profile> {
+        let required = |value: Option<String>, field: &str| {
+            value
+                .map(|value| sanitize_input(&value))
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| OidcError::MissingField(field.to_string()))
+        };
+
+        let mut profile = self.profile;
+        profile.client_id = required(self.client_id, "client_id")?;
+        profile.redirect_uri = required(self.redirect_uri, "redirect_uri")?;
+        profile.scope = required(self.scope, "scope")?;
+        for uri in [
+            &mut profile.client_secret,
+            &mut profile.discovery_uri,
+            &mut profile.authorization_endpoint,
+            &mut profile.token_endpoint,
+            &mut profile.device_authorization_endpoint,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *uri = sanitize_input(uri);
+        }
+
+        validate_profile_input(
+            &profile.client_id,
+            &profile.redirect_uri,
+            &profile.scope,
+            profile.discovery_uri.as_deref(),
+            profile.authorization_endpoint.as_deref(),
+            profile.token_endpoint.as_deref(),
+        )?;
+        profile.validate()?;
+        Ok(profile)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manual() -> ProfileBuilder {
+        ProfileBuilder::new()
+            .client_id("client")
+            .redirect_uri("http://localhost:8080/callback")
+            .scope("openid")
+            .authorization_endpoint("https://auth.example.com/authorize")
+            .token_endpoint("https://auth.example.com/token")
+    }
+
+    fn missing_field(builder: ProfileBuilder) -> String {
+        match builder.build() {
+            Err(OidcError::MissingField(field)) => field,
+            other => panic!("expected a missing field, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_builder_enforces_required_fields() {
+        assert!(manual().build().is_ok());
+
+        assert_eq!(missing_field(ProfileBuilder::new()), "client_id");
+        assert_eq!(missing_field(manual().client_id("  ")), "client_id");
+        let mut no_redirect = manual();
+        no_redirect.redirect_uri = None;
+        assert_eq!(missing_field(no_redirect), "redirect_uri");
+        let mut no_scope = manual();
+        no_scope.scope = None;
+        assert_eq!(missing_field(no_scope), "scope");
+
+        let mut no_token_endpoint = manual();
+        no_token_endpoint.profile.token_endpoint = None;
+        assert!(no_token_endpoint.build().is_err());
+    }
+
+    #[test]
+    fn test_builder_trims_and_validates() {
+        let profile = manual()
+            .client_id(" client ")
+            .client_secret("secret ")
+            .pkce_method("plain")
+            .build()
+            .unwrap();
+        assert_eq!(profile.client_id, "client");
+        assert_eq!(profile.client_secret.as_deref(), Some("secret"));
+        assert_eq!(profile.pkce_method.as_deref(), Some("plain"));
+
+        assert!(manual().pkce_method("S512").build().is_err());
+        assert!(manual().redirect_uri("not a uri").build().is_err());
+        assert!(
+            manual().no_pkce(true).build().is_err(),
+            "public clients need PKCE"
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use serde::Serialize;
+
+use crate::config::Profile;
+use crate::utils::mask::mask_secret;
+
+/// Comparison of a single profile field between two profiles
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FieldDiff {
+    pub field: &'static str,
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub differs: bool,
+}
+
+/// Compare two profiles field by field, masking client secrets.
+///
+/// Secrets are compared on their real values so a changed secret is still
+/// reported as a difference, but only the masked form is returned.
+pub fn diff_profiles(left: &Profile, right: &Profile) -> Vec<FieldDiff> {
+    let mut fields = vec![
+        plain_field("client_id", Some(&left.client_id), Some(&right.client_id)),
+        FieldDiff {
+            field: "client_secret",
+            left: mask_secret(left.client_secret.as_deref()),
+            right: mask_secret(right.client_secret.as_deref()),
+            differs: left.client_secret != right.client_secret,
+        },
+        plain_field(
+            "redirect_uri",
+            Some(&left.redirect_uri),
+            Some(&right.redirect_uri),
+        ),
+        plain_field("scope", Some(&left.scope), Some(&right.scope)),
+        plain_field(
+            "discovery_uri",
+            left.discovery_uri.as_ref(),
+            right.discovery_uri.as_ref(),
+        ),
+        plain_field(
+            "authorization_endpoint",
+            left.authorization_endpoint.as_ref(),
+            right.authorization_endpoint.as_ref(),
+        ),
+        plain_field(
+            "token_endpoint",
+            left.token_endpoint.as_ref(),
+            right.token_endpoint.as_ref(),
+        ),
+        plain_field(
+            "request_uri",
+            left.request_uri.as_ref(),
+            right.request_uri.as_ref(),
+        ),
+        plain_field(
+            "request_state",
+            left.request_state.as_ref(),
+            right.request_state.as_ref(),
+        ),
+        plain_field(
+            "no_pkce",
+            Some(&left.no_pkce.to_string()),
+            Some(&right.no_pkce.to_string()),
+        ),
+        plain_field(
+            "pkce_method",
+            left.pkce_method.as_ref(),
+            right.pkce_method.as_ref(),
+        ),
+        plain_field(
+            "grant_type",
+            left.grant_type.as_ref(),
+            right.grant_type.as_ref(),
+        ),
+        plain_field(
+            "no_auto_openid",
+            Some(&left.no_auto_openid.to_string()),
+            Some(&right.no_auto_openid.to_string()),
+        ),
+        plain_field(
+            "response_type",
+            left.response_type.as_ref(),
+            right.response_type.as_ref(),
+        ),
+        plain_field(
+            "token_request_format",
+            left.token_request_format.as_ref(),
+            right.token_request_format.as_ref(),
+        ),
+        plain_field(
+            "apis",
+            api_summary(left).as_ref(),
+            api_summary(right).as_ref(),
+        ),
+        plain_field(
+            "pin_sha256",
+            Some(&left.pin_sha256.join(", ")).filter(|pins| !pins.is_empty()),
+            Some(&right.pin_sha256.join(", ")).filter(|pins| !pins.is_empty()),
+        ),
+    ];
+
+    // Scopes are a set; ordering alone is not a meaningful difference
+    if let Some(scope) = fields.iter_mut().find(|f| f.field == "scope") {
+        let mut left_scopes: Vec<&str> = left.scope.split_whitespace().collect();
+        let mut right_scopes: Vec<&str> = right.scope.split_whitespace().collect();
+        left_scopes.sort_unstable();
+        right_scopes.sort_unstable();
+        scope.differs = left_scopes != right_scopes;
+    }
+
+    fields
+}
+
+/// `name: scope (audience)` for each API, sorted by name; `None` without any
+fn api_summary(profile: &Profile) -> Option<String> {
+    let mut entries: Vec<String> = profile
+        .apis
+        .iter()
+        .map(|(name, target)| match target.audience {
+            Some(ref audience) => format!("{name}: {} ({audience})", target.scope),
+            None => format!("{name}: {}", target.scope),
+        })
+        .collect();
+    entries.sort();
+    (!entries.is_empty()).then(|| entries.join("; "))
+}
+
+fn plain_field(field: &'static str, left: Option<&String>, right: Option<&String>) -> FieldDiff {
+    FieldDiff {
+        field,
+        left: left.cloned(),
+        right: right.cloned(),
+        differs: left != right,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_profile() -> Profile {
+        Profile {
+            discovery_uri: Some("https://example.com/.well-known/openid-configuration".to_string()),
+            client_id: "test-client".to_string(),
+            client_secret: Some("test-secret".to_string()),
+            redirect_uri: "http://localhost:8080/callback".to_string(),
+            scope: "openid profile email".to_string(),
+            authorization_endpoint: None,
+            token_endpoint: None,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff_identical_profiles() {
+        let profile = create_test_profile();
+        let diff = diff_profiles(&profile, &profile.clone());
+        assert!(diff.iter().all(|f| !f.differs));
+    }
+
+    #[test]
+    fn test_diff_differing_profiles() {
+        let left = create_test_profile();
+        let mut right = create_test_profile();
+        right.client_id = "prod-client".to_string();
+        right.client_secret = Some("prod-secret".to_string());
+        right.scope = "email openid profile".to_string();
+
+        let diff = diff_profiles(&left, &right);
+        let differing: Vec<&str> = diff.iter().filter(|f| f.differs).map(|f| f.field).collect();
+        assert_eq!(differing, vec!["client_id", "client_secret"]);
+
+        let secret = diff.iter().find(|f| f.field == "client_secret").unwrap();
+        assert_eq!(secret.left.as_deref(), Some("****"));
+        assert_eq!(secret.right.as_deref(), Some("****"));
+    }
+}
@@ -0,0 +1,323 @@
+//! Adapters turning other tools' OAuth 2.0 settings into profiles for `import --from`.
+//!
+//! Only the authorization code grant maps onto a profile; blocks using other grants
+//! (client credentials, password, implicit) are skipped.
+
+use serde_json::Value;
+use std::path::Path;
+
+use crate::config::{Config, Profile};
+use crate::error::{OidcError, Result};
+
+/// Redirect URI used when the source has none, or only the tool's hosted callback
+pub const DEFAULT_IMPORT_REDIRECT_URI: &str = "http://localhost:8080/callback";
+
+/// Scope used when the source leaves it empty
+pub const DEFAULT_IMPORT_SCOPE: &str = "openid";
+
+/// Postman's hosted callback only works inside Postman
+const POSTMAN_CALLBACK: &str = "https://oauth.pstmn.io/v1/callback";
+
+/// File formats accepted by `import --from`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ImportFormat {
+    /// oidc-cli's own export format
+    #[default]
+    Native,
+    /// Postman collection (v2.x) or environment export
+    Postman,
+    /// Insomnia export (format 4)
+    Insomnia,
+}
+
+/// Read a Postman or Insomnia export into a config holding one validated
+/// profile per OAuth 2.0 authorization code configuration found
+pub fn read_foreign_config(path: &Path, format: ImportFormat) -> Result<Config> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| OidcError::Config(format!("Failed to read {path:?}: {e}")))?;
+    let json: Value = serde_json::from_str(&content)
+        .map_err(|e| OidcError::Config(format!("Failed to parse {path:?}: {e}")))?;
+
+    let found = match format {
+        ImportFormat::Postman => profiles_from_postman(&json)?,
+        ImportFormat::Insomnia => profiles_from_insomnia(&json)?,
+        ImportFormat::Native => {
+            return Err(OidcError::Config(
+                "Native exports are read by the regular import".to_string(),
+            ))
+        }
+    };
+    if found.is_empty() {
+        return Err(OidcError::Config(format!(
+            "No OAuth 2.0 authorization code settings found in {path:?}"
+        )));
+    }
+
+    let mut config = Config::new();
+    for (name, profile) in found {
+        profile
+            .validate()
+            .map_err(|e| OidcError::Config(format!("Imported profile '{name}' is invalid: {e}")))?;
+        let name = unique_name(&config, &name);
+        config.profiles.insert(name, profile);
+    }
+    Ok(config)
+}
+
+/// Profiles from a Postman collection (collection, folder and request `auth`
+/// blocks) or environment export (well-known variable names)
+pub fn profiles_from_postman(json: &Value) -> Result<Vec<(String, Profile)>> {
+    if let Some(values) = json.get("values").and_then(Value::as_array) {
+        let name = json["name"].as_str().unwrap_or("postman");
+        let enabled = values
+            .iter()
+            .filter(|v| v["enabled"].as_bool() != Some(false))
+            .filter_map(|v| Some((v["key"].as_str()?, v["value"].as_str()?)));
+        let fields = OAuthFields::from_pairs(enabled);
+        return Ok(fields
+            .into_profile()
+            .map(|profile| vec![(slug(name), profile)])
+            .unwrap_or_default());
+    }
+
+    if json.get("info").is_none() {
+        return Err(OidcError::Config(
+            "Not a Postman collection or environment export".to_string(),
+        ));
+    }
+
+    let mut found = Vec::new();
+    let name = json["info"]["name"].as_str().unwrap_or("postman");
+    collect_postman(json, name, &mut found);
+    Ok(found)
+}
+
+fn collect_postman(node: &Value, name: &str, found: &mut Vec<(String, Profile)>) {
+    let auth = node.get("auth").or_else(|| node["request"].get("auth"));
+    if let Some(auth) = auth.filter(|auth| auth["type"] == "oauth2") {
+        let pairs = auth["oauth2"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|v| Some((v["key"].as_str()?, v["value"].as_str()?)));
+        if let Some(profile) = OAuthFields::from_pairs(pairs).into_profile() {
+            found.push((slug(name), profile));
+        }
+    }
+
+    for item in node["item"].as_array().into_iter().flatten() {
+        collect_postman(item, item["name"].as_str().unwrap_or(name), found);
+    }
+}
+
+/// Profiles from the `authentication` blocks of an Insomnia export's requests and folders
+pub fn profiles_from_insomnia(json: &Value) -> Result<Vec<(String, Profile)>> {
+    let resources = json["resources"].as_array().ok_or_else(|| {
+        OidcError::Config("Not an Insomnia export (no 'resources' array)".to_string())
+    })?;
+
+    Ok(resources
+        .iter()
+        .filter(|resource| resource["authentication"]["type"] == "oauth2")
+        .filter_map(|resource| {
+            let auth = resource["authentication"].as_object()?;
+            let pairs = auth
+                .iter()
+                .filter_map(|(key, value)| Some((key.as_str(), value.as_str()?)));
+            let profile = OAuthFields::from_pairs(pairs).into_profile()?;
+            Some((
+                slug(resource["name"].as_str().unwrap_or("insomnia")),
+                profile,
+            ))
+        })
+        .collect())
+}
+
+/// OAuth settings gathered from a tool's key/value pairs
+#[derive(Default)]
+struct OAuthFields {
+    grant_type: Option<String>,
+    authorization_endpoint: Option<String>,
+    token_endpoint: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    scope: Option<String>,
+    redirect_uri: Option<String>,
+}
+
+impl OAuthFields {
+    fn from_pairs<'a>(pairs: impl Iterator<Item = (&'a str, &'a str)>) -> Self {
+        let mut fields = Self::default();
+        for (key, value) in pairs {
+            let value = convert_template(value.trim());
+            if value.is_empty() {
+                continue;
+            }
+            let slot = match key.to_ascii_lowercase().replace(['_', '-'], "").as_str() {
+                "granttype" => &mut fields.grant_type,
+                "authurl" | "authorizationurl" | "authorizationendpoint" => {
+                    &mut fields.authorization_endpoint
+                }
+                "accesstokenurl" | "tokenurl" | "tokenendpoint" => &mut fields.token_endpoint,
+                "clientid" => &mut fields.client_id,
+                "clientsecret" => &mut fields.client_secret,
+                "scope" | "scopes" => &mut fields.scope,
+                "redirecturi" | "redirecturl" | "callbackurl" => &mut fields.redirect_uri,
+                _ => continue,
+            };
+            *slot = Some(value);
+        }
+        fields
+    }
+
+    /// `None` unless this is an authorization code configuration with both endpoints
+    fn into_profile(self) -> Option<Profile> {
+        let grant = self.grant_type.as_deref().unwrap_or("authorization_code");
+        if !grant.starts_with("authorization_code") {
+            return None;
+        }
+
+        Some(Profile {
+            client_id: self.client_id?,
+            client_secret: self.client_secret,
+            redirect_uri: self
+                .redirect_uri
+                .filter(|uri| uri != POSTMAN_CALLBACK)
+                .unwrap_or_else(|| DEFAULT_IMPORT_REDIRECT_URI.to_string()),
+            scope: self
+                .scope
+                .map(|scope| scope.replace(',', " "))
+                .unwrap_or_else(|| DEFAULT_IMPORT_SCOPE.to_string()),
+            authorization_endpoint: Some(self.authorization_endpoint?),
+            token_endpoint: Some(self.token_endpoint?),
+            ..Default::default()
+        })
+    }
+}
+
+/// Rewrite `{{name}}` (Postman) and `{{ _.name }}` (Insomnia) variable references
+/// as `${name}`, which profiles expand from the environment
+fn convert_template(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let inner = rest[start + 2..start + end].trim();
+        let inner = inner.strip_prefix("_.").unwrap_or(inner);
+        out.push_str(&rest[..start]);
+        out.push_str(&format!("${{{inner}}}"));
+        rest = &rest[start + end + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Lowercase, dash-separated profile name
+fn slug(name: &str) -> String {
+    let slug = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_ascii_lowercase();
+    if slug.is_empty() {
+        "imported".to_string()
+    } else {
+        slug
+    }
+}
+
+fn unique_name(config: &Config, name: &str) -> String {
+    let mut candidate = name.to_string();
+    let mut n = 2;
+    while config.profiles.contains_key(&candidate) {
+        candidate = format!("{name}-{n}");
+        n += 1;
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn test_postman_collection_import() {
+        let config =
+            read_foreign_config(&fixture("postman-collection.json"), ImportFormat::Postman)
+                .unwrap();
+        let mut names: Vec<_> = config.profiles.keys().cloned().collect();
+        names.sort();
+        // The client credentials request is skipped
+        assert_eq!(names, vec!["delete-order", "orders-api"]);
+
+        let orders = &config.profiles["orders-api"];
+        assert_eq!(orders.client_id, "orders-cli");
+        assert_eq!(orders.client_secret.as_deref(), Some("${orders_secret}"));
+        assert_eq!(orders.scope, "openid orders:read");
+        assert_eq!(orders.redirect_uri, "http://localhost:9000/callback");
+        assert_eq!(
+            orders.token_endpoint.as_deref(),
+            Some("https://auth.example.com/oauth2/token")
+        );
+
+        let admin = &config.profiles["delete-order"];
+        assert_eq!(admin.client_id, "orders-admin");
+        assert_eq!(admin.redirect_uri, DEFAULT_IMPORT_REDIRECT_URI);
+    }
+
+    #[test]
+    fn test_postman_environment_import() {
+        let config =
+            read_foreign_config(&fixture("postman-environment.json"), ImportFormat::Postman)
+                .unwrap();
+        let staging = &config.profiles["staging"];
+        assert_eq!(staging.client_id, "staging-client");
+        assert_eq!(staging.client_secret.as_deref(), Some("staging-secret"));
+        assert_eq!(
+            staging.authorization_endpoint.as_deref(),
+            Some("https://staging.example.com/authorize")
+        );
+        // The disabled callbackUrl variable is ignored
+        assert_eq!(staging.redirect_uri, DEFAULT_IMPORT_REDIRECT_URI);
+    }
+
+    #[test]
+    fn test_insomnia_import() {
+        let config =
+            read_foreign_config(&fixture("insomnia-export.json"), ImportFormat::Insomnia).unwrap();
+        assert_eq!(config.profiles.len(), 1, "password grant is skipped");
+
+        let invoices = &config.profiles["invoices"];
+        assert_eq!(invoices.client_id, "billing-web");
+        assert_eq!(invoices.client_secret.as_deref(), Some("${billing_secret}"));
+        assert_eq!(invoices.redirect_uri, "http://localhost:8765/callback");
+        assert_eq!(invoices.scope, "openid invoices");
+    }
+
+    #[test]
+    fn test_wrong_format_is_rejected() {
+        assert!(
+            read_foreign_config(&fixture("insomnia-export.json"), ImportFormat::Postman).is_err()
+        );
+        assert!(
+            read_foreign_config(&fixture("postman-collection.json"), ImportFormat::Insomnia)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_convert_template() {
+        assert_eq!(convert_template("{{secret}}"), "${secret}");
+        assert_eq!(convert_template("{{ _.base }}/token"), "${base}/token");
+        assert_eq!(convert_template("plain {{unclosed"), "plain {{unclosed");
+    }
+}
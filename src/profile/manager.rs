@@ -1,11 +1,14 @@
 #![allow(dead_code)]
 
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 use crate::config::{Config, Profile};
 use crate::error::{OidcError, Result};
+use crate::profile::foreign::{read_foreign_config, ImportFormat};
 use crate::profile::storage::ProfileStorage;
 use crate::profile::validation::{sanitize_input, validate_profile_input};
+use crate::utils::scope::{merge_scopes, normalize_scope};
 
 pub struct ProfileParams {
     pub name: String,
@@ -21,6 +24,9 @@ pub struct ProfileParams {
 pub struct ProfileManager {
     config: Config,
     test_dir: Option<PathBuf>,
+    profiles_file: Option<String>,
+    config_file: Option<PathBuf>,
+    normalize_scopes: bool,
 }
 
 impl ProfileManager {
@@ -29,8 +35,61 @@ impl ProfileManager {
     }
 
     pub fn new_with_test_dir(test_dir: Option<PathBuf>) -> Result<Self> {
-        let config = ProfileStorage::load_config_with_override(test_dir.clone())?;
-        Ok(ProfileManager { config, test_dir })
+        Self::new_with_location(test_dir, None)
+    }
+
+    /// Load profiles from `profiles_file` (default `profiles.json`) in the config dir
+    pub fn new_with_location(
+        config_dir: Option<PathBuf>,
+        profiles_file: Option<String>,
+    ) -> Result<Self> {
+        let config = ProfileStorage::load_config_with_override(
+            config_dir.clone(),
+            profiles_file.as_deref(),
+        )?;
+        Ok(ProfileManager {
+            config,
+            test_dir: config_dir,
+            profiles_file,
+            config_file: None,
+            normalize_scopes: true,
+        })
+    }
+
+    /// Load and save profiles at an explicit JSON/YAML file (`--config`) instead of
+    /// the config dir, which still holds tokens and other state
+    pub fn new_with_config_file(config_file: PathBuf, config_dir: Option<PathBuf>) -> Result<Self> {
+        let config = ProfileStorage::load_config_file(&config_file)?;
+        Ok(ProfileManager {
+            config,
+            test_dir: config_dir,
+            profiles_file: None,
+            config_file: Some(config_file),
+            normalize_scopes: true,
+        })
+    }
+
+    /// Config directory override shared with the other on-disk stores
+    pub fn config_dir_override(&self) -> Option<PathBuf> {
+        self.test_dir.clone()
+    }
+
+    /// Whether create/update dedupe the scope string (on by default)
+    pub fn set_normalize_scopes(&mut self, normalize: bool) {
+        self.normalize_scopes = normalize;
+    }
+
+    fn sanitize_scope(&self, scope: &str) -> String {
+        let scope = sanitize_input(scope);
+        if self.normalize_scopes {
+            normalize_scope(&scope)
+        } else {
+            scope
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
     }
 
     pub fn list_profiles(&self) -> Vec<&String> {
@@ -45,7 +104,7 @@ impl ProfileManager {
         let name = sanitize_input(&params.name);
         let client_id = sanitize_input(&params.client_id);
         let redirect_uri = sanitize_input(&params.redirect_uri);
-        let scope = sanitize_input(&params.scope);
+        let scope = self.sanitize_scope(&params.scope);
 
         let client_secret = params.client_secret.map(|s| sanitize_input(&s));
         let discovery_uri = params.discovery_uri.map(|s| sanitize_input(&s));
@@ -75,6 +134,7 @@ impl ProfileManager {
             scope,
             authorization_endpoint,
             token_endpoint,
+            ..Default::default()
         };
 
         self.config.add_profile(name, profile)?;
@@ -82,11 +142,26 @@ impl ProfileManager {
         Ok(())
     }
 
+    /// Save an already-built profile (e.g. from [`ProfileBuilder`](crate::profile::builder::ProfileBuilder))
+    /// under `name`
+    pub fn add_profile(&mut self, name: &str, profile: Profile) -> Result<()> {
+        let name = sanitize_input(name);
+        if name.is_empty() {
+            return Err(OidcError::Config(
+                "Profile name cannot be empty".to_string(),
+            ));
+        }
+
+        self.config.add_profile(name, profile)?;
+        self.save()?;
+        Ok(())
+    }
+
     pub fn update_profile(&mut self, params: ProfileParams) -> Result<()> {
         let name = sanitize_input(&params.name);
         let client_id = sanitize_input(&params.client_id);
         let redirect_uri = sanitize_input(&params.redirect_uri);
-        let scope = sanitize_input(&params.scope);
+        let scope = self.sanitize_scope(&params.scope);
 
         let client_secret = params.client_secret.map(|s| sanitize_input(&s));
         let discovery_uri = params.discovery_uri.map(|s| sanitize_input(&s));
@@ -102,15 +177,15 @@ impl ProfileManager {
             token_endpoint.as_deref(),
         )?;
 
-        let profile = Profile {
-            discovery_uri,
-            client_id,
-            client_secret,
-            redirect_uri,
-            scope,
-            authorization_endpoint,
-            token_endpoint,
-        };
+        // Start from the stored profile so settings not covered by the params survive an edit
+        let mut profile = self.config.get_profile(&name)?.clone();
+        profile.discovery_uri = discovery_uri;
+        profile.client_id = client_id;
+        profile.client_secret = client_secret;
+        profile.redirect_uri = redirect_uri;
+        profile.scope = scope;
+        profile.authorization_endpoint = authorization_endpoint;
+        profile.token_endpoint = token_endpoint;
 
         self.config.update_profile(name, profile)?;
         self.save()?;
@@ -141,6 +216,7 @@ impl ProfileManager {
         &self,
         file_path: &Path,
         profile_names: Option<Vec<String>>,
+        passphrase: Option<&str>,
     ) -> Result<()> {
         let export_config = if let Some(names) = profile_names {
             let mut filtered_config = Config::new();
@@ -153,11 +229,49 @@ impl ProfileManager {
             self.config.clone()
         };
 
-        ProfileStorage::export_config(&export_config, file_path)
+        ProfileStorage::export_config(&export_config, file_path, passphrase)
     }
 
-    pub fn import_profiles(&mut self, file_path: &Path, overwrite: bool) -> Result<Vec<String>> {
-        let imported_config = ProfileStorage::import_config(file_path)?;
+    /// Import profiles from a native export. Profiles carrying a `post_login_hook`
+    /// are refused unless `allow_hooks` is set, since the file may come from someone else.
+    pub fn import_profiles(
+        &mut self,
+        file_path: &Path,
+        overwrite: bool,
+        passphrase: Option<&str>,
+        allow_hooks: bool,
+    ) -> Result<Vec<String>> {
+        let imported_config = ProfileStorage::import_config(file_path, passphrase)?;
+        if !allow_hooks {
+            let mut hooked: Vec<&str> = imported_config
+                .profiles
+                .iter()
+                .filter(|(_, profile)| profile.post_login_hook.is_some())
+                .map(|(name, _)| name.as_str())
+                .collect();
+            if !hooked.is_empty() {
+                hooked.sort_unstable();
+                return Err(OidcError::Profile(format!(
+                    "Imported profile(s) {} run a post_login_hook; review the command and pass --allow-hook to import it",
+                    hooked.join(", ")
+                )));
+            }
+        }
+        self.merge_imported(imported_config, overwrite)
+    }
+
+    /// Import the OAuth settings of a Postman or Insomnia export
+    pub fn import_foreign_profiles(
+        &mut self,
+        file_path: &Path,
+        format: ImportFormat,
+        overwrite: bool,
+    ) -> Result<Vec<String>> {
+        let imported_config = read_foreign_config(file_path, format)?;
+        self.merge_imported(imported_config, overwrite)
+    }
+
+    fn merge_imported(&mut self, imported_config: Config, overwrite: bool) -> Result<Vec<String>> {
         let mut imported_names = Vec::new();
 
         for (name, profile) in imported_config.profiles {
@@ -180,6 +294,52 @@ impl ProfileManager {
         Ok(imported_names)
     }
 
+    pub fn required_scopes(&self) -> &[String] {
+        &self.config.required_scopes
+    }
+
+    pub fn claims_presets(&self) -> &HashMap<String, Vec<String>> {
+        &self.config.claims_presets
+    }
+
+    pub fn scope_presets(&self) -> &HashMap<String, String> {
+        &self.config.scope_presets
+    }
+
+    pub fn add_scope_preset(&mut self, name: &str, scope: &str) -> Result<()> {
+        let name = sanitize_input(name);
+        if name.is_empty() {
+            return Err(OidcError::Config(
+                "Scope preset name cannot be empty".to_string(),
+            ));
+        }
+
+        self.config.add_scope_preset(name, sanitize_input(scope))?;
+        self.save()
+    }
+
+    pub fn remove_scope_preset(&mut self, name: &str) -> Result<()> {
+        self.config.remove_scope_preset(name)?;
+        self.save()
+    }
+
+    /// Combine an optional preset with an explicit scope; `None` when neither is given
+    pub fn resolve_scope(
+        &self,
+        preset: Option<&str>,
+        scope: Option<&str>,
+    ) -> Result<Option<String>> {
+        let preset_scope = preset
+            .map(|name| self.config.get_scope_preset(name))
+            .transpose()?;
+
+        Ok(match (preset_scope, scope) {
+            (Some(preset_scope), Some(scope)) => Some(merge_scopes(preset_scope, scope)),
+            (Some(preset_scope), None) => Some(preset_scope.clone()),
+            (None, scope) => scope.map(str::to_string),
+        })
+    }
+
     pub fn has_profiles(&self) -> bool {
         !self.config.profiles.is_empty()
     }
@@ -193,7 +353,14 @@ impl ProfileManager {
     }
 
     fn save(&self) -> Result<()> {
-        ProfileStorage::save_config_with_override(&self.config, self.test_dir.clone())
+        if let Some(config_file) = &self.config_file {
+            return ProfileStorage::save_config_file(&self.config, config_file);
+        }
+        ProfileStorage::save_config_with_override(
+            &self.config,
+            self.test_dir.clone(),
+            self.profiles_file.as_deref(),
+        )
     }
 }
 
@@ -202,6 +369,9 @@ impl Clone for ProfileManager {
         ProfileManager {
             config: self.config.clone(),
             test_dir: self.test_dir.clone(),
+            profiles_file: self.profiles_file.clone(),
+            config_file: self.config_file.clone(),
+            normalize_scopes: self.normalize_scopes,
         }
     }
 }
@@ -218,9 +388,73 @@ mod tests {
         ProfileManager {
             config: Config::new(),
             test_dir: Some(temp_path),
+            profiles_file: None,
+            config_file: None,
+            normalize_scopes: true,
         }
     }
 
+    fn params_with_scope(name: &str, scope: &str) -> ProfileParams {
+        ProfileParams {
+            name: name.to_string(),
+            client_id: "client".to_string(),
+            client_secret: None,
+            redirect_uri: "http://localhost:8080/callback".to_string(),
+            scope: scope.to_string(),
+            discovery_uri: Some("https://example.com/.well-known/openid-configuration".to_string()),
+            authorization_endpoint: None,
+            token_endpoint: None,
+        }
+    }
+
+    #[test]
+    fn test_scopes_normalized_on_save() {
+        let mut manager = create_test_profile_manager();
+        manager
+            .create_profile(params_with_scope("dev", "openid openid profile"))
+            .unwrap();
+        assert_eq!(manager.get_profile("dev").unwrap().scope, "openid profile");
+
+        manager
+            .update_profile(params_with_scope("dev", "email  openid email"))
+            .unwrap();
+        assert_eq!(manager.get_profile("dev").unwrap().scope, "email openid");
+
+        manager.set_normalize_scopes(false);
+        manager
+            .update_profile(params_with_scope("dev", "openid openid profile"))
+            .unwrap();
+        assert_eq!(
+            manager.get_profile("dev").unwrap().scope,
+            "openid openid profile"
+        );
+    }
+
+    #[test]
+    fn test_resolve_scope_with_preset() {
+        let mut manager = create_test_profile_manager();
+        manager
+            .add_scope_preset("api", "openid api:read api:write")
+            .unwrap();
+
+        assert_eq!(
+            manager.resolve_scope(Some("api"), None).unwrap().as_deref(),
+            Some("openid api:read api:write")
+        );
+        assert_eq!(
+            manager
+                .resolve_scope(Some("api"), Some("email openid"))
+                .unwrap()
+                .as_deref(),
+            Some("openid api:read api:write email")
+        );
+        assert_eq!(manager.resolve_scope(None, None).unwrap(), None);
+        assert!(manager.resolve_scope(Some("missing"), None).is_err());
+
+        manager.remove_scope_preset("api").unwrap();
+        assert!(manager.scope_presets().is_empty());
+    }
+
     #[test]
     fn test_create_profile() {
         let mut manager = create_test_profile_manager();
@@ -240,6 +474,58 @@ mod tests {
         assert!(manager.get_profile("test").is_ok());
     }
 
+    #[test]
+    fn test_add_built_profile() {
+        let mut manager = create_test_profile_manager();
+        let profile = crate::profile::builder::ProfileBuilder::new()
+            .client_id("built-client")
+            .redirect_uri("http://localhost:8080/callback")
+            .scope("openid")
+            .discovery_uri("https://example.com/.well-known/openid-configuration")
+            .build()
+            .unwrap();
+
+        manager.add_profile("built", profile.clone()).unwrap();
+        assert_eq!(
+            manager.get_profile("built").unwrap().client_id,
+            "built-client"
+        );
+        assert!(manager.add_profile("built", profile.clone()).is_err());
+        assert!(manager.add_profile(" ", profile).is_err());
+    }
+
+    #[test]
+    fn test_import_refuses_hooks_without_opt_in() {
+        let mut source = create_test_profile_manager();
+        source
+            .create_profile(params_with_scope("plain", "openid"))
+            .unwrap();
+        let mut hooked = source.get_profile("plain").unwrap().clone();
+        hooked.post_login_hook = Some("./deploy.sh".to_string());
+        source.add_profile("hooked", hooked).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("export.json");
+        source.export_profiles(&file, None, None).unwrap();
+
+        let mut target = create_test_profile_manager();
+        let err = target
+            .import_profiles(&file, false, None, false)
+            .unwrap_err();
+        assert!(err.to_string().contains("hooked"), "{err}");
+        assert!(target.list_profiles().is_empty());
+
+        target.import_profiles(&file, false, None, true).unwrap();
+        assert_eq!(
+            target
+                .get_profile("hooked")
+                .unwrap()
+                .post_login_hook
+                .as_deref(),
+            Some("./deploy.sh")
+        );
+    }
+
     #[test]
     fn test_create_duplicate_profile() {
         let mut manager = create_test_profile_manager();
@@ -321,4 +607,35 @@ mod tests {
         assert!(manager.get_profile("test").is_err());
         assert!(manager.get_profile("new-test").is_ok());
     }
+
+    #[test]
+    fn test_explicit_config_file_is_used_for_save() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_file = temp_dir.path().join("project/oidc.yml");
+        let state_dir = temp_dir.path().join("state");
+
+        let mut manager =
+            ProfileManager::new_with_config_file(config_file.clone(), Some(state_dir.clone()))
+                .unwrap();
+        assert!(manager.list_profiles().is_empty());
+        manager
+            .create_profile(ProfileParams {
+                name: "test".to_string(),
+                client_id: "test-client".to_string(),
+                client_secret: None,
+                redirect_uri: "http://localhost:8080/callback".to_string(),
+                scope: "openid".to_string(),
+                discovery_uri: Some(
+                    "https://example.com/.well-known/openid-configuration".to_string(),
+                ),
+                authorization_endpoint: None,
+                token_endpoint: None,
+            })
+            .unwrap();
+
+        assert!(config_file.exists());
+        assert!(!state_dir.join("profiles.json").exists());
+        let reloaded = ProfileManager::new_with_config_file(config_file, Some(state_dir)).unwrap();
+        assert!(reloaded.get_profile("test").is_ok());
+    }
 }
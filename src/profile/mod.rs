@@ -1,4 +1,8 @@
+pub mod builder;
+pub mod diff;
+pub mod foreign;
 pub mod manager;
+pub mod share;
 pub mod storage;
 pub mod validation;
 pub use manager::*;
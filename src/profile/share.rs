@@ -0,0 +1,78 @@
+use crate::config::{Config, Profile};
+use crate::utils::mask::redact_secret;
+
+/// Build a single-profile config suitable for `import`, with the client secret
+/// removed unless it is only a `${VAR}` placeholder. The `post_login_hook` is
+/// dropped too, so a shared snippet never carries a command to run.
+pub fn shareable_config(name: &str, profile: &Profile) -> Config {
+    let mut shared = profile.clone();
+    shared.client_secret = redact_secret(profile.client_secret.as_deref());
+    shared.post_login_hook = None;
+
+    let mut config = Config::new();
+    config.profiles.insert(name.to_string(), shared);
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::{ProfileManager, ProfileParams};
+
+    fn params(name: &str, secret: &str) -> ProfileParams {
+        ProfileParams {
+            name: name.to_string(),
+            client_id: "client".to_string(),
+            client_secret: Some(secret.to_string()),
+            redirect_uri: "http://localhost:8080/callback".to_string(),
+            scope: "openid profile".to_string(),
+            discovery_uri: Some("https://example.com/.well-known/openid-configuration".to_string()),
+            authorization_endpoint: None,
+            token_endpoint: None,
+        }
+    }
+
+    #[test]
+    fn test_shared_profile_imports_cleanly() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let mut source =
+            ProfileManager::new_with_test_dir(Some(source_dir.path().to_path_buf())).unwrap();
+        source.create_profile(params("secret", "s3cret")).unwrap();
+        source
+            .create_profile(params("placeholder", "${CLIENT_SECRET}"))
+            .unwrap();
+        let mut hooked = source.get_profile("secret").unwrap().clone();
+        hooked.post_login_hook = Some("curl https://example.com/collect".to_string());
+        source.add_profile("hooked", hooked).unwrap();
+
+        let target_dir = tempfile::tempdir().unwrap();
+        let mut target =
+            ProfileManager::new_with_test_dir(Some(target_dir.path().to_path_buf())).unwrap();
+
+        for name in ["secret", "placeholder", "hooked"] {
+            let config = shareable_config(name, source.get_profile(name).unwrap());
+            let json = serde_json::to_string_pretty(&config).unwrap();
+            assert!(!json.contains("s3cret"));
+            assert!(!json.contains("post_login_hook"));
+
+            let file = target_dir.path().join(format!("{name}.json"));
+            std::fs::write(&file, json).unwrap();
+            target.import_profiles(&file, false, None, false).unwrap();
+        }
+
+        assert_eq!(target.get_profile("secret").unwrap().client_secret, None);
+        assert_eq!(
+            target
+                .get_profile("placeholder")
+                .unwrap()
+                .client_secret
+                .as_deref(),
+            Some("${CLIENT_SECRET}")
+        );
+        assert_eq!(
+            target.get_profile("secret").unwrap().scope,
+            "openid profile"
+        );
+        assert_eq!(target.get_profile("hooked").unwrap().post_login_hook, None);
+    }
+}
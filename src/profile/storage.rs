@@ -1,31 +1,54 @@
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 
-use crate::config::{get_config_dir_with_override, get_config_file_path_with_override, Config};
+use crate::config::{get_config_file_path_with_override, Config};
+use crate::crypto::EncryptedBlob;
 use crate::error::{OidcError, Result};
+use crate::profile::validation::validate_scope_syntax;
+use crate::utils::fs::{create_secure_file, ensure_safe_write_target, write_atomic, FileLock};
 
 pub struct ProfileStorage;
 
+fn is_yaml_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+}
+
 impl ProfileStorage {
-    pub fn load_config_with_override(override_dir: Option<PathBuf>) -> Result<Config> {
-        let config_path = get_config_file_path_with_override(override_dir)?;
+    pub fn load_config_with_override(
+        override_dir: Option<PathBuf>,
+        profiles_file: Option<&str>,
+    ) -> Result<Config> {
+        let config_path = get_config_file_path_with_override(override_dir, profiles_file)?;
+        Self::load_config_file(&config_path)
+    }
 
+    /// Load a profiles file at an explicit path (`--config`); a missing file is an
+    /// empty config. Files ending in `.yaml`/`.yml` are YAML, anything else JSON.
+    pub fn load_config_file(config_path: &Path) -> Result<Config> {
         if !config_path.exists() {
             return Ok(Config::new());
         }
 
-        let content = fs::read_to_string(&config_path)
+        let content = fs::read_to_string(config_path)
             .map_err(|e| OidcError::Profile(format!("Failed to read config file: {e}")))?;
 
         if content.trim().is_empty() {
             return Ok(Config::new());
         }
 
-        let config: Config = serde_json::from_str(&content)
-            .map_err(|e| OidcError::Profile(format!("Failed to parse config file: {e}")))?;
+        let config: Config = if is_yaml_path(config_path) {
+            serde_yaml::from_str(&content)
+                .map_err(|e| OidcError::Profile(format!("Failed to parse config file: {e}")))?
+        } else {
+            serde_json::from_str(&content)
+                .map_err(|e| OidcError::Profile(format!("Failed to parse config file: {e}")))?
+        };
 
         for (name, profile) in &config.profiles {
             profile
@@ -33,35 +56,72 @@ impl ProfileStorage {
                 .map_err(|e| OidcError::Profile(format!("Invalid profile '{name}': {e}")))?;
         }
 
+        for (name, scope) in &config.scope_presets {
+            validate_scope_syntax(scope)
+                .map_err(|e| OidcError::Profile(format!("Invalid scope preset '{name}': {e}")))?;
+        }
+
+        for scope in &config.required_scopes {
+            validate_scope_syntax(scope).map_err(|e| {
+                OidcError::Profile(format!("Invalid required scope '{scope}': {e}"))
+            })?;
+        }
+
         Ok(config)
     }
 
-    pub fn save_config_with_override(config: &Config, override_dir: Option<PathBuf>) -> Result<()> {
-        let config_dir = get_config_dir_with_override(override_dir.clone())?;
-        let config_path = get_config_file_path_with_override(override_dir)?;
+    pub fn save_config_with_override(
+        config: &Config,
+        override_dir: Option<PathBuf>,
+        profiles_file: Option<&str>,
+    ) -> Result<()> {
+        let config_path = get_config_file_path_with_override(override_dir, profiles_file)?;
+        Self::save_config_file(config, &config_path)
+    }
 
-        if !config_dir.exists() {
-            fs::create_dir_all(&config_dir).map_err(|e| {
+    /// Save to an explicit path in the format its extension selects, creating
+    /// missing parent directories
+    pub fn save_config_file(config: &Config, config_path: &Path) -> Result<()> {
+        if let Some(config_dir) = config_path.parent().filter(|dir| !dir.exists()) {
+            fs::create_dir_all(config_dir).map_err(|e| {
                 OidcError::Profile(format!("Failed to create config directory: {e}"))
             })?;
         }
 
-        let json = serde_json::to_string_pretty(config)
-            .map_err(|e| OidcError::Profile(format!("Failed to serialize config: {e}")))?;
+        let content = if is_yaml_path(config_path) {
+            serde_yaml::to_string(config)
+                .map_err(|e| OidcError::Profile(format!("Failed to serialize config: {e}")))?
+        } else {
+            serde_json::to_string_pretty(config)
+                .map_err(|e| OidcError::Profile(format!("Failed to serialize config: {e}")))?
+        };
 
-        fs::write(&config_path, json)
+        ensure_safe_write_target(config_path)?;
+        let _lock = FileLock::exclusive(config_path)?;
+        write_atomic(config_path, content.as_bytes())
             .map_err(|e| OidcError::Profile(format!("Failed to write config file: {e}")))?;
 
-        Self::set_secure_permissions(&config_path)?;
-
         Ok(())
     }
 
-    pub fn export_config(config: &Config, file_path: &Path) -> Result<()> {
-        let json = serde_json::to_string_pretty(config)
+    /// Write `config` as JSON, encrypted with `passphrase` when one is given
+    pub fn export_config(
+        config: &Config,
+        file_path: &Path,
+        passphrase: Option<&str>,
+    ) -> Result<()> {
+        let mut json = serde_json::to_string_pretty(config)
             .map_err(|e| OidcError::Profile(format!("Failed to serialize config: {e}")))?;
 
-        fs::write(file_path, json)
+        if let Some(passphrase) = passphrase {
+            let blob = EncryptedBlob::seal(json.as_bytes(), passphrase)?;
+            json = serde_json::to_string_pretty(&blob)
+                .map_err(|e| OidcError::Profile(format!("Failed to serialize export: {e}")))?;
+        }
+
+        ensure_safe_write_target(file_path)?;
+        create_secure_file(file_path)?
+            .write_all(json.as_bytes())
             .map_err(|e| OidcError::Profile(format!("Failed to write export file: {e}")))?;
 
         Self::set_secure_permissions(file_path)?;
@@ -69,16 +129,35 @@ impl ProfileStorage {
         Ok(())
     }
 
-    pub fn import_config(file_path: &Path) -> Result<Config> {
+    /// Whether an export file was written with `export --encrypt`
+    pub fn is_encrypted_export(file_path: &Path) -> Result<bool> {
+        let content = fs::read_to_string(file_path)
+            .map_err(|e| OidcError::Profile(format!("Failed to read import file: {e}")))?;
+        Ok(EncryptedBlob::detect(&content).is_some())
+    }
+
+    /// Read an export file, decrypting it with `passphrase` if it is encrypted
+    pub fn import_config(file_path: &Path, passphrase: Option<&str>) -> Result<Config> {
         if !file_path.exists() {
             return Err(OidcError::Profile(format!(
                 "Import file not found: {file_path:?}"
             )));
         }
 
-        let content = fs::read_to_string(file_path)
+        let mut content = fs::read_to_string(file_path)
             .map_err(|e| OidcError::Profile(format!("Failed to read import file: {e}")))?;
 
+        if let Some(blob) = EncryptedBlob::detect(&content) {
+            let passphrase = passphrase.ok_or_else(|| {
+                OidcError::Profile(format!(
+                    "Import file {file_path:?} is encrypted; a passphrase is required"
+                ))
+            })?;
+            content = String::from_utf8(blob.open(passphrase)?).map_err(|_| {
+                OidcError::Profile("Decrypted import file is not valid UTF-8".to_string())
+            })?;
+        }
+
         let config: Config = serde_json::from_str(&content)
             .map_err(|e| OidcError::Profile(format!("Failed to parse import file: {e}")))?;
 
@@ -127,6 +206,7 @@ mod tests {
             scope: "openid profile email".to_string(),
             authorization_endpoint: None,
             token_endpoint: None,
+            ..Default::default()
         };
         config.profiles.insert("test".to_string(), profile);
         config
@@ -139,10 +219,10 @@ mod tests {
 
         let original_config = create_test_config();
 
-        ProfileStorage::export_config(&original_config, &export_path).unwrap();
+        ProfileStorage::export_config(&original_config, &export_path, None).unwrap();
         assert!(export_path.exists());
 
-        let imported_config = ProfileStorage::import_config(&export_path).unwrap();
+        let imported_config = ProfileStorage::import_config(&export_path, None).unwrap();
 
         assert_eq!(
             original_config.profiles.len(),
@@ -151,12 +231,110 @@ mod tests {
         assert!(imported_config.profiles.contains_key("test"));
     }
 
+    #[test]
+    fn test_encrypted_export_import_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let export_path = temp_dir.path().join("bundle.json");
+
+        ProfileStorage::export_config(&create_test_config(), &export_path, Some("s3cret")).unwrap();
+        let content = fs::read_to_string(&export_path).unwrap();
+        assert!(!content.contains("test-secret"));
+        assert!(ProfileStorage::is_encrypted_export(&export_path).unwrap());
+
+        assert!(ProfileStorage::import_config(&export_path, None).is_err());
+        assert!(ProfileStorage::import_config(&export_path, Some("wrong")).is_err());
+
+        let imported = ProfileStorage::import_config(&export_path, Some("s3cret")).unwrap();
+        assert_eq!(
+            imported.profiles["test"].client_secret.as_deref(),
+            Some("test-secret")
+        );
+    }
+
+    #[test]
+    fn test_custom_profiles_file_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let dir = Some(temp_dir.path().to_path_buf());
+        let config = create_test_config();
+
+        ProfileStorage::save_config_with_override(&config, dir.clone(), Some("work.json")).unwrap();
+        assert!(temp_dir.path().join("work.json").exists());
+        assert!(!temp_dir.path().join("profiles.json").exists());
+
+        let loaded =
+            ProfileStorage::load_config_with_override(dir.clone(), Some("work.json")).unwrap();
+        assert!(loaded.profiles.contains_key("test"));
+
+        let other =
+            ProfileStorage::load_config_with_override(dir.clone(), Some("personal.json")).unwrap();
+        assert!(other.profiles.is_empty());
+
+        let export_path = temp_dir.path().join("export.json");
+        ProfileStorage::export_config(&loaded, &export_path, None).unwrap();
+        let imported = ProfileStorage::import_config(&export_path, None).unwrap();
+        ProfileStorage::save_config_with_override(&imported, dir.clone(), Some("personal.json"))
+            .unwrap();
+        let personal =
+            ProfileStorage::load_config_with_override(dir, Some("personal.json")).unwrap();
+        assert!(personal.profiles.contains_key("test"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_targets_are_not_written() {
+        let temp_dir = tempdir().unwrap();
+        let victim = temp_dir.path().join("victim");
+        fs::write(&victim, "untouched").unwrap();
+        let config = create_test_config();
+
+        let export_link = temp_dir.path().join("export.json");
+        std::os::unix::fs::symlink(&victim, &export_link).unwrap();
+        let err = ProfileStorage::export_config(&config, &export_link, None).unwrap_err();
+        assert!(err.to_string().contains("symbolic link"), "{err}");
+
+        let config_link = temp_dir.path().join("profiles.json");
+        std::os::unix::fs::symlink(&victim, &config_link).unwrap();
+        assert!(ProfileStorage::save_config_file(&config, &config_link).is_err());
+
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "untouched");
+        assert!(fs::symlink_metadata(&config_link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+    }
+
+    #[test]
+    fn test_explicit_config_file_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let config = create_test_config();
+
+        for name in ["dotfiles/oidc.json", "repo/oidc.yaml"] {
+            let path = temp_dir.path().join(name);
+            assert!(ProfileStorage::load_config_file(&path)
+                .unwrap()
+                .profiles
+                .is_empty());
+
+            ProfileStorage::save_config_file(&config, &path).unwrap();
+            let loaded = ProfileStorage::load_config_file(&path).unwrap();
+            assert_eq!(
+                loaded.profiles["test"].client_id,
+                config.profiles["test"].client_id
+            );
+        }
+
+        let yaml = fs::read_to_string(temp_dir.path().join("repo/oidc.yaml")).unwrap();
+        assert!(yaml.contains("client_id: test-client"), "{yaml}");
+        // Nothing is written to the default config directory layout
+        assert!(!temp_dir.path().join("profiles.json").exists());
+    }
+
     #[test]
     fn test_import_nonexistent_file() {
         let temp_dir = tempdir().unwrap();
         let nonexistent_path = temp_dir.path().join("nonexistent.json");
 
-        let result = ProfileStorage::import_config(&nonexistent_path);
+        let result = ProfileStorage::import_config(&nonexistent_path, None);
         assert!(result.is_err());
     }
 }
@@ -1,4 +1,6 @@
+use crate::crypto::PkceMethod;
 use crate::error::{OidcError, Result};
+use crate::settings;
 use url::Url;
 
 pub fn validate_profile_input(
@@ -50,7 +52,17 @@ pub fn validate_client_id(client_id: &str) -> Result<()> {
     Ok(())
 }
 
+/// Validate with loopback aliases allowed only under `--allow-insecure-localhost-http`
 pub fn validate_redirect_uri(redirect_uri: &str) -> Result<()> {
+    validate_redirect_uri_with(redirect_uri, settings::get().allow_insecure_localhost_http)
+}
+
+/// Redirect URIs must use https unless they point at the loopback interface
+/// (`localhost`, `127.0.0.0/8`, `::1`). `*.localhost` hosts, which only behave like
+/// loopback, additionally need `allow_loopback_aliases`. The wildcard bind addresses
+/// `0.0.0.0` and `::` are refused, since a browser redirect to them need not reach
+/// the local listener.
+pub fn validate_redirect_uri_with(redirect_uri: &str, allow_loopback_aliases: bool) -> Result<()> {
     if redirect_uri.is_empty() {
         return Err(OidcError::Config(
             "Redirect URI cannot be empty".to_string(),
@@ -60,29 +72,186 @@ pub fn validate_redirect_uri(redirect_uri: &str) -> Result<()> {
     let url = Url::parse(redirect_uri)
         .map_err(|_| OidcError::InvalidRedirectUri(redirect_uri.to_string()))?;
 
+    let host = url.host().ok_or_else(|| {
+        OidcError::InvalidRedirectUri("Redirect URI must have a valid host".to_string())
+    })?;
+
+    if is_wildcard_address(&host) {
+        return Err(OidcError::InvalidRedirectUri(format!(
+            "{redirect_uri}: '{host}' is a wildcard bind address, not a loopback address; \
+             use localhost, 127.0.0.1 or [::1]"
+        )));
+    }
+
     match url.scheme() {
-        "http" | "https" => {
-            if url.host_str().is_none() {
-                return Err(OidcError::InvalidRedirectUri(
-                    "Redirect URI must have a valid host".to_string(),
-                ));
+        "https" => Ok(()),
+        "http" if is_loopback_host(&host) => Ok(()),
+        "http" if is_loopback_alias(&host) => {
+            if allow_loopback_aliases {
+                Ok(())
+            } else {
+                Err(OidcError::InvalidRedirectUri(format!(
+                    "{redirect_uri}: '{host}' is not a loopback address; use localhost, \
+                     127.0.0.1 or [::1], or pass --allow-insecure-localhost-http"
+                )))
             }
         }
-        _ => {
-            return Err(OidcError::InvalidRedirectUri(
-                "Redirect URI must use http or https scheme".to_string(),
-            ));
+        "http" => Err(OidcError::InvalidRedirectUri(format!(
+            "{redirect_uri}: plain http is only allowed for loopback redirect URIs; use https"
+        ))),
+        _ => Err(OidcError::InvalidRedirectUri(
+            "Redirect URI must use http or https scheme".to_string(),
+        )),
+    }
+}
+
+/// Set (to any value) to enforce OAuth 2.1 rules, like `--oauth21`
+pub const OAUTH21_ENV: &str = "OIDC_CLI_OAUTH21";
+
+/// Whether `--oauth21` (or [`OAUTH21_ENV`]) is in effect
+pub fn oauth21_enforced() -> bool {
+    settings::get().oauth21
+}
+
+/// OAuth 2.1 only allows plain http on the loopback interface; `what` names the
+/// URL in the error, e.g. "token endpoint"
+pub fn validate_oauth21_url(url: &str, what: &str) -> Result<()> {
+    let parsed =
+        Url::parse(url).map_err(|_| OidcError::Config(format!("Invalid {what}: {url}")))?;
+    let loopback = parsed.host().is_some_and(|host| is_loopback_host(&host));
+    match parsed.scheme() {
+        "https" => {}
+        "http" if loopback => {}
+        scheme => {
+            return Err(OidcError::Config(format!(
+                "OAuth 2.1 mode: {what} {url} must use https ({scheme} is only allowed for loopback addresses)"
+            )))
         }
     }
+    if parsed.fragment().is_some() {
+        return Err(OidcError::Config(format!(
+            "OAuth 2.1 mode: {what} {url} must not contain a fragment"
+        )));
+    }
+    Ok(())
+}
+
+/// OAuth 2.1 requires exact redirect URI matching, so the URI sent to the provider
+/// must be the one the callback is received on
+pub fn validate_oauth21_redirect_match(sent: &str, callback: &str) -> Result<()> {
+    if sent != callback {
+        return Err(OidcError::Config(format!(
+            "OAuth 2.1 mode: redirect URI {sent} must exactly match the callback URI {callback}"
+        )));
+    }
+    Ok(())
+}
 
+/// OAuth 2.1 only allows S256, so a provider that offers nothing but plain is refused
+/// rather than negotiated down to it
+pub fn validate_oauth21_pkce_method(method: PkceMethod) -> Result<()> {
+    if method == PkceMethod::Plain {
+        return Err(OidcError::Config(
+            "OAuth 2.1 mode: the provider does not offer the S256 PKCE method".to_string(),
+        ));
+    }
     Ok(())
 }
 
+fn is_loopback_host(host: &url::Host<&str>) -> bool {
+    match host {
+        url::Host::Domain(domain) => domain.eq_ignore_ascii_case("localhost"),
+        url::Host::Ipv4(addr) => addr.is_loopback(),
+        url::Host::Ipv6(addr) => addr.is_loopback(),
+    }
+}
+
+fn is_loopback_alias(host: &url::Host<&str>) -> bool {
+    matches!(host, url::Host::Domain(domain) if domain.to_ascii_lowercase().ends_with(".localhost"))
+}
+
+fn is_wildcard_address(host: &url::Host<&str>) -> bool {
+    match host {
+        url::Host::Domain(_) => false,
+        url::Host::Ipv4(addr) => addr.is_unspecified(),
+        url::Host::Ipv6(addr) => addr.is_unspecified(),
+    }
+}
+
+/// Default upper bound on the whole scope string, well below common URL length limits
+pub const DEFAULT_MAX_SCOPE_LENGTH: usize = 2000;
+
+/// Default upper bound on the number of scope values
+pub const DEFAULT_MAX_SCOPE_COUNT: usize = 100;
+
+/// Environment variable overriding [`DEFAULT_MAX_SCOPE_LENGTH`]
+pub const MAX_SCOPE_LENGTH_ENV: &str = "OIDC_CLI_MAX_SCOPE_LENGTH";
+
+/// Environment variable overriding [`DEFAULT_MAX_SCOPE_COUNT`]
+pub const MAX_SCOPE_COUNT_ENV: &str = "OIDC_CLI_MAX_SCOPE_COUNT";
+
+/// Size limits applied to scope strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScopeLimits {
+    pub max_length: usize,
+    pub max_count: usize,
+}
+
+impl Default for ScopeLimits {
+    fn default() -> Self {
+        Self {
+            max_length: DEFAULT_MAX_SCOPE_LENGTH,
+            max_count: DEFAULT_MAX_SCOPE_COUNT,
+        }
+    }
+}
+
+impl ScopeLimits {
+    /// The defaults, each overridden by its environment variable when set to a valid number
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: usize| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.trim().parse().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            max_length: read(MAX_SCOPE_LENGTH_ENV, DEFAULT_MAX_SCOPE_LENGTH),
+            max_count: read(MAX_SCOPE_COUNT_ENV, DEFAULT_MAX_SCOPE_COUNT),
+        }
+    }
+}
+
 pub fn validate_scope(scope: &str) -> Result<()> {
+    validate_scope_with_limits(scope, ScopeLimits::from_env())
+}
+
+/// Check scope values without the size limits. Used when loading a config, so that
+/// a stricter limit set later can't lock the user out of profiles they already have;
+/// the limits apply when a scope is created, edited or logged in with.
+pub fn validate_scope_syntax(scope: &str) -> Result<()> {
+    validate_scope_with_limits(
+        scope,
+        ScopeLimits {
+            max_length: usize::MAX,
+            max_count: usize::MAX,
+        },
+    )
+}
+
+pub fn validate_scope_with_limits(scope: &str, limits: ScopeLimits) -> Result<()> {
     if scope.is_empty() {
         return Err(OidcError::Config("Scope cannot be empty".to_string()));
     }
 
+    if scope.len() > limits.max_length {
+        return Err(OidcError::Config(format!(
+            "Scope is {} characters long; the limit is {} ({MAX_SCOPE_LENGTH_ENV} raises it)",
+            scope.len(),
+            limits.max_length
+        )));
+    }
+
     let scopes: Vec<&str> = scope.split_whitespace().collect();
     if scopes.is_empty() {
         return Err(OidcError::Config(
@@ -90,6 +259,14 @@ pub fn validate_scope(scope: &str) -> Result<()> {
         ));
     }
 
+    if scopes.len() > limits.max_count {
+        return Err(OidcError::Config(format!(
+            "Scope has {} values; the limit is {} ({MAX_SCOPE_COUNT_ENV} raises it)",
+            scopes.len(),
+            limits.max_count
+        )));
+    }
+
     for scope_value in scopes {
         if scope_value.is_empty() {
             return Err(OidcError::Config(
@@ -171,6 +348,34 @@ pub fn validate_endpoint_configuration(
     Ok(())
 }
 
+/// `grant_type` of the default browser-based flow
+pub const AUTHORIZATION_CODE_GRANT: &str = "authorization_code";
+
+/// `grant_type` for machine-to-machine tokens without a user
+pub const CLIENT_CREDENTIALS_GRANT: &str = "client_credentials";
+
+/// A client credentials profile authenticates with its secret at the token
+/// endpoint, so it needs both but no authorization endpoint or redirect URI
+pub fn validate_client_credentials_configuration(
+    client_secret: Option<&str>,
+    discovery_uri: Option<&str>,
+    token_endpoint: Option<&str>,
+) -> Result<()> {
+    if client_secret.is_none_or(str::is_empty) {
+        return Err(OidcError::Config(
+            "The client_credentials grant requires a client secret".to_string(),
+        ));
+    }
+
+    if discovery_uri.is_none() && token_endpoint.is_none() {
+        return Err(OidcError::Config(
+            "The client_credentials grant requires a discovery URI or token endpoint".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
 pub fn sanitize_input(input: &str) -> String {
     input.trim().to_string()
 }
@@ -196,6 +401,45 @@ mod tests {
         assert!(validate_redirect_uri("ftp://example.com/callback").is_err());
     }
 
+    #[test]
+    fn test_remote_http_redirect_rejected() {
+        for uri in [
+            "http://example.com/callback",
+            "http://192.168.1.10:8080/callback",
+            "http://[2001:db8::1]/callback",
+            "http://localhost.example.com/callback",
+        ] {
+            assert!(validate_redirect_uri_with(uri, false).is_err(), "{uri}");
+            assert!(validate_redirect_uri_with(uri, true).is_err(), "{uri}");
+        }
+        assert!(validate_redirect_uri_with("https://192.168.1.10/callback", false).is_ok());
+    }
+
+    #[test]
+    fn test_loopback_http_redirect_accepted() {
+        for uri in [
+            "http://localhost:8080/callback",
+            "http://LOCALHOST/callback",
+            "http://127.0.0.1:8080/callback",
+            "http://127.8.9.10/callback",
+            "http://[::1]:8080/callback",
+        ] {
+            assert!(validate_redirect_uri_with(uri, false).is_ok(), "{uri}");
+        }
+    }
+
+    #[test]
+    fn test_loopback_aliases_need_override() {
+        let uri = "http://app.localhost:8080/callback";
+        assert!(validate_redirect_uri_with(uri, false).is_err());
+        assert!(validate_redirect_uri_with(uri, true).is_ok());
+
+        for uri in ["http://0.0.0.0:8080/callback", "http://[::]:8080/callback"] {
+            let err = validate_redirect_uri_with(uri, true).unwrap_err();
+            assert!(err.to_string().contains("wildcard bind address"), "{err}");
+        }
+    }
+
     #[test]
     fn test_validate_scope() {
         assert!(validate_scope("openid profile email").is_ok());
@@ -205,6 +449,53 @@ mod tests {
         assert!(validate_scope("invalid scope!").is_err());
     }
 
+    #[test]
+    fn test_validate_scope_limits() {
+        let limits = ScopeLimits {
+            max_length: 20,
+            max_count: 3,
+        };
+        // Exactly at each limit passes, one past fails
+        assert!(validate_scope_with_limits(&"a".repeat(20), limits).is_ok());
+        assert!(validate_scope_with_limits(&"a".repeat(21), limits).is_err());
+        assert!(validate_scope_with_limits("a b c", limits).is_ok());
+        let err = validate_scope_with_limits("a b c d", limits).unwrap_err();
+        assert!(err.to_string().contains("4 values"), "{err}");
+
+        let defaults = ScopeLimits::default();
+        let many: Vec<String> = (0..DEFAULT_MAX_SCOPE_COUNT)
+            .map(|i| format!("s{i}"))
+            .collect();
+        assert!(validate_scope_with_limits(&many.join(" "), defaults).is_ok());
+        let too_many = format!("{} extra", many.join(" "));
+        assert!(validate_scope_with_limits(&too_many, defaults).is_err());
+        let long = format!("openid {}", "x".repeat(DEFAULT_MAX_SCOPE_LENGTH));
+        assert!(validate_scope_with_limits(&long, defaults).is_err());
+
+        // Loading a config checks only the values themselves
+        assert!(validate_scope_syntax(&too_many).is_ok());
+        assert!(validate_scope_syntax(&long).is_ok());
+        assert!(validate_scope_syntax("openid bad!").is_err());
+    }
+
+    #[test]
+    fn test_validate_oauth21_redirect_match() {
+        let callback = "http://localhost:8080/callback";
+        assert!(validate_oauth21_redirect_match(callback, callback).is_ok());
+        assert!(
+            validate_oauth21_redirect_match("http://localhost:8080/callback/", callback).is_err()
+        );
+        assert!(validate_oauth21_redirect_match("https://proxy.example.com/cb", callback).is_err());
+        assert!(validate_oauth21_url("myapp://callback", "redirect URI").is_err());
+        assert!(validate_oauth21_url("https://app.example.com/cb#frag", "redirect URI").is_err());
+    }
+
+    #[test]
+    fn test_validate_oauth21_pkce_method() {
+        assert!(validate_oauth21_pkce_method(PkceMethod::S256).is_ok());
+        assert!(validate_oauth21_pkce_method(PkceMethod::Plain).is_err());
+    }
+
     #[test]
     fn test_validate_discovery_uri() {
         assert!(
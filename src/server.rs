@@ -3,12 +3,15 @@ use hyper::{Body, Method, Request, Response, Server, StatusCode};
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::net::SocketAddr;
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, Notify, RwLock};
 use url::Url;
 
 use crate::auth::TokenResponse;
-use crate::error::Result;
+use crate::error::{OidcError, Result};
+use crate::utils::url::parse_query_params;
 
 pub fn extract_path_from_redirect_uri(redirect_uri: &str) -> String {
     if let Ok(url) = Url::parse(redirect_uri) {
@@ -23,8 +26,66 @@ pub struct CallbackResult {
     pub state: String,
     pub error: Option<String>,
     pub error_description: Option<String>,
-    #[allow(dead_code)]
+    /// Access token delivered in the query by the `code token` hybrid flow
     pub access_token: Option<String>,
+    pub token_type: Option<String>,
+    pub expires_in: Option<u64>,
+    pub session_state: Option<String>,
+}
+
+impl CallbackResult {
+    /// The access token from the callback query, if the provider sent one
+    pub fn query_token(&self) -> Option<TokenResponse> {
+        let access_token = self.access_token.clone()?;
+        Some(TokenResponse {
+            access_token,
+            token_type: self
+                .token_type
+                .clone()
+                .unwrap_or_else(|| "Bearer".to_string()),
+            expires_in: self.expires_in,
+            session_state: self.session_state.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Build a callback result from the redirect query, or `None` if it carries
+/// neither an error nor a code and state
+pub fn callback_result_from_params(params: &HashMap<String, String>) -> Option<CallbackResult> {
+    if let Some(error) = params.get("error") {
+        return Some(CallbackResult {
+            code: String::new(),
+            state: params.get("state").cloned().unwrap_or_default(),
+            error: Some(error.clone()),
+            error_description: params.get("error_description").cloned(),
+            access_token: None,
+            token_type: None,
+            expires_in: None,
+            session_state: None,
+        });
+    }
+
+    let (code, state) = (params.get("code")?, params.get("state")?);
+    Some(CallbackResult {
+        code: code.clone(),
+        state: state.clone(),
+        error: None,
+        error_description: None,
+        access_token: params.get("access_token").cloned(),
+        token_type: params.get("token_type").cloned(),
+        expires_in: params.get("expires_in").and_then(|v| v.parse().ok()),
+        session_state: params.get("session_state").cloned(),
+    })
+}
+
+/// How much of the tokens the success page may show once the exchange completes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BrowserTokenDisplay {
+    /// First and last characters only, without copy buttons
+    Preview,
+    /// The complete tokens, with copy buttons
+    Full,
 }
 
 #[derive(Clone)]
@@ -33,6 +94,27 @@ pub struct CallbackServer {
     sender: Option<mpsc::Sender<CallbackResult>>,
     callback_path: String,
     token_store: Arc<RwLock<Option<TokenResponse>>>,
+    /// `None` keeps tokens out of the browser: the page does not poll and `/tokens` is 404
+    token_display: Option<BrowserTokenDisplay>,
+    /// Signalled on every request, so an idle timeout can tell an active user from an abandoned flow
+    activity: Option<mpsc::Sender<()>>,
+    shutdown: Arc<Notify>,
+    #[cfg(unix)]
+    unix_socket: Option<PathBuf>,
+    /// Shared by clones, so the socket file goes away with the last handle
+    #[cfg(unix)]
+    socket_file: Option<Arc<SocketFile>>,
+}
+
+/// Unlinks the bound callback socket when dropped, so no stale socket is left behind
+#[cfg(unix)]
+struct SocketFile(PathBuf);
+
+#[cfg(unix)]
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
 }
 
 impl CallbackServer {
@@ -44,9 +126,43 @@ impl CallbackServer {
             sender: None,
             callback_path,
             token_store: Arc::new(RwLock::new(None)),
+            token_display: None,
+            activity: None,
+            shutdown: Arc::new(Notify::new()),
+            #[cfg(unix)]
+            unix_socket: None,
+            #[cfg(unix)]
+            socket_file: None,
         })
     }
 
+    /// Serve the callback on a Unix domain socket, e.g. behind a reverse proxy.
+    /// The redirect URI is only used for its path; the proxy maps the public URL to the socket.
+    #[cfg(unix)]
+    pub fn new_unix(socket_path: &Path, redirect_uri: &str) -> Result<Self> {
+        let mut server = Self::new(0, redirect_uri)?;
+        server.unix_socket = Some(socket_path.to_path_buf());
+        Ok(server)
+    }
+
+    /// Let the success page show the tokens passed to `set_tokens`
+    pub fn with_token_display(mut self, token_display: Option<BrowserTokenDisplay>) -> Self {
+        self.token_display = token_display;
+        self
+    }
+
+    pub fn token_display(&self) -> Option<BrowserTokenDisplay> {
+        self.token_display
+    }
+
+    /// Receive a signal for each request the server handles, including non-callback
+    /// paths like `/favicon.ico`. Call before `start`.
+    pub fn track_activity(&mut self) -> mpsc::Receiver<()> {
+        let (tx, rx) = mpsc::channel(1);
+        self.activity = Some(tx);
+        rx
+    }
+
     pub async fn start(&mut self) -> Result<mpsc::Receiver<CallbackResult>> {
         let (tx, rx) = mpsc::channel::<CallbackResult>(1);
         self.sender = Some(tx.clone());
@@ -55,20 +171,45 @@ impl CallbackServer {
         let addr = self.addr;
         let callback_path = Arc::new(self.callback_path.clone());
         let token_store = self.token_store.clone();
+        let token_display = self.token_display;
+        let activity = self.activity.clone();
+
+        #[cfg(unix)]
+        if let Some(ref socket_path) = self.unix_socket {
+            start_unix_listener(
+                socket_path,
+                tx_arc,
+                callback_path,
+                token_store,
+                token_display,
+                activity,
+            )?;
+            self.socket_file = Some(Arc::new(SocketFile(socket_path.clone())));
+            return Ok(rx);
+        }
 
         let make_svc = make_service_fn(move |_conn| {
             let tx = tx_arc.clone();
             let path = callback_path.clone();
             let store = token_store.clone();
+            let activity = activity.clone();
             async move {
                 Ok::<_, Infallible>(service_fn(move |req| {
-                    handle_request(req, tx.clone(), path.clone(), store.clone())
+                    signal_activity(activity.as_ref());
+                    handle_request(req, tx.clone(), path.clone(), store.clone(), token_display)
                 }))
             }
         });
 
+        let server = Server::try_bind(&addr)
+            .map_err(|e| OidcError::Server(format!("Failed to bind {addr}: {e}")))?
+            .serve(make_svc);
+        // Report the real port when bound to port 0
+        self.addr = server.local_addr();
+
+        let shutdown = self.shutdown.clone();
         tokio::spawn(async move {
-            let server = Server::bind(&addr).serve(make_svc);
+            let server = server.with_graceful_shutdown(async move { shutdown.notified().await });
 
             if let Err(e) = server.await {
                 eprintln!("Server error: {e}");
@@ -78,6 +219,11 @@ impl CallbackServer {
         Ok(rx)
     }
 
+    /// Stop accepting connections and let in-flight requests finish
+    pub fn shutdown(&self) {
+        self.shutdown.notify_one();
+    }
+
     #[allow(dead_code)]
     pub fn get_redirect_uri(&self) -> String {
         format!(
@@ -99,11 +245,73 @@ impl CallbackServer {
     }
 }
 
+#[cfg(unix)]
+fn start_unix_listener(
+    socket_path: &Path,
+    tx: Arc<mpsc::Sender<CallbackResult>>,
+    callback_path: Arc<String>,
+    token_store: Arc<RwLock<Option<TokenResponse>>>,
+    token_display: Option<BrowserTokenDisplay>,
+    activity: Option<mpsc::Sender<()>>,
+) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    // Remove a stale socket left behind by a previous run, but never any other file type
+    if let Ok(metadata) = std::fs::symlink_metadata(socket_path) {
+        if metadata.file_type().is_socket() {
+            std::fs::remove_file(socket_path)?;
+        }
+    }
+
+    let listener = tokio::net::UnixListener::bind(socket_path).map_err(|e| {
+        OidcError::Server(format!("Failed to bind Unix socket {socket_path:?}: {e}"))
+    })?;
+
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("Server error: {e}");
+                    break;
+                }
+            };
+
+            let tx = tx.clone();
+            let path = callback_path.clone();
+            let store = token_store.clone();
+            let activity = activity.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| {
+                    signal_activity(activity.as_ref());
+                    handle_request(req, tx.clone(), path.clone(), store.clone(), token_display)
+                });
+                if let Err(e) = hyper::server::conn::Http::new()
+                    .serve_connection(stream, service)
+                    .await
+                {
+                    eprintln!("Server error: {e}");
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+/// A full channel already holds a pending signal, so extra ones are dropped
+fn signal_activity(activity: Option<&mpsc::Sender<()>>) {
+    if let Some(activity) = activity {
+        let _ = activity.try_send(());
+    }
+}
+
 async fn handle_request(
     req: Request<Body>,
     tx: Arc<mpsc::Sender<CallbackResult>>,
     callback_path: Arc<String>,
     token_store: Arc<RwLock<Option<TokenResponse>>>,
+    token_display: Option<BrowserTokenDisplay>,
 ) -> std::result::Result<Response<Body>, Infallible> {
     match req.method() {
         &Method::GET => {
@@ -113,34 +321,17 @@ async fn handle_request(
                 if let Some(query) = uri.query() {
                     let params = parse_query_params(query);
 
-                    if let Some(error) = params.get("error") {
-                        let error_description = params.get("error_description").cloned();
-                        let error_desc_ref = error_description.as_deref();
-                        let result = CallbackResult {
-                            code: String::new(),
-                            state: params.get("state").cloned().unwrap_or_default(),
-                            error: Some(error.clone()),
-                            error_description: error_description.clone(),
-                            access_token: None,
-                        };
-
-                        let _ = tx.send(result).await;
-                        return Ok(create_error_response(error, error_desc_ref));
-                    }
-
-                    if let (Some(code), Some(state)) = (params.get("code"), params.get("state")) {
-                        let result = CallbackResult {
-                            code: code.clone(),
-                            state: state.clone(),
-                            error: None,
-                            error_description: None,
-                            access_token: None,
+                    if let Some(result) = callback_result_from_params(&params) {
+                        let response = match result.error {
+                            Some(ref error) => {
+                                create_error_response(error, result.error_description.as_deref())
+                            }
+                            // Serve the success page immediately; JavaScript polls for tokens when display is on
+                            None => create_success_response(token_display.is_some()),
                         };
 
                         let _ = tx.send(result).await;
-
-                        // Always serve success page immediately, let JavaScript polling handle token display
-                        return Ok(create_success_response());
+                        return Ok(response);
                     }
                 }
 
@@ -150,17 +341,10 @@ async fn handle_request(
                 ));
             }
 
-            if uri.path() == "/tokens" {
+            if let (Some(display), "/tokens") = (token_display, uri.path()) {
                 let token_guard = token_store.read().await;
                 if let Some(ref token_response) = *token_guard {
-                    let json_response = serde_json::json!({
-                        "access_token": token_response.access_token,
-                        "id_token": token_response.id_token,
-                        "refresh_token": token_response.refresh_token,
-                        "token_type": token_response.token_type,
-                        "expires_in": token_response.expires_in,
-                        "scope": token_response.scope
-                    });
+                    let json_response = browser_token_json(token_response, display);
 
                     return Ok(Response::builder()
                         .status(StatusCode::OK)
@@ -189,28 +373,47 @@ async fn handle_request(
     }
 }
 
-pub fn parse_query_params(query: &str) -> HashMap<String, String> {
-    let mut params = HashMap::new();
+/// The `/tokens` payload, with token values shortened in preview mode
+pub fn browser_token_json(
+    token_response: &TokenResponse,
+    display: BrowserTokenDisplay,
+) -> serde_json::Value {
+    let show = |token: &str| match display {
+        BrowserTokenDisplay::Full => token.to_string(),
+        BrowserTokenDisplay::Preview => preview_token(token),
+    };
+    serde_json::json!({
+        "access_token": show(&token_response.access_token),
+        "id_token": token_response.id_token.as_deref().map(show),
+        "refresh_token": token_response.refresh_token.as_deref().map(show),
+        "token_type": token_response.token_type,
+        "expires_in": token_response.expires_in,
+        "scope": token_response.scope,
+        "redacted": display == BrowserTokenDisplay::Preview
+    })
+}
 
-    for pair in query.split('&') {
-        if let Some((key, value)) = pair.split_once('=') {
-            if let (Ok(decoded_key), Ok(decoded_value)) =
-                (urlencoding::decode(key), urlencoding::decode(value))
-            {
-                params.insert(decoded_key.to_string(), decoded_value.to_string());
-            }
-        }
+/// Keep only the first 8 and last 4 characters; short tokens are hidden entirely
+fn preview_token(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    if chars.len() <= 16 {
+        return "****".to_string();
     }
-
-    params
+    let head: String = chars[..8].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{head}…{tail}")
 }
 
-fn create_success_response() -> Response<Body> {
-    create_success_response_with_tokens(None)
+fn create_success_response(poll_tokens: bool) -> Response<Body> {
+    create_success_response_with_tokens(None, poll_tokens)
 }
 
-fn create_success_response_with_tokens(token_response: Option<&TokenResponse>) -> Response<Body> {
-    let mut html = include_str!("templates/success.html").to_string();
+fn create_success_response_with_tokens(
+    token_response: Option<&TokenResponse>,
+    poll_tokens: bool,
+) -> Response<Body> {
+    let mut html =
+        include_str!("templates/success.html").replace("{poll_tokens}", &poll_tokens.to_string());
 
     if let Some(tokens) = token_response {
         html = html.replace("{access_token}", &tokens.access_token);
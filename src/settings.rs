@@ -0,0 +1,49 @@
+//! Global options that affect lower layers (HTTP clients, discovery, validation and
+//! diagnostics). They are fixed once at startup, before any threads run, and read
+//! from here rather than from the process environment.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::http::Correlation;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Settings {
+    /// `--proxy` (or `OIDC_CLI_PROXY`)
+    pub proxy: Option<String>,
+    /// `Accept` header for discovery requests
+    pub discovery_accept: Option<String>,
+    /// Deadline for the whole discovery request
+    pub discovery_timeout: Option<Duration>,
+    /// Reject, rather than warn about, an issuer on another host
+    pub strict_issuer: bool,
+    /// `--oauth21` (or `OIDC_CLI_OAUTH21`)
+    pub oauth21: bool,
+    /// Header sent on every request of this invocation
+    pub correlation: Option<Correlation>,
+    /// Accept plain-http redirect URIs on `*.localhost` loopback aliases such as `app.localhost`
+    pub allow_insecure_localhost_http: bool,
+    /// Silence non-fatal warnings; security warnings are still shown
+    pub quiet_warnings: bool,
+    /// `--lang`; takes precedence over `LC_ALL`, `LC_MESSAGES` and `LANG`
+    pub lang: Option<String>,
+    /// Write diagnostics to stderr as JSON because stdout carries a JSON document
+    pub json_diagnostics: bool,
+    /// `OIDC_CLI_MAX_RESPONSE_BYTES`; the default limit applies when unset
+    pub max_response_bytes: Option<usize>,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Fix the settings for this process; only the first call has an effect
+pub fn init(settings: Settings) {
+    let _ = SETTINGS.set(settings);
+}
+
+/// The settings given to [`init`], or the defaults when it was never called (tests)
+pub fn get() -> &'static Settings {
+    static DEFAULT: OnceLock<Settings> = OnceLock::new();
+    SETTINGS
+        .get()
+        .unwrap_or_else(|| DEFAULT.get_or_init(Settings::default))
+}
@@ -0,0 +1,365 @@
+//! Per-profile token store in `tokens.json` beside `profiles.json`, written atomically
+//! with mode 0600 under a lock. `login --cached` fills it; `refresh`, `revoke`,
+//! `status` and `cache` read it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::auth::TokenResponse;
+use crate::config::get_config_dir_with_override;
+use crate::error::{OidcError, Result};
+use crate::utils::fs::{write_atomic, FileLock};
+use crate::utils::scope::same_scopes;
+
+/// Tokens treated as expired this long before their actual expiry
+pub const DEFAULT_EXPIRY_LEEWAY_SECS: u64 = 30;
+
+/// A token response stored with the time it was obtained
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedToken {
+    pub tokens: TokenResponse,
+    pub obtained_at: u64,
+    /// Scope requested when the token was obtained; the granted scope is `tokens.scope`
+    #[serde(default)]
+    pub requested_scope: Option<String>,
+}
+
+impl CachedToken {
+    pub fn new(tokens: TokenResponse, requested_scope: &str) -> Self {
+        Self {
+            tokens,
+            obtained_at: now_secs(),
+            requested_scope: Some(requested_scope.to_string()),
+        }
+    }
+
+    /// Whether the token was obtained for the same set of scopes, ignoring order
+    pub fn matches_scope(&self, scope: &str) -> bool {
+        self.requested_scope
+            .as_deref()
+            .is_some_and(|requested| same_scopes(requested, scope))
+    }
+
+    /// Falls back to the id_token's `exp` when the response had no `expires_in`
+    pub fn expires_at(&self) -> Option<u64> {
+        self.tokens.expires_at(self.obtained_at)
+    }
+
+    /// When the refresh token itself expires, if the provider reported `refresh_expires_in`
+    pub fn refresh_expires_at(&self) -> Option<u64> {
+        self.tokens
+            .refresh_expires_in
+            .map(|expires_in| self.obtained_at + expires_in)
+    }
+
+    /// Seconds the refresh token has left, if the provider reported its lifetime
+    pub fn refresh_lifetime_left(&self) -> Option<u64> {
+        self.refresh_expires_at()
+            .map(|expires_at| expires_at.saturating_sub(now_secs()))
+    }
+
+    /// Whether the cached refresh token can still be used; once it has expired
+    /// only a full login yields new tokens
+    pub fn can_refresh(&self, leeway: Duration) -> bool {
+        self.tokens.refresh_token.is_some()
+            && match self.refresh_expires_at() {
+                Some(expires_at) => now_secs() + leeway.as_secs() < expires_at,
+                None => true,
+            }
+    }
+
+    /// Tokens with neither `expires_in` nor an id_token `exp` are assumed valid
+    /// since their lifetime is unknown
+    pub fn is_valid(&self, leeway: Duration) -> bool {
+        match self.expires_at() {
+            Some(expires_at) => now_secs() + leeway.as_secs() < expires_at,
+            None => true,
+        }
+    }
+}
+
+/// Per-profile token cache stored as `tokens.json` in the config directory.
+///
+/// Writers take an exclusive lock and replace the file atomically; readers take
+/// a shared lock, so concurrent invocations never observe a partial write.
+pub struct TokenCache {
+    path: PathBuf,
+}
+
+impl TokenCache {
+    pub fn new_with_override(override_dir: Option<PathBuf>) -> Result<Self> {
+        let mut path = get_config_dir_with_override(override_dir)?;
+        path.push("tokens.json");
+        Ok(Self { path })
+    }
+
+    /// Read every cached entry under a shared lock
+    pub fn load(&self) -> Result<HashMap<String, CachedToken>> {
+        let _lock = FileLock::shared(&self.path)?;
+        self.read_unlocked()
+    }
+
+    /// Re-read the cache and return the profile's token if it is still valid
+    /// after applying `leeway`
+    pub fn get_valid(&self, profile: &str, leeway: Duration) -> Result<Option<CachedToken>> {
+        Ok(self
+            .load()?
+            .remove(profile)
+            .filter(|cached| cached.is_valid(leeway)))
+    }
+
+    /// Like [`get_valid`](Self::get_valid), but a token requested with a different
+    /// scope set is treated as a miss since it may lack the newly requested scopes
+    pub fn get_valid_for_scope(
+        &self,
+        profile: &str,
+        scope: &str,
+        leeway: Duration,
+    ) -> Result<Option<CachedToken>> {
+        Ok(self
+            .get_valid(profile, leeway)?
+            .filter(|cached| cached.matches_scope(scope)))
+    }
+
+    /// The last id_token cached for a profile, even if the access token has expired
+    pub fn cached_id_token(&self, profile: &str) -> Result<Option<String>> {
+        Ok(self
+            .load()?
+            .remove(profile)
+            .and_then(|cached| cached.tokens.id_token))
+    }
+
+    pub fn store(
+        &self,
+        profile: &str,
+        tokens: &TokenResponse,
+        requested_scope: &str,
+    ) -> Result<()> {
+        self.update(|entries| {
+            entries.insert(
+                profile.to_string(),
+                CachedToken::new(tokens.clone(), requested_scope),
+            );
+        })
+    }
+
+    pub fn remove(&self, profile: &str) -> Result<bool> {
+        let mut removed = false;
+        self.update(|entries| removed = entries.remove(profile).is_some())?;
+        Ok(removed)
+    }
+
+    /// Drop every cached token, returning how many there were
+    pub fn clear(&self) -> Result<usize> {
+        let mut removed = 0;
+        self.update(|entries| {
+            removed = entries.len();
+            entries.clear();
+        })?;
+        Ok(removed)
+    }
+
+    /// Read-modify-write under an exclusive lock
+    fn update<F: FnOnce(&mut HashMap<String, CachedToken>)>(&self, f: F) -> Result<()> {
+        let _lock = FileLock::exclusive(&self.path)?;
+        let mut entries = self.read_unlocked()?;
+        f(&mut entries);
+
+        let json = serde_json::to_string_pretty(&entries)
+            .map_err(|e| OidcError::Config(format!("Failed to serialize token cache: {e}")))?;
+        write_atomic(&self.path, json.as_bytes())
+            .map_err(|e| OidcError::Config(format!("Failed to write token cache: {e}")))
+    }
+
+    fn read_unlocked(&self) -> Result<HashMap<String, CachedToken>> {
+        if !self.path.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let content = fs::read_to_string(&self.path)
+            .map_err(|e| OidcError::Config(format!("Failed to read token cache: {e}")))?;
+        if content.trim().is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        serde_json::from_str(&content)
+            .map_err(|e| OidcError::Config(format!("Failed to parse token cache: {e}")))
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn tokens(expires_in: Option<u64>) -> TokenResponse {
+        TokenResponse {
+            access_token: "access".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_get_valid_applies_leeway() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = TokenCache::new_with_override(Some(dir.path().to_path_buf())).unwrap();
+
+        cache.store("long", &tokens(Some(3600)), "openid").unwrap();
+        cache.store("short", &tokens(Some(10)), "openid").unwrap();
+
+        let leeway = Duration::from_secs(DEFAULT_EXPIRY_LEEWAY_SECS);
+        assert!(cache.get_valid("long", leeway).unwrap().is_some());
+        assert!(cache.get_valid("short", leeway).unwrap().is_none());
+        assert!(cache.get_valid("missing", leeway).unwrap().is_none());
+
+        assert!(cache.remove("long").unwrap());
+        assert!(cache.get_valid("long", leeway).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_concurrent_readers_never_see_partial_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache =
+            Arc::new(TokenCache::new_with_override(Some(dir.path().to_path_buf())).unwrap());
+
+        // Large enough that a non-atomic write would be observable mid-way
+        let mut big = tokens(Some(3600));
+        big.id_token = Some("x".repeat(256 * 1024));
+        cache.store("profile", &big, "openid").unwrap();
+
+        let writer = {
+            let cache = cache.clone();
+            thread::spawn(move || {
+                for i in 0..50 {
+                    let mut t = big.clone();
+                    t.access_token = format!("access-{i}");
+                    cache.store("profile", &t, "openid").unwrap();
+                }
+            })
+        };
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let cache = cache.clone();
+                thread::spawn(move || {
+                    for _ in 0..50 {
+                        let cached = cache
+                            .get_valid("profile", Duration::ZERO)
+                            .expect("reader observed corrupt cache")
+                            .expect("token should be present");
+                        assert!(cached.tokens.access_token.starts_with("access"));
+                    }
+                })
+            })
+            .collect();
+
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_scope_change_bypasses_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = TokenCache::new_with_override(Some(dir.path().to_path_buf())).unwrap();
+        cache
+            .store("profile", &tokens(Some(3600)), "openid profile")
+            .unwrap();
+
+        let leeway = Duration::from_secs(DEFAULT_EXPIRY_LEEWAY_SECS);
+        assert!(cache
+            .get_valid_for_scope("profile", "profile  openid", leeway)
+            .unwrap()
+            .is_some());
+        assert!(cache
+            .get_valid_for_scope("profile", "openid profile email", leeway)
+            .unwrap()
+            .is_none());
+        assert!(cache
+            .get_valid_for_scope("profile", "openid", leeway)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_id_token_exp_bounds_validity_without_expires_in() {
+        use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache = TokenCache::new_with_override(Some(dir.path().to_path_buf())).unwrap();
+        let with_exp = |exp: u64| TokenResponse {
+            id_token: Some(format!(
+                "e30.{}.sig",
+                URL_SAFE_NO_PAD.encode(format!(r#"{{"exp":{exp}}}"#))
+            )),
+            ..tokens(None)
+        };
+
+        cache
+            .store("fresh", &with_exp(now_secs() + 3600), "openid")
+            .unwrap();
+        cache
+            .store("stale", &with_exp(now_secs() - 10), "openid")
+            .unwrap();
+
+        let leeway = Duration::from_secs(DEFAULT_EXPIRY_LEEWAY_SECS);
+        assert!(cache.get_valid("fresh", leeway).unwrap().is_some());
+        assert!(cache.get_valid("stale", leeway).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_refresh_token_expiry() {
+        let leeway = Duration::from_secs(DEFAULT_EXPIRY_LEEWAY_SECS);
+        let cached = |refresh_token: Option<&str>, refresh_expires_in| CachedToken {
+            tokens: TokenResponse {
+                refresh_token: refresh_token.map(str::to_string),
+                refresh_expires_in,
+                ..tokens(Some(300))
+            },
+            obtained_at: now_secs(),
+            requested_scope: None,
+        };
+
+        assert!(cached(Some("rt"), Some(1800)).can_refresh(leeway));
+        assert!(cached(Some("rt"), None).can_refresh(leeway));
+        assert!(!cached(Some("rt"), Some(10)).can_refresh(leeway));
+        assert!(!cached(None, Some(1800)).can_refresh(leeway));
+
+        let token = cached(Some("rt"), Some(1800));
+        assert_eq!(token.refresh_expires_at(), Some(token.obtained_at + 1800));
+    }
+
+    #[test]
+    fn test_cached_id_token_survives_expiry() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = TokenCache::new_with_override(Some(dir.path().to_path_buf())).unwrap();
+
+        let mut expired = tokens(Some(0));
+        expired.id_token = Some("header.payload.sig".to_string());
+        cache.store("with-id", &expired, "openid").unwrap();
+        cache
+            .store("without-id", &tokens(Some(3600)), "openid")
+            .unwrap();
+
+        assert_eq!(
+            cache.cached_id_token("with-id").unwrap().as_deref(),
+            Some("header.payload.sig")
+        );
+        assert_eq!(cache.cached_id_token("without-id").unwrap(), None);
+        assert_eq!(cache.cached_id_token("missing").unwrap(), None);
+    }
+}
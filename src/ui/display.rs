@@ -1,37 +1,294 @@
+use serde_json::Value;
+use std::time::{Duration, UNIX_EPOCH};
+
 use crate::auth;
+use crate::crypto::decode_jwt_segments;
 use crate::error::Result;
+use crate::ui::warn::{warn, warnings_suppressed};
+
+/// Lifetimes below this many seconds are likely a provider misconfiguration
+pub const SHORT_EXPIRY_WARNING_SECS: u64 = 60;
+
+/// Lifetimes above this many seconds (24 hours) are a security smell
+pub const LONG_EXPIRY_WARNING_SECS: u64 = 24 * 60 * 60;
+
+/// Heuristic warning for suspiciously short or long token lifetimes
+pub fn expiry_warning(expires_in: u64) -> Option<String> {
+    if expires_in < SHORT_EXPIRY_WARNING_SECS {
+        Some(format!(
+            "Access token expires in only {expires_in} seconds; the provider may be misconfigured"
+        ))
+    } else if expires_in > LONG_EXPIRY_WARNING_SECS {
+        Some(format!(
+            "Access token lifetime of {expires_in} seconds exceeds 24 hours; consider shorter-lived tokens"
+        ))
+    } else {
+        None
+    }
+}
+
+/// Render a user-supplied template with `{access_token}`, `{id_token}`, `{refresh_token}`,
+/// `{token_type}`, `{expires_in}` and `{scope}` placeholders.
+///
+/// Known placeholders for absent fields render as empty strings. Unknown placeholders are
+/// left untouched and returned so the caller can warn about them. Nothing is escaped.
+pub fn render_output_template(
+    template: &str,
+    token_response: &auth::TokenResponse,
+) -> (String, Vec<String>) {
+    let mut output = String::with_capacity(template.len());
+    let mut unknown = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        let Some(end) = after.find('}') else {
+            output.push_str(&rest[start..]);
+            return (output, unknown);
+        };
+
+        let name = &after[..end];
+        let value = match name {
+            "access_token" => Some(token_response.access_token.clone()),
+            "token_type" => Some(token_response.token_type.clone()),
+            "id_token" => Some(token_response.id_token.clone().unwrap_or_default()),
+            "refresh_token" => Some(token_response.refresh_token.clone().unwrap_or_default()),
+            "expires_in" => Some(
+                token_response
+                    .expires_in
+                    .map(|e| e.to_string())
+                    .unwrap_or_default(),
+            ),
+            "scope" => Some(token_response.scope.clone().unwrap_or_default()),
+            _ => None,
+        };
+
+        match value {
+            Some(value) => output.push_str(&value),
+            None => {
+                output.push('{');
+                output.push_str(name);
+                output.push('}');
+                unknown.push(name.to_string());
+            }
+        }
+
+        rest = &after[end + 1..];
+    }
+
+    output.push_str(rest);
+    (output, unknown)
+}
+
+/// Top-level claims holding Unix timestamps, shown with their date and distance from now
+const TIMESTAMP_CLAIMS: &[&str] = &["exp", "iat", "nbf", "auth_time", "updated_at"];
+
+/// Nesting below this depth is printed as compact JSON on one line
+const MAX_CLAIM_TREE_DEPTH: usize = 8;
+
+/// What a registered top-level claim means, for the annotated tree
+fn claim_description(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "iss" => "issuer",
+        "sub" => "subject",
+        "aud" => "audience",
+        "azp" => "authorized party",
+        "jti" => "token ID",
+        "sid" => "session ID",
+        "nonce" => "nonce",
+        "acr" => "authentication context",
+        "amr" => "authentication methods",
+        _ => return None,
+    })
+}
+
+/// Render decoded JWT claims as an indented tree. Registered timestamps get their
+/// date and relative time (`exp: 1700000000 → Tue, 14 Nov 2023 22:13:20 GMT (in 59m)`)
+/// and other registered claims a short description.
+pub fn render_claims_tree(claims: &Value, now: u64) -> String {
+    let mut out = String::new();
+    match claims {
+        Value::Object(map) => {
+            for (name, value) in map {
+                let annotation = match (value.as_u64(), claim_description(name)) {
+                    (Some(secs), _) if TIMESTAMP_CLAIMS.contains(&name.as_str()) => {
+                        Some(format!("→ {}", annotate_timestamp(secs, now)))
+                    }
+                    (_, Some(description)) => Some(format!("({description})")),
+                    _ => None,
+                };
+                write_claim(
+                    &mut out,
+                    0,
+                    &format!("{name}: "),
+                    value,
+                    annotation.as_deref(),
+                );
+            }
+        }
+        other => write_claim(&mut out, 0, "", other, None),
+    }
+    out
+}
+
+/// `<date> (in 59m)` or `<date> (3h ago)` for a Unix timestamp
+pub fn annotate_timestamp(secs: u64, now: u64) -> String {
+    let date = httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(secs));
+    let relative = if secs >= now {
+        format!("in {}", coarse_duration(secs - now))
+    } else {
+        format!("{} ago", coarse_duration(now - secs))
+    };
+    format!("{date} ({relative})")
+}
+
+/// Largest whole unit: `45s`, `59m`, `3h`, `2d`
+fn coarse_duration(secs: u64) -> String {
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3_599 => format!("{}m", secs / 60),
+        3_600..=86_399 => format!("{}h", secs / 3_600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+/// Append `value` at `depth`, prefixed by `label` (`name: ` or `- ` for array items)
+fn write_claim(
+    out: &mut String,
+    depth: usize,
+    label: &str,
+    value: &Value,
+    annotation: Option<&str>,
+) {
+    let indent = "  ".repeat(depth);
+    let nested = match value {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => false,
+    };
+
+    let annotation = annotation.map(|a| format!(" {a}")).unwrap_or_default();
+    if !nested || depth >= MAX_CLAIM_TREE_DEPTH {
+        let scalar = match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        out.push_str(&format!("{indent}{label}{scalar}{annotation}\n"));
+        return;
+    }
+
+    out.push_str(&format!("{indent}{}{annotation}\n", label.trim_end()));
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                write_claim(out, depth + 1, &format!("{key}: "), child, None);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                write_claim(out, depth + 1, "- ", item, None);
+            }
+        }
+        _ => unreachable!("only non-empty objects and arrays nest"),
+    }
+}
+
+/// The decoded header and claims of a JWT as indented trees for printing under the
+/// token, with timestamps shown as dates; `None` for opaque tokens
+pub fn render_jwt_claims(token: &str, now: u64) -> Option<String> {
+    let (header, claims) = decode_jwt_segments(token).ok()?;
+    let mut out = String::new();
+    for (title, value) in [("Header", &header), ("Claims", &claims)] {
+        out.push_str(&format!("{title}:\n"));
+        for line in render_claims_tree(value, now).lines() {
+            out.push_str(&format!("  {line}\n"));
+        }
+    }
+    Some(out)
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
 
-pub fn display_tokens(token_response: &auth::TokenResponse, copy: bool) -> Result<()> {
+/// Seconds until the id_token's `exp`, saturating at zero
+fn id_token_remaining(token_response: &auth::TokenResponse) -> Option<u64> {
+    token_response
+        .id_token_exp()
+        .map(|exp| exp.saturating_sub(now_secs()))
+}
+
+pub fn display_tokens(
+    token_response: &auth::TokenResponse,
+    copy: bool,
+    selection: auth::TokenSelection,
+) -> Result<()> {
     println!("🎉 Authentication successful!");
     println!();
 
+    if let Some(warning) = token_response.expires_in.and_then(expiry_warning) {
+        warn(&warning);
+        if !warnings_suppressed() {
+            eprintln!();
+        }
+    }
+
     println!("=== TOKENS ===");
     println!();
 
-    println!("Access Token:");
-    println!("{}", token_response.access_token);
-    println!("Type: {}", token_response.token_type);
-    if let Some(expires_in) = token_response.expires_in {
-        println!("Expires In: {expires_in} seconds");
-    } else {
-        println!("Expires In: Not specified");
+    if selection.access {
+        println!("Access Token:");
+        println!("{}", token_response.access_token);
+        println!("Type: {}", token_response.token_type);
+        match (
+            token_response.expires_in,
+            id_token_remaining(token_response),
+        ) {
+            (Some(expires_in), _) => println!("Expires In: {expires_in} seconds"),
+            (None, Some(remaining)) => {
+                println!(
+                    "Expires In: ~{remaining} seconds (id_token exp; may differ for this token)"
+                )
+            }
+            (None, None) => println!("Expires In: Not specified"),
+        }
+        if let Some(claims) = render_jwt_claims(&token_response.access_token, now_secs()) {
+            print!("{claims}");
+        }
+        println!();
     }
-    println!();
 
-    if let Some(ref id_token) = token_response.id_token {
+    if let Some(id_token) = token_response.id_token.as_ref().filter(|_| selection.id) {
         println!("ID Token:");
         println!("{id_token}");
         if let Some(expires_in) = token_response.expires_in {
             println!("Expires In: {expires_in} seconds (same as access token)");
+        } else if let Some(remaining) = id_token_remaining(token_response) {
+            println!("Expires In: {remaining} seconds (from the 'exp' claim)");
         } else {
             println!("Expires In: Check token 'exp' claim for exact expiration");
         }
+        if let Some(claims) = render_jwt_claims(id_token, now_secs()) {
+            print!("{claims}");
+        }
         println!();
     }
 
-    if let Some(ref refresh_token) = token_response.refresh_token {
+    if let Some(refresh_token) = token_response
+        .refresh_token
+        .as_ref()
+        .filter(|_| selection.refresh)
+    {
         println!("Refresh Token:");
         println!("{refresh_token}");
+        if let Some(refresh_expires_in) = token_response.refresh_expires_in {
+            println!("Expires In: {refresh_expires_in} seconds");
+        }
         println!();
     }
 
@@ -40,6 +297,11 @@ pub fn display_tokens(token_response: &auth::TokenResponse, copy: bool) -> Resul
         println!();
     }
 
+    if let Some(ref session_state) = token_response.session_state {
+        println!("Session State: {session_state}");
+        println!();
+    }
+
     if copy {
         #[cfg(feature = "clipboard")]
         {
@@ -59,3 +321,126 @@ pub fn display_tokens(token_response: &auth::TokenResponse, copy: bool) -> Resul
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_tokens() -> auth::TokenResponse {
+        auth::TokenResponse {
+            access_token: "access".to_string(),
+            token_type: "Bearer".to_string(),
+            expires_in: Some(3600),
+            refresh_token: None,
+            id_token: Some("id".to_string()),
+            scope: Some("openid".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_output_template_substitution() {
+        let tokens = create_test_tokens();
+        let (output, unknown) = render_output_template(
+            "Authorization: {token_type} {access_token} ({expires_in}s, {scope}, {id_token})",
+            &tokens,
+        );
+        assert_eq!(output, "Authorization: Bearer access (3600s, openid, id)");
+        assert!(unknown.is_empty());
+    }
+
+    #[test]
+    fn test_render_output_template_missing_and_unknown() {
+        let tokens = create_test_tokens();
+        let (output, unknown) =
+            render_output_template("export RT='{refresh_token}' {nope} {unclosed", &tokens);
+        assert_eq!(output, "export RT='' {nope} {unclosed");
+        assert_eq!(unknown, vec!["nope".to_string()]);
+    }
+
+    #[test]
+    fn test_expiry_warning_thresholds() {
+        assert!(expiry_warning(0).is_some());
+        assert!(expiry_warning(SHORT_EXPIRY_WARNING_SECS - 1).is_some());
+        assert!(expiry_warning(SHORT_EXPIRY_WARNING_SECS).is_none());
+        assert!(expiry_warning(3600).is_none());
+        assert!(expiry_warning(LONG_EXPIRY_WARNING_SECS).is_none());
+        assert!(expiry_warning(LONG_EXPIRY_WARNING_SECS + 1).is_some());
+    }
+
+    #[test]
+    fn test_timestamp_claims_are_annotated() {
+        let now = 1_700_000_000;
+        let claims = serde_json::json!({
+            "exp": now + 3_540,
+            "iat": now - 60,
+            "nbf": now - 2 * 86_400,
+            "iss": "https://issuer.example.com",
+            "aud": ["client", "api"],
+        });
+        let tree = render_claims_tree(&claims, now);
+
+        assert!(
+            tree.contains("exp: 1700003540 → Tue, 14 Nov 2023 23:12:20 GMT (in 59m)"),
+            "{tree}"
+        );
+        assert!(tree.contains("iat: 1699999940 → Tue, 14 Nov 2023 22:12:20 GMT (1m ago)"));
+        assert!(tree.contains("nbf: 1699827200 → Sun, 12 Nov 2023 22:13:20 GMT (2d ago)"));
+        assert!(tree.contains("iss: https://issuer.example.com (issuer)"));
+        assert!(tree.starts_with("aud: (audience)\n  - client\n  - api\n"));
+    }
+
+    #[test]
+    fn test_annotate_timestamp_relative_units() {
+        let now = 1_700_000_000;
+        assert!(annotate_timestamp(now, now).ends_with("(in 0s)"));
+        assert!(annotate_timestamp(now + 45, now).ends_with("(in 45s)"));
+        assert!(annotate_timestamp(now + 7_200, now).ends_with("(in 2h)"));
+        assert!(annotate_timestamp(now - 3 * 86_400, now).ends_with("(3d ago)"));
+    }
+
+    #[test]
+    fn test_claims_tree_nesting() {
+        let claims = serde_json::json!({
+            "roles": ["admin", {"scope": "read"}],
+            "address": {"country": "NZ", "empty": {}},
+            "exp": "not a number",
+        });
+        assert_eq!(
+            render_claims_tree(&claims, 0),
+            "address:\n  country: NZ\n  empty: {}\nexp: not a number\nroles:\n  - admin\n  -\n    scope: read\n"
+        );
+
+        // Nesting past the depth cap collapses to compact JSON
+        let mut deep = serde_json::json!("leaf");
+        for _ in 0..20 {
+            deep = serde_json::json!({ "a": deep });
+        }
+        let tree = render_claims_tree(&deep, 0);
+        assert_eq!(tree.lines().count(), MAX_CLAIM_TREE_DEPTH + 1);
+        assert!(tree.lines().last().unwrap().contains(r#"{"a":"#));
+    }
+
+    #[test]
+    fn test_render_jwt_claims() {
+        use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        use base64::Engine;
+
+        let token = format!(
+            "{}.{}.sig",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256"}"#),
+            URL_SAFE_NO_PAD.encode(r#"{"sub":"user","exp":1700003600,"nbf":1700000000}"#)
+        );
+        let rendered = render_jwt_claims(&token, 1_700_000_000).unwrap();
+        assert_eq!(
+            rendered,
+            "Header:\n  alg: RS256\nClaims:\n  \
+             exp: 1700003600 → Tue, 14 Nov 2023 23:13:20 GMT (in 1h)\n  \
+             nbf: 1700000000 → Tue, 14 Nov 2023 22:13:20 GMT (in 0s)\n  \
+             sub: user (subject)\n"
+        );
+
+        assert!(render_jwt_claims("opaque-access-token", 0).is_none());
+        assert!(render_jwt_claims("a.b.c", 0).is_none());
+    }
+}
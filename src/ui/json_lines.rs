@@ -0,0 +1,62 @@
+use serde::Serialize;
+use std::io::Write;
+
+use crate::error::{OidcError, Result};
+
+/// Writes one compact JSON object per line and flushes after each, so batch
+/// commands can be consumed incrementally (`--json-lines`)
+pub struct JsonLinesWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self { out }
+    }
+
+    pub fn emit<T: Serialize>(&mut self, record: &T) -> Result<()> {
+        let line = serde_json::to_string(record)
+            .map_err(|e| OidcError::Config(format!("Failed to serialize output: {e}")))?;
+        writeln!(self.out, "{line}")?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+impl JsonLinesWriter<std::io::Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(std::io::stdout())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_each_line_is_valid_json() {
+        let mut buffer = Vec::new();
+        {
+            let mut writer = JsonLinesWriter::new(&mut buffer);
+            writer
+                .emit(&json!({"profile": "dev", "detail": "multi\nline"}))
+                .unwrap();
+            writer
+                .emit(&json!({"profile": "prod", "state": "valid"}))
+                .unwrap();
+        }
+
+        let output = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            let value: Value = serde_json::from_str(line).unwrap();
+            assert!(value.is_object());
+        }
+        assert_eq!(
+            serde_json::from_str::<Value>(lines[0]).unwrap()["detail"],
+            "multi\nline"
+        );
+    }
+}
@@ -1,9 +1,43 @@
 use crate::error::Result;
+use crate::server::{callback_result_from_params, CallbackResult};
+use crate::ui::prompts::read_prompt_line;
 use crate::utils::url::parse_query_params;
 use std::io::{self, Write};
 use url::Url;
 
-pub async fn handle_manual_code_entry(quiet: bool) -> Result<String> {
+/// What the user pasted at the manual code prompt
+#[derive(Debug, PartialEq, Eq)]
+pub enum CodeEntry {
+    /// A callback URL; `state` is `None` when the URL has no `state` parameter
+    Callback { code: String, state: Option<String> },
+    /// Just the code, so the state has to be asked for separately
+    BareCode(String),
+}
+
+pub fn parse_code_entry(input: &str) -> Option<CodeEntry> {
+    let input = input.trim();
+    if input.is_empty() {
+        return None;
+    }
+
+    if let Ok(url) = Url::parse(input) {
+        if let Some(query) = url.query() {
+            let params = parse_query_params(query);
+            return params.get("code").map(|code| CodeEntry::Callback {
+                code: code.clone(),
+                state: params.get("state").cloned(),
+            });
+        }
+    }
+
+    // If not a URL with a query, treat as direct code; codes may contain ':' and so
+    // parse as URLs like `foo:bar`
+    (!input.contains("://")).then(|| CodeEntry::BareCode(input.to_string()))
+}
+
+/// Prompt for the authorization code and the `state` it came with, which the
+/// caller checks against the login's own state
+pub async fn handle_manual_code_entry(quiet: bool) -> Result<(String, Option<String>)> {
     if !quiet {
         println!("Since your redirect URI is not localhost, you'll need to manually enter the authorization code.");
         println!("After authorizing in your browser, copy the full callback URL or just the 'code' parameter.");
@@ -13,29 +47,125 @@ pub async fn handle_manual_code_entry(quiet: bool) -> Result<String> {
         print!("Enter the authorization code or full callback URL: ");
         io::stdout().flush().unwrap();
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        let input = read_prompt_line()?;
+
+        match parse_code_entry(&input) {
+            Some(CodeEntry::Callback { code, state }) => return Ok((code, state)),
+            Some(CodeEntry::BareCode(code)) => return Ok((code, Some(read_state()?))),
+            None if input.trim().is_empty() => {
+                println!("Authorization code cannot be empty. Please try again.")
+            }
+            None => {
+                println!("Could not extract authorization code from the input. Please try again.")
+            }
+        }
+    }
+}
+
+/// Ask for the `state` parameter separately when only a bare code was pasted
+fn read_state() -> Result<String> {
+    loop {
+        print!("Enter the 'state' parameter from the callback URL: ");
+        io::stdout().flush().unwrap();
+
+        let input = read_prompt_line()?;
         let input = input.trim();
+        let state = input.strip_prefix("state=").unwrap_or(input);
 
-        if input.is_empty() {
-            println!("Authorization code cannot be empty. Please try again.");
-            continue;
+        if !state.is_empty() {
+            return Ok(state.to_string());
         }
+        println!("The state is needed to match the code to this login. Please try again.");
+    }
+}
+
+/// Prompt for the full callback URL, which must carry `code` and `state` (or an `error`)
+pub async fn read_callback_url() -> Result<CallbackResult> {
+    loop {
+        print!("Paste the full callback URL: ");
+        io::stdout().flush().unwrap();
+
+        let input = read_prompt_line()?;
 
-        if let Ok(url) = Url::parse(input) {
-            if let Some(query) = url.query() {
-                let params = parse_query_params(query);
-                if let Some(code) = params.get("code") {
-                    return Ok(code.clone());
-                }
+        match parse_callback_url(input.trim()) {
+            Some(result) => return Ok(result),
+            None => {
+                println!("The URL must contain 'code' and 'state' parameters. Please try again.")
             }
         }
+    }
+}
 
-        // If not a URL, treat as direct code
-        if !input.contains("://") {
-            return Ok(input.to_string());
-        }
+pub fn parse_callback_url(input: &str) -> Option<CallbackResult> {
+    let url = Url::parse(input).ok()?;
+    callback_result_from_params(&parse_query_params(url.query()?))
+}
+
+/// Parse an authorization response written by another tool: a full callback URL
+/// or a bare query string. Both must carry `state` (or an `error`) so it can be checked.
+pub fn parse_callback_response(input: &str) -> Option<CallbackResult> {
+    let input = input.trim();
+    if input.contains("://") {
+        return parse_callback_url(input);
+    }
+    callback_result_from_params(&parse_query_params(input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_callback_url() {
+        let result =
+            parse_callback_url("https://app.example.com/callback?code=abc&state=xyz").unwrap();
+        assert_eq!(result.code, "abc");
+        assert_eq!(result.state, "xyz");
+
+        assert!(parse_callback_url("https://app.example.com/callback?code=abc").is_none());
+        assert!(parse_callback_url("abc").is_none());
+    }
+
+    #[test]
+    fn test_parse_code_entry() {
+        assert_eq!(
+            parse_code_entry("https://app.example.com/cb?code=abc&state=xyz\n"),
+            Some(CodeEntry::Callback {
+                code: "abc".to_string(),
+                state: Some("xyz".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_code_entry("https://app.example.com/cb?code=abc"),
+            Some(CodeEntry::Callback {
+                code: "abc".to_string(),
+                state: None,
+            })
+        );
+        assert_eq!(
+            parse_code_entry(" abc "),
+            Some(CodeEntry::BareCode("abc".to_string()))
+        );
+        assert!(parse_code_entry("https://app.example.com/cb?state=xyz").is_none());
+        assert!(parse_code_entry("https://app.example.com/cb").is_none());
+        assert_eq!(
+            parse_code_entry("foo:bar"),
+            Some(CodeEntry::BareCode("foo:bar".to_string()))
+        );
+        assert!(parse_code_entry("").is_none());
+    }
+
+    #[test]
+    fn test_parse_callback_response() {
+        let from_url =
+            parse_callback_response("https://app.example.com/cb?code=abc&state=xyz\n").unwrap();
+        assert_eq!(from_url.code, "abc");
+
+        let from_query = parse_callback_response("?code=abc&state=xyz").unwrap();
+        assert_eq!(from_query.state, "xyz");
 
-        println!("Could not extract authorization code from the input. Please try again.");
+        // A bare code cannot be matched to this login
+        assert!(parse_callback_response("abc").is_none());
+        assert!(parse_callback_response("").is_none());
     }
 }
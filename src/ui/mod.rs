@@ -1,7 +1,15 @@
 pub mod display;
+pub mod json_lines;
 pub mod manual_entry;
 pub mod prompts;
+pub mod token_input;
+pub mod url_params;
+pub mod warn;
 
 pub use display::*;
+pub use json_lines::*;
 pub use manual_entry::*;
 pub use prompts::*;
+pub use token_input::*;
+pub use url_params::*;
+pub use warn::*;
@@ -1,14 +1,24 @@
 use crate::error::{OidcError, Result};
+use crate::messages::{tr, tr_with, Message};
 use crate::profile::ProfileManager;
-use std::io::{self, Write};
+use crate::ui::warn::security_warning;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Read a line typed at a prompt. End of input means nobody is left to answer, so
+/// the prompt is cancelled rather than asked again.
+pub fn read_prompt_line() -> Result<String> {
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        return Err(OidcError::Cancelled);
+    }
+    Ok(input)
+}
 
 pub fn select_profile(profile_manager: &ProfileManager, quiet: bool) -> Result<String> {
     let profiles = profile_manager.list_profiles();
 
     if profiles.is_empty() {
-        return Err(OidcError::Profile(
-            "No profiles found. Create a profile first using 'create' command.".to_string(),
-        ));
+        return Err(OidcError::Profile(tr(Message::NoProfiles)));
     }
 
     if profiles.len() == 1 {
@@ -16,22 +26,20 @@ pub fn select_profile(profile_manager: &ProfileManager, quiet: bool) -> Result<S
     }
 
     if quiet {
-        return Err(OidcError::Profile(
-            "Multiple profiles available. Please specify a profile name.".to_string(),
-        ));
+        return Err(OidcError::Profile(tr(Message::MultipleProfilesSpecify)));
     }
 
-    println!("Multiple profiles available:");
+    let max = profiles.len().to_string();
+    println!("{}", tr(Message::MultipleProfilesHeading));
     for (i, profile) in profiles.iter().enumerate() {
         println!("  {}. {}", i + 1, profile);
     }
 
     loop {
-        print!("Select a profile (1-{}): ", profiles.len());
+        print!("{}", tr_with(Message::SelectProfile, &[("max", &max)]));
         io::stdout().flush().unwrap();
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        let input = read_prompt_line()?;
 
         if let Ok(choice) = input.trim().parse::<usize>() {
             if choice > 0 && choice <= profiles.len() {
@@ -40,8 +48,8 @@ pub fn select_profile(profile_manager: &ProfileManager, quiet: bool) -> Result<S
         }
 
         println!(
-            "Invalid selection. Please enter a number between 1 and {}.",
-            profiles.len()
+            "{}",
+            tr_with(Message::InvalidProfileSelection, &[("max", &max)])
         );
     }
 }
@@ -51,12 +59,11 @@ pub fn prompt_input(prompt: &str, required: bool) -> Result<String> {
         print!("{prompt}: ");
         io::stdout().flush().unwrap();
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        let input = read_prompt_line()?;
         let input = input.trim();
 
         if input.is_empty() && required {
-            println!("This field is required. Please enter a value.");
+            println!("{}", tr(Message::FieldRequired));
             continue;
         }
 
@@ -129,3 +136,152 @@ pub fn prompt_optional_input_with_current(
         Ok(Some(input.to_string()))
     }
 }
+
+/// Prompt for an optional secret without echo. When stdin is not a terminal
+/// (e.g. piped input) the line is read visibly instead, with a warning.
+pub fn prompt_secret(prompt: &str) -> Result<Option<String>> {
+    if io::stdin().is_terminal() {
+        return Ok(non_empty(rpassword::prompt_password(format!(
+            "{prompt}: "
+        ))?));
+    }
+
+    security_warning(tr(Message::SecretShownWarning));
+    print!("{prompt}: ");
+    io::stdout().flush().unwrap();
+    read_secret_line(io::stdin().lock())
+}
+
+/// Read one line from `reader` as an optional secret, ignoring surrounding whitespace
+pub fn read_secret_line<R: BufRead>(mut reader: R) -> Result<Option<String>> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    Ok(non_empty(line))
+}
+
+fn non_empty(input: String) -> Option<String> {
+    let input = input.trim();
+    (!input.is_empty()).then(|| input.to_string())
+}
+
+/// Read a passphrase without echo; with `confirm` it must be entered twice
+pub fn prompt_passphrase(confirm: bool) -> Result<String> {
+    let passphrase = rpassword::prompt_password(tr(Message::PassphrasePrompt))?;
+    if passphrase.is_empty() {
+        return Err(OidcError::Config(tr(Message::PassphraseEmpty)));
+    }
+
+    if confirm && rpassword::prompt_password(tr(Message::ConfirmPassphrasePrompt))? != passphrase {
+        return Err(OidcError::Config(tr(Message::PassphraseMismatch)));
+    }
+
+    Ok(passphrase)
+}
+
+/// Let the user adjust a scope set interactively. Entries start checked when they
+/// appear in `current`; scopes in `current` that the provider does not advertise
+/// are listed too so they are not dropped silently. Numbers toggle entries and an
+/// empty line accepts the selection.
+pub fn prompt_scope_selection(available: &[String], current: &str) -> Result<String> {
+    let mut options: Vec<String> = available.to_vec();
+    for scope in current.split_whitespace() {
+        if !options.iter().any(|option| option == scope) {
+            options.push(scope.to_string());
+        }
+    }
+    let mut selected: Vec<bool> = options
+        .iter()
+        .map(|option| current.split_whitespace().any(|scope| scope == option))
+        .collect();
+
+    loop {
+        println!("{}", tr(Message::ScopesHeading));
+        for (i, (option, checked)) in options.iter().zip(&selected).enumerate() {
+            let mark = if *checked { 'x' } else { ' ' };
+            println!("  [{mark}] {}. {option}", i + 1);
+        }
+        print!("{}", tr(Message::ToggleScopesPrompt));
+        io::stdout().flush().unwrap();
+
+        let input = read_prompt_line()?;
+
+        if input.trim().is_empty() {
+            let scope = selected_scopes(&options, &selected);
+            if scope.is_empty() {
+                println!("{}", tr(Message::SelectAtLeastOneScope));
+                continue;
+            }
+            return Ok(scope);
+        }
+
+        if let Err(message) = toggle_scope_selection(&mut selected, &input) {
+            println!("{message}");
+        }
+    }
+}
+
+/// Apply a line of 1-based numbers (space or comma separated) as toggles.
+/// Nothing changes if any number is invalid.
+pub fn toggle_scope_selection(
+    selected: &mut [bool],
+    input: &str,
+) -> std::result::Result<(), String> {
+    let indices = input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(|part| match part.parse::<usize>() {
+            Ok(n) if (1..=selected.len()).contains(&n) => Ok(n - 1),
+            _ => Err(tr_with(
+                Message::InvalidScopeSelection,
+                &[("input", part), ("max", &selected.len().to_string())],
+            )),
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    for index in indices {
+        selected[index] = !selected[index];
+    }
+    Ok(())
+}
+
+fn selected_scopes(options: &[String], selected: &[bool]) -> String {
+    options
+        .iter()
+        .zip(selected)
+        .filter(|(_, checked)| **checked)
+        .map(|(option, _)| option.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_secret_line_from_piped_input() {
+        assert_eq!(
+            read_secret_line("  s3cr3t \nnext field\n".as_bytes()).unwrap(),
+            Some("s3cr3t".to_string())
+        );
+        assert_eq!(read_secret_line("\n".as_bytes()).unwrap(), None);
+        assert_eq!(read_secret_line("".as_bytes()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_toggle_scope_selection() {
+        let mut selected = vec![true, false, false];
+        toggle_scope_selection(&mut selected, "2, 3 1").unwrap();
+        assert_eq!(selected, vec![false, true, true]);
+
+        assert!(toggle_scope_selection(&mut selected, "2 4").is_err());
+        assert!(toggle_scope_selection(&mut selected, "abc").is_err());
+        assert_eq!(selected, vec![false, true, true]);
+
+        let options: Vec<String> = ["openid", "email", "profile"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(selected_scopes(&options, &selected), "email profile");
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::{self, BufRead, IsTerminal};
+
+use crate::error::{OidcError, Result};
+
+/// Resolve the token for commands that accept `--token` or `--token-stdin`.
+///
+/// Giving both is an error rather than silently preferring one. With
+/// `--token-stdin` on a terminal the token is read without echo; otherwise the
+/// first line of stdin is used.
+pub fn read_token_input(token: Option<String>, token_stdin: bool) -> Result<String> {
+    match (token, token_stdin) {
+        (Some(_), true) => Err(OidcError::Config(
+            "Use either --token or --token-stdin, not both".to_string(),
+        )),
+        (Some(token), false) => non_empty_token(token),
+        (None, true) => {
+            if io::stdin().is_terminal() {
+                non_empty_token(rpassword::prompt_password("Token: ")?)
+            } else {
+                read_token_line(io::stdin().lock())
+            }
+        }
+        (None, false) => Err(OidcError::MissingField(
+            "token (pass --token or --token-stdin)".to_string(),
+        )),
+    }
+}
+
+/// Read a single-line token from `reader`, ignoring surrounding whitespace
+pub fn read_token_line<R: BufRead>(mut reader: R) -> Result<String> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    non_empty_token(line)
+}
+
+fn non_empty_token(token: String) -> Result<String> {
+    let token = token.trim();
+    if token.is_empty() {
+        return Err(OidcError::MissingField("token".to_string()));
+    }
+    Ok(token.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_token_line() {
+        assert_eq!(
+            read_token_line("  eyJ.abc.def \nsecond line\n".as_bytes()).unwrap(),
+            "eyJ.abc.def"
+        );
+        assert_eq!(
+            read_token_line("no-newline".as_bytes()).unwrap(),
+            "no-newline"
+        );
+        assert!(read_token_line("".as_bytes()).is_err());
+        assert!(read_token_line("   \n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_read_token_input_precedence() {
+        assert_eq!(
+            read_token_input(Some("tok".to_string()), false).unwrap(),
+            "tok"
+        );
+        assert!(read_token_input(Some("tok".to_string()), true).is_err());
+        assert!(read_token_input(None, false).is_err());
+    }
+}
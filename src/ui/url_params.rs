@@ -0,0 +1,85 @@
+use serde::Serialize;
+use url::Url;
+
+use crate::error::Result;
+
+/// A URL split into its endpoint and decoded query parameters, in request order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UrlBreakdown {
+    pub endpoint: String,
+    pub parameters: Vec<UrlParameter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UrlParameter {
+    pub name: String,
+    pub value: String,
+}
+
+/// Decode a URL's query for inspection; repeated parameters are kept as separate rows
+pub fn explain_url(url: &str) -> Result<UrlBreakdown> {
+    let parsed = Url::parse(url)?;
+
+    let mut endpoint = parsed.clone();
+    endpoint.set_query(None);
+    endpoint.set_fragment(None);
+
+    Ok(UrlBreakdown {
+        endpoint: endpoint.to_string(),
+        parameters: parsed
+            .query_pairs()
+            .map(|(name, value)| UrlParameter {
+                name: name.into_owned(),
+                value: value.into_owned(),
+            })
+            .collect(),
+    })
+}
+
+/// Render the breakdown as an aligned two-column table
+pub fn format_url_breakdown(breakdown: &UrlBreakdown) -> String {
+    let width = breakdown
+        .parameters
+        .iter()
+        .map(|p| p.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("Parameter".len());
+
+    let mut out = format!("Endpoint: {}\n\n", breakdown.endpoint);
+    out.push_str(&format!("{:<width$}  Value\n", "Parameter"));
+    out.push_str(&format!("{}  {}\n", "-".repeat(width), "-".repeat(5)));
+    for parameter in &breakdown.parameters {
+        out.push_str(&format!(
+            "{:<width$}  {}\n",
+            parameter.name, parameter.value
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain_url_decodes_parameters_in_order() {
+        let breakdown = explain_url(
+            "https://idp.example.com/authorize?response_type=code&scope=openid%20email&state=abc&scope=extra#frag",
+        )
+        .unwrap();
+        assert_eq!(breakdown.endpoint, "https://idp.example.com/authorize");
+
+        let names: Vec<_> = breakdown
+            .parameters
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["response_type", "scope", "state", "scope"]);
+        assert_eq!(breakdown.parameters[1].value, "openid email");
+
+        let table = format_url_breakdown(&breakdown);
+        assert!(table.contains("response_type  code\n"));
+        assert!(table.contains("scope          openid email\n"));
+    }
+}
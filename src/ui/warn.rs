@@ -0,0 +1,142 @@
+use std::fmt::Display;
+use std::io::Write;
+
+use crate::settings::{self, Settings};
+
+/// Whether `--quiet-warnings` silences non-fatal warnings
+pub fn warnings_suppressed() -> bool {
+    settings::get().quiet_warnings
+}
+
+/// Whether diagnostics go to stderr as JSON objects (set for `--json` output)
+pub fn json_diagnostics() -> bool {
+    settings::get().json_diagnostics
+}
+
+/// Print a non-fatal warning to stderr unless `--quiet-warnings` is in effect
+pub fn warn(message: impl Display) {
+    emit("warning", "Warning", message, true);
+}
+
+/// Like [`warn`], but for a weakened security property (plain or disabled PKCE, an
+/// issuer on another host, secrets left on disk or screen). These are always shown,
+/// since `--quiet-warnings` is meant for noise, not for hiding risks.
+pub fn security_warning(message: impl Display) {
+    emit("warning", "Warning", message, false);
+}
+
+/// Like [`warn`], for informational notices about adjustments made on the user's behalf
+pub fn note(message: impl Display) {
+    emit("info", "Note", message, true);
+}
+
+/// Print a progress message to stdout, or to stderr as JSON when JSON diagnostics are on
+/// so stdout carries only the JSON document
+pub fn progress(message: impl Display) {
+    if json_diagnostics() {
+        write_json_diagnostic(&mut std::io::stderr(), "info", message);
+    } else {
+        println!("{message}");
+    }
+}
+
+fn emit(level: &str, label: &str, message: impl Display, suppressible: bool) {
+    emit_to(
+        &mut std::io::stderr(),
+        settings::get(),
+        level,
+        label,
+        message,
+        suppressible,
+    );
+}
+
+fn emit_to<W: Write>(
+    out: &mut W,
+    settings: &Settings,
+    level: &str,
+    label: &str,
+    message: impl Display,
+    suppressible: bool,
+) {
+    if suppressible && settings.quiet_warnings {
+        return;
+    }
+    if settings.json_diagnostics {
+        write_json_diagnostic(out, level, message);
+    } else {
+        write_warning(out, label, message);
+    }
+}
+
+/// Write `<label>: <message>` to `out`; each line of a multi-line message gets its own label
+pub fn write_warning<W: Write>(out: &mut W, label: &str, message: impl Display) {
+    for line in message.to_string().lines() {
+        // stderr is best effort; a closed pipe must not turn a warning into a failure
+        let _ = writeln!(out, "{label}: {line}");
+    }
+}
+
+/// Write `{"level":<level>,"msg":<message>}` as a single line to `out`
+pub fn write_json_diagnostic<W: Write>(out: &mut W, level: &str, message: impl Display) {
+    let line = serde_json::json!({ "level": level, "msg": message.to_string() });
+    let _ = writeln!(out, "{line}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_warning_prefixes_each_line() {
+        let mut out = Vec::new();
+        write_warning(&mut out, "Warning", "first\nsecond");
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Warning: first\nWarning: second\n"
+        );
+    }
+
+    #[test]
+    fn test_quiet_warnings_suppresses_warnings_but_not_security_warnings() {
+        let message = "--verify requested but no id_token was returned";
+        let quiet = Settings {
+            quiet_warnings: true,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        emit_to(&mut out, &quiet, "warning", "Warning", message, true);
+        assert!(out.is_empty());
+
+        emit_to(
+            &mut out,
+            &Settings::default(),
+            "warning",
+            "Warning",
+            message,
+            true,
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("Warning: {message}\n")
+        );
+
+        let mut out = Vec::new();
+        emit_to(&mut out, &quiet, "warning", "Warning", message, false);
+        assert!(!out.is_empty(), "security warnings are never suppressed");
+    }
+
+    #[test]
+    fn test_write_json_diagnostic() {
+        let mut out = Vec::new();
+        write_json_diagnostic(&mut out, "warning", "no id_token\nwas returned");
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 1, "one object per diagnostic");
+        let value: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({ "level": "warning", "msg": "no id_token\nwas returned" })
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::{OidcError, Result};
+
+/// Load a `.env`-style file into the process environment.
+///
+/// Variables already present in the real environment take precedence and are
+/// never overwritten, so `FOO=bar oidc-cli --env-file .env ...` keeps `bar`.
+/// Returns the names of the variables that were injected.
+pub fn load_env_file(path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| OidcError::Config(format!("Failed to read env file {path:?}: {e}")))?;
+
+    let mut injected = Vec::new();
+    for (key, value) in parse_env_file(&content)? {
+        if std::env::var_os(&key).is_none() {
+            std::env::set_var(&key, value);
+            injected.push(key);
+        }
+    }
+
+    Ok(injected)
+}
+
+/// Parse `KEY=VALUE` lines, supporting `#` comments, an optional `export `
+/// prefix, and single- or double-quoted values.
+pub fn parse_env_file(content: &str) -> Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+
+    for (index, raw_line) in content.lines().enumerate() {
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line.split_once('=').ok_or_else(|| {
+            OidcError::Config(format!(
+                "Invalid env file line {}: expected KEY=VALUE",
+                index + 1
+            ))
+        })?;
+
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(OidcError::Config(format!(
+                "Invalid env file line {}: bad variable name '{key}'",
+                index + 1
+            )));
+        }
+
+        entries.push((key.to_string(), parse_env_value(value.trim())));
+    }
+
+    Ok(entries)
+}
+
+fn parse_env_value(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        let inner = &value[1..value.len() - 1];
+        let mut result = String::with_capacity(inner.len());
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.next() {
+                    Some('n') => result.push('\n'),
+                    Some(other) => result.push(other),
+                    None => result.push('\\'),
+                }
+            } else {
+                result.push(c);
+            }
+        }
+        return result;
+    }
+
+    if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        return value[1..value.len() - 1].to_string();
+    }
+
+    // Unquoted values may carry a trailing ` # comment`
+    match value.find(" #") {
+        Some(pos) => value[..pos].trim_end().to_string(),
+        None => value.to_string(),
+    }
+}
+
+/// Expand `${VAR}` references using the process environment.
+///
+/// Referencing an unset variable is an error rather than an empty expansion so
+/// that a missing secret never silently produces a blank credential.
+pub fn expand_env_vars(input: &str) -> Result<String> {
+    let mut result = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find("${") {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find('}').ok_or_else(|| {
+            OidcError::Config(format!("Unterminated ${{...}} reference in '{input}'"))
+        })?;
+
+        let name = &after[..end];
+        let value = std::env::var(name)
+            .map_err(|_| OidcError::Config(format!("Environment variable '{name}' is not set")))?;
+        result.push_str(&value);
+        rest = &after[end + 1..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_env_file() {
+        let content = r#"
+# comment
+CLIENT_ID=my-client
+export CLIENT_SECRET="s3cr\"et"
+SINGLE='raw $value'
+INLINE=value # trailing comment
+EMPTY=
+"#;
+        let entries = parse_env_file(content).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ("CLIENT_ID".to_string(), "my-client".to_string()),
+                ("CLIENT_SECRET".to_string(), "s3cr\"et".to_string()),
+                ("SINGLE".to_string(), "raw $value".to_string()),
+                ("INLINE".to_string(), "value".to_string()),
+                ("EMPTY".to_string(), "".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_env_file_invalid_lines() {
+        assert!(parse_env_file("NOT_AN_ASSIGNMENT").is_err());
+        assert!(parse_env_file("BAD-NAME=value").is_err());
+    }
+
+    #[test]
+    fn test_load_env_file_keeps_real_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        fs::write(
+            &path,
+            "OIDC_CLI_ENV_TEST_NEW=from-file\nOIDC_CLI_ENV_TEST_SET=from-file\n",
+        )
+        .unwrap();
+        std::env::set_var("OIDC_CLI_ENV_TEST_SET", "from-env");
+
+        let injected = load_env_file(&path).unwrap();
+
+        assert_eq!(injected, vec!["OIDC_CLI_ENV_TEST_NEW".to_string()]);
+        assert_eq!(std::env::var("OIDC_CLI_ENV_TEST_NEW").unwrap(), "from-file");
+        assert_eq!(std::env::var("OIDC_CLI_ENV_TEST_SET").unwrap(), "from-env");
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        std::env::set_var("OIDC_CLI_EXPAND_TEST", "secret");
+        assert_eq!(
+            expand_env_vars("prefix-${OIDC_CLI_EXPAND_TEST}").unwrap(),
+            "prefix-secret"
+        );
+        assert_eq!(expand_env_vars("no refs").unwrap(), "no refs");
+        assert!(expand_env_vars("${OIDC_CLI_EXPAND_TEST_UNSET}").is_err());
+        assert!(expand_env_vars("${UNTERMINATED").is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use fs2::FileExt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+
+use crate::error::{OidcError, Result};
+
+/// Advisory lock held on a sidecar `.lock` file; released on drop
+pub struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    /// Block until a shared (reader) lock is held for `path`
+    pub fn shared(path: &Path) -> Result<Self> {
+        let file = open_lock_file(path)?;
+        FileExt::lock_shared(&file)?;
+        Ok(Self { file })
+    }
+
+    /// Block until an exclusive (writer) lock is held for `path`
+    pub fn exclusive(path: &Path) -> Result<Self> {
+        let file = open_lock_file(path)?;
+        FileExt::lock_exclusive(&file)?;
+        Ok(Self { file })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// The lock lives beside the data file rather than on it, because atomic
+/// writes replace the data file's inode on every save.
+pub fn lock_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".lock");
+    path.with_file_name(name)
+}
+
+fn open_lock_file(path: &Path) -> Result<File> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path_for(path))?)
+}
+
+/// Refuse to write `path` when it is a symbolic link, which could redirect a
+/// secret-bearing file somewhere else. On Unix the parent directory must also
+/// belong to this user or root, and unless it is sticky (like `/tmp`) it must not
+/// be world-writable, nor group-writable by a group other than the user's own, so
+/// nobody else can swap the file out.
+pub fn ensure_safe_write_target(path: &Path) -> Result<()> {
+    if fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink()) {
+        return Err(OidcError::Config(format!(
+            "Refusing to write {path:?}: it is a symbolic link"
+        )));
+    }
+
+    #[cfg(unix)]
+    {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let meta = fs::metadata(parent)?;
+        // SAFETY: geteuid has no preconditions and cannot fail
+        let uid = unsafe { libc::geteuid() };
+        if meta.uid() != uid && meta.uid() != 0 {
+            return Err(OidcError::Config(format!(
+                "Refusing to write {path:?}: {parent:?} belongs to another user"
+            )));
+        }
+        let mode = meta.mode();
+        // SAFETY: getegid has no preconditions and cannot fail
+        let foreign_group = meta.gid() != unsafe { libc::getegid() };
+        let sticky = mode & 0o1000 != 0;
+        if !sticky && (mode & 0o002 != 0 || (mode & 0o020 != 0 && foreign_group)) {
+            return Err(OidcError::Config(format!(
+                "Refusing to write {path:?}: {parent:?} is writable by other users"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Create or truncate `path` for writing, owner-only on Unix. A symbolic link at
+/// `path` is not followed (`O_NOFOLLOW`), so the open fails instead.
+pub fn create_secure_file(path: &Path) -> Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600).custom_flags(libc::O_NOFOLLOW);
+    options.open(path).map_err(|e| {
+        #[cfg(unix)]
+        if e.raw_os_error() == Some(libc::ELOOP) {
+            return OidcError::Config(format!("Refusing to write {path:?}: it is a symbolic link"));
+        }
+        e.into()
+    })
+}
+
+/// Write `contents` to a temporary sibling file and rename it over `path`, so
+/// readers see either the old or the new contents and never a partial write.
+/// The file is created with owner-only permissions on Unix.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".tmp-{}", std::process::id()));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let result = (|| -> Result<()> {
+        let mut file = create_secure_file(&tmp_path)?;
+        #[cfg(unix)]
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+
+    result
+}
+
+/// Take ownership of an inherited file descriptor for writing, e.g. one end of a
+/// pipe set up by a wrapping process. The descriptor must be open for writing, must
+/// be a pipe, regular file or socket, and must not be stdin/stdout/stderr. It is
+/// marked close-on-exec so hooks and browsers don't inherit it, and is closed when
+/// the returned file drops.
+#[cfg(unix)]
+pub fn open_output_fd(fd: i32) -> Result<File> {
+    use std::os::unix::io::FromRawFd;
+
+    if fd <= 2 {
+        return Err(OidcError::Config(format!(
+            "--output-fd {fd} is a standard stream; pass a separate descriptor"
+        )));
+    }
+
+    // SAFETY: F_GETFL only queries the descriptor's status flags
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 {
+        return Err(OidcError::Config(format!(
+            "File descriptor {fd} is not open: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    let mode = flags & libc::O_ACCMODE;
+    if mode != libc::O_WRONLY && mode != libc::O_RDWR {
+        return Err(OidcError::Config(format!(
+            "File descriptor {fd} is not open for writing"
+        )));
+    }
+
+    // SAFETY: fstat only fills in the zeroed stat buffer for an open descriptor
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let file_type = stat.st_mode & libc::S_IFMT;
+    if ![libc::S_IFIFO, libc::S_IFREG, libc::S_IFSOCK].contains(&file_type) {
+        return Err(OidcError::Config(format!(
+            "File descriptor {fd} is not a pipe, regular file or socket"
+        )));
+    }
+
+    // SAFETY: F_GETFD/F_SETFD only read and update the descriptor's flags
+    let fd_flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if fd_flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFD, fd_flags | libc::FD_CLOEXEC) } < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    // SAFETY: the descriptor is open, and it was inherited for this process to
+    // consume, so nothing else in the process owns it
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+#[cfg(not(unix))]
+pub fn open_output_fd(_fd: i32) -> Result<File> {
+    Err(OidcError::Config(
+        "--output-fd is only supported on Unix".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomic_replaces_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.json");
+
+        write_atomic(&path, b"first").unwrap();
+        write_atomic(&path, b"second").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        let entries: Vec<_> = fs::read_dir(dir.path()).unwrap().collect();
+        assert_eq!(entries.len(), 1, "temporary file should not be left behind");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unsafe_write_targets_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("secret.json");
+        let link = dir.path().join("link.json");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        assert!(ensure_safe_write_target(&target).is_ok());
+        assert!(ensure_safe_write_target(&link).is_err());
+        assert!(create_secure_file(&link).is_err());
+        assert!(!target.exists(), "the link must not be followed");
+
+        let shared = dir.path().join("shared");
+        fs::create_dir(&shared).unwrap();
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(ensure_safe_write_target(&shared.join("secret.json")).is_err());
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o1777)).unwrap();
+        assert!(ensure_safe_write_target(&shared.join("secret.json")).is_ok());
+
+        // A new directory gets the user's own group, so group write is fine
+        fs::set_permissions(&shared, fs::Permissions::from_mode(0o775)).unwrap();
+        assert!(ensure_safe_write_target(&shared.join("secret.json")).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_open_output_fd_writes_to_pipe() {
+        use std::io::Read;
+        use std::os::unix::io::FromRawFd;
+
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let [read_fd, write_fd] = fds;
+
+        assert!(open_output_fd(read_fd).is_err(), "read end is not writable");
+        assert!(open_output_fd(1).is_err());
+
+        let mut writer = open_output_fd(write_fd).unwrap();
+        let fd_flags = unsafe { libc::fcntl(write_fd, libc::F_GETFD) };
+        assert_ne!(
+            fd_flags & libc::FD_CLOEXEC,
+            0,
+            "must not leak into children"
+        );
+        writer.write_all(b"{\"access_token\":\"at\"}").unwrap();
+        drop(writer);
+
+        let mut reader = unsafe { File::from_raw_fd(read_fd) };
+        let mut received = String::new();
+        reader.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "{\"access_token\":\"at\"}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_open_output_fd_rejects_devices() {
+        use std::os::unix::io::IntoRawFd;
+
+        // Writable, but a character device rather than a pipe, file or socket
+        let fd = OpenOptions::new()
+            .write(true)
+            .open("/dev/null")
+            .unwrap()
+            .into_raw_fd();
+        let err = open_output_fd(fd).unwrap_err();
+        assert!(err.to_string().contains("not a pipe"), "{err}");
+        unsafe { libc::close(fd) };
+    }
+
+    #[test]
+    fn test_lock_path_for() {
+        assert_eq!(
+            lock_path_for(Path::new("/tmp/oidc/tokens.json")),
+            PathBuf::from("/tmp/oidc/tokens.json.lock")
+        );
+    }
+}
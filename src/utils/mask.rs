@@ -0,0 +1,52 @@
+/// Placeholder shown instead of secret values
+pub const MASKED_SECRET: &str = "****";
+
+/// Mask an optional secret for display, keeping whether it is set visible
+pub fn mask_secret(secret: Option<&str>) -> Option<String> {
+    secret.map(|_| MASKED_SECRET.to_string())
+}
+
+/// Whether a value consists only of `${VAR}` references, which are safe to share
+pub fn is_env_placeholder(value: &str) -> bool {
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return false;
+    }
+
+    while !rest.is_empty() {
+        let Some(after) = rest.strip_prefix("${") else {
+            return false;
+        };
+        let Some(end) = after.find('}') else {
+            return false;
+        };
+        if end == 0 {
+            return false;
+        }
+        rest = &after[end + 1..];
+    }
+
+    true
+}
+
+/// Drop a secret unless it is only an environment placeholder
+pub fn redact_secret(secret: Option<&str>) -> Option<String> {
+    secret.filter(|s| is_env_placeholder(s)).map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_secret_keeps_placeholders() {
+        assert_eq!(
+            redact_secret(Some("${CLIENT_SECRET}")).as_deref(),
+            Some("${CLIENT_SECRET}")
+        );
+        assert_eq!(redact_secret(Some("${A}${B}")).as_deref(), Some("${A}${B}"));
+        assert_eq!(redact_secret(Some("plain-secret")), None);
+        assert_eq!(redact_secret(Some("prefix-${VAR}")), None);
+        assert_eq!(redact_secret(None), None);
+    }
+}
@@ -1 +1,6 @@
+pub mod env;
+pub mod fs;
+pub mod mask;
+pub mod proxy;
+pub mod scope;
 pub mod url;
@@ -0,0 +1,157 @@
+use std::net::IpAddr;
+
+/// `NO_PROXY` bypass rules, following the common curl conventions:
+///
+/// - `*` bypasses the proxy for every host
+/// - `example.com`, `.example.com` and `*.example.com` match the domain and its subdomains
+/// - IP addresses match exactly, and `10.0.0.0/8`-style CIDR blocks match by prefix
+/// - a trailing `:port` on an entry is ignored
+#[derive(Debug, Clone, Default)]
+pub struct NoProxy {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+enum Rule {
+    All,
+    Domain(String),
+    Network(IpAddr, u8),
+}
+
+impl NoProxy {
+    /// Read `NO_PROXY`, falling back to `no_proxy`
+    pub fn from_env() -> Self {
+        std::env::var("NO_PROXY")
+            .or_else(|_| std::env::var("no_proxy"))
+            .map(|value| Self::parse(&value))
+            .unwrap_or_default()
+    }
+
+    pub fn parse(value: &str) -> Self {
+        let rules = value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .filter_map(parse_rule)
+            .collect();
+        Self { rules }
+    }
+
+    /// Whether requests to `host` should skip the proxy
+    pub fn matches(&self, host: &str) -> bool {
+        let host = host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+        let ip = host.parse::<IpAddr>().ok();
+
+        self.rules.iter().any(|rule| match rule {
+            Rule::All => true,
+            Rule::Domain(domain) => {
+                host == *domain
+                    || host
+                        .strip_suffix(domain.as_str())
+                        .is_some_and(|prefix| prefix.ends_with('.'))
+            }
+            Rule::Network(network, prefix_len) => {
+                ip.is_some_and(|ip| in_network(ip, *network, *prefix_len))
+            }
+        })
+    }
+}
+
+fn parse_rule(entry: &str) -> Option<Rule> {
+    if entry == "*" {
+        return Some(Rule::All);
+    }
+
+    if let Some((addr, prefix_len)) = entry.split_once('/') {
+        let network: IpAddr = addr.trim_matches(|c| c == '[' || c == ']').parse().ok()?;
+        let prefix_len: u8 = prefix_len.parse().ok()?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        return (prefix_len <= max).then_some(Rule::Network(network, prefix_len));
+    }
+
+    let bare = entry.trim_start_matches('[');
+    if let Some(end) = bare.find(']') {
+        // Bracketed IPv6, possibly with a port
+        let ip: IpAddr = bare[..end].parse().ok()?;
+        return Some(Rule::Network(ip, if ip.is_ipv4() { 32 } else { 128 }));
+    }
+
+    if let Ok(ip) = entry.parse::<IpAddr>() {
+        return Some(Rule::Network(ip, if ip.is_ipv4() { 32 } else { 128 }));
+    }
+
+    let host = match entry.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => host,
+        _ => entry,
+    };
+
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Some(Rule::Network(ip, if ip.is_ipv4() { 32 } else { 128 }));
+    }
+
+    let domain = host
+        .trim_start_matches('*')
+        .trim_start_matches('.')
+        .trim_end_matches('.')
+        .to_ascii_lowercase();
+    (!domain.is_empty()).then_some(Rule::Domain(domain))
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix_len: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX
+                .checked_shl(32 - u32::from(prefix_len))
+                .unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX
+                .checked_shl(128 - u32::from(prefix_len))
+                .unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_domain_rules() {
+        let no_proxy = NoProxy::parse("example.com, .corp.internal,*.dev.local, auth.test:8443");
+        assert!(no_proxy.matches("example.com"));
+        assert!(no_proxy.matches("login.EXAMPLE.com"));
+        assert!(!no_proxy.matches("badexample.com"));
+        assert!(no_proxy.matches("corp.internal"));
+        assert!(no_proxy.matches("sso.corp.internal"));
+        assert!(no_proxy.matches("idp.dev.local"));
+        assert!(no_proxy.matches("auth.test"));
+        assert!(!no_proxy.matches("other.org"));
+    }
+
+    #[test]
+    fn test_ip_and_cidr_rules() {
+        let no_proxy = NoProxy::parse("127.0.0.1,10.0.0.0/8,fd00::/8,[::1]");
+        assert!(no_proxy.matches("127.0.0.1"));
+        assert!(!no_proxy.matches("127.0.0.2"));
+        assert!(no_proxy.matches("10.42.1.7"));
+        assert!(!no_proxy.matches("11.0.0.1"));
+        assert!(no_proxy.matches("[fd12::1]"));
+        assert!(no_proxy.matches("[::1]"));
+        assert!(!no_proxy.matches("[fe80::1]"));
+    }
+
+    #[test]
+    fn test_wildcard_and_empty() {
+        assert!(NoProxy::parse("*").matches("anything.example"));
+        assert!(!NoProxy::parse("").matches("example.com"));
+        assert!(!NoProxy::parse("10.0.0.0/99").matches("10.0.0.1"));
+    }
+}
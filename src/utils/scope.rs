@@ -0,0 +1,80 @@
+use std::collections::BTreeSet;
+
+/// Compare two space-delimited scope strings as sets
+pub fn same_scopes(a: &str, b: &str) -> bool {
+    let a: BTreeSet<&str> = a.split_whitespace().collect();
+    let b: BTreeSet<&str> = b.split_whitespace().collect();
+    a == b
+}
+
+/// Union of two scope strings, keeping first-seen order and dropping duplicates
+pub fn merge_scopes(base: &str, extra: &str) -> String {
+    let mut merged: Vec<&str> = Vec::new();
+    for scope in base.split_whitespace().chain(extra.split_whitespace()) {
+        if !merged.contains(&scope) {
+            merged.push(scope);
+        }
+    }
+    merged.join(" ")
+}
+
+/// Collapse whitespace and drop duplicate scopes, keeping first-seen order
+pub fn normalize_scope(scope: &str) -> String {
+    merge_scopes(scope, "")
+}
+
+/// Append any `required` scopes missing from `scope`, returning the
+/// effective scope and the scopes that had to be added
+pub fn enforce_required_scopes(scope: &str, required: &[String]) -> (String, Vec<String>) {
+    let present: Vec<&str> = scope.split_whitespace().collect();
+    let mut added: Vec<String> = Vec::new();
+    for required_scope in required {
+        if !present.contains(&required_scope.as_str()) && !added.contains(required_scope) {
+            added.push(required_scope.clone());
+        }
+    }
+
+    if added.is_empty() {
+        (scope.to_string(), added)
+    } else {
+        (merge_scopes(scope, &added.join(" ")), added)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_scopes() {
+        assert!(same_scopes("openid profile", "profile  openid"));
+        assert!(!same_scopes("openid", "openid email"));
+    }
+
+    #[test]
+    fn test_merge_scopes() {
+        assert_eq!(
+            merge_scopes("openid profile", "email openid"),
+            "openid profile email"
+        );
+        assert_eq!(merge_scopes("", "openid"), "openid");
+    }
+
+    #[test]
+    fn test_normalize_scope() {
+        assert_eq!(normalize_scope("openid openid profile"), "openid profile");
+        assert_eq!(normalize_scope("  email\topenid  email "), "email openid");
+    }
+
+    #[test]
+    fn test_enforce_required_scopes() {
+        let required = vec!["openid".to_string(), "offline_access".to_string()];
+        let (scope, added) = enforce_required_scopes("email openid", &required);
+        assert_eq!(scope, "email openid offline_access");
+        assert_eq!(added, vec!["offline_access".to_string()]);
+
+        let (scope, added) = enforce_required_scopes("openid offline_access", &required);
+        assert_eq!(scope, "openid offline_access");
+        assert!(added.is_empty());
+    }
+}
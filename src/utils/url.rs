@@ -29,17 +29,22 @@ pub fn extract_port_from_redirect_uri(uri: &str) -> Option<u16> {
     None
 }
 
+/// Parse an `application/x-www-form-urlencoded` query string.
+///
+/// `+` decodes to a space, a key without `=` maps to an empty string, and
+/// invalid percent-escapes or UTF-8 are decoded lossily rather than dropped.
+/// When a key repeats, the first occurrence wins so that parameters appended
+/// after the provider's own (e.g. a second `state`) cannot override them.
 pub fn parse_query_params(query: &str) -> HashMap<String, String> {
     let mut params = HashMap::new();
 
-    for pair in query.split('&') {
-        if let Some((key, value)) = pair.split_once('=') {
-            if let (Ok(decoded_key), Ok(decoded_value)) =
-                (urlencoding::decode(key), urlencoding::decode(value))
-            {
-                params.insert(decoded_key.to_string(), decoded_value.to_string());
-            }
+    for (key, value) in url::form_urlencoded::parse(query.trim_start_matches('?').as_bytes()) {
+        if key.is_empty() {
+            continue;
         }
+        params
+            .entry(key.into_owned())
+            .or_insert_with(|| value.into_owned());
     }
 
     params
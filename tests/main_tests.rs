@@ -48,3 +48,44 @@ fn test_parse_query_params() {
     let empty_params = parse_query_params("");
     assert!(empty_params.is_empty());
 }
+
+#[test]
+fn test_parse_query_params_form_encoding() {
+    let params = parse_query_params("?scope=openid+profile&error_description=a%2Bb+c&flag");
+    assert_eq!(params["scope"], "openid profile");
+    assert_eq!(params["error_description"], "a+b c");
+    assert_eq!(params["flag"], "");
+}
+
+#[test]
+fn test_parse_query_params_repeated_keys_keep_first() {
+    let params = parse_query_params("state=good&code=abc&state=injected");
+    assert_eq!(params["state"], "good");
+    assert_eq!(params["code"], "abc");
+}
+
+#[test]
+fn test_parse_query_params_malformed_input() {
+    let malformed = [
+        "&&&",
+        "=",
+        "==value",
+        "=orphan&code=abc",
+        "code=%",
+        "code=%zz%",
+        "code=%FF%FE",
+        "a=b=c",
+        "%00=%00",
+        "code=abc&&state=&",
+    ];
+    for query in malformed {
+        let params = parse_query_params(query);
+        assert!(!params.contains_key(""), "empty key kept for {query:?}");
+    }
+
+    assert_eq!(parse_query_params("=orphan&code=abc")["code"], "abc");
+    assert_eq!(parse_query_params("code=%zz%")["code"], "%zz%");
+    assert_eq!(parse_query_params("a=b=c")["a"], "b=c");
+    assert_eq!(parse_query_params("code=abc&&state=&")["state"], "");
+    assert!(parse_query_params("code=%FF%FE")["code"].contains('\u{FFFD}'));
+}
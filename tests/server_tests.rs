@@ -1,9 +1,13 @@
-use oidc_cli::{extract_path_from_redirect_uri, server_parse_query_params, CallbackServer};
+use oidc_cli::auth::TokenResponse;
+use oidc_cli::{
+    browser_token_json, callback_result_from_params, extract_path_from_redirect_uri,
+    parse_query_params, BrowserTokenDisplay, CallbackServer,
+};
 
 #[test]
 fn test_parse_query_params() {
     let query = "code=abc123&state=xyz789&scope=openid%20profile";
-    let params = server_parse_query_params(query);
+    let params = parse_query_params(query);
 
     assert_eq!(params.get("code"), Some(&"abc123".to_string()));
     assert_eq!(params.get("state"), Some(&"xyz789".to_string()));
@@ -43,3 +47,123 @@ async fn test_callback_server_start() {
     let receiver = server.start().await;
     assert!(receiver.is_ok());
 }
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_callback_server_unix_socket() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let dir = tempfile::tempdir().unwrap();
+    let socket_path = dir.path().join("callback.sock");
+    let mut server =
+        CallbackServer::new_unix(&socket_path, "https://app.example.com/callback").unwrap();
+    let mut receiver = server.start().await.unwrap();
+
+    let mut stream = tokio::net::UnixStream::connect(&socket_path).await.unwrap();
+    stream
+        .write_all(b"GET /callback?code=abc&state=xyz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"));
+
+    let result = receiver.recv().await.unwrap();
+    assert_eq!(result.code, "abc");
+    assert_eq!(result.state, "xyz");
+
+    let handle = server.clone();
+    drop(server);
+    assert!(socket_path.exists(), "a clone still holds the socket");
+    drop(handle);
+    assert!(
+        !socket_path.exists(),
+        "the socket file is removed with the server"
+    );
+}
+
+#[tokio::test]
+async fn test_callback_server_reports_bound_port_and_bind_errors() {
+    let mut server = CallbackServer::new(0, "http://localhost:8080/callback").unwrap();
+    let _receiver = server.start().await.unwrap();
+    let port = server.get_port();
+    assert_ne!(port, 0);
+
+    let mut second = CallbackServer::new(port, "http://localhost:8080/callback").unwrap();
+    assert!(second.start().await.is_err());
+
+    server.shutdown();
+}
+
+#[test]
+fn test_callback_result_extracts_query_token() {
+    let params = parse_query_params(
+        "code=abc&state=xyz&access_token=at-123&token_type=Bearer&expires_in=3600",
+    );
+    let result = callback_result_from_params(&params).unwrap();
+    assert_eq!(result.code, "abc");
+
+    let token = result.query_token().unwrap();
+    assert_eq!(token.access_token, "at-123");
+    assert_eq!(token.token_type, "Bearer");
+    assert_eq!(token.expires_in, Some(3600));
+
+    let params = parse_query_params("code=abc&state=xyz");
+    let result = callback_result_from_params(&params).unwrap();
+    assert!(result.query_token().is_none());
+
+    let params = parse_query_params("access_token=at-123");
+    assert!(callback_result_from_params(&params).is_none());
+}
+
+#[tokio::test]
+async fn test_browser_token_display_toggle() {
+    let tokens = TokenResponse {
+        access_token: "eyJhbGciOiJSUzI1NiJ9.access.signature".to_string(),
+        token_type: "Bearer".to_string(),
+        refresh_token: Some("short".to_string()),
+        ..Default::default()
+    };
+
+    let fetch = |display: Option<BrowserTokenDisplay>| {
+        let tokens = tokens.clone();
+        async move {
+            let mut server = CallbackServer::new(0, "http://localhost:8080/callback")
+                .unwrap()
+                .with_token_display(display);
+            let _receiver = server.start().await.unwrap();
+            server.set_tokens(tokens).await;
+            let base = format!("http://127.0.0.1:{}", server.get_port());
+
+            let page = reqwest::get(format!("{base}/callback?code=abc&state=xyz"))
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap();
+            let response = reqwest::get(format!("{base}/tokens")).await.unwrap();
+            server.shutdown();
+            (page, response)
+        }
+    };
+
+    // Off by default: the page does not poll and the endpoint is not served
+    let (page, response) = fetch(None).await;
+    assert!(page.contains("const pollTokens = false;"));
+    assert_eq!(response.status(), 404);
+
+    let (page, response) = fetch(Some(BrowserTokenDisplay::Full)).await;
+    assert!(page.contains("const pollTokens = true;"));
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["access_token"], tokens.access_token);
+    assert_eq!(body["redacted"], false);
+
+    let (_, response) = fetch(Some(BrowserTokenDisplay::Preview)).await;
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["access_token"], "eyJhbGci…ture");
+    assert_eq!(body["refresh_token"], "****");
+    assert_eq!(
+        body,
+        browser_token_json(&tokens, BrowserTokenDisplay::Preview)
+    );
+}
@@ -0,0 +1,20 @@
+use clap::Parser;
+use oidc_cli::cli::Cli;
+use oidc_cli::settings;
+
+// Settings are fixed once per process, so this binary holds a single test
+#[test]
+fn test_env_file_reaches_settings() {
+    let dir = tempfile::tempdir().unwrap();
+    let env_file = dir.path().join(".env");
+    std::fs::write(&env_file, "OIDC_CLI_PROXY=http://proxy.example:3128\n").unwrap();
+    assert!(std::env::var_os("OIDC_CLI_PROXY").is_none());
+
+    let cli = Cli::parse_from(["oidc-cli", "--env-file", env_file.to_str().unwrap(), "list"]);
+    cli.prepare().unwrap();
+
+    assert_eq!(
+        settings::get().proxy.as_deref(),
+        Some("http://proxy.example:3128")
+    );
+}